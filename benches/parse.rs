@@ -0,0 +1,95 @@
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use picmes::{
+    chunk::{checksum, ChunkRef},
+    parse::ChunkRefs,
+    Chunk, ChunkType, Png,
+};
+
+/// Encoders split the image data into IDAT chunks of this size
+const IDAT_LENGTH: usize = 1 << 16;
+
+/// Synthetic files from a tiny icon up to a large screenshot, named by
+/// their total image data
+const CORPUS: [(&str, usize); 3] = [
+    ("icon-1KiB", 1 << 10),
+    ("photo-4MiB", 4 << 20),
+    ("screenshot-100MiB", 100 << 20),
+];
+
+/// Bytes that don't compress, like real image data
+fn noise(length: usize) -> Vec<u8> {
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    (0..length)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
+
+fn png(image_data: usize) -> Vec<u8> {
+    let mut chunks = vec![Chunk::new(ChunkType::IHDR, vec![0; 13])];
+    for data in noise(image_data).chunks(IDAT_LENGTH) {
+        chunks.push(Chunk::new(ChunkType::IDAT, data.to_vec()));
+    }
+    chunks.push(Chunk::new(ChunkType::tEXt, b"Comment\0benchmark".to_vec()));
+    chunks.push(Chunk::new(ChunkType::IEND, Vec::new()));
+    Png::from_chunks(chunks).as_bytes()
+}
+
+fn crc(c: &mut Criterion) {
+    let mut group = c.benchmark_group("crc");
+    for length in [1 << 10, 1 << 20, 16 << 20] {
+        let data = noise(length);
+        group.throughput(Throughput::Bytes(length as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(length), &data, |b, data| {
+            b.iter(|| checksum(black_box(data)))
+        });
+    }
+    group.finish();
+}
+
+fn chunk_parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("chunk parse");
+    for length in [0, 1 << 10, IDAT_LENGTH] {
+        let bytes = Chunk::new(ChunkType::IDAT, noise(length)).as_bytes();
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+        group.bench_with_input(BenchmarkId::new("owned", length), &bytes, |b, bytes| {
+            b.iter(|| Chunk::try_from(black_box(bytes.as_slice())).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("borrowed", length), &bytes, |b, bytes| {
+            b.iter(|| ChunkRef::try_from(black_box(bytes.as_slice())).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn png_round_trip(c: &mut Criterion) {
+    let mut group = c.benchmark_group("png");
+    group.sample_size(10);
+    for (name, image_data) in CORPUS {
+        let bytes = png(image_data);
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+        group.bench_with_input(BenchmarkId::new("parse", name), &bytes, |b, bytes| {
+            b.iter(|| Png::try_from(black_box(bytes.as_slice())).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("scan", name), &bytes, |b, bytes| {
+            b.iter(|| ChunkRefs::new(black_box(&bytes[Png::HEADER_LENGTH..])).count())
+        });
+        group.bench_with_input(BenchmarkId::new("round trip", name), &bytes, |b, bytes| {
+            b.iter(|| {
+                Png::try_from(black_box(bytes.as_slice()))
+                    .unwrap()
+                    .as_bytes()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, crc, chunk_parse, png_round_trip);
+criterion_main!(benches);
@@ -0,0 +1,15 @@
+//! Generates include/picmes.h from the extern "C" functions in src/ffi.rs
+//! when the ffi feature is on
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    #[cfg(feature = "ffi")]
+    {
+        println!("cargo:rerun-if-changed=src/ffi.rs");
+        println!("cargo:rerun-if-changed=cbindgen.toml");
+        let dir = std::env::var("CARGO_MANIFEST_DIR").expect("cargo sets CARGO_MANIFEST_DIR");
+        cbindgen::generate(&dir)
+            .expect("src/ffi.rs should be parsable by cbindgen")
+            .write_to_file(std::path::Path::new(&dir).join("include/picmes.h"));
+    }
+}
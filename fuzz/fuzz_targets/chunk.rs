@@ -0,0 +1,15 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use picmes::{Chunk, ChunkRef};
+
+fuzz_target!(|data: &[u8]| {
+    // parsing may fail but must never panic, and whatever parses has to
+    // serialize back to the bytes it was read from
+    if let Ok(chunk) = ChunkRef::try_from(data) {
+        let length = chunk.length() + Chunk::META_DATA_LENGTH;
+        if let Ok(owned) = Chunk::try_from(chunk) {
+            assert_eq!(owned.as_bytes(), &data[..length]);
+        }
+    }
+});
@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use picmes::{png::ParseOptions, Png};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = Png::try_from(data);
+    let _ = Png::try_from_with(data, ParseOptions { lenient: true });
+    let _ = Png::from_reader(data);
+});
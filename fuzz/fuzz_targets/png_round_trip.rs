@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use picmes::Png;
+
+fuzz_target!(|png: Png| {
+    let bytes = png.as_bytes();
+    let parsed = Png::try_from(bytes.as_slice()).expect("a written png parses again");
+    assert_eq!(parsed.as_bytes(), bytes);
+});
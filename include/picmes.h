@@ -0,0 +1,152 @@
+#ifndef PICMES_H
+#define PICMES_H
+
+/* Generated by cbindgen from src/ffi.rs, do not edit */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * What the functions that can fail return. After an error
+ * `picmes_last_error` says what went wrong
+ */
+typedef enum PicmesStatus {
+  PICMES_STATUS_OK = 0,
+  PICMES_STATUS_ERROR = -1,
+} PicmesStatus;
+
+/**
+ * A png opened through the C API. It belongs to the caller until passed to
+ * `picmes_free`
+ */
+typedef struct PicmesPng PicmesPng;
+
+/**
+ * The object storage services picmes can read from and write to
+ */
+typedef struct Scheme Scheme;
+
+/**
+ * A chunk as `picmes_chunk_info` describes it
+ */
+typedef struct PicmesChunkInfo {
+  /**
+   * The four letters of the chunk type and a terminating nul
+   */
+  char chunk_type[5];
+  size_t length;
+  uint32_t crc;
+} PicmesChunkInfo;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * The message of the last error on this thread, or null if there was none.
+ * The string stays valid until the next call that fails
+ */
+const char *picmes_last_error(void);
+
+/**
+ * Reads and parses the png file at `path`, returning null on failure
+ *
+ * # Safety
+ * `path` is a nul terminated UTF-8 string
+ */
+struct PicmesPng *picmes_open(const char *path);
+
+/**
+ * Parses the `len` bytes at `data` as a png, returning null on failure
+ *
+ * # Safety
+ * `data` points to `len` readable bytes
+ */
+struct PicmesPng *picmes_parse(const uint8_t *data, size_t len);
+
+/**
+ * Frees a png from `picmes_open` or `picmes_parse`. Null is ignored
+ *
+ * # Safety
+ * `png` came from `picmes_open` or `picmes_parse` and isn't used afterwards
+ */
+void picmes_free(struct PicmesPng *png);
+
+/**
+ * Frees bytes from `picmes_extract` or `picmes_to_bytes`. Null is ignored
+ *
+ * # Safety
+ * `bytes` and `len` are exactly what one of those functions returned, and
+ * the bytes aren't used afterwards
+ */
+void picmes_free_bytes(uint8_t *bytes, size_t len);
+
+/**
+ * The number of chunks in `png`, 0 for null
+ *
+ * # Safety
+ * `png` is null or a live png from this API
+ */
+size_t picmes_chunk_count(const struct PicmesPng *png);
+
+/**
+ * Describes the chunk at `index` in file order
+ *
+ * # Safety
+ * `png` is a live png from this API and `info` points to writable memory
+ * for a `PicmesChunkInfo`
+ */
+enum PicmesStatus picmes_chunk_info(const struct PicmesPng *png,
+                                    size_t index,
+                                    struct PicmesChunkInfo *info);
+
+/**
+ * Adds a chunk of `chunk_type` holding the `len` bytes at `data` before
+ * IEND
+ *
+ * # Safety
+ * `png` is a live png from this API, `chunk_type` a nul terminated string
+ * and `data` points to `len` readable bytes
+ */
+enum PicmesStatus picmes_embed(struct PicmesPng *png,
+                               const char *chunk_type,
+                               const uint8_t *data,
+                               size_t len);
+
+/**
+ * Copies the data of the first chunk of `chunk_type`, storing its length in
+ * `len`. Returns null on failure, free the bytes with `picmes_free_bytes`
+ *
+ * # Safety
+ * `png` is a live png from this API, `chunk_type` a nul terminated string
+ * and `len` points to writable memory for a size_t
+ */
+uint8_t *picmes_extract(const struct PicmesPng *png, const char *chunk_type, size_t *len);
+
+/**
+ * Serializes `png`, storing the length in `len`. Returns null on failure,
+ * free the bytes with `picmes_free_bytes`
+ *
+ * # Safety
+ * `png` is a live png from this API and `len` points to writable memory for
+ * a size_t
+ */
+uint8_t *picmes_to_bytes(const struct PicmesPng *png, size_t *len);
+
+/**
+ * Writes `png` to the file at `path`, replacing it atomically
+ *
+ * # Safety
+ * `png` is a live png from this API and `path` a nul terminated UTF-8
+ * string
+ */
+enum PicmesStatus picmes_save(const struct PicmesPng *png, const char *path);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* PICMES_H */
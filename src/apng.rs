@@ -0,0 +1,250 @@
+use std::fmt::Display;
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, Error, Result};
+
+#[derive(Debug)]
+pub enum ApngError {
+    WrongChunkType(ChunkType, String),
+    InvalidLength(ChunkType, usize),
+}
+
+impl std::error::Error for ApngError {}
+
+impl Display for ApngError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::WrongChunkType(expected, found) => {
+                write!(f, "Expected a {} chunk but found {}", expected, found)
+            }
+            Self::InvalidLength(chunk_type, length) => {
+                let expected = match *chunk_type {
+                    ChunkType::acTL => AnimationControl::LENGTH,
+                    _ => FrameControl::LENGTH,
+                };
+                write!(
+                    f,
+                    "{} data should be {} bytes but is {}",
+                    chunk_type, expected, length
+                )
+            }
+        }
+    }
+}
+
+/// Every chunk an animated png adds
+pub const ANIMATION_CHUNKS: [ChunkType; 3] = [ChunkType::acTL, ChunkType::fcTL, ChunkType::fdAT];
+
+/// The chunks that carry a sequence number, which has to count up from
+/// zero across all of them in file order
+pub const SEQUENCED: [ChunkType; 2] = [ChunkType::fcTL, ChunkType::fdAT];
+
+/// The sequence number at the start of an fcTL or fdAT chunk
+pub fn sequence_number(chunk: &Chunk) -> Option<u32> {
+    if !SEQUENCED.contains(chunk.chunk_type()) {
+        return None;
+    }
+    Some(u32::from_be_bytes(chunk.data().get(..4)?.try_into().ok()?))
+}
+
+fn expect(chunk: &Chunk, chunk_type: ChunkType, length: usize) -> Result<&[u8]> {
+    if chunk.chunk_type() != &chunk_type {
+        return Err(ApngError::WrongChunkType(chunk_type, chunk.chunk_type().to_string()).into());
+    }
+    match chunk.data() {
+        data if data.len() == length => Ok(data),
+        data => Err(ApngError::InvalidLength(chunk_type, data.len()).into()),
+    }
+}
+
+/// The animation control stored in the acTL chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnimationControl {
+    pub frames: u32,
+    /// How often the animation plays, 0 meaning forever
+    pub plays: u32,
+}
+
+impl AnimationControl {
+    pub const LENGTH: usize = 8;
+}
+
+impl TryFrom<&Chunk> for AnimationControl {
+    type Error = Error;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        let data = expect(chunk, ChunkType::acTL, Self::LENGTH)?;
+        Ok(Self {
+            frames: u32::from_be_bytes(data[0..4].try_into()?),
+            plays: u32::from_be_bytes(data[4..8].try_into()?),
+        })
+    }
+}
+
+/// The frame control stored in an fcTL chunk. Only the fields picmes
+/// reports on are kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameControl {
+    pub sequence_number: u32,
+    pub width: u32,
+    pub height: u32,
+    pub delay_numerator: u16,
+    pub delay_denominator: u16,
+}
+
+impl FrameControl {
+    pub const LENGTH: usize = 26;
+
+    /// How long the frame is shown, in seconds
+    pub fn delay(&self) -> f64 {
+        // a zero denominator means hundredths of a second
+        let denominator = match self.delay_denominator {
+            0 => 100,
+            denominator => denominator,
+        };
+        f64::from(self.delay_numerator) / f64::from(denominator)
+    }
+}
+
+impl TryFrom<&Chunk> for FrameControl {
+    type Error = Error;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        let data = expect(chunk, ChunkType::fcTL, Self::LENGTH)?;
+        Ok(Self {
+            sequence_number: u32::from_be_bytes(data[0..4].try_into()?),
+            width: u32::from_be_bytes(data[4..8].try_into()?),
+            height: u32::from_be_bytes(data[8..12].try_into()?),
+            delay_numerator: u16::from_be_bytes(data[20..22].try_into()?),
+            delay_denominator: u16::from_be_bytes(data[22..24].try_into()?),
+        })
+    }
+}
+
+/// What an animated png says about its animation
+#[derive(Debug, Clone, PartialEq)]
+pub struct Animation {
+    pub control: AnimationControl,
+    pub frames: Vec<FrameControl>,
+}
+
+impl Animation {
+    /// Parses the acTL and fcTL chunks among `chunks`, or returns None for
+    /// a png without an acTL chunk
+    pub fn from_chunks<'a>(chunks: impl IntoIterator<Item = &'a Chunk>) -> Result<Option<Self>> {
+        let mut control = None;
+        let mut frames = Vec::new();
+        for chunk in chunks {
+            match *chunk.chunk_type() {
+                ChunkType::acTL if control.is_none() => {
+                    control = Some(AnimationControl::try_from(chunk)?)
+                }
+                ChunkType::fcTL => frames.push(FrameControl::try_from(chunk)?),
+                _ => {}
+            }
+        }
+        Ok(control.map(|control| Self { control, frames }))
+    }
+
+    /// The time one play of the animation takes, in seconds
+    pub fn duration(&self) -> f64 {
+        self.frames.iter().map(FrameControl::delay).sum()
+    }
+}
+
+impl Display for Animation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "animation: {} frame(s), {:.2}s",
+            self.frames.len(),
+            self.duration()
+        )?;
+        match self.control.plays {
+            0 => write!(f, ", loops forever"),
+            plays => write!(f, ", plays {} time(s)", plays),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn actl_chunk(frames: u32, plays: u32) -> Chunk {
+        let data = [frames.to_be_bytes(), plays.to_be_bytes()].concat();
+        Chunk::new(ChunkType::acTL, data)
+    }
+
+    /// A full size frame control for a 1x1 image shown for `delay` of
+    /// `denominator` seconds
+    pub(crate) fn fctl_chunk(sequence_number: u32, delay: u16, denominator: u16) -> Chunk {
+        let mut data = sequence_number.to_be_bytes().to_vec();
+        data.extend(1u32.to_be_bytes());
+        data.extend(1u32.to_be_bytes());
+        data.extend([0; 8]);
+        data.extend(delay.to_be_bytes());
+        data.extend(denominator.to_be_bytes());
+        data.extend([0, 0]);
+        Chunk::new(ChunkType::fcTL, data)
+    }
+
+    pub(crate) fn fdat_chunk(sequence_number: u32) -> Chunk {
+        let mut data = sequence_number.to_be_bytes().to_vec();
+        data.extend([1, 2, 3]);
+        Chunk::new(ChunkType::fdAT, data)
+    }
+
+    #[test]
+    fn test_parse_controls() {
+        let control = AnimationControl::try_from(&actl_chunk(2, 3)).unwrap();
+        assert_eq!(
+            control,
+            AnimationControl {
+                frames: 2,
+                plays: 3
+            }
+        );
+
+        let frame = FrameControl::try_from(&fctl_chunk(4, 1, 4)).unwrap();
+        assert_eq!(frame.sequence_number, 4);
+        assert_eq!((frame.width, frame.height), (1, 1));
+        assert_eq!(frame.delay(), 0.25);
+        assert_eq!(
+            FrameControl::try_from(&fctl_chunk(0, 5, 0))
+                .unwrap()
+                .delay(),
+            0.05
+        );
+
+        assert_eq!(sequence_number(&fdat_chunk(7)), Some(7));
+        assert_eq!(sequence_number(&actl_chunk(1, 0)), None);
+    }
+
+    #[test]
+    fn test_invalid_controls() {
+        assert!(AnimationControl::try_from(&fctl_chunk(0, 1, 1)).is_err());
+        let short = Chunk::new(ChunkType::acTL, vec![0; 4]);
+        assert!(AnimationControl::try_from(&short).is_err());
+        let short = Chunk::new(ChunkType::fcTL, vec![0; 25]);
+        assert!(FrameControl::try_from(&short).is_err());
+    }
+
+    #[test]
+    fn test_animation() {
+        let chunks = [
+            actl_chunk(2, 0),
+            fctl_chunk(0, 1, 2),
+            fctl_chunk(1, 1, 4),
+            fdat_chunk(2),
+        ];
+        let animation = Animation::from_chunks(&chunks).unwrap().unwrap();
+        assert_eq!(animation.frames.len(), 2);
+        assert_eq!(animation.duration(), 0.75);
+        assert_eq!(
+            animation.to_string(),
+            "animation: 2 frame(s), 0.75s, loops forever"
+        );
+
+        assert_eq!(Animation::from_chunks(&chunks[1..]).unwrap(), None);
+    }
+}
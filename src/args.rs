@@ -0,0 +1,1490 @@
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+
+use crate::{
+    bmp::Region, compress::Compressor, crypto::Cipher, fec, jpeg::SegmentKind, report::Format,
+    signing::HashAlgorithm,
+};
+
+#[derive(Debug, Parser)]
+#[command(name = "picmes", version, about = "Hide secret messages inside images")]
+pub struct Cli {
+    /// Print nothing once the arguments are parsed and only exit with a
+    /// code telling how it went: 0 on success, 2 on bad arguments, 3 when a
+    /// file is not found, 4 on a CRC error, 5 on a wrong passphrase, 6 when
+    /// there is no payload and 1 on any other failure
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+    /// Don't show progress bars for batches and large files. They are only
+    /// shown when stderr is a terminal
+    #[arg(long, global = true)]
+    pub no_progress: bool,
+    #[command(subcommand)]
+    pub command: PicmesArgs,
+}
+
+#[derive(Debug, Subcommand)]
+// parsed once at startup, so the size of the largest variant does not matter
+#[allow(clippy::large_enum_variant)]
+pub enum PicmesArgs {
+    /// Embed a message in a new chunk
+    Encode(EncodeArgs),
+    /// Print the message stored in a chunk
+    Decode(DecodeArgs),
+    /// Remove a chunk from a file
+    Remove(RemoveArgs),
+    /// List every chunk in a file
+    Print(PrintArgs),
+    /// Flag chunks that are not part of the PNG specification or repeat
+    /// another chunk, data after IEND and archives or programs hidden in
+    /// either
+    Scan(ScanArgs),
+    /// Estimate how likely files are to hold hidden data, from the entropy
+    /// of their chunks and the low bits of their pixels
+    Detect(DetectArgs),
+    /// Break the size of a file down by chunk type
+    Stats(StatsArgs),
+    /// Check the signature, CRCs and chunk ordering of a file
+    Verify(VerifyArgs),
+    /// Report the chunks added, removed, changed or reordered between two
+    /// files
+    Diff(DiffArgs),
+    /// Print a hash of each file that ignores its file signature chunk
+    Hash(HashArgs),
+    /// Sign a whole file, storing the signature in a chunk of its own
+    Sign(SignArgs),
+    /// Check the signature stored by sign against a public key
+    VerifyFile(VerifyFileArgs),
+    /// Write a fixed copy of a damaged file
+    Repair(RepairArgs),
+    /// Remove all ancillary chunks from a file
+    Strip(StripArgs),
+    /// Write the raw contents of a chunk to a file
+    Extract(ExtractArgs),
+    /// Find complete PNG files inside any other file, such as a memory dump
+    /// or disk image, and write each one out
+    Carve(CarveArgs),
+    /// Print an offset, hex and ASCII dump of chunk contents
+    Hexdump(HexdumpArgs),
+    /// Copy every payload of one file into another, of any format
+    Migrate(MigrateArgs),
+    /// Encrypt every custom chunk into a single sealed chunk
+    Seal(SealArgs),
+    /// Decrypt a sealed chunk and restore the chunks it holds
+    Unseal(UnsealArgs),
+    /// Report how many bytes each hiding method can store in a file
+    Capacity(CapacityArgs),
+    /// Hide a payload in the pixel data instead of a chunk
+    #[command(subcommand)]
+    Stego(StegoCommand),
+    /// List the changes picmes recorded in a file with --record
+    History(HistoryArgs),
+    /// Read or set the last modification time stored in the tIME chunk
+    #[command(subcommand)]
+    Time(TimeCommand),
+    /// Show, extract or replace the EXIF data stored in the eXIf chunk
+    Exif(ExifArgs),
+    /// Keep passphrases and private keys in the platform keychain
+    #[cfg(feature = "keyring")]
+    #[command(subcommand)]
+    Keys(KeysCommand),
+    /// Run a command on every image added to a directory
+    #[cfg(feature = "watch")]
+    Watch(WatchArgs),
+    /// Print a shell completion script
+    Completions(CompletionsArgs),
+}
+
+#[derive(Debug, Default, Args)]
+pub struct EncodeArgs {
+    /// PNG, JPEG, GIF, WebP, BMP, TIFF or ICO file, glob or directory to embed the message in
+    pub file: String,
+    /// Four letter chunk type, e.g. ruSt, or any label with --type-key
+    pub chunk_type: String,
+    /// Message to embed, omitted when --input is used
+    #[arg(required_unless_present = "input")]
+    pub message: Option<String>,
+    /// Write the result here instead of overwriting the input file
+    pub output: Option<PathBuf>,
+    /// Embed the contents of this file instead of a message
+    #[arg(short, long)]
+    pub input: Option<PathBuf>,
+    /// Store the message as a keyword and text pair like a tEXt chunk does.
+    /// tEXt, zTXt and iTXt chunks use the keyword Comment unless one is given,
+    /// zTXt messages are compressed and iTXt messages may be any UTF-8 text
+    #[arg(short, long, conflicts_with = "input")]
+    pub keyword: Option<String>,
+    /// Insert the chunk at this index instead of the last position the PNG
+    /// specification allows for its type
+    #[arg(long, value_name = "INDEX")]
+    pub at: Option<usize>,
+    /// The segment payloads are stored in when embedding in a jpeg
+    #[arg(long, value_enum, default_value_t)]
+    pub segment: SegmentKind,
+    /// Where payloads are stored when embedding in a bmp
+    #[arg(long, value_enum, default_value_t)]
+    pub bmp_region: Region,
+    /// The entry of an icon or cursor to embed in, which has to be PNG
+    /// encoded. The first PNG entry by default
+    #[arg(long, value_name = "INDEX")]
+    pub entry: Option<usize>,
+    /// Compress the payload before encrypting and embedding it, with zstd
+    /// unless another algorithm is given
+    #[arg(long, value_enum, value_name = "ALGORITHM", num_args = 0..=1, default_missing_value = "zstd", conflicts_with = "keyword")]
+    pub compress: Option<Compressor>,
+    /// Encrypt the payload with AES-256-GCM before embedding it
+    #[arg(short, long, conflicts_with = "keyword")]
+    pub encrypt: bool,
+    /// Passphrase to encrypt with, asked for on the terminal when omitted
+    #[arg(short, long, requires = "encrypt")]
+    pub passphrase: Option<String>,
+    /// Cipher to encrypt with, recorded in the payload for decoding
+    #[arg(long, value_enum, default_value_t, requires = "encrypt")]
+    pub cipher: Cipher,
+    /// Memory Argon2id may use to derive the key, in KiB. Higher values make
+    /// guessing the passphrase slower
+    #[arg(long, value_name = "KIB", requires = "encrypt")]
+    pub kdf_memory: Option<u32>,
+    /// Number of Argon2id passes over that memory
+    #[arg(long, value_name = "N", requires = "encrypt")]
+    pub kdf_iterations: Option<u32>,
+    /// Derive the nonce from the message so encoding it again with the same
+    /// passphrase gives identical bytes. Reveals repeated messages
+    #[arg(long, requires = "encrypt")]
+    pub deterministic: bool,
+    /// Also embed this innocuous message, encrypted with --decoy-passphrase.
+    /// The two payloads look alike and each passphrase reveals only its own
+    #[arg(long, value_name = "MESSAGE", requires_all = ["encrypt", "decoy_passphrase"], conflicts_with = "split_across")]
+    pub decoy: Option<String>,
+    /// Passphrase that decodes the decoy message
+    #[arg(long, value_name = "PASSPHRASE", requires = "decoy")]
+    pub decoy_passphrase: Option<String>,
+    /// Encrypt the payload with age to this public key (age1...) instead of a
+    /// passphrase. May be given more than once
+    #[arg(long, value_name = "AGE_KEY", conflicts_with_all = ["encrypt", "keyword"])]
+    pub recipient: Vec<String>,
+    /// Also encrypt to every public key in this file, one per line. Blank
+    /// lines and lines starting with # are ignored
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["encrypt", "keyword"])]
+    pub recipients_file: Option<PathBuf>,
+    /// Append an Ed25519 signature made with this PKCS#8 PEM private key
+    #[arg(long, value_name = "KEY", conflicts_with = "keyword")]
+    pub sign: Option<PathBuf>,
+    /// Append an HMAC-SHA256 tag made with this shared key, for tamper
+    /// detection without encryption
+    #[arg(long, value_name = "KEY", conflicts_with_all = ["keyword", "encrypt", "recipient", "recipients_file"])]
+    pub hmac_key: Option<String>,
+    /// Add this many Reed-Solomon parity shards, 4 when no number is given,
+    /// so the payload survives that many damaged or missing shards
+    #[arg(long, value_name = "PARITY", num_args = 0..=1, default_missing_value = "4", value_parser = clap::value_parser!(u8).range(1..=fec::MAX_PARITY_SHARDS as i64), conflicts_with = "keyword")]
+    pub fec: Option<u8>,
+    /// Store the chunk under a type derived from this key and CHUNK_TYPE,
+    /// so it cannot be found by looking for a known name
+    #[arg(long, value_name = "KEY", conflicts_with = "keyword")]
+    pub type_key: Option<String>,
+    /// Split payloads larger than this many bytes across several chunks
+    /// plus a manifest. Payloads too big for one chunk are always split
+    #[arg(long, value_name = "BYTES", value_parser = clap::value_parser!(u32).range(1..), conflicts_with = "split_across")]
+    pub split_size: Option<u32>,
+    /// Spread the payload over FILE and these images, one shard each. Every
+    /// image is changed in place and decode --join puts the shards together
+    #[arg(long, value_name = "IMAGE", num_args = 1.., conflicts_with_all = ["output", "keyword"])]
+    pub split_across: Vec<PathBuf>,
+    /// Include images in subdirectories of directory inputs
+    #[arg(short, long)]
+    pub recursive: bool,
+    /// Show what would change without writing anything
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Set the tIME chunk to the current time
+    #[arg(long)]
+    pub touch: bool,
+    /// Record this change in the picmes history chunk, see the history
+    /// command
+    #[arg(long)]
+    pub record: bool,
+    /// Add the chunk by writing over the IEND chunk at the end of the png,
+    /// leaving the rest of the file as it is instead of parsing and
+    /// rewriting it. Much faster on large images, but not atomic: a crash
+    /// while writing leaves the png without its IEND chunk
+    #[arg(long, conflicts_with_all = ["output", "at", "touch", "record", "split_across"])]
+    pub append: bool,
+    /// Embed in the image on the clipboard instead of reading FILE, and
+    /// write the result to FILE as a png
+    #[cfg(feature = "clipboard")]
+    #[arg(long, conflicts_with_all = ["output", "recursive", "append", "split_across"])]
+    pub from_clipboard: bool,
+}
+
+#[derive(Debug, Default, Args)]
+pub struct DecodeArgs {
+    /// PNG, JPEG, GIF, WebP, BMP, TIFF or ICO files, globs or directories to read the message from
+    #[arg(required = true)]
+    pub files: Vec<String>,
+    /// Chunk type the message was stored under, or the label given to
+    /// encode with --type-key
+    pub chunk_type: String,
+    /// Print the payload as JSON, base64 encoding binary data
+    #[arg(long, conflicts_with = "out")]
+    pub json: bool,
+    /// Write the raw payload bytes to this file instead of printing them
+    #[arg(short, long)]
+    pub out: Option<PathBuf>,
+    /// Print every chunk of the type instead of only the first
+    #[arg(short, long, conflicts_with = "out")]
+    pub all: bool,
+    /// Passphrase for encrypted payloads, asked for on the terminal when
+    /// omitted
+    #[arg(short, long)]
+    pub passphrase: Option<String>,
+    /// age identity file to decrypt payloads encrypted to a recipient
+    #[arg(long, value_name = "FILE")]
+    pub identity: Option<PathBuf>,
+    /// Refuse to output payloads without a valid signature from this PEM
+    /// public key
+    #[arg(long, value_name = "KEY")]
+    pub verify: Option<PathBuf>,
+    /// Refuse to output payloads without a valid HMAC tag made with this key
+    #[arg(long, value_name = "KEY")]
+    pub hmac_key: Option<String>,
+    /// Look for the chunk type encode derived from this key and CHUNK_TYPE
+    #[arg(long, value_name = "KEY")]
+    pub type_key: Option<String>,
+    /// Reassemble one payload from the shards spread over all the files by
+    /// encode --split-across
+    #[arg(short, long, conflicts_with = "all")]
+    pub join: bool,
+    /// Include images in subdirectories of directory inputs
+    #[arg(short, long)]
+    pub recursive: bool,
+    /// Render the message as a QR code and write it to this png instead of
+    /// printing it
+    #[cfg(feature = "qr")]
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["out", "json", "all"])]
+    #[cfg_attr(feature = "clipboard", arg(conflicts_with = "to_clipboard"))]
+    pub qr: Option<PathBuf>,
+    /// Copy the message to the clipboard instead of printing it, and clear
+    /// it again after --clear-after seconds
+    #[cfg(feature = "clipboard")]
+    #[arg(long, conflicts_with_all = ["out", "json", "all"])]
+    pub to_clipboard: bool,
+    /// Seconds the message stays on the clipboard, picmes waits for them to
+    /// pass before exiting
+    #[cfg(feature = "clipboard")]
+    #[arg(long, value_name = "SECONDS", default_value_t = 45)]
+    pub clear_after: u64,
+}
+
+#[derive(Debug, Default, Args)]
+pub struct RemoveArgs {
+    /// PNG, JPEG, GIF, WebP, BMP, TIFF or ICO file to remove the chunk from
+    pub file: PathBuf,
+    /// Type of the chunk to remove
+    pub chunk_type: String,
+    /// Allow removing critical chunks such as IDAT
+    #[arg(long)]
+    pub force: bool,
+    /// Remove every chunk of the type instead of only the first
+    #[arg(short, long)]
+    pub all: bool,
+    /// Show what would change without writing anything
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Set the tIME chunk to the current time
+    #[arg(long)]
+    pub touch: bool,
+}
+
+#[derive(Debug, Default, Args)]
+pub struct PrintArgs {
+    /// PNG, JPEG, GIF, WebP, BMP, TIFF or ICO files, globs or directories to list
+    #[arg(required = true)]
+    pub files: Vec<String>,
+    /// Print the chunk listing as JSON
+    #[arg(long)]
+    pub json: bool,
+    /// Also print the entropy of each PNG chunk's data in bits per byte
+    #[arg(short, long)]
+    pub verbose: bool,
+    /// Include images in subdirectories of directory inputs
+    #[arg(short, long)]
+    pub recursive: bool,
+}
+
+#[derive(Debug, Default, Args)]
+pub struct DetectArgs {
+    /// PNG files, globs or directories to analyse
+    #[arg(required = true)]
+    pub files: Vec<String>,
+    /// Print the evidence as JSON
+    #[arg(long)]
+    pub json: bool,
+    /// Include PNG files in subdirectories of directory inputs
+    #[arg(short, long)]
+    pub recursive: bool,
+}
+
+#[derive(Debug, Default, Args)]
+pub struct ScanArgs {
+    /// PNG files, globs or directories to scan
+    #[arg(required = true)]
+    pub files: Vec<String>,
+    /// Print the findings as JSON, short for --format json
+    #[arg(long, conflicts_with = "format")]
+    pub json: bool,
+    /// Print the findings as text, JSON, CSV or YAML
+    #[arg(long, value_enum, default_value_t)]
+    pub format: Format,
+    /// Include PNG files in subdirectories of directory inputs
+    #[arg(short, long)]
+    pub recursive: bool,
+}
+
+#[derive(Debug, Default, Args)]
+pub struct VerifyArgs {
+    /// PNG files, globs or directories to verify
+    #[arg(required = true)]
+    pub files: Vec<String>,
+    /// Print the problems found as JSON, short for --format json
+    #[arg(long, conflicts_with = "format")]
+    pub json: bool,
+    /// Print the problems found as text, JSON, CSV or YAML
+    #[arg(long, value_enum, default_value_t)]
+    pub format: Format,
+    /// Include PNG files in subdirectories of directory inputs
+    #[arg(short, long)]
+    pub recursive: bool,
+}
+
+#[derive(Debug, Default, Args)]
+pub struct RepairArgs {
+    /// Damaged PNG file
+    pub file: PathBuf,
+    /// Where to write the fixed copy, defaults to <file>-repaired.png
+    pub output: Option<PathBuf>,
+    /// Keep the remaining data of a chunk cut off by the end of the file
+    #[arg(long)]
+    pub truncate: bool,
+    /// Remove chunks that cannot be recovered instead of failing
+    #[arg(long)]
+    pub drop: bool,
+    /// Remove ancillary chunks identical to an earlier chunk
+    #[arg(long)]
+    pub dedup: bool,
+    /// Show what would change without writing anything
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Set the tIME chunk to the current time
+    #[arg(long)]
+    pub touch: bool,
+    /// Record this change in the picmes history chunk, see the history
+    /// command
+    #[arg(long)]
+    pub record: bool,
+}
+
+#[derive(Debug, Default, Args)]
+pub struct StripArgs {
+    /// PNG files, globs or directories to strip
+    #[arg(required = true)]
+    pub files: Vec<String>,
+    /// Ancillary chunk types to keep, e.g. tEXt,pHYs
+    #[arg(long, value_delimiter = ',')]
+    pub keep: Vec<String>,
+    /// Include PNG files in subdirectories of directory inputs
+    #[arg(short, long)]
+    pub recursive: bool,
+    /// Show what would change without writing anything
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Set the tIME chunk to the current time
+    #[arg(long)]
+    pub touch: bool,
+    /// Record this change in the picmes history chunk, see the history
+    /// command
+    #[arg(long)]
+    pub record: bool,
+}
+
+#[derive(Debug, Default, Args)]
+pub struct ExtractArgs {
+    /// PNG file to extract from
+    pub file: PathBuf,
+    /// Type of the chunk to extract
+    pub chunk_type: String,
+    /// File to write the chunk contents to
+    #[arg(short, long)]
+    pub out: PathBuf,
+    /// Write the whole chunk including its length, type and CRC instead of
+    /// only the data field
+    #[arg(long)]
+    pub whole: bool,
+}
+
+#[derive(Debug, Default, Args)]
+pub struct CarveArgs {
+    /// File of any kind to search for PNG files
+    pub file: PathBuf,
+    /// Directory to write the PNG files found to, each named after the
+    /// searched file and its offset in it
+    #[arg(short, long, value_name = "DIR", default_value = ".")]
+    pub out: PathBuf,
+    /// Only list the PNG files found instead of writing them
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Default, Args)]
+pub struct StatsArgs {
+    /// PNG file to summarize
+    pub file: PathBuf,
+    /// How many of the largest chunks to list
+    #[arg(long, value_name = "N", default_value_t = 5)]
+    pub top: usize,
+    /// Print the summary as JSON, short for --format json
+    #[arg(long, conflicts_with = "format")]
+    pub json: bool,
+    /// Print the summary as text, JSON, CSV or YAML
+    #[arg(long, value_enum, default_value_t)]
+    pub format: Format,
+}
+
+#[derive(Debug, Default, Args)]
+pub struct DiffArgs {
+    /// PNG file to compare against
+    pub old: PathBuf,
+    /// PNG file to compare
+    pub new: PathBuf,
+    /// Print the differences as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Default, Args)]
+pub struct HashArgs {
+    /// PNG files, globs or directories to hash
+    #[arg(required = true)]
+    pub files: Vec<String>,
+    /// Hash function to use
+    #[arg(short, long, value_enum, default_value_t)]
+    pub algorithm: HashAlgorithm,
+    /// Include PNG files in subdirectories of directory inputs
+    #[arg(short, long)]
+    pub recursive: bool,
+}
+
+#[derive(Debug, Default, Args)]
+pub struct SignArgs {
+    /// PNG file to sign in place
+    pub file: PathBuf,
+    /// PKCS#8 PEM private key to sign with
+    #[arg(short, long, value_name = "KEY")]
+    pub key: PathBuf,
+    /// Print the change instead of writing it
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Default, Args)]
+pub struct VerifyFileArgs {
+    /// Signed PNG file
+    pub file: PathBuf,
+    /// PEM public key of the signer
+    #[arg(short, long, value_name = "KEY")]
+    pub key: PathBuf,
+}
+
+#[derive(Debug, Default, Args)]
+pub struct HexdumpArgs {
+    /// PNG, JPEG, GIF, WebP, BMP, TIFF or ICO file to dump chunks of
+    pub file: PathBuf,
+    /// Only dump chunks of this type instead of every chunk
+    pub chunk_type: Option<String>,
+    /// Only dump the chunk at this index among the chunks of the type, or
+    /// among all chunks when no type is given
+    #[arg(long, value_name = "N")]
+    pub index: Option<usize>,
+}
+
+#[derive(Debug, Default, Args)]
+pub struct MigrateArgs {
+    /// PNG, JPEG, GIF, WebP, BMP, TIFF or ICO file to take the payloads from
+    pub source: PathBuf,
+    /// PNG, JPEG, GIF, WebP, BMP, TIFF or ICO file to embed the payloads in
+    pub target: PathBuf,
+    /// Write the result here instead of changing TARGET
+    #[arg(short, long)]
+    pub out: Option<PathBuf>,
+    /// Also move chunks of this type, e.g. tEXt or the type of a payload
+    /// stored without encryption, compression, signing or error correction.
+    /// Other chunks are only moved when they hold one of those
+    #[arg(long = "chunk-type", value_name = "TYPE")]
+    pub chunk_types: Vec<String>,
+    /// Remove the payloads from SOURCE once they are embedded
+    #[arg(long)]
+    pub remove: bool,
+    /// Show what would change without writing anything
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Default, Args)]
+pub struct SealArgs {
+    /// PNG file whose custom chunks to seal
+    pub file: PathBuf,
+    /// Passphrase to encrypt with, asked for on the terminal when omitted
+    #[arg(short, long)]
+    pub passphrase: Option<String>,
+    /// Also seal chunks of this type, e.g. tEXt or the type of a payload
+    /// stored without encryption, compression, signing or error correction.
+    /// Other chunks are only sealed when they hold one of those
+    #[arg(long = "chunk-type", value_name = "TYPE")]
+    pub chunk_types: Vec<String>,
+    /// Cipher to encrypt with, recorded in the payload for unsealing
+    #[arg(long, value_enum, default_value_t)]
+    pub cipher: Cipher,
+    /// Show what would change without writing anything
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Set the tIME chunk to the current time
+    #[arg(long)]
+    pub touch: bool,
+}
+
+#[derive(Debug, Default, Args)]
+pub struct UnsealArgs {
+    /// PNG file with a sealed chunk
+    pub file: PathBuf,
+    /// Passphrase the chunks were sealed with, asked for on the terminal when
+    /// omitted
+    #[arg(short, long)]
+    pub passphrase: Option<String>,
+    /// Show what would change without writing anything
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Set the tIME chunk to the current time
+    #[arg(long)]
+    pub touch: bool,
+}
+
+/// Where a payload can be hidden
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum HidingMethod {
+    /// In a chunk of its own, as encode does
+    Chunk,
+    /// In the least significant bits of the pixel data, as stego hide does
+    Lsb,
+}
+
+#[derive(Debug, Default, Args)]
+pub struct CapacityArgs {
+    /// PNG file to measure
+    pub file: PathBuf,
+    /// Only report this method instead of every one
+    #[arg(long, value_enum)]
+    pub method: Option<HidingMethod>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum StegoCommand {
+    /// Hide a message in the least significant bits of the image samples
+    Hide(StegoHideArgs),
+    /// Print or save a message hidden in the image samples
+    Reveal(StegoRevealArgs),
+}
+
+#[derive(Debug, Default, Args)]
+pub struct StegoHideArgs {
+    /// PNG file to hide the message in
+    pub file: PathBuf,
+    /// Message to hide, omitted when --input is used
+    #[arg(required_unless_present = "input", conflicts_with = "input")]
+    pub message: Option<String>,
+    /// Hide the contents of this file instead of a message
+    #[arg(short, long, value_name = "FILE")]
+    pub input: Option<PathBuf>,
+    /// Where to write the result, defaults to the input file
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+    /// Show what would change without writing anything
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Default, Args)]
+pub struct StegoRevealArgs {
+    /// PNG file with a hidden message
+    pub file: PathBuf,
+    /// Write the raw payload bytes to this file instead of printing them
+    #[arg(short, long)]
+    pub out: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Args)]
+pub struct HistoryArgs {
+    /// PNG file to read the history of
+    pub file: PathBuf,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TimeCommand {
+    /// Print the last modification time
+    Get(TimeGetArgs),
+    /// Set the last modification time
+    Set(TimeSetArgs),
+}
+
+#[derive(Debug, Default, Args)]
+pub struct TimeGetArgs {
+    /// PNG file to read the time from
+    pub file: PathBuf,
+}
+
+#[derive(Debug, Default, Args)]
+pub struct TimeSetArgs {
+    /// PNG file to set the time in
+    pub file: PathBuf,
+    /// Time in UTC as YYYY-MM-DDTHH:MM:SS, defaults to now
+    pub time: Option<String>,
+    /// Show what would change without writing anything
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Default, Args)]
+pub struct ExifArgs {
+    /// PNG file to read or update
+    pub file: PathBuf,
+    /// Write the raw EXIF blob to this file instead of printing its tags
+    #[arg(short, long, conflicts_with = "set")]
+    pub out: Option<PathBuf>,
+    /// Insert or replace the EXIF data with the contents of this file
+    #[arg(long, value_name = "FILE")]
+    pub set: Option<PathBuf>,
+    /// Show what would change without writing anything
+    #[arg(long, requires = "set")]
+    pub dry_run: bool,
+    /// Set the tIME chunk to the current time
+    #[arg(long, requires = "set")]
+    pub touch: bool,
+}
+
+#[cfg(feature = "keyring")]
+#[derive(Debug, Subcommand)]
+pub enum KeysCommand {
+    /// Save a passphrase, or the contents of a key file, under a name
+    Store(KeysStoreArgs),
+    /// Print a stored secret or write it to a file
+    Load(KeysLoadArgs),
+    /// Remove a stored secret
+    Delete(KeysDeleteArgs),
+}
+
+#[cfg(feature = "keyring")]
+#[derive(Debug, Default, Args)]
+pub struct KeysStoreArgs {
+    /// Name to store the secret under
+    pub name: String,
+    /// Store the contents of this file, e.g. a PEM private key or an age
+    /// identity, instead of asking for a passphrase
+    #[arg(long, value_name = "FILE")]
+    pub file: Option<PathBuf>,
+}
+
+#[cfg(feature = "keyring")]
+#[derive(Debug, Default, Args)]
+pub struct KeysLoadArgs {
+    /// Name the secret was stored under
+    pub name: String,
+    /// Write the secret to this file instead of printing it
+    #[arg(short, long)]
+    pub out: Option<PathBuf>,
+}
+
+#[cfg(feature = "keyring")]
+#[derive(Debug, Default, Args)]
+pub struct KeysDeleteArgs {
+    /// Name the secret was stored under
+    pub name: String,
+}
+
+#[cfg(feature = "watch")]
+#[derive(Debug, Default, Args)]
+pub struct WatchArgs {
+    /// Directory to watch
+    pub dir: PathBuf,
+    /// picmes command to run on each new image, e.g. "strip --keep tEXt".
+    /// {} stands for the image, which is added at the end otherwise
+    #[arg(long, value_name = "COMMAND")]
+    pub on_add: String,
+    /// Also watch the subdirectories
+    #[arg(short, long)]
+    pub recursive: bool,
+    /// Run the command on the images already in the directory first
+    #[arg(long)]
+    pub existing: bool,
+    /// Milliseconds a new image has to go without changes before the
+    /// command runs on it, so files still being copied in are left alone
+    #[arg(long, value_name = "MS", default_value_t = 500)]
+    pub settle: u64,
+}
+
+#[derive(Debug, Args)]
+pub struct CompletionsArgs {
+    /// Shell to generate completions for
+    pub shell: Shell,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    fn parse(args: &[&str]) -> Result<PicmesArgs, clap::Error> {
+        Cli::try_parse_from(std::iter::once("picmes").chain(args.iter().copied()))
+            .map(|cli| cli.command)
+    }
+
+    #[test]
+    fn test_command_definition() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_parse_global_flags() {
+        for args in [
+            ["picmes", "-q", "print", "a.png"],
+            ["picmes", "print", "a.png", "--quiet"],
+        ] {
+            let cli = Cli::try_parse_from(args).unwrap();
+            assert!(cli.quiet && !cli.no_progress);
+            assert!(matches!(cli.command, PicmesArgs::Print(_)));
+        }
+        let cli = Cli::try_parse_from(["picmes", "scan", "dir", "--no-progress"]).unwrap();
+        assert!(cli.no_progress && !cli.quiet);
+    }
+
+    #[test]
+    fn test_parse_encode() {
+        let PicmesArgs::Encode(args) = parse(&["encode", "in.png", "ruSt", "hello"]).unwrap()
+        else {
+            panic!("expected encode");
+        };
+        assert_eq!(args.file, "in.png");
+        assert_eq!(args.chunk_type, "ruSt");
+        assert_eq!(args.message.as_deref(), Some("hello"));
+        assert_eq!(args.output, None);
+    }
+
+    #[test]
+    fn test_parse_encode_with_output() {
+        let PicmesArgs::Encode(args) =
+            parse(&["encode", "in.png", "ruSt", "hello", "out.png"]).unwrap()
+        else {
+            panic!("expected encode");
+        };
+        assert_eq!(args.output, Some(PathBuf::from("out.png")));
+    }
+
+    #[test]
+    fn test_parse_encode_input() {
+        let PicmesArgs::Encode(args) =
+            parse(&["encode", "in.png", "ruSt", "--input", "payload.bin"]).unwrap()
+        else {
+            panic!("expected encode");
+        };
+        assert_eq!(args.input, Some(PathBuf::from("payload.bin")));
+        assert_eq!(args.message, None);
+        assert!(parse(&["encode", "in.png", "ruSt"]).is_err());
+    }
+
+    #[cfg(feature = "watch")]
+    #[test]
+    fn test_parse_watch() {
+        let PicmesArgs::Watch(args) =
+            parse(&["watch", "drop", "--on-add", "strip --keep tEXt", "-r"]).unwrap()
+        else {
+            panic!("expected watch");
+        };
+        assert_eq!(args.dir, PathBuf::from("drop"));
+        assert_eq!(args.on_add, "strip --keep tEXt");
+        assert!(args.recursive && !args.existing);
+        assert_eq!(args.settle, 500);
+        assert!(parse(&["watch", "drop"]).is_err());
+    }
+
+    #[cfg(feature = "qr")]
+    #[test]
+    fn test_parse_decode_qr() {
+        let PicmesArgs::Decode(args) =
+            parse(&["decode", "in.png", "ruSt", "--qr", "code.png"]).unwrap()
+        else {
+            panic!("expected decode");
+        };
+        assert_eq!(args.qr, Some(PathBuf::from("code.png")));
+        assert!(parse(&["decode", "in.png", "ruSt", "--qr", "code.png", "--json"]).is_err());
+    }
+
+    #[cfg(feature = "clipboard")]
+    #[test]
+    fn test_parse_clipboard() {
+        let PicmesArgs::Encode(args) =
+            parse(&["encode", "--from-clipboard", "out.png", "ruSt", "hello"]).unwrap()
+        else {
+            panic!("expected encode");
+        };
+        assert!(args.from_clipboard);
+        assert_eq!(args.file, "out.png");
+        assert!(parse(&["encode", "--from-clipboard", "a.png", "ruSt", "hi", "b.png"]).is_err());
+
+        let PicmesArgs::Decode(args) =
+            parse(&["decode", "--to-clipboard", "in.png", "ruSt"]).unwrap()
+        else {
+            panic!("expected decode");
+        };
+        assert!(args.to_clipboard);
+        assert_eq!(args.clear_after, 45);
+        assert!(parse(&["decode", "--to-clipboard", "--json", "in.png", "ruSt"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_decode() {
+        let PicmesArgs::Decode(args) = parse(&["decode", "in.png", "ruSt"]).unwrap() else {
+            panic!("expected decode");
+        };
+        assert_eq!(args.files, ["in.png"]);
+        assert_eq!(args.chunk_type, "ruSt");
+        assert!(!args.json);
+        assert!(!args.all);
+        assert!(parse(&["decode", "in.png"]).is_err());
+
+        let PicmesArgs::Decode(args) = parse(&["decode", "--all", "in.png", "ruSt"]).unwrap()
+        else {
+            panic!("expected decode");
+        };
+        assert!(args.all);
+        assert!(parse(&["decode", "-a", "in.png", "ruSt", "-o", "p.bin"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_decode_out() {
+        let PicmesArgs::Decode(args) =
+            parse(&["decode", "in.png", "ruSt", "--out", "payload.bin"]).unwrap()
+        else {
+            panic!("expected decode");
+        };
+        assert_eq!(args.out, Some(PathBuf::from("payload.bin")));
+        assert!(parse(&["decode", "in.png", "ruSt", "-o", "p.bin", "--json"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_remove() {
+        let PicmesArgs::Remove(args) = parse(&["remove", "in.png", "ruSt"]).unwrap() else {
+            panic!("expected remove");
+        };
+        assert!(!args.force);
+        assert!(!args.all);
+
+        let PicmesArgs::Remove(args) = parse(&["remove", "-a", "in.png", "ruSt"]).unwrap() else {
+            panic!("expected remove");
+        };
+        assert!(args.all);
+
+        let PicmesArgs::Remove(args) = parse(&["remove", "--force", "in.png", "IDAT"]).unwrap()
+        else {
+            panic!("expected remove");
+        };
+        assert!(args.force);
+        assert!(!args.dry_run);
+        assert_eq!(args.chunk_type, "IDAT");
+    }
+
+    #[test]
+    fn test_parse_dry_run() {
+        let PicmesArgs::Remove(args) = parse(&["remove", "in.png", "ruSt", "--dry-run"]).unwrap()
+        else {
+            panic!("expected remove");
+        };
+        assert!(args.dry_run);
+
+        let PicmesArgs::Encode(args) =
+            parse(&["encode", "--dry-run", "in.png", "ruSt", "hi"]).unwrap()
+        else {
+            panic!("expected encode");
+        };
+        assert!(args.dry_run);
+    }
+
+    #[test]
+    fn test_parse_print() {
+        let PicmesArgs::Print(args) = parse(&["print", "in.png"]).unwrap() else {
+            panic!("expected print");
+        };
+        assert_eq!(args.files, ["in.png"]);
+        assert!(!args.json);
+        assert!(parse(&["print"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_scan() {
+        let PicmesArgs::Scan(args) = parse(&["scan", "--json", "-r", "images/"]).unwrap() else {
+            panic!("expected scan");
+        };
+        assert_eq!(args.files, ["images/"]);
+        assert!(args.json);
+        assert!(args.recursive);
+        assert_eq!(args.format, Format::Text);
+        assert!(parse(&["scan"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_format() {
+        let PicmesArgs::Verify(args) = parse(&["verify", "a.png", "--format", "csv"]).unwrap()
+        else {
+            panic!("expected verify");
+        };
+        assert_eq!(args.format, Format::Csv);
+        let PicmesArgs::Stats(args) = parse(&["stats", "a.png", "--format", "yaml"]).unwrap()
+        else {
+            panic!("expected stats");
+        };
+        assert_eq!(args.format, Format::Yaml);
+        assert!(parse(&["scan", "a.png", "--format", "json", "--json"]).is_err());
+        assert!(parse(&["scan", "a.png", "--format", "xml"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_detect() {
+        let PicmesArgs::Detect(args) = parse(&["detect", "a.png", "b.png", "--json"]).unwrap()
+        else {
+            panic!("expected detect");
+        };
+        assert_eq!(args.files, ["a.png", "b.png"]);
+        assert!(args.json);
+        assert!(parse(&["detect"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_verify() {
+        let PicmesArgs::Verify(args) = parse(&["verify", "a.png", "b.png"]).unwrap() else {
+            panic!("expected verify");
+        };
+        assert_eq!(args.files, ["a.png", "b.png"]);
+        assert!(!args.json);
+        assert!(parse(&["verify"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_repair() {
+        let PicmesArgs::Repair(args) = parse(&["repair", "bad.png", "--truncate"]).unwrap() else {
+            panic!("expected repair");
+        };
+        assert_eq!(args.file, PathBuf::from("bad.png"));
+        assert_eq!(args.output, None);
+        assert!(args.truncate);
+        assert!(!args.drop);
+
+        let PicmesArgs::Repair(args) = parse(&["repair", "bad.png", "good.png", "--drop"]).unwrap()
+        else {
+            panic!("expected repair");
+        };
+        assert_eq!(args.output, Some(PathBuf::from("good.png")));
+        assert!(args.drop);
+    }
+
+    #[test]
+    fn test_parse_strip() {
+        let PicmesArgs::Strip(args) = parse(&["strip", "a.png", "--keep", "tEXt,pHYs"]).unwrap()
+        else {
+            panic!("expected strip");
+        };
+        assert_eq!(args.files, ["a.png"]);
+        assert_eq!(args.keep, ["tEXt", "pHYs"]);
+
+        let PicmesArgs::Strip(args) = parse(&["strip", "a.png", "b.png"]).unwrap() else {
+            panic!("expected strip");
+        };
+        assert!(args.keep.is_empty());
+    }
+
+    #[test]
+    fn test_parse_extract() {
+        let PicmesArgs::Extract(args) =
+            parse(&["extract", "in.png", "ruSt", "--out", "payload.bin"]).unwrap()
+        else {
+            panic!("expected extract");
+        };
+        assert_eq!(args.file, PathBuf::from("in.png"));
+        assert_eq!(args.chunk_type, "ruSt");
+        assert_eq!(args.out, PathBuf::from("payload.bin"));
+        assert!(!args.whole);
+        assert!(parse(&["extract", "in.png", "ruSt"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_encode_at() {
+        let PicmesArgs::Encode(args) =
+            parse(&["encode", "in.png", "ruSt", "hi", "--at", "2"]).unwrap()
+        else {
+            panic!("expected encode");
+        };
+        assert_eq!(args.at, Some(2));
+        assert!(parse(&["encode", "in.png", "ruSt", "hi", "--at", "-1"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_encode_keyword() {
+        let PicmesArgs::Encode(args) =
+            parse(&["encode", "in.png", "tEXt", "hi", "-k", "Title"]).unwrap()
+        else {
+            panic!("expected encode");
+        };
+        assert_eq!(args.keyword.as_deref(), Some("Title"));
+        assert!(parse(&["encode", "in.png", "tEXt", "-i", "a.txt", "-k", "Title"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_completions() {
+        let PicmesArgs::Completions(args) = parse(&["completions", "zsh"]).unwrap() else {
+            panic!("expected completions");
+        };
+        assert_eq!(args.shell, Shell::Zsh);
+        assert!(parse(&["completions", "tcsh"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_seal() {
+        let PicmesArgs::Seal(args) =
+            parse(&["seal", "in.png", "-p", "secret", "--cipher", "chacha20"]).unwrap()
+        else {
+            panic!("expected seal");
+        };
+        assert_eq!(args.passphrase.as_deref(), Some("secret"));
+        assert_eq!(args.cipher, Cipher::ChaCha20Poly1305);
+
+        let PicmesArgs::Unseal(args) = parse(&["unseal", "in.png", "--dry-run"]).unwrap() else {
+            panic!("expected unseal");
+        };
+        assert!(args.dry_run);
+        assert!(parse(&["unseal", "in.png", "--cipher", "aes"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_capacity() {
+        let PicmesArgs::Capacity(args) = parse(&["capacity", "in.png"]).unwrap() else {
+            panic!("expected capacity");
+        };
+        assert_eq!(args.method, None);
+
+        let PicmesArgs::Capacity(args) = parse(&["capacity", "in.png", "--method", "lsb"]).unwrap()
+        else {
+            panic!("expected capacity");
+        };
+        assert_eq!(args.method, Some(HidingMethod::Lsb));
+        assert!(parse(&["capacity", "in.png", "--method", "exif"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_stego() {
+        let PicmesArgs::Stego(StegoCommand::Hide(args)) =
+            parse(&["stego", "hide", "in.png", "hello", "-o", "out.png"]).unwrap()
+        else {
+            panic!("expected stego hide");
+        };
+        assert_eq!(args.message.as_deref(), Some("hello"));
+        assert_eq!(args.output, Some(PathBuf::from("out.png")));
+        assert!(parse(&["stego", "hide", "in.png"]).is_err());
+        assert!(parse(&["stego", "hide", "in.png", "hi", "-i", "msg.txt"]).is_err());
+
+        let PicmesArgs::Stego(StegoCommand::Reveal(args)) =
+            parse(&["stego", "reveal", "in.png", "--out", "msg.txt"]).unwrap()
+        else {
+            panic!("expected stego reveal");
+        };
+        assert_eq!(args.out, Some(PathBuf::from("msg.txt")));
+    }
+
+    #[test]
+    fn test_parse_time() {
+        let PicmesArgs::Time(TimeCommand::Get(args)) = parse(&["time", "get", "in.png"]).unwrap()
+        else {
+            panic!("expected time get");
+        };
+        assert_eq!(args.file, PathBuf::from("in.png"));
+
+        let PicmesArgs::Time(TimeCommand::Set(args)) =
+            parse(&["time", "set", "in.png", "2024-02-29T13:05:09Z"]).unwrap()
+        else {
+            panic!("expected time set");
+        };
+        assert_eq!(args.time.as_deref(), Some("2024-02-29T13:05:09Z"));
+        assert!(parse(&["time", "in.png"]).is_err());
+
+        let PicmesArgs::Strip(args) = parse(&["strip", "--touch", "a.png"]).unwrap() else {
+            panic!("expected strip");
+        };
+        assert!(args.touch);
+    }
+
+    #[cfg(feature = "keyring")]
+    #[test]
+    fn test_parse_keys() {
+        let PicmesArgs::Keys(KeysCommand::Store(args)) =
+            parse(&["keys", "store", "signing", "--file", "key.pem"]).unwrap()
+        else {
+            panic!("expected keys store");
+        };
+        assert_eq!(args.name, "signing");
+        assert_eq!(args.file, Some(PathBuf::from("key.pem")));
+
+        let PicmesArgs::Keys(KeysCommand::Load(args)) =
+            parse(&["keys", "load", "signing", "-o", "key.pem"]).unwrap()
+        else {
+            panic!("expected keys load");
+        };
+        assert_eq!(args.out, Some(PathBuf::from("key.pem")));
+        assert!(parse(&["keys", "delete"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_exif() {
+        let PicmesArgs::Exif(args) = parse(&["exif", "in.png"]).unwrap() else {
+            panic!("expected exif");
+        };
+        assert_eq!(args.file, PathBuf::from("in.png"));
+        assert_eq!((args.out, args.set), (None, None));
+
+        let PicmesArgs::Exif(args) = parse(&["exif", "in.png", "--set", "exif.bin"]).unwrap()
+        else {
+            panic!("expected exif");
+        };
+        assert_eq!(args.set, Some(PathBuf::from("exif.bin")));
+        assert!(parse(&["exif", "in.png", "--set", "a.bin", "-o", "b.bin"]).is_err());
+        assert!(parse(&["exif", "in.png", "--dry-run"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_encryption() {
+        let PicmesArgs::Encode(args) = parse(&[
+            "encode",
+            "in.png",
+            "ruSt",
+            "hi",
+            "--encrypt",
+            "-p",
+            "secret",
+        ])
+        .unwrap() else {
+            panic!("expected encode");
+        };
+        assert!(args.encrypt);
+        assert_eq!(args.passphrase.as_deref(), Some("secret"));
+        assert_eq!(args.cipher, Cipher::Aes256Gcm);
+
+        let PicmesArgs::Encode(args) = parse(&[
+            "encode", "in.png", "ruSt", "hi", "-e", "--cipher", "chacha20",
+        ])
+        .unwrap() else {
+            panic!("expected encode");
+        };
+        assert_eq!(args.cipher, Cipher::ChaCha20Poly1305);
+        assert!(parse(&["encode", "in.png", "ruSt", "hi", "-e", "--cipher", "des"]).is_err());
+        assert!(parse(&["encode", "in.png", "ruSt", "hi", "--cipher", "aes"]).is_err());
+
+        let PicmesArgs::Encode(args) = parse(&[
+            "encode",
+            "in.png",
+            "ruSt",
+            "hi",
+            "-e",
+            "--kdf-memory",
+            "65536",
+            "--kdf-iterations",
+            "3",
+        ])
+        .unwrap() else {
+            panic!("expected encode");
+        };
+        assert_eq!(
+            (args.kdf_memory, args.kdf_iterations),
+            (Some(65536), Some(3))
+        );
+        assert!(parse(&["encode", "in.png", "ruSt", "hi", "--kdf-memory", "8"]).is_err());
+        let PicmesArgs::Encode(args) =
+            parse(&["encode", "in.png", "ruSt", "hi", "-e", "--deterministic"]).unwrap()
+        else {
+            panic!("expected encode");
+        };
+        assert!(args.deterministic);
+        assert!(parse(&["encode", "in.png", "ruSt", "hi", "--deterministic"]).is_err());
+
+        let PicmesArgs::Encode(args) = parse(&[
+            "encode",
+            "in.png",
+            "ruSt",
+            "hi",
+            "--recipient",
+            "age1a",
+            "--recipient",
+            "age1b",
+        ])
+        .unwrap() else {
+            panic!("expected encode");
+        };
+        assert_eq!(args.recipient, ["age1a", "age1b"]);
+        let PicmesArgs::Encode(args) = parse(&[
+            "encode",
+            "in.png",
+            "ruSt",
+            "hi",
+            "--recipients-file",
+            "team.txt",
+        ])
+        .unwrap() else {
+            panic!("expected encode");
+        };
+        assert_eq!(args.recipients_file, Some(PathBuf::from("team.txt")));
+        assert!(parse(&[
+            "encode",
+            "in.png",
+            "ruSt",
+            "hi",
+            "-e",
+            "--recipient",
+            "age1a"
+        ])
+        .is_err());
+        assert!(parse(&["encode", "in.png", "ruSt", "hi", "-p", "secret"]).is_err());
+        assert!(parse(&["encode", "in.png", "tEXt", "hi", "-e", "-k", "Title"]).is_err());
+
+        let PicmesArgs::Decode(args) =
+            parse(&["decode", "in.png", "ruSt", "--passphrase", "secret"]).unwrap()
+        else {
+            panic!("expected decode");
+        };
+        assert_eq!(args.passphrase.as_deref(), Some("secret"));
+
+        let PicmesArgs::Decode(args) =
+            parse(&["decode", "in.png", "ruSt", "--identity", "key.txt"]).unwrap()
+        else {
+            panic!("expected decode");
+        };
+        assert_eq!(args.identity, Some(PathBuf::from("key.txt")));
+    }
+
+    #[test]
+    fn test_parse_signing() {
+        let PicmesArgs::Encode(args) =
+            parse(&["encode", "in.png", "ruSt", "hi", "--sign", "key.pem"]).unwrap()
+        else {
+            panic!("expected encode");
+        };
+        assert_eq!(args.sign, Some(PathBuf::from("key.pem")));
+        assert!(parse(&["encode", "in.png", "tEXt", "hi", "--sign", "k.pem", "-k", "T"]).is_err());
+
+        let PicmesArgs::Decode(args) =
+            parse(&["decode", "in.png", "ruSt", "--verify", "pub.pem"]).unwrap()
+        else {
+            panic!("expected decode");
+        };
+        assert_eq!(args.verify, Some(PathBuf::from("pub.pem")));
+
+        let PicmesArgs::Encode(args) =
+            parse(&["encode", "in.png", "ruSt", "hi", "--hmac-key", "shared"]).unwrap()
+        else {
+            panic!("expected encode");
+        };
+        assert_eq!(args.hmac_key.as_deref(), Some("shared"));
+        assert!(parse(&["encode", "in.png", "ruSt", "hi", "-e", "--hmac-key", "k"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_split_size() {
+        let PicmesArgs::Encode(args) =
+            parse(&["encode", "in.png", "ruSt", "hi", "--split-size", "4096"]).unwrap()
+        else {
+            panic!("expected encode");
+        };
+        assert_eq!(args.split_size, Some(4096));
+        assert!(parse(&["encode", "in.png", "ruSt", "hi", "--split-size", "0"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_type_key() {
+        let PicmesArgs::Encode(args) =
+            parse(&["encode", "in.png", "notes", "hi", "--type-key", "k"]).unwrap()
+        else {
+            panic!("expected encode");
+        };
+        assert_eq!(args.type_key.as_deref(), Some("k"));
+        assert_eq!(args.chunk_type, "notes");
+
+        let PicmesArgs::Decode(args) =
+            parse(&["decode", "in.png", "notes", "--type-key", "k"]).unwrap()
+        else {
+            panic!("expected decode");
+        };
+        assert_eq!(args.type_key.as_deref(), Some("k"));
+    }
+
+    #[test]
+    fn test_parse_decoy() {
+        let PicmesArgs::Encode(args) = parse(&[
+            "encode",
+            "in.png",
+            "ruSt",
+            "hi",
+            "-e",
+            "--decoy",
+            "bye",
+            "--decoy-passphrase",
+            "d",
+        ])
+        .unwrap() else {
+            panic!("expected encode");
+        };
+        assert_eq!(args.decoy.as_deref(), Some("bye"));
+        assert_eq!(args.decoy_passphrase.as_deref(), Some("d"));
+        assert!(parse(&["encode", "in.png", "ruSt", "hi", "-e", "--decoy", "bye"]).is_err());
+        assert!(parse(&[
+            "encode",
+            "in.png",
+            "ruSt",
+            "hi",
+            "--decoy",
+            "bye",
+            "--decoy-passphrase",
+            "d",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_parse_compress() {
+        let parse_compress = |extra: &[&str]| {
+            let mut args = vec!["encode", "in.png", "ruSt", "hi"];
+            args.extend(extra);
+            match parse(&args) {
+                Ok(PicmesArgs::Encode(args)) => Ok(args.compress),
+                Ok(_) => panic!("expected encode"),
+                Err(e) => Err(e),
+            }
+        };
+        assert_eq!(parse_compress(&[]).unwrap(), None);
+        assert_eq!(
+            parse_compress(&["--compress"]).unwrap(),
+            Some(Compressor::Zstd)
+        );
+        assert_eq!(
+            parse_compress(&["--compress", "deflate"]).unwrap(),
+            Some(Compressor::Deflate)
+        );
+        assert!(parse_compress(&["--compress", "lzma"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_fec() {
+        let parse_fec = |extra: &[&str]| {
+            let mut args = vec!["encode", "in.png", "ruSt", "hi"];
+            args.extend(extra);
+            match parse(&args) {
+                Ok(PicmesArgs::Encode(args)) => Ok(args.fec),
+                Ok(_) => panic!("expected encode"),
+                Err(e) => Err(e),
+            }
+        };
+        assert_eq!(parse_fec(&[]).unwrap(), None);
+        assert_eq!(parse_fec(&["--fec"]).unwrap(), Some(4));
+        assert_eq!(parse_fec(&["--fec", "9"]).unwrap(), Some(9));
+        assert!(parse_fec(&["--fec", "0"]).is_err());
+        assert!(parse_fec(&["--fec", "-k", "Title"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_split_across() {
+        let PicmesArgs::Encode(args) = parse(&[
+            "encode",
+            "a.png",
+            "ruSt",
+            "hi",
+            "--split-across",
+            "b.png",
+            "c.png",
+        ])
+        .unwrap() else {
+            panic!("expected encode");
+        };
+        assert_eq!(
+            args.split_across,
+            [PathBuf::from("b.png"), PathBuf::from("c.png")]
+        );
+        assert!(parse(&[
+            "encode",
+            "a.png",
+            "ruSt",
+            "hi",
+            "out.png",
+            "--split-across",
+            "b.png"
+        ])
+        .is_err());
+
+        let PicmesArgs::Decode(args) =
+            parse(&["decode", "a.png", "b.png", "ruSt", "--join"]).unwrap()
+        else {
+            panic!("expected decode");
+        };
+        assert!(args.join);
+        assert!(parse(&["decode", "a.png", "ruSt", "--join", "--all"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let PicmesArgs::Print(args) = parse(&["print", "--json", "in.png"]).unwrap() else {
+            panic!("expected print");
+        };
+        assert!(args.json);
+
+        let PicmesArgs::Decode(args) = parse(&["decode", "in.png", "ruSt", "--json"]).unwrap()
+        else {
+            panic!("expected decode");
+        };
+        assert!(args.json);
+    }
+
+    #[test]
+    fn test_parse_batch() {
+        let PicmesArgs::Decode(args) =
+            parse(&["decode", "-r", "a.png", "images/", "*.png", "ruSt"]).unwrap()
+        else {
+            panic!("expected decode");
+        };
+        assert_eq!(args.files, ["a.png", "images/", "*.png"]);
+        assert_eq!(args.chunk_type, "ruSt");
+        assert!(args.recursive);
+
+        let PicmesArgs::Print(args) = parse(&["print", "a.png", "b.png", "--recursive"]).unwrap()
+        else {
+            panic!("expected print");
+        };
+        assert_eq!(args.files, ["a.png", "b.png"]);
+        assert!(args.recursive);
+
+        let PicmesArgs::Encode(args) = parse(&["encode", "-r", "images/", "ruSt", "hi"]).unwrap()
+        else {
+            panic!("expected encode");
+        };
+        assert!(args.recursive);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["explode"]).is_err());
+        assert!(parse(&["remove", "in.png", "ruSt", "--nope"]).is_err());
+        assert!(parse(&["decode", "in.png", "ruSt", "--force"]).is_err());
+        assert!(parse(&["encode", "in.png", "ruSt", "hello", "out.png", "x"]).is_err());
+    }
+}
@@ -0,0 +1,111 @@
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{
+    chunk::Chunk,
+    png::{self, Png},
+    Result,
+};
+
+impl Png {
+    /// Parses a png from an async `reader` the way `from_reader` does, one
+    /// chunk at a time
+    pub async fn from_async_reader(mut reader: impl AsyncRead + Unpin) -> Result<Self> {
+        let mut header = [0; Png::HEADER_LENGTH];
+        let filled = read_up_to(&mut reader, &mut header).await?;
+        png::check_signature(&header[..filled])?;
+
+        let mut chunks = Vec::new();
+        loop {
+            let mut length = [0; Chunk::LEN_DATA_LENGTH];
+            let filled = read_up_to(&mut reader, &mut length).await?;
+            let Some(remaining) = png::chunk_remaining(&length[..filled])? else {
+                break;
+            };
+
+            let mut bytes = length.to_vec();
+            (&mut reader)
+                .take(remaining as u64)
+                .read_to_end(&mut bytes)
+                .await?;
+            chunks.push(png::read_chunk(&bytes, remaining)?);
+        }
+
+        Ok(Png::from_chunks(chunks))
+    }
+
+    /// Writes the png to an async `writer`, serializing one chunk at a time
+    /// like `write_to`
+    pub async fn write_async(&self, mut writer: impl AsyncWrite + Unpin) -> Result<()> {
+        writer.write_all(&Png::STANDARD_HEADER).await?;
+        let mut buffer = Vec::new();
+        for chunk in self.chunks() {
+            buffer.clear();
+            chunk.extend_into(&mut buffer);
+            writer.write_all(&buffer).await?;
+        }
+        writer.flush().await?;
+        Ok(())
+    }
+}
+
+/// Async counterpart of the reader helper in `png`, a short read only means
+/// the stream ended
+async fn read_up_to(reader: &mut (impl AsyncRead + Unpin), buf: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]).await {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chunk_type::ChunkType,
+        png::{
+            tests::{testing_file, testing_png_bytes},
+            PngError,
+        },
+    };
+
+    #[tokio::test]
+    async fn test_async_round_trip() {
+        let path = testing_file("async");
+        let file = tokio::fs::File::open(&path).await.unwrap();
+        let mut png = Png::from_async_reader(tokio::io::BufReader::new(file))
+            .await
+            .unwrap();
+        assert_eq!(png.as_bytes(), testing_png_bytes());
+
+        png.insert_chunk(Chunk::new(ChunkType::tEXt, b"Comment\0async".to_vec()));
+        let file = tokio::fs::File::create(&path).await.unwrap();
+        png.write_async(tokio::io::BufWriter::new(file))
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), png.as_bytes());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_async_reader_rejects_damaged_pngs() {
+        let bytes = testing_png_bytes();
+        assert!(Png::from_async_reader(&bytes[..4]).await.is_err());
+        assert!(Png::from_async_reader(&bytes[1..]).await.is_err());
+        assert!(Png::from_async_reader(&bytes[..bytes.len() - 1])
+            .await
+            .is_err());
+        let mut too_long = bytes.clone();
+        too_long[Png::HEADER_LENGTH..Png::HEADER_LENGTH + 4]
+            .copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(matches!(
+            Png::from_async_reader(too_long.as_slice()).await,
+            Err(crate::Error::Png(PngError::ChunkTooLong(_)))
+        ));
+    }
+}
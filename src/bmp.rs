@@ -0,0 +1,488 @@
+use std::{convert::TryFrom, fmt::Display, path::Path};
+
+use crate::{
+    carrier::{self, Section},
+    chunk::Chunk,
+    plan, Result,
+};
+
+#[derive(Debug)]
+pub enum BmpError {
+    InvalidHeader,
+    Truncated,
+}
+
+impl std::error::Error for BmpError {}
+
+impl Display for BmpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidHeader => write!(f, "A bmp should start with BM and a known header"),
+            Self::Truncated => write!(f, "The file is shorter than its headers say"),
+        }
+    }
+}
+
+/// Containers are padded so the pixel array after one stays aligned
+const CONTAINER_ALIGNMENT: usize = 4;
+
+const FILE_HEADER_LENGTH: usize = 14;
+const FILE_SIZE_OFFSET: usize = 2;
+const PIXEL_OFFSET_OFFSET: usize = 10;
+/// The OS/2 header, with 16-bit dimensions and three byte palette entries
+const CORE_HEADER_LENGTH: usize = 12;
+const INFO_HEADER_LENGTH: usize = 40;
+const V5_HEADER_LENGTH: usize = 124;
+const BI_BITFIELDS: u32 = 3;
+const BI_ALPHABITFIELDS: u32 = 6;
+/// `bV5CSType` value of a profile stored in the file
+const PROFILE_EMBEDDED: u32 = u32::from_be_bytes(*b"MBED");
+
+/// Where in a bmp new chunks are stored
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Region {
+    /// Between the headers and the pixel array, moving the pixels back
+    #[default]
+    Gap,
+    /// Right after the pixel array
+    Append,
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        bytes.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        bytes.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn set_u32_at(bytes: &mut [u8], offset: usize, value: u32) {
+    bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+/// The layout the headers of a bmp describe
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Layout {
+    /// End of the headers, colour masks and palette, which can run past
+    /// `pixel_offset` in a damaged file
+    header_end: usize,
+    pixel_offset: usize,
+    pixel_end: usize,
+}
+
+impl Layout {
+    fn parse(bytes: &[u8]) -> Option<Self> {
+        let dib_length = u32_at(bytes, FILE_HEADER_LENGTH)? as usize;
+        let dib = bytes.get(FILE_HEADER_LENGTH..FILE_HEADER_LENGTH + dib_length)?;
+        let pixel_offset = u32_at(bytes, PIXEL_OFFSET_OFFSET)? as usize;
+        if pixel_offset < FILE_HEADER_LENGTH + dib_length {
+            return None;
+        }
+
+        let (width, height, bit_count, palette_entry) = match dib_length {
+            CORE_HEADER_LENGTH => (
+                u16_at(dib, 4)? as u64,
+                u16_at(dib, 6)? as u64,
+                u16_at(dib, 10)?,
+                3,
+            ),
+            _ if dib_length >= INFO_HEADER_LENGTH => (
+                u64::try_from(u32_at(dib, 4)? as i32).ok()?,
+                (u32_at(dib, 8)? as i32).unsigned_abs() as u64,
+                u16_at(dib, 14)?,
+                4,
+            ),
+            _ => return None,
+        };
+        if width == 0 {
+            return None;
+        }
+        let (compression, image_size, colours_used) = match dib_length {
+            CORE_HEADER_LENGTH => (0, 0, 0),
+            _ => (u32_at(dib, 16)?, u32_at(dib, 20)?, u32_at(dib, 32)?),
+        };
+        // the masks only follow the plain info header, later ones hold them
+        let masks = match (dib_length, compression) {
+            (INFO_HEADER_LENGTH, BI_BITFIELDS) => 12,
+            (INFO_HEADER_LENGTH, BI_ALPHABITFIELDS) => 16,
+            _ => 0,
+        };
+        let colours = match (colours_used, bit_count) {
+            (0, 1..=8) => 1 << bit_count,
+            (used, _) => used as usize,
+        };
+        let header_end = FILE_HEADER_LENGTH + dib_length + masks + colours * palette_entry;
+
+        let pixel_length = match image_size {
+            0 => (bit_count as u64)
+                .checked_mul(width)?
+                .div_ceil(32)
+                .checked_mul(4)?
+                .checked_mul(height)?,
+            size => size as u64,
+        };
+        let pixel_end = (pixel_offset as u64)
+            .checked_add(pixel_length)?
+            .min(bytes.len() as u64) as usize;
+        (pixel_offset <= bytes.len()).then_some(Self {
+            header_end,
+            pixel_offset,
+            pixel_end,
+        })
+    }
+
+    /// Whether there is room between the palette and the pixel array for
+    /// the gap container
+    fn has_gap(&self) -> bool {
+        self.header_end <= self.pixel_offset
+    }
+}
+
+/// Moves every offset the headers hold that points at or past `position`
+/// by `delta` bytes: the pixel array and an embedded colour profile
+fn shift_offsets(bytes: &mut [u8], position: usize, delta: isize) {
+    let shift = |offset: usize| (offset as isize + delta) as u32;
+    if let Some(offset) = u32_at(bytes, PIXEL_OFFSET_OFFSET).map(|o| o as usize) {
+        if offset >= position {
+            set_u32_at(bytes, PIXEL_OFFSET_OFFSET, shift(offset));
+        }
+    }
+    let dib_length = u32_at(bytes, FILE_HEADER_LENGTH).unwrap_or(0) as usize;
+    if dib_length >= V5_HEADER_LENGTH
+        && u32_at(bytes, FILE_HEADER_LENGTH + 56) == Some(PROFILE_EMBEDDED)
+    {
+        // profile offsets count from the start of the info header
+        let field = FILE_HEADER_LENGTH + 112;
+        if let Some(offset) = u32_at(bytes, field).map(|o| o as usize + FILE_HEADER_LENGTH) {
+            if offset >= position {
+                set_u32_at(bytes, field, shift(offset) - FILE_HEADER_LENGTH as u32);
+            }
+        }
+    }
+}
+
+/// A bmp with the chunks hidden in it held apart from the image itself.
+/// They live in containers in the gap before the pixel array and right
+/// after it, and the header offsets are fixed up whenever those change
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bmp {
+    /// The file with both containers taken out
+    bytes: Vec<u8>,
+    layout: Layout,
+    gap: Vec<Chunk>,
+    appended: Vec<Chunk>,
+}
+
+impl Bmp {
+    pub const SIGNATURE: &'static [u8] = b"BM";
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        Self::try_from(std::fs::read(path)?.as_slice())
+    }
+
+    /// The hidden chunks, those in the gap first
+    pub fn chunks(&self) -> impl Iterator<Item = &Chunk> {
+        self.gap.iter().chain(&self.appended)
+    }
+
+    /// Lists the headers, gap, pixel array and whatever follows it, with
+    /// the chunks of both containers
+    pub fn sections(&self) -> Vec<Section> {
+        let section = |name: &str, length: usize| Section {
+            name: name.to_string(),
+            length,
+            chunk_type: None,
+        };
+        let chunk_sections = |chunks: &[Chunk], name: &str| -> Vec<Section> {
+            chunks
+                .iter()
+                .map(|chunk| Section {
+                    chunk_type: Some(chunk.chunk_type().clone()),
+                    ..section(name, chunk.length() + Chunk::META_DATA_LENGTH)
+                })
+                .collect()
+        };
+        let Layout {
+            header_end,
+            pixel_offset,
+            pixel_end,
+        } = self.layout;
+        let mut sections = vec![section("headers", header_end.min(pixel_offset))];
+        sections.extend(chunk_sections(&self.gap, "gap chunk"));
+        if pixel_offset > header_end {
+            sections.push(section("gap", pixel_offset - header_end));
+        }
+        sections.push(section("pixels", pixel_end - pixel_offset));
+        sections.extend(chunk_sections(&self.appended, "appended chunk"));
+        if self.bytes.len() > pixel_end {
+            sections.push(section("trailing data", self.bytes.len() - pixel_end));
+        }
+        sections
+    }
+
+    /// Adds `chunk` to the container of `region`. A palette that runs into
+    /// the pixel array leaves no gap, so the chunk is appended instead
+    pub fn insert_chunk(&mut self, chunk: Chunk, region: Region) {
+        match region {
+            Region::Gap if self.layout.has_gap() => self.gap.push(chunk),
+            _ => self.appended.push(chunk),
+        }
+    }
+
+    /// Removes every chunk matching `predicate`, returning them in file order
+    pub fn remove_chunks_where(&mut self, mut predicate: impl FnMut(&Chunk) -> bool) -> Vec<Chunk> {
+        let mut removed = Vec::new();
+        for chunks in [&mut self.gap, &mut self.appended] {
+            let mut kept = Vec::new();
+            for chunk in chunks.drain(..) {
+                match predicate(&chunk) {
+                    true => removed.push(chunk),
+                    false => kept.push(chunk),
+                }
+            }
+            *chunks = kept;
+        }
+        removed
+    }
+
+    pub fn remove_first_chunk(&mut self, chunk_type: &str) -> Option<Chunk> {
+        let mut first = true;
+        self.remove_chunks_where(|chunk| {
+            chunk.chunk_type().to_string() == chunk_type && std::mem::take(&mut first)
+        })
+        .pop()
+    }
+
+    pub fn remove_all_chunks(&mut self, chunk_type: &str) -> Vec<Chunk> {
+        self.remove_chunks_where(|chunk| chunk.chunk_type().to_string() == chunk_type)
+    }
+
+    /// Puts the containers back in, moving the pixel array and anything
+    /// after them and updating the headers to match
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let gap = carrier::pack_chunks(&self.gap, CONTAINER_ALIGNMENT);
+        let appended = carrier::pack_chunks(&self.appended, CONTAINER_ALIGNMENT);
+        let Layout {
+            header_end,
+            pixel_end,
+            ..
+        } = self.layout;
+
+        let mut bytes = self.bytes.clone();
+        shift_offsets(&mut bytes, pixel_end, appended.len() as isize);
+        bytes.splice(pixel_end..pixel_end, appended);
+        // only filled when the layout has a gap, `header_end` can lie past
+        // the end of the file otherwise
+        if !gap.is_empty() {
+            shift_offsets(&mut bytes, header_end, gap.len() as isize);
+            bytes.splice(header_end..header_end, gap);
+        }
+        let size = bytes.len() as u32;
+        set_u32_at(&mut bytes, FILE_SIZE_OFFSET, size);
+        bytes
+    }
+
+    /// The size of the bmp in bytes once written
+    pub fn encoded_length(&self) -> usize {
+        self.bytes.len()
+            + carrier::packed_length(&self.gap, CONTAINER_ALIGNMENT)
+            + carrier::packed_length(&self.appended, CONTAINER_ALIGNMENT)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        plan::write_atomic(path.as_ref(), &self.as_bytes())
+    }
+}
+
+impl TryFrom<&[u8]> for Bmp {
+    type Error = crate::Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        if !bytes.starts_with(Self::SIGNATURE) {
+            return Err(BmpError::InvalidHeader.into());
+        }
+        if bytes.len() < FILE_HEADER_LENGTH + CORE_HEADER_LENGTH {
+            return Err(BmpError::Truncated.into());
+        }
+        let mut bytes = bytes.to_vec();
+        let layout = Layout::parse(&bytes).ok_or(BmpError::InvalidHeader)?;
+
+        let mut gap = Vec::new();
+        let gap_bytes = match layout.has_gap() {
+            true => &bytes[layout.header_end..layout.pixel_offset],
+            false => &[],
+        };
+        if let Some((chunks, length)) = carrier::unpack_chunks(gap_bytes)? {
+            gap = chunks;
+            bytes.drain(layout.header_end..layout.header_end + length);
+            shift_offsets(&mut bytes, layout.header_end, -(length as isize));
+        }
+        let layout = Layout::parse(&bytes).ok_or(BmpError::Truncated)?;
+
+        let mut appended = Vec::new();
+        if let Some((chunks, length)) = carrier::unpack_chunks(&bytes[layout.pixel_end..])? {
+            appended = chunks;
+            bytes.drain(layout.pixel_end..layout.pixel_end + length);
+            shift_offsets(&mut bytes, layout.pixel_end, -(length as isize));
+        }
+        // the size is rewritten on every save anyway
+        let size = bytes.len() as u32;
+        set_u32_at(&mut bytes, FILE_SIZE_OFFSET, size);
+        Ok(Self {
+            layout: Layout::parse(&bytes).ok_or(BmpError::Truncated)?,
+            bytes,
+            gap,
+            appended,
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{
+        carrier::tests::{assert_chunks_round_trip, assert_round_trip},
+        png::tests::testing_chunk,
+    };
+
+    /// A 2x2 24-bit bmp with a two byte gap before the pixel array, so
+    /// every row is padded to four bytes
+    pub(crate) fn testing_bmp_bytes() -> Vec<u8> {
+        let mut bytes = b"BM".to_vec();
+        bytes.extend(0u32.to_le_bytes());
+        bytes.extend([0; 4]);
+        bytes.extend(56u32.to_le_bytes());
+        bytes.extend(40u32.to_le_bytes());
+        bytes.extend(2i32.to_le_bytes());
+        bytes.extend(2i32.to_le_bytes());
+        bytes.extend(1u16.to_le_bytes());
+        bytes.extend(24u16.to_le_bytes());
+        bytes.extend([0; 24]);
+        bytes.extend([0xaa, 0xbb]);
+        bytes.extend([0, 0, 255, 255, 255, 255, 0, 0]);
+        bytes.extend([255, 0, 0, 0, 255, 0, 0, 0]);
+        let size = bytes.len() as u32;
+        set_u32_at(&mut bytes, FILE_SIZE_OFFSET, size);
+        bytes
+    }
+
+    fn names(bmp: &Bmp) -> Vec<String> {
+        bmp.sections().into_iter().map(|s| s.name).collect()
+    }
+
+    #[test]
+    fn test_layout() {
+        let bytes = testing_bmp_bytes();
+        let bmp = Bmp::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(
+            bmp.layout,
+            Layout {
+                header_end: 54,
+                pixel_offset: 56,
+                pixel_end: 72,
+            }
+        );
+        assert_eq!(names(&bmp), ["headers", "gap", "pixels"]);
+        assert_round_trip(&bytes);
+    }
+
+    #[test]
+    fn test_insert_and_remove_chunks() {
+        let bytes = testing_bmp_bytes();
+        let mut bmp = Bmp::try_from(bytes.as_slice()).unwrap();
+        bmp.insert_chunk(testing_chunk(b"in the gap"), Region::Gap);
+        bmp.insert_chunk(testing_chunk(b"after"), Region::Append);
+        assert_eq!(
+            names(&bmp),
+            ["headers", "gap chunk", "gap", "pixels", "appended chunk"]
+        );
+
+        let written = bmp.as_bytes();
+        assert_eq!(
+            u32_at(&written, FILE_SIZE_OFFSET),
+            Some(written.len() as u32)
+        );
+        let pixel_offset = u32_at(&written, PIXEL_OFFSET_OFFSET).unwrap() as usize;
+        assert_eq!(pixel_offset % CONTAINER_ALIGNMENT, 0);
+        assert_eq!(
+            &written[pixel_offset - 2..pixel_offset + 16],
+            &bytes[54..72]
+        );
+
+        let removed = assert_chunks_round_trip(bmp, &[b"in the gap", b"after"]);
+        assert_eq!(removed.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_embedded_profile_offset_is_fixed() {
+        // a v5 header whose profile follows the pixel array
+        let mut bytes = b"BM".to_vec();
+        bytes.extend([0; 8]);
+        bytes.extend(138u32.to_le_bytes());
+        let mut dib = vec![0; V5_HEADER_LENGTH];
+        set_u32_at(&mut dib, 0, V5_HEADER_LENGTH as u32);
+        set_u32_at(&mut dib, 4, 1);
+        set_u32_at(&mut dib, 8, 1);
+        dib[14] = 32;
+        set_u32_at(&mut dib, 56, PROFILE_EMBEDDED);
+        set_u32_at(&mut dib, 112, V5_HEADER_LENGTH as u32 + 4);
+        set_u32_at(&mut dib, 116, 3);
+        bytes.extend(dib);
+        bytes.extend([1, 2, 3, 4]);
+        bytes.extend(b"icc");
+
+        let mut bmp = Bmp::try_from(bytes.as_slice()).unwrap();
+        bmp.insert_chunk(testing_chunk(b"moves the profile"), Region::Gap);
+        bmp.insert_chunk(testing_chunk(b"and again"), Region::Append);
+        let written = bmp.as_bytes();
+        let profile = u32_at(&written, FILE_HEADER_LENGTH + 112).unwrap() as usize;
+        assert_eq!(&written[FILE_HEADER_LENGTH + profile..], b"icc");
+        assert_eq!(Bmp::try_from(written.as_slice()).unwrap(), bmp);
+    }
+
+    #[test]
+    fn test_palette_past_the_pixels() {
+        // 1000 palette entries end long after the pixel array starts
+        let mut bytes = testing_bmp_bytes();
+        set_u32_at(&mut bytes, FILE_HEADER_LENGTH + 32, 1000);
+        let mut bmp = Bmp::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(names(&bmp), ["headers", "pixels"]);
+        assert_round_trip(&bytes);
+
+        bmp.insert_chunk(testing_chunk(b"no gap"), Region::Gap);
+        assert_eq!(names(&bmp), ["headers", "pixels", "appended chunk"]);
+        let removed = assert_chunks_round_trip(bmp, &[b"no gap"]);
+        assert_eq!(removed.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_invalid_bmps() {
+        assert!(Bmp::try_from(&b"BA"[..]).is_err());
+        let bytes = testing_bmp_bytes();
+        assert!(Bmp::try_from(&bytes[..20]).is_err());
+
+        // the pixel array can't start inside the headers
+        let mut overlapping = bytes.clone();
+        set_u32_at(&mut overlapping, PIXEL_OFFSET_OFFSET, 20);
+        assert!(Bmp::try_from(overlapping.as_slice()).is_err());
+
+        for width in [0, -1, i32::MAX] {
+            let mut huge = bytes.clone();
+            huge[18..22].copy_from_slice(&width.to_le_bytes());
+            huge[22..26].copy_from_slice(&i32::MAX.to_le_bytes());
+            huge[28..30].copy_from_slice(&u16::MAX.to_le_bytes());
+            assert!(Bmp::try_from(huge.as_slice()).is_err());
+        }
+
+        let mut damaged = Bmp::try_from(bytes.as_slice()).unwrap();
+        damaged.insert_chunk(testing_chunk(b"damaged"), Region::Gap);
+        let mut written = damaged.as_bytes();
+        written[70] ^= 1;
+        assert!(Bmp::try_from(written.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,574 @@
+use std::{
+    fmt::Display,
+    fs::File,
+    io::{BufReader, Read},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    bmp::{self, Bmp},
+    chunk::{Chunk, MAX_CHUNK_LENGTH},
+    chunk_type::ChunkType,
+    gif::Gif,
+    ico::Ico,
+    jpeg::{Jpeg, SegmentKind},
+    png::{Png, PngError},
+    tiff::Tiff,
+    webp::Webp,
+    Result,
+};
+
+#[derive(Debug)]
+pub enum CarrierError {
+    Unsupported {
+        format: &'static str,
+        feature: &'static str,
+    },
+    InvalidContainer,
+    UnknownFormat(PathBuf),
+}
+
+impl std::error::Error for CarrierError {}
+
+impl Display for CarrierError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unsupported { format, feature } => {
+                write!(
+                    f,
+                    "{} is only supported for png files, not {}",
+                    feature, format
+                )
+            }
+            Self::InvalidContainer => write!(f, "The hidden chunk container is damaged"),
+            Self::UnknownFormat(path) => write!(
+                f,
+                "'{}' is not an image picmes can read, the supported formats are {}",
+                path.display(),
+                FORMATS.join(", ")
+            ),
+        }
+    }
+}
+
+/// Starts a container of chunks stored in a format without chunks of its
+/// own. The last byte is the format version
+const CONTAINER_MAGIC: &[u8] = b"pmC\x01";
+/// `CONTAINER_MAGIC | length of the chunks and padding`
+const CONTAINER_HEADER_LENGTH: usize = CONTAINER_MAGIC.len() + 4;
+
+/// The formats carriers can be read from, as `Carrier::format` names them
+pub const FORMATS: [&str; 8] = ["png", "jpeg", "gif", "webp", "bmp", "tiff", "ico", "cur"];
+
+/// Enough bytes to tell every supported format apart
+const SNIFF_LENGTH: u64 = 12;
+
+/// Where new chunks go in the formats that offer a choice
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Placement {
+    pub segment: SegmentKind,
+    pub region: bmp::Region,
+}
+
+/// One entry of the structural listing of a carrier, a chunk, segment or
+/// block depending on the format
+#[derive(Debug, PartialEq)]
+pub struct Section {
+    pub name: String,
+    /// The length in the file, headers included
+    pub length: usize,
+    /// The type of the payload chunk held by sections picmes wrote
+    pub chunk_type: Option<ChunkType>,
+}
+
+/// An image payload chunks can be hidden in. Pngs store them as chunks of
+/// their own, other formats in whatever place they have for extra data
+#[derive(Debug)]
+pub enum Carrier {
+    Png(Png),
+    Jpeg(Jpeg),
+    Gif(Gif),
+    Webp(Webp),
+    Bmp(Bmp),
+    Tiff(Tiff),
+    Ico(Ico),
+}
+
+impl From<Png> for Carrier {
+    fn from(png: Png) -> Self {
+        Self::Png(png)
+    }
+}
+
+impl From<Jpeg> for Carrier {
+    fn from(jpeg: Jpeg) -> Self {
+        Self::Jpeg(jpeg)
+    }
+}
+
+impl From<Gif> for Carrier {
+    fn from(gif: Gif) -> Self {
+        Self::Gif(gif)
+    }
+}
+
+impl From<Webp> for Carrier {
+    fn from(webp: Webp) -> Self {
+        Self::Webp(webp)
+    }
+}
+
+impl From<Bmp> for Carrier {
+    fn from(bmp: Bmp) -> Self {
+        Self::Bmp(bmp)
+    }
+}
+
+impl From<Tiff> for Carrier {
+    fn from(tiff: Tiff) -> Self {
+        Self::Tiff(tiff)
+    }
+}
+
+impl From<Ico> for Carrier {
+    fn from(ico: Ico) -> Self {
+        Self::Ico(ico)
+    }
+}
+
+impl Carrier {
+    /// Reads the image at `path`, telling the format from its first bytes
+    /// rather than its extension
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        Self::from_reader(BufReader::new(File::open(path)?), path)
+    }
+
+    /// Reads an image from `reader`, telling the format from its first bytes.
+    /// A png is parsed one chunk at a time as it is read, the other formats
+    /// are read whole first. `source` only names the image in errors
+    pub fn from_reader(mut reader: impl Read, source: impl AsRef<Path>) -> Result<Self> {
+        let mut start = Vec::new();
+        (&mut reader).take(SNIFF_LENGTH).read_to_end(&mut start)?;
+        let mut reader = start.as_slice().chain(reader);
+        if start.starts_with(&Png::STANDARD_HEADER) {
+            return Ok(Self::Png(Png::from_reader(reader)?));
+        }
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        Self::from_bytes(&bytes, source)
+    }
+
+    /// Parses an image already in memory, telling the format from its first
+    /// bytes like `from_file`. `source` only names the image in errors
+    pub fn from_bytes(bytes: &[u8], source: impl AsRef<Path>) -> Result<Self> {
+        if bytes.starts_with(&Png::STANDARD_HEADER) {
+            Ok(Self::Png(Png::try_from(bytes)?))
+        } else if bytes.starts_with(&Jpeg::START_OF_IMAGE) {
+            Ok(Self::Jpeg(Jpeg::try_from(bytes)?))
+        } else if bytes.starts_with(Gif::SIGNATURE) {
+            Ok(Self::Gif(Gif::try_from(bytes)?))
+        } else if Webp::has_signature(bytes) {
+            Ok(Self::Webp(Webp::try_from(bytes)?))
+        } else if bytes.starts_with(Bmp::SIGNATURE) {
+            Ok(Self::Bmp(Bmp::try_from(bytes)?))
+        } else if Tiff::has_signature(bytes) {
+            Ok(Self::Tiff(Tiff::try_from(bytes)?))
+        } else if Ico::has_signature(bytes) {
+            Ok(Self::Ico(Ico::try_from(bytes)?))
+        } else {
+            Err(CarrierError::UnknownFormat(source.as_ref().to_path_buf()).into())
+        }
+    }
+
+    pub fn format(&self) -> &'static str {
+        match self {
+            Self::Png(_) => "png",
+            Self::Jpeg(_) => "jpeg",
+            Self::Gif(_) => "gif",
+            Self::Webp(_) => "webp",
+            Self::Bmp(_) => "bmp",
+            Self::Tiff(_) => "tiff",
+            Self::Ico(ico) => ico.format(),
+        }
+    }
+
+    /// The png, or the selected entry of an icon, for features that only
+    /// pngs support
+    pub fn png_mut(&mut self, feature: &'static str) -> Result<&mut Png> {
+        match self {
+            Self::Png(png) => Ok(png),
+            Self::Ico(ico) => ico.png_mut(),
+            _ => Err(CarrierError::Unsupported {
+                format: self.format(),
+                feature,
+            }
+            .into()),
+        }
+    }
+
+    /// Lists the structure of the file. Png chunks are listed as such, so
+    /// only ancillary ones carry a chunk type
+    pub fn sections(&self) -> Vec<Section> {
+        match self {
+            Self::Png(png) => png
+                .chunks()
+                .map(|chunk| Section {
+                    name: chunk.chunk_type().to_string(),
+                    length: chunk.length() + Chunk::META_DATA_LENGTH,
+                    chunk_type: (!chunk.chunk_type().is_critical())
+                        .then(|| chunk.chunk_type().clone()),
+                })
+                .collect(),
+            Self::Jpeg(jpeg) => jpeg.sections(),
+            Self::Gif(gif) => gif.sections(),
+            Self::Webp(webp) => webp.sections(),
+            Self::Bmp(bmp) => bmp.sections(),
+            Self::Tiff(tiff) => tiff.sections(),
+            Self::Ico(ico) => ico.sections(),
+        }
+    }
+
+    /// Every chunk of a png, or the payload chunks stored in other formats
+    pub fn chunks(&self) -> Box<dyn Iterator<Item = &Chunk> + '_> {
+        match self {
+            Self::Png(png) => Box::new(png.chunks()),
+            Self::Jpeg(jpeg) => Box::new(jpeg.chunks()),
+            Self::Gif(gif) => Box::new(gif.chunks()),
+            Self::Webp(webp) => Box::new(webp.chunks()),
+            Self::Bmp(bmp) => Box::new(bmp.chunks()),
+            Self::Tiff(tiff) => Box::new(tiff.chunks()),
+            Self::Ico(ico) => Box::new(ico.chunks()),
+        }
+    }
+
+    /// Iterates over every chunk of the given type in file order
+    pub fn chunks_by_type<'a>(
+        &'a self,
+        chunk_type: &'a str,
+    ) -> Box<dyn Iterator<Item = &'a Chunk> + 'a> {
+        match self {
+            Self::Png(png) => Box::new(png.chunks_by_type(chunk_type)),
+            Self::Ico(ico) => Box::new(ico.chunks_by_type(chunk_type)),
+            _ => Box::new(
+                self.chunks()
+                    .filter(move |c| c.chunk_type().bytes() == chunk_type.as_bytes()),
+            ),
+        }
+    }
+
+    /// The most data a single chunk can hold in this format
+    pub fn max_chunk_length(&self) -> usize {
+        match self {
+            Self::Png(_)
+            | Self::Gif(_)
+            | Self::Webp(_)
+            | Self::Bmp(_)
+            | Self::Tiff(_)
+            | Self::Ico(_) => MAX_CHUNK_LENGTH,
+            Self::Jpeg(_) => Jpeg::MAX_PAYLOAD_LENGTH,
+        }
+    }
+
+    /// Inserts `chunk` where the format allows it, following `placement`
+    /// where the format leaves a choice
+    pub fn insert_chunk(&mut self, chunk: Chunk, placement: Placement) -> Result<()> {
+        match self {
+            Self::Png(png) => png.insert_chunk(chunk),
+            Self::Jpeg(jpeg) => jpeg.insert_chunk(chunk, placement.segment)?,
+            Self::Gif(gif) => gif.insert_chunk(chunk),
+            Self::Webp(webp) => webp.insert_chunk(chunk)?,
+            Self::Bmp(bmp) => bmp.insert_chunk(chunk, placement.region),
+            Self::Tiff(tiff) => tiff.insert_chunk(chunk),
+            Self::Ico(ico) => ico.insert_chunk(chunk)?,
+        }
+        Ok(())
+    }
+
+    /// Removes the first chunk of the given type
+    pub fn remove_first_chunk(&mut self, chunk_type: &str) -> Result<Chunk> {
+        match self {
+            Self::Png(png) => png.remove_first_chunk(chunk_type),
+            Self::Jpeg(jpeg) => Ok(jpeg
+                .remove_first_chunk(chunk_type)
+                .ok_or(PngError::UnknownChunkType)?),
+            Self::Gif(gif) => Ok(gif
+                .remove_first_chunk(chunk_type)
+                .ok_or(PngError::UnknownChunkType)?),
+            Self::Webp(webp) => Ok(webp
+                .remove_first_chunk(chunk_type)
+                .ok_or(PngError::UnknownChunkType)?),
+            Self::Bmp(bmp) => Ok(bmp
+                .remove_first_chunk(chunk_type)
+                .ok_or(PngError::UnknownChunkType)?),
+            Self::Tiff(tiff) => Ok(tiff
+                .remove_first_chunk(chunk_type)
+                .ok_or(PngError::UnknownChunkType)?),
+            Self::Ico(ico) => ico.remove_first_chunk(chunk_type),
+        }
+    }
+
+    /// Removes every chunk of the given type, returning them in file order
+    pub fn remove_all_chunks(&mut self, chunk_type: &str) -> Vec<Chunk> {
+        match self {
+            Self::Png(png) => png.remove_all_chunks(chunk_type),
+            Self::Jpeg(jpeg) => jpeg.remove_all_chunks(chunk_type),
+            Self::Gif(gif) => gif.remove_all_chunks(chunk_type),
+            Self::Webp(webp) => webp.remove_all_chunks(chunk_type),
+            Self::Bmp(bmp) => bmp.remove_all_chunks(chunk_type),
+            Self::Tiff(tiff) => tiff.remove_all_chunks(chunk_type),
+            Self::Ico(ico) => ico.remove_all_chunks(chunk_type),
+        }
+    }
+
+    /// Removes every chunk matching `predicate` where it lies, returning
+    /// them in file order
+    pub fn remove_chunks_where(&mut self, predicate: impl FnMut(&Chunk) -> bool) -> Vec<Chunk> {
+        match self {
+            Self::Png(png) => png.remove_chunks_where(predicate),
+            Self::Jpeg(jpeg) => jpeg.remove_chunks_where(predicate),
+            Self::Gif(gif) => gif.remove_chunks_where(predicate),
+            Self::Webp(webp) => webp.remove_chunks_where(predicate),
+            Self::Bmp(bmp) => bmp.remove_chunks_where(predicate),
+            Self::Tiff(tiff) => tiff.remove_chunks_where(predicate),
+            Self::Ico(ico) => ico.remove_chunks_where(predicate),
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::Png(png) => png.as_bytes(),
+            Self::Jpeg(jpeg) => jpeg.as_bytes(),
+            Self::Gif(gif) => gif.as_bytes(),
+            Self::Webp(webp) => webp.as_bytes(),
+            Self::Bmp(bmp) => bmp.as_bytes(),
+            Self::Tiff(tiff) => tiff.as_bytes(),
+            Self::Ico(ico) => ico.as_bytes(),
+        }
+    }
+
+    /// The size of the image in bytes once written, without writing it
+    pub fn encoded_length(&self) -> usize {
+        match self {
+            Self::Png(png) => png.encoded_length(),
+            Self::Jpeg(jpeg) => jpeg.encoded_length(),
+            Self::Gif(gif) => gif.encoded_length(),
+            Self::Webp(webp) => webp.encoded_length(),
+            Self::Bmp(bmp) => bmp.encoded_length(),
+            Self::Tiff(tiff) => tiff.encoded_length(),
+            Self::Ico(ico) => ico.encoded_length(),
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        match self {
+            Self::Png(png) => png.save(path),
+            Self::Jpeg(jpeg) => jpeg.save(path),
+            Self::Gif(gif) => gif.save(path),
+            Self::Webp(webp) => webp.save(path),
+            Self::Bmp(bmp) => bmp.save(path),
+            Self::Tiff(tiff) => tiff.save(path),
+            Self::Ico(ico) => ico.save(path),
+        }
+    }
+}
+
+/// The length of what `pack_chunks` returns for the same arguments
+pub fn packed_length(chunks: &[Chunk], alignment: usize) -> usize {
+    if chunks.is_empty() {
+        return 0;
+    }
+    let chunks: usize = chunks
+        .iter()
+        .map(|chunk| chunk.length() + Chunk::META_DATA_LENGTH)
+        .sum();
+    (CONTAINER_HEADER_LENGTH + chunks).next_multiple_of(alignment)
+}
+
+/// Packs `chunks` into a container, padded to a multiple of `alignment`
+/// bytes. No chunks need no container
+pub fn pack_chunks(chunks: &[Chunk], alignment: usize) -> Vec<u8> {
+    if chunks.is_empty() {
+        return Vec::new();
+    }
+    // the body length is filled in once the chunks are written
+    let mut bytes = CONTAINER_MAGIC.to_vec();
+    bytes.extend([0; 4]);
+    for chunk in chunks {
+        chunk.extend_into(&mut bytes);
+    }
+    bytes.resize(bytes.len().next_multiple_of(alignment), 0);
+    let body_length = (bytes.len() - CONTAINER_HEADER_LENGTH) as u32;
+    bytes[CONTAINER_MAGIC.len()..CONTAINER_HEADER_LENGTH]
+        .copy_from_slice(&body_length.to_be_bytes());
+    bytes
+}
+
+/// The chunks and length of the container at the start of `bytes`, if
+/// there is one
+pub fn unpack_chunks(bytes: &[u8]) -> Result<Option<(Vec<Chunk>, usize)>> {
+    if !bytes.starts_with(CONTAINER_MAGIC) {
+        return Ok(None);
+    }
+    let length = bytes
+        .get(CONTAINER_MAGIC.len()..CONTAINER_HEADER_LENGTH)
+        .ok_or(CarrierError::InvalidContainer)?;
+    let length = u32::from_be_bytes(length.try_into()?) as usize;
+    let body = bytes
+        .get(CONTAINER_HEADER_LENGTH..CONTAINER_HEADER_LENGTH + length)
+        .ok_or(CarrierError::InvalidContainer)?;
+
+    let mut chunks = Vec::new();
+    let mut offset = 0;
+    // whatever is too short to be a chunk is padding
+    while body.len() - offset >= Chunk::META_DATA_LENGTH {
+        let data_length = u32::from_be_bytes(body[offset..offset + 4].try_into()?) as usize;
+        let end = offset + Chunk::META_DATA_LENGTH + data_length;
+        let chunk = body
+            .get(offset..end)
+            .ok_or(CarrierError::InvalidContainer)?;
+        chunks.push(Chunk::try_from(chunk)?);
+        offset = end;
+    }
+    Ok(Some((chunks, CONTAINER_HEADER_LENGTH + length)))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{
+        bmp::tests::testing_bmp_bytes, gif::tests::testing_gif_bytes,
+        ico::tests::testing_ico_bytes, jpeg::tests::testing_jpeg_bytes, png::tests::testing_file,
+        tiff::tests::testing_tiff_bytes, webp::tests::testing_webp_bytes,
+    };
+    use std::{fs, str::FromStr};
+
+    /// Checks that `bytes` parse into a carrier without payloads that writes
+    /// them back unchanged
+    pub(crate) fn assert_round_trip(bytes: &[u8]) {
+        let carrier = Carrier::from_bytes(bytes, "image").unwrap();
+        assert_eq!(carrier.chunks_by_type("ruSt").count(), 0);
+        assert_eq!(carrier.as_bytes(), bytes);
+    }
+
+    /// Checks that `carrier`, holding ruSt chunks with `data` in file order,
+    /// writes them so they read back the same and can be removed again.
+    /// Returns the reread carrier without them
+    pub(crate) fn assert_chunks_round_trip(carrier: impl Into<Carrier>, data: &[&[u8]]) -> Carrier {
+        let written = carrier.into().as_bytes();
+        let mut reread = Carrier::from_bytes(&written, "image").unwrap();
+        assert_eq!(reread.as_bytes(), written);
+        let stored: Vec<&[u8]> = reread.chunks_by_type("ruSt").map(Chunk::data).collect();
+        assert_eq!(stored, data);
+
+        assert_eq!(reread.remove_first_chunk("ruSt").unwrap().data(), data[0]);
+        assert_eq!(reread.remove_all_chunks("ruSt").len(), data.len() - 1);
+        assert!(reread.remove_first_chunk("ruSt").is_err());
+        reread
+    }
+
+    #[test]
+    fn test_from_file_sniffs_format() {
+        // named .png whatever it holds
+        let file = testing_file("carrier-png");
+        assert_eq!(Carrier::from_file(&file).unwrap().format(), "png");
+        fs::write(&file, testing_jpeg_bytes()).unwrap();
+        assert_eq!(Carrier::from_file(&file).unwrap().format(), "jpeg");
+        fs::write(&file, testing_gif_bytes()).unwrap();
+        assert_eq!(Carrier::from_file(&file).unwrap().format(), "gif");
+        fs::write(&file, testing_webp_bytes()).unwrap();
+        assert_eq!(Carrier::from_file(&file).unwrap().format(), "webp");
+        fs::write(&file, testing_bmp_bytes()).unwrap();
+        assert_eq!(Carrier::from_file(&file).unwrap().format(), "bmp");
+        fs::write(&file, testing_tiff_bytes()).unwrap();
+        assert_eq!(Carrier::from_file(&file).unwrap().format(), "tiff");
+        fs::write(&file, testing_ico_bytes()).unwrap();
+        assert_eq!(Carrier::from_file(&file).unwrap().format(), "ico");
+        fs::write(&file, b"neither").unwrap();
+        let error = Carrier::from_file(&file).unwrap_err().to_string();
+        assert!(error.contains("png, jpeg, gif, webp, bmp, tiff, ico, cur"));
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_from_reader_matches_from_bytes() {
+        /// Hands out a byte at a time, like a slow pipe
+        struct Trickle<'a>(&'a [u8]);
+
+        impl Read for Trickle<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let n = buf.len().min(self.0.len()).min(1);
+                buf[..n].copy_from_slice(&self.0[..n]);
+                self.0 = &self.0[n..];
+                Ok(n)
+            }
+        }
+
+        for bytes in [crate::png::tests::testing_png_bytes(), testing_jpeg_bytes()] {
+            let carrier = Carrier::from_reader(Trickle(&bytes), "trickle").unwrap();
+            assert_eq!(carrier.as_bytes(), bytes);
+        }
+        assert!(Carrier::from_reader(Trickle(b"neither"), "trickle").is_err());
+    }
+
+    #[test]
+    fn test_from_bytes_sniffs_format() {
+        let images = [
+            (crate::png::tests::testing_png_bytes(), "png"),
+            (testing_jpeg_bytes(), "jpeg"),
+            (testing_gif_bytes(), "gif"),
+            (testing_webp_bytes(), "webp"),
+            (testing_bmp_bytes(), "bmp"),
+            (testing_tiff_bytes(), "tiff"),
+            (testing_ico_bytes(), "ico"),
+        ];
+        for (bytes, format) in images {
+            let mut carrier = Carrier::from_bytes(&bytes, "image").unwrap();
+            assert_eq!(carrier.format(), format);
+            assert_eq!(carrier.encoded_length(), bytes.len());
+            let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), vec![7; 300]);
+            carrier.insert_chunk(chunk, Placement::default()).unwrap();
+            assert_eq!(carrier.encoded_length(), carrier.as_bytes().len());
+        }
+        let error = Carrier::from_bytes(b"neither", "https://example.com/a.png").unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("'https://example.com/a.png' is not"));
+    }
+
+    #[test]
+    fn test_pack_and_unpack_chunks() {
+        let chunks = vec![
+            Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"first".to_vec()),
+            Chunk::new(ChunkType::from_str("ruSt").unwrap(), vec![]),
+        ];
+        let packed = pack_chunks(&chunks, 4);
+        assert_eq!(packed.len() % 4, 0);
+        assert_eq!(packed_length(&chunks, 4), packed.len());
+        let mut trailing = packed.clone();
+        trailing.extend(b"rest of the file");
+        assert_eq!(
+            unpack_chunks(&trailing).unwrap(),
+            Some((chunks, packed.len()))
+        );
+
+        assert!(pack_chunks(&[], 4).is_empty());
+        assert_eq!(unpack_chunks(b"not a container").unwrap(), None);
+        assert!(unpack_chunks(&packed[..packed.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_jpeg_carrier() {
+        let bytes = testing_jpeg_bytes();
+        let mut carrier = Carrier::from(Jpeg::try_from(bytes.as_slice()).unwrap());
+        let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"hidden".to_vec());
+        carrier
+            .insert_chunk(chunk.clone(), Placement::default())
+            .unwrap();
+        assert_eq!(carrier.chunks_by_type("ruSt").collect::<Vec<_>>(), [&chunk]);
+        assert!(carrier.png_mut("--touch").is_err());
+        assert_eq!(carrier.remove_first_chunk("ruSt").unwrap(), chunk);
+        assert!(carrier.remove_first_chunk("ruSt").is_err());
+        assert_eq!(carrier.as_bytes(), bytes);
+    }
+}
@@ -0,0 +1,79 @@
+use crate::{
+    parse::{self, SIGNATURE},
+    png::Png,
+};
+
+/// A complete png found inside other data
+#[derive(Debug)]
+pub struct Carved {
+    /// Byte offset of the png signature in the data searched
+    pub offset: usize,
+    /// Length of the png in the data, up to the end of its IEND chunk
+    pub length: usize,
+    pub png: Png,
+}
+
+/// Finds every complete png in `bytes`, which can be anything from a memory
+/// dump to a document with pictures in it. Each png signature is followed
+/// chunk by chunk up to IEND and kept only if all of it parses, CRCs
+/// included. Searching carries on right after each signature, so pngs hidden
+/// in the chunks of another png are found too
+pub fn carve(bytes: &[u8]) -> Vec<Carved> {
+    let mut carved = Vec::new();
+    let mut start = 0;
+    while let Some(found) = bytes[start..]
+        .windows(SIGNATURE.len())
+        .position(|window| window == SIGNATURE)
+    {
+        let offset = start + found;
+        let rest = &bytes[offset..];
+        if let Some(length) = parse::png_length(rest) {
+            if let Ok(png) = Png::try_from(&rest[..length]) {
+                carved.push(Carved {
+                    offset,
+                    length,
+                    png,
+                });
+            }
+        }
+        start = offset + 1;
+    }
+    carved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chunk::Chunk, chunk_type::ChunkType, png::tests::testing_png_bytes};
+
+    #[test]
+    fn test_carve() {
+        let png = testing_png_bytes();
+        let mut outer = Png::try_from(png.as_slice()).unwrap();
+        let nested = [b"thumbnail".as_slice(), &png].concat();
+        outer.insert_chunk(Chunk::new(ChunkType(*b"thUm"), nested));
+        let outer = outer.as_bytes();
+
+        let blob = [b"dump".as_slice(), &png, &[0; 5], &outer, &png[..30]].concat();
+        let carved = carve(&blob);
+        let found: Vec<(usize, usize)> = carved.iter().map(|c| (c.offset, c.length)).collect();
+        let second = 4 + png.len() + 5;
+        let nested = second + Png::HEADER_LENGTH + 25 + 15 + 8 + 9;
+        assert_eq!(
+            found,
+            [(4, png.len()), (second, outer.len()), (nested, png.len())]
+        );
+        assert_eq!(carved[0].png.as_bytes(), png);
+        assert_eq!(carved[1].png.chunks().count(), 4);
+    }
+
+    #[test]
+    fn test_carve_skips_damaged_pngs() {
+        let mut png = testing_png_bytes();
+        assert!(carve(&png[..png.len() - 1]).is_empty());
+        // corrupt the crc of the IDAT chunk
+        png[Png::HEADER_LENGTH + 25 + 14] ^= 0xff;
+        assert!(carve(&png).is_empty());
+        assert!(carve(b"").is_empty());
+    }
+}
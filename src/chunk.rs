@@ -1,22 +1,38 @@
-use std::fmt::Display;
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt::Display, iter::Filter};
+#[cfg(feature = "std")]
+use std::io::Write;
 
 use crate::{Error, Result};
-use crc::{Crc, CRC_32_ISO_HDLC};
-pub const CHECK_SUM_32: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
 
 use crate::chunk_type::ChunkType;
 
+/// The largest chunk length allowed by the PNG specification
+pub const MAX_CHUNK_LENGTH: usize = (1 << 31) - 1;
+
+/// The CRC-32 png chunks use, computed with the CPU's carry-less multiply
+/// instructions where they are available
+pub fn checksum(bytes: &[u8]) -> u32 {
+    crc32fast::hash(bytes)
+}
+
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum ChunkError {
     InvalidInput(String),
     InvalidChunkType,
     InvalidCheckSum(u32, u32),
+    TooLong(usize),
+    TruncatedChunk { length: usize, available: usize },
 }
 
-impl std::error::Error for ChunkError {}
+impl core::error::Error for ChunkError {}
 
 impl Display for ChunkError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             Self::InvalidInput(s) => write!(f, "{}", s),
             Self::InvalidChunkType => write!(f, "A chunk contains an invliad chunk type"),
@@ -25,14 +41,25 @@ impl Display for ChunkError {
                 "The checksum should be '{}' but found '{}' instead",
                 expected, actual
             ),
+            Self::TooLong(length) => write!(
+                f,
+                "Chunk data is {} bytes but at most {} are allowed",
+                length, MAX_CHUNK_LENGTH
+            ),
+            Self::TruncatedChunk { length, available } => write!(
+                f,
+                "Chunk declares {} data bytes but only {} remain",
+                length, available
+            ),
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Chunk {
-    pub chunk_data: Vec<u8>,
-    pub chunk_type: ChunkType,
+    chunk_data: Vec<u8>,
+    chunk_type: ChunkType,
+    crc: u32,
 }
 
 impl Chunk {
@@ -44,12 +71,39 @@ impl Chunk {
         Chunk::LEN_DATA_LENGTH + Chunk::CHUNK_TYPE_LENGTH + Chunk::CRC_LENGTH;
 
     pub fn new(chunk_type: ChunkType, chunk_data: Vec<u8>) -> Self {
+        let crc = Chunk::compute_crc(&chunk_type, &chunk_data);
         Self {
             chunk_type,
             chunk_data,
+            crc,
         }
     }
 
+    /// Like `new`, but rejects chunk types with an invalid reserved bit and
+    /// data longer than the specification allows
+    pub fn try_new(chunk_type: ChunkType, chunk_data: Vec<u8>) -> Result<Self> {
+        if !chunk_type.is_valid() {
+            return Err(ChunkError::InvalidChunkType.into());
+        }
+        if chunk_data.len() > MAX_CHUNK_LENGTH {
+            return Err(ChunkError::TooLong(chunk_data.len()).into());
+        }
+        Ok(Chunk::new(chunk_type, chunk_data))
+    }
+
+    /// Replaces the data, recomputing the CRC
+    pub fn set_data(&mut self, chunk_data: Vec<u8>) {
+        self.crc = Chunk::compute_crc(&self.chunk_type, &chunk_data);
+        self.chunk_data = chunk_data;
+    }
+
+    fn compute_crc(chunk_type: &ChunkType, chunk_data: &[u8]) -> u32 {
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(&chunk_type.bytes());
+        hasher.update(chunk_data);
+        hasher.finalize()
+    }
+
     pub fn length(&self) -> usize {
         self.chunk_data.len()
     }
@@ -59,38 +113,56 @@ impl Chunk {
         &self.chunk_type
     }
 
+    pub fn data(&self) -> &[u8] {
+        &self.chunk_data
+    }
+
+    pub fn into_data(self) -> Vec<u8> {
+        self.chunk_data
+    }
+
+    /// CRC of the type and data, computed once when the data is set
     pub fn crc(&self) -> u32 {
-        let b: Vec<u8> = self
-            .chunk_type
-            .bytes()
-            .iter()
-            .chain(self.chunk_data.iter())
-            .copied()
-            .collect();
-        CHECK_SUM_32.checksum(&b)
+        self.crc
     }
 
-    /// Entire chunk represented as bytes
+    /// Entire chunk represented as bytes, in a single allocation of exactly
+    /// the right size
     pub fn as_bytes(&self) -> Vec<u8> {
-        let data_length = self.chunk_data.len() as u32;
-        data_length
-            .to_be_bytes()
-            .iter()
-            .chain(self.chunk_type.bytes().iter())
-            .chain(self.chunk_data.iter())
-            .chain(self.crc().to_be_bytes().iter())
-            .copied()
-            .collect()
+        let mut bytes = Vec::with_capacity(self.length() + Chunk::META_DATA_LENGTH);
+        self.extend_into(&mut bytes);
+        bytes
+    }
+
+    /// Appends the entire chunk to `bytes`, so many chunks can be serialized
+    /// into one buffer without a Vec for each
+    pub fn extend_into(&self, bytes: &mut Vec<u8>) {
+        bytes.reserve(self.length() + Chunk::META_DATA_LENGTH);
+        bytes.extend_from_slice(&(self.chunk_data.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&self.chunk_type.bytes());
+        bytes.extend_from_slice(&self.chunk_data);
+        bytes.extend_from_slice(&self.crc.to_be_bytes());
+    }
+
+    /// Writes the chunk to `writer` field by field, without first collecting
+    /// it into a buffer like `as_bytes` does
+    #[cfg(feature = "std")]
+    pub fn write_to(&self, writer: &mut impl Write) -> Result<()> {
+        writer.write_all(&(self.chunk_data.len() as u32).to_be_bytes())?;
+        writer.write_all(&self.chunk_type.bytes())?;
+        writer.write_all(&self.chunk_data)?;
+        writer.write_all(&self.crc().to_be_bytes())?;
+        Ok(())
     }
 
     pub fn data_as_string(&self) -> Result<String> {
-        let s = std::str::from_utf8(&self.chunk_data)?;
+        let s = core::str::from_utf8(&self.chunk_data)?;
         Ok(s.to_string())
     }
 }
 
 impl Display for Chunk {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "{:?}", &self.data_as_string())?;
         Ok(())
     }
@@ -99,17 +171,85 @@ impl Display for Chunk {
 impl TryFrom<&[u8]> for Chunk {
     type Error = Error;
 
+    fn try_from(value: &[u8]) -> Result<Self> {
+        Chunk::try_from(ChunkRef::try_from(value)?)
+    }
+}
+
+impl TryFrom<ChunkRef<'_>> for Chunk {
+    type Error = Error;
+
+    /// Copies the data out of the buffer, rejecting a chunk whose crc
+    /// doesn't match
+    fn try_from(chunk: ChunkRef<'_>) -> Result<Self> {
+        let new_chunk = Chunk::try_new(chunk.chunk_type, chunk.data.to_vec())?;
+        if new_chunk.crc != chunk.crc {
+            return Err(ChunkError::InvalidCheckSum(chunk.crc, new_chunk.crc).into());
+        }
+        Ok(new_chunk)
+    }
+}
+
+/// A chunk borrowed from the buffer it was parsed from. Nothing is copied
+/// and the crc is only checked when asked for, so large IDAT chunks can be
+/// skipped over for free
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkRef<'a> {
+    chunk_type: ChunkType,
+    data: &'a [u8],
+    crc: u32,
+}
+
+impl<'a> ChunkRef<'a> {
+    pub fn chunk_type(&self) -> &ChunkType {
+        &self.chunk_type
+    }
+
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn length(&self) -> usize {
+        self.data.len()
+    }
+
+    /// The crc stored in the chunk, which may not match its contents
+    pub fn crc(&self) -> u32 {
+        self.crc
+    }
+
+    /// Computes the crc of the type and data and compares it to the stored one
+    pub fn verify_crc(&self) -> Result<()> {
+        let actual = Chunk::compute_crc(&self.chunk_type, self.data);
+        if actual != self.crc {
+            return Err(ChunkError::InvalidCheckSum(self.crc, actual).into());
+        }
+        Ok(())
+    }
+}
+
+impl<'a> From<&'a Chunk> for ChunkRef<'a> {
+    fn from(chunk: &'a Chunk) -> Self {
+        Self {
+            chunk_type: chunk.chunk_type.clone(),
+            data: &chunk.chunk_data,
+            crc: chunk.crc,
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for ChunkRef<'a> {
+    type Error = Error;
+
     // Chunk layout ( in order )
     // -- length - 4 bytes
     // -- chunk type - 4 bytes
     // -- data - N bytes
     // -- crc checksum - 4 bytes
 
-    fn try_from(value: &[u8]) -> Result<Self> {
+    fn try_from(value: &'a [u8]) -> Result<Self> {
         if value.len() < Chunk::META_DATA_LENGTH {
-            return Err(Box::new(ChunkError::InvalidInput(
-                "Chunk is too small".to_string(),
-            )));
+            return Err(ChunkError::InvalidInput("Chunk is too small".to_string()).into());
         }
 
         let (data_length, rest) = value.split_at(Chunk::LEN_DATA_LENGTH);
@@ -119,28 +259,147 @@ impl TryFrom<&[u8]> for Chunk {
         let chunk_type_b: [u8; 4] = type_slice.try_into()?;
         let chunk_type = ChunkType::try_from(chunk_type_b)?;
 
-        if !chunk_type.is_valid() {
-            return Err(Box::new(ChunkError::InvalidChunkType));
+        // the length comes straight from the input, so it is checked before
+        // anything is sliced with it
+        if data_length > MAX_CHUNK_LENGTH {
+            return Err(ChunkError::TooLong(data_length).into());
         }
+        let available = rest.len() - Chunk::CRC_LENGTH;
+        if data_length > available {
+            return Err(ChunkError::TruncatedChunk {
+                length: data_length,
+                available,
+            }
+            .into());
+        }
+        let (data, rest) = rest.split_at(data_length);
+        let crc = u32::from_be_bytes(rest[..Chunk::CRC_LENGTH].try_into()?);
 
-        // good up to now
-        let (data_slice, rest) = rest.split_at(data_length);
-        let (crc_slice, _) = rest.split_at(Chunk::CRC_LENGTH);
-
-     
-        let new_chunk = Self {
+        Ok(ChunkRef {
             chunk_type,
-            chunk_data: data_slice.into(),
-        };
+            data,
+            crc,
+        })
+    }
+}
 
-        let new_crc = new_chunk.crc();
-        let expected_crc = u32::from_be_bytes(crc_slice.try_into()?);
+type ChunkPredicate<'a> = fn(&&'a Chunk) -> bool;
 
-        if new_crc != expected_crc {
-            return Err(Box::new(ChunkError::InvalidCheckSum(expected_crc, new_crc)));
+/// Filters for any iterator over chunks, such as `Png::chunks`, by the
+/// property bits of their chunk types
+pub trait ChunkFilters<'a>: Iterator<Item = &'a Chunk> + Sized {
+    /// Chunks a decoder has to understand to show the image
+    fn critical(self) -> Filter<Self, ChunkPredicate<'a>> {
+        self.filter(|chunk| chunk.chunk_type().is_critical())
+    }
+
+    /// Chunks a decoder may ignore
+    fn ancillary(self) -> Filter<Self, ChunkPredicate<'a>> {
+        self.filter(|chunk| !chunk.chunk_type().is_critical())
+    }
+
+    /// Chunks of types registered or reserved for registration
+    fn public(self) -> Filter<Self, ChunkPredicate<'a>> {
+        self.filter(|chunk| chunk.chunk_type().is_public())
+    }
+
+    /// Chunks of types defined by some application
+    fn private(self) -> Filter<Self, ChunkPredicate<'a>> {
+        self.filter(|chunk| !chunk.chunk_type().is_public())
+    }
+
+    /// Chunks an editor may copy over even after changing critical chunks
+    fn safe_to_copy(self) -> Filter<Self, ChunkPredicate<'a>> {
+        self.filter(|chunk| chunk.chunk_type().is_safe_to_copy())
+    }
+
+    /// Chunks that depend on the critical chunks and go stale when they change
+    fn unsafe_to_copy(self) -> Filter<Self, ChunkPredicate<'a>> {
+        self.filter(|chunk| !chunk.chunk_type().is_safe_to_copy())
+    }
+}
+
+impl<'a, I: Iterator<Item = &'a Chunk>> ChunkFilters<'a> for I {}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Chunk {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Chunk::new(u.arbitrary()?, u.arbitrary()?))
+    }
+}
+
+/// Chunk data is base64 in human readable formats and raw bytes otherwise
+#[cfg(feature = "serde")]
+mod data {
+    use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&BASE64.encode(data))
+        } else {
+            serializer.serialize_bytes(data)
         }
+    }
 
-        Ok(new_chunk)
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        if deserializer.is_human_readable() {
+            let encoded = String::deserialize(deserializer)?;
+            BASE64.decode(encoded).map_err(D::Error::custom)
+        } else {
+            Vec::deserialize(deserializer)
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct SerializedChunk<'a> {
+    chunk_type: &'a ChunkType,
+    #[serde(with = "data")]
+    data: &'a [u8],
+    crc: u32,
+}
+
+/// The crc is optional when deserializing, but has to match when present
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct DeserializedChunk {
+    chunk_type: ChunkType,
+    #[serde(with = "data")]
+    data: Vec<u8>,
+    crc: Option<u32>,
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Chunk {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        SerializedChunk {
+            chunk_type: &self.chunk_type,
+            data: &self.chunk_data,
+            crc: self.crc,
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Chunk {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        use serde::de::Error as _;
+        let fields = DeserializedChunk::deserialize(deserializer)?;
+        let chunk = Chunk::try_new(fields.chunk_type, fields.data).map_err(D::Error::custom)?;
+        match fields.crc {
+            Some(crc) if crc != chunk.crc => Err(D::Error::custom(ChunkError::InvalidCheckSum(
+                crc, chunk.crc,
+            ))),
+            _ => Ok(chunk),
+        }
     }
 }
 
@@ -169,6 +428,34 @@ mod tests {
         assert_eq!(chunk.crc(), 2882656334);
     }
 
+    #[test]
+    fn test_checksum() {
+        // the standard check value of CRC-32/ISO-HDLC
+        assert_eq!(checksum(b"123456789"), 0xcbf43926);
+        let chunk = testing_chunk();
+        assert_eq!(
+            checksum(&chunk.as_bytes()[4..chunk.length() + 8]),
+            chunk.crc()
+        );
+    }
+
+    #[test]
+    fn test_extend_into() {
+        let chunk = testing_chunk();
+        let mut bytes = vec![0xff];
+        chunk.extend_into(&mut bytes);
+        assert_eq!(bytes[0], 0xff);
+        assert_eq!(bytes[1..], chunk.as_bytes());
+        let bytes = chunk.as_bytes();
+        assert_eq!(bytes.len(), bytes.capacity());
+        assert_eq!(u32::from_be_bytes(bytes[..4].try_into().unwrap()), 42);
+        assert_eq!(bytes[4..8], *b"RuSt");
+        assert_eq!(
+            u32::from_be_bytes(bytes[50..].try_into().unwrap()),
+            chunk.crc()
+        );
+    }
+
     #[test]
     fn test_chunk_length() {
         let chunk = testing_chunk();
@@ -243,6 +530,42 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    fn test_try_new() {
+        let data = b"payload".to_vec();
+        assert!(Chunk::try_new(ChunkType::from_str("RuSt").unwrap(), data.clone()).is_ok());
+        assert!(Chunk::try_new(ChunkType::from_str("Rust").unwrap(), data).is_err());
+    }
+
+    #[test]
+    fn test_data_accessors() {
+        let chunk = testing_chunk();
+        assert_eq!(chunk.data(), b"This is where your secret message will be!");
+        assert_eq!(chunk.clone().into_data(), chunk.data());
+    }
+
+    #[test]
+    fn test_set_data_updates_crc() {
+        let mut chunk = testing_chunk();
+        chunk.set_data(b"another message".to_vec());
+        let expected = Chunk::new(
+            ChunkType::from_str("RuSt").unwrap(),
+            b"another message".to_vec(),
+        );
+        assert_eq!(chunk.crc(), expected.crc());
+        assert_ne!(chunk.crc(), 2882656334);
+        assert!(Chunk::try_from(chunk.as_bytes().as_ref()).is_ok());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_write_to_matches_as_bytes() {
+        let chunk = testing_chunk();
+        let mut written = Vec::new();
+        chunk.write_to(&mut written).unwrap();
+        assert_eq!(written, chunk.as_bytes());
+    }
+
     #[test]
     pub fn test_chunk_trait_impls() {
         let data_length: u32 = 42;
@@ -263,4 +586,132 @@ mod tests {
 
         let _chunk_string = format!("{}", chunk);
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let chunk = Chunk::new(ChunkType::from_str("RuSt").unwrap(), vec![0xff, 0, 0xfe]);
+        let json = serde_json::to_value(&chunk).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"chunk_type": "RuSt", "data": "/wD+", "crc": chunk.crc()})
+        );
+        assert_eq!(serde_json::from_value::<Chunk>(json).unwrap(), chunk);
+
+        let without_crc = serde_json::json!({"chunk_type": "RuSt", "data": "/wD+"});
+        assert_eq!(serde_json::from_value::<Chunk>(without_crc).unwrap(), chunk);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_rejects_invalid_chunks() {
+        let wrong_crc = serde_json::json!({"chunk_type": "RuSt", "data": "/wD+", "crc": 1});
+        assert!(serde_json::from_value::<Chunk>(wrong_crc).is_err());
+        let invalid_type = serde_json::json!({"chunk_type": "Ru1t", "data": ""});
+        assert!(serde_json::from_value::<Chunk>(invalid_type).is_err());
+        let reserved_bit = serde_json::json!({"chunk_type": "Rust", "data": ""});
+        assert!(serde_json::from_value::<Chunk>(reserved_bit).is_err());
+        let not_base64 = serde_json::json!({"chunk_type": "RuSt", "data": "?"});
+        assert!(serde_json::from_value::<Chunk>(not_base64).is_err());
+    }
+
+    #[test]
+    fn test_chunk_filters() {
+        let chunks = [
+            Chunk::new(ChunkType::IHDR, vec![0; 13]),
+            Chunk::new(ChunkType::tEXt, vec![]),
+            Chunk::new(ChunkType::from_str("ruSt").unwrap(), vec![]),
+            Chunk::new(ChunkType::from_str("ruST").unwrap(), vec![]),
+            Chunk::new(ChunkType::IEND, vec![]),
+        ];
+        let types = |filtered: Vec<&Chunk>| -> Vec<String> {
+            filtered
+                .iter()
+                .map(|c| c.chunk_type().to_string())
+                .collect()
+        };
+        assert_eq!(types(chunks.iter().critical().collect()), ["IHDR", "IEND"]);
+        assert_eq!(
+            types(chunks.iter().ancillary().collect()),
+            ["tEXt", "ruSt", "ruST"]
+        );
+        assert_eq!(
+            types(chunks.iter().public().collect()),
+            ["IHDR", "tEXt", "IEND"]
+        );
+        assert_eq!(types(chunks.iter().private().collect()), ["ruSt", "ruST"]);
+        assert_eq!(
+            types(chunks.iter().safe_to_copy().collect()),
+            ["tEXt", "ruSt"]
+        );
+        assert_eq!(
+            types(chunks.iter().private().unsafe_to_copy().collect()),
+            ["ruST"]
+        );
+    }
+
+    fn chunk_with_length(length: u32, data_length: usize) -> Vec<u8> {
+        let mut bytes = length.to_be_bytes().to_vec();
+        bytes.extend(b"RuSt");
+        bytes.extend(vec![0; data_length]);
+        bytes.extend([0; Chunk::CRC_LENGTH]);
+        bytes
+    }
+
+    #[test]
+    fn test_declared_length_past_the_end() {
+        for (length, data_length) in [(43, 42), (1024, 0), (MAX_CHUNK_LENGTH as u32, 3)] {
+            let bytes = chunk_with_length(length, data_length);
+            let error = Chunk::try_from(bytes.as_slice()).unwrap_err();
+            assert!(
+                matches!(
+                    error,
+                    Error::Chunk(ChunkError::TruncatedChunk { length: l, available })
+                        if l == length as usize && available == data_length
+                ),
+                "{}",
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn test_declared_length_over_the_maximum() {
+        for length in [MAX_CHUNK_LENGTH as u32 + 1, u32::MAX] {
+            let bytes = chunk_with_length(length, 16);
+            let error = ChunkRef::try_from(bytes.as_slice()).unwrap_err();
+            assert!(matches!(error, Error::Chunk(ChunkError::TooLong(l)) if l == length as usize));
+        }
+    }
+
+    #[test]
+    fn test_adversarial_inputs_do_not_panic() {
+        let valid = testing_chunk().as_bytes();
+        for end in 0..valid.len() {
+            assert!(Chunk::try_from(&valid[..end]).is_err());
+        }
+        // trailing bytes past the crc are ignored
+        let mut longer = valid.clone();
+        longer.extend([0xff; 8]);
+        assert_eq!(Chunk::try_from(longer.as_slice()).unwrap(), testing_chunk());
+
+        assert!(Chunk::try_from(chunk_with_length(u32::MAX, 0).as_slice()).is_err());
+        assert!(Chunk::try_from([0xff; Chunk::META_DATA_LENGTH].as_slice()).is_err());
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn test_arbitrary_chunks_round_trip() {
+        use arbitrary::{Arbitrary, Unstructured};
+
+        let seed: Vec<u8> = (0..4096u32)
+            .map(|i| (i.wrapping_mul(2654435761) >> 13) as u8)
+            .collect();
+        let mut u = Unstructured::new(&seed);
+        for _ in 0..32 {
+            let chunk = Chunk::arbitrary(&mut u).unwrap();
+            assert!(chunk.chunk_type().is_valid());
+            assert_eq!(Chunk::try_from(chunk.as_bytes().as_slice()).unwrap(), chunk);
+        }
+    }
 }
@@ -1,5 +1,5 @@
 use crate::Error;
-use std::{fmt::Display, str::FromStr};
+use core::{fmt::Display, str::FromStr};
 
 // ---------------------------------------
 // ---------------  Errors ---------------
@@ -10,10 +10,10 @@ pub enum ChunkTypeError {
     InvalidCharacter,
 }
 
-impl std::error::Error for ChunkTypeError {}
+impl core::error::Error for ChunkTypeError {}
 
 impl Display for ChunkTypeError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             ChunkTypeError::ByteLengthError(actual) => write!(
                 f,
@@ -29,13 +29,20 @@ impl Display for ChunkTypeError {
 // ---------------------------------------
 // ---------------  ChunkType ------------
 // ---------------------------------------
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
 pub struct ChunkType(pub [u8; 4]);
 
 impl Display for ChunkType {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let output = std::str::from_utf8(&self.0).map_err(|_| std::fmt::Error)?;
-        write!(f, "{}", output)
+    /// Writes the letters of the type. Chunks read leniently can hold any
+    /// bytes, and those that aren't letters are escaped as `\xff`
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for &byte in &self.0 {
+            match byte.is_ascii_alphabetic() {
+                true => write!(f, "{}", byte as char)?,
+                false => write!(f, "\\x{:02x}", byte)?,
+            }
+        }
+        Ok(())
     }
 }
 
@@ -43,7 +50,7 @@ impl TryFrom<[u8; 4]> for ChunkType {
     type Error = Error;
 
     fn try_from(value: [u8; 4]) -> Result<Self, Self::Error> {
-        Ok(ChunkType(value))
+        ChunkType::new(value)
     }
 }
 
@@ -52,74 +59,214 @@ impl FromStr for ChunkType {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         if s.len() != 4 {
-            return Err(Box::new(ChunkTypeError::ByteLengthError(s.len())));
+            return Err(ChunkTypeError::ByteLengthError(s.len()).into());
         }
         let mut ret: [u8; 4] = [0; 4];
         ret.copy_from_slice(&s.as_bytes()[..4]);
+        ChunkType::new(ret)
+    }
+}
+
+/// Written as its four letters in human readable formats and as its four
+/// bytes otherwise
+#[cfg(feature = "serde")]
+impl serde::Serialize for ChunkType {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            let letters = core::str::from_utf8(&self.0).map_err(serde::ser::Error::custom)?;
+            serializer.serialize_str(letters)
+        } else {
+            self.0.serialize(serializer)
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ChunkType {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error as _;
+        if deserializer.is_human_readable() {
+            let letters = String::deserialize(deserializer)?;
+            letters.parse().map_err(D::Error::custom)
+        } else {
+            ChunkType::new(<[u8; 4]>::deserialize(deserializer)?).map_err(D::Error::custom)
+        }
+    }
+}
 
-        let chars_valid = s.as_bytes().iter().all(|&byte| byte.is_ascii_alphabetic());
-        if !chars_valid {
-            return Err(Box::new(ChunkTypeError::InvalidCharacter));
+/// Only generates valid chunk types, with the reserved bit unset, so anything
+/// built from them survives being written and parsed again
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for ChunkType {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let mut bytes = [0; 4];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            let letter = u.int_in_range(0..=25)?;
+            *byte = match i != 2 && u.arbitrary()? {
+                true => b'a' + letter,
+                false => b'A' + letter,
+            };
         }
-        return Ok(ChunkType(ret));
+        Ok(ChunkType(bytes))
+    }
+
+    fn size_hint(_depth: usize) -> (usize, Option<usize>) {
+        (8, Some(8))
     }
 }
 
+// Constants keep the spelling of the specification, case bits included
+#[allow(non_upper_case_globals)]
 impl ChunkType {
+    /// Image header
+    pub const IHDR: ChunkType = ChunkType(*b"IHDR");
+    /// Palette
+    pub const PLTE: ChunkType = ChunkType(*b"PLTE");
+    /// Image data
+    pub const IDAT: ChunkType = ChunkType(*b"IDAT");
+    /// Image trailer
+    pub const IEND: ChunkType = ChunkType(*b"IEND");
+    /// Animation control
+    pub const acTL: ChunkType = ChunkType(*b"acTL");
+    /// Frame control
+    pub const fcTL: ChunkType = ChunkType(*b"fcTL");
+    /// Frame data
+    pub const fdAT: ChunkType = ChunkType(*b"fdAT");
+    /// Primary chromaticities
+    pub const cHRM: ChunkType = ChunkType(*b"cHRM");
+    /// Coding-independent code points
+    pub const cICP: ChunkType = ChunkType(*b"cICP");
+    /// Content light level
+    pub const cLLI: ChunkType = ChunkType(*b"cLLI");
+    /// Mastering display colour volume
+    pub const mDCV: ChunkType = ChunkType(*b"mDCV");
+    /// Image gamma
+    pub const gAMA: ChunkType = ChunkType(*b"gAMA");
+    /// Embedded ICC profile
+    pub const iCCP: ChunkType = ChunkType(*b"iCCP");
+    /// Significant bits
+    pub const sBIT: ChunkType = ChunkType(*b"sBIT");
+    /// Standard RGB colour space
+    pub const sRGB: ChunkType = ChunkType(*b"sRGB");
+    /// Background colour
+    pub const bKGD: ChunkType = ChunkType(*b"bKGD");
+    /// Palette histogram
+    pub const hIST: ChunkType = ChunkType(*b"hIST");
+    /// Transparency
+    pub const tRNS: ChunkType = ChunkType(*b"tRNS");
+    /// Exchangeable image file profile
+    pub const eXIf: ChunkType = ChunkType(*b"eXIf");
+    /// Physical pixel dimensions
+    pub const pHYs: ChunkType = ChunkType(*b"pHYs");
+    /// Suggested palette
+    pub const sPLT: ChunkType = ChunkType(*b"sPLT");
+    /// Last modification time
+    pub const tIME: ChunkType = ChunkType(*b"tIME");
+    /// Textual data
+    pub const tEXt: ChunkType = ChunkType(*b"tEXt");
+    /// Compressed textual data
+    pub const zTXt: ChunkType = ChunkType(*b"zTXt");
+    /// International textual data
+    pub const iTXt: ChunkType = ChunkType(*b"iTXt");
+    /// Image offset
+    pub const oFFs: ChunkType = ChunkType(*b"oFFs");
+    /// Pixel calibration
+    pub const pCAL: ChunkType = ChunkType(*b"pCAL");
+    /// Physical scale
+    pub const sCAL: ChunkType = ChunkType(*b"sCAL");
+    /// Stereo image indicator
+    pub const sTER: ChunkType = ChunkType(*b"sTER");
+    /// Digital signature
+    pub const dSIG: ChunkType = ChunkType(*b"dSIG");
+    /// GIF graphic control extension
+    pub const gIFg: ChunkType = ChunkType(*b"gIFg");
+    /// GIF application extension
+    pub const gIFx: ChunkType = ChunkType(*b"gIFx");
+    /// GIF plain text extension
+    pub const gIFt: ChunkType = ChunkType(*b"gIFt");
+    /// Fractal image parameters
+    pub const fRAc: ChunkType = ChunkType(*b"fRAc");
+}
+
+/// Chunk types defined by the PNG specification and its registered public
+/// extensions
+pub const REGISTERED: [ChunkType; 34] = [
+    ChunkType::IHDR,
+    ChunkType::PLTE,
+    ChunkType::IDAT,
+    ChunkType::IEND,
+    ChunkType::acTL,
+    ChunkType::fcTL,
+    ChunkType::fdAT,
+    ChunkType::cHRM,
+    ChunkType::cICP,
+    ChunkType::cLLI,
+    ChunkType::mDCV,
+    ChunkType::gAMA,
+    ChunkType::iCCP,
+    ChunkType::sBIT,
+    ChunkType::sRGB,
+    ChunkType::bKGD,
+    ChunkType::hIST,
+    ChunkType::tRNS,
+    ChunkType::eXIf,
+    ChunkType::pHYs,
+    ChunkType::sPLT,
+    ChunkType::tIME,
+    ChunkType::tEXt,
+    ChunkType::zTXt,
+    ChunkType::iTXt,
+    ChunkType::oFFs,
+    ChunkType::pCAL,
+    ChunkType::sCAL,
+    ChunkType::sTER,
+    ChunkType::dSIG,
+    ChunkType::gIFg,
+    ChunkType::gIFx,
+    ChunkType::gIFt,
+    ChunkType::fRAc,
+];
+
+impl ChunkType {
+    /// Creates a chunk type, rejecting bytes that are not ASCII letters
+    pub fn new(bytes: [u8; 4]) -> Result<Self, Error> {
+        if !bytes.iter().all(u8::is_ascii_alphabetic) {
+            return Err(ChunkTypeError::InvalidCharacter.into());
+        }
+        Ok(ChunkType(bytes))
+    }
+
+    /// Creates a chunk type from any bytes, for inspecting damaged files
+    pub fn new_unchecked(bytes: [u8; 4]) -> Self {
+        ChunkType(bytes)
+    }
+
     pub fn bytes(&self) -> [u8; 4] {
         self.0
     }
     pub fn is_critical(&self) -> bool {
-        let char = char::from(self.0[0]);
-        if char.is_lowercase() {
-            return false;
-        } else if char.is_uppercase() {
-            return true;
-        } else {
-            return false;
-        }
+        self.0[0].is_ascii_uppercase()
     }
 
     pub fn is_public(&self) -> bool {
-        let char = char::from(self.0[1]);
-        if char.is_lowercase() {
-            return false;
-        } else if char.is_uppercase() {
-            return true;
-        } else {
-            return false;
-        }
+        self.0[1].is_ascii_uppercase()
     }
+
     pub fn is_reserved_bit_valid(&self) -> bool {
-        let char = char::from(self.0[2]);
-        if !char.is_alphabetic() {
-            return false;
-        }
-        if char.is_lowercase() {
-            return false;
-        } else if char.is_uppercase() {
-            return true;
-        } else {
-            return false;
-        }
+        self.0[2].is_ascii_uppercase()
     }
+
     pub fn is_safe_to_copy(&self) -> bool {
-        let char = char::from(self.0[3]);
+        self.0[3].is_ascii_lowercase()
+    }
 
-        if char.is_lowercase() {
-            true
-        } else if char.is_uppercase() {
-            false
-        } else {
-            false
-        }
+    /// Whether this is one of the registered chunk types in `REGISTERED`
+    pub fn is_registered(&self) -> bool {
+        REGISTERED.contains(self)
     }
 
     pub fn is_valid(&self) -> bool {
-        let valid_chars = self
-            .0
-            .iter()
-            .all(|&b| (b >= b'a' && b <= b'z' || (b >= b'A' && b <= b'Z')));
+        let valid_chars = self.0.iter().all(|b| b.is_ascii_alphabetic());
         valid_chars && self.is_reserved_bit_valid()
     }
 }
@@ -138,6 +285,15 @@ mod tests {
         assert_eq!(expected, actual.bytes());
     }
 
+    #[test]
+    pub fn test_chunk_type_from_invalid_bytes() {
+        assert!(ChunkType::try_from([82, 117, 49, 116]).is_err());
+        assert!(ChunkType::try_from([0, 0, 0, 0]).is_err());
+        let unchecked = ChunkType::new_unchecked([82, 117, 49, 116]);
+        assert_eq!(unchecked.bytes(), [82, 117, 49, 116]);
+        assert!(!unchecked.is_valid());
+    }
+
     #[test]
     pub fn test_chunk_type_from_str() {
         let expected = ChunkType::try_from([82, 117, 83, 116]).unwrap();
@@ -208,10 +364,24 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    pub fn test_registered_chunk_types() {
+        assert!(ChunkType::IHDR.is_registered());
+        assert!(ChunkType::from_str("tEXt").unwrap().is_registered());
+        assert!(!ChunkType::from_str("TEXT").unwrap().is_registered());
+        assert!(!ChunkType::from_str("ruSt").unwrap().is_registered());
+        assert_eq!(ChunkType::IEND.to_string(), "IEND");
+        for chunk_type in REGISTERED {
+            assert!(chunk_type.is_valid(), "{}", chunk_type);
+        }
+    }
+
     #[test]
     pub fn test_chunk_type_string() {
         let chunk = ChunkType::from_str("RuSt").unwrap();
         assert_eq!(&chunk.to_string(), "RuSt");
+        let chunk = ChunkType::new_unchecked([0x49, 0x7f, 0xff, b'1']);
+        assert_eq!(&chunk.to_string(), "I\\x7f\\xff\\x31");
     }
 
     #[test]
@@ -0,0 +1,58 @@
+use std::{thread, time::Duration};
+
+use arboard::Clipboard;
+
+use crate::{ihdr::Ihdr, image_data::ImageData, png::Png, Result};
+
+/// The image on the clipboard as a png. Programs hand images over as RGBA
+/// pixels, so whatever chunks the copied file had are gone
+pub fn paste_image() -> Result<Png> {
+    to_png(Clipboard::new()?.get_image()?)
+}
+
+fn to_png(image: arboard::ImageData) -> Result<Png> {
+    // a size cut short by the casts no longer matches the number of pixels,
+    // which ImageData::new checks
+    let header = Ihdr::rgba(image.width as u32, image.height as u32);
+    ImageData::new(header, image.bytes.into_owned())?.to_png()
+}
+
+/// Puts `text` on the clipboard and clears it after `clear_after`, unless
+/// something else was copied in the meantime. Blocks until then, which on
+/// Linux is also what keeps the text available, as X11 and Wayland
+/// clipboards only hold what a running program serves
+pub fn copy_secret(text: &str, clear_after: Duration) -> Result<()> {
+    let mut clipboard = Clipboard::new()?;
+    clipboard.set_text(text)?;
+    thread::sleep(clear_after);
+    if clipboard.get_text().is_ok_and(|current| current == text) {
+        clipboard.clear()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::borrow::Cow;
+
+    #[test]
+    fn test_clipboard_image_to_png() {
+        let pixels: Vec<u8> = (0..2 * 3 * 4).collect();
+        let image = arboard::ImageData {
+            width: 2,
+            height: 3,
+            bytes: Cow::Borrowed(&pixels),
+        };
+        let png = to_png(image).unwrap();
+        assert_eq!(png.header().unwrap(), Ihdr::rgba(2, 3));
+        assert_eq!(ImageData::from_png(&png).unwrap().samples(), pixels);
+
+        let truncated = arboard::ImageData {
+            width: 2,
+            height: 3,
+            bytes: Cow::Borrowed(&pixels[1..]),
+        };
+        assert!(to_png(truncated).is_err());
+    }
+}
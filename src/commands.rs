@@ -0,0 +1,3947 @@
+use std::{
+    fmt::Display,
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use clap::CommandFactory;
+use ed25519_dalek::VerifyingKey;
+use serde::Serialize;
+
+#[cfg(feature = "clipboard")]
+use crate::clipboard;
+#[cfg(feature = "qr")]
+use crate::qr;
+use crate::{
+    apng::ANIMATION_CHUNKS,
+    args::{
+        CapacityArgs, CarveArgs, Cli, CompletionsArgs, DecodeArgs, DetectArgs, DiffArgs,
+        EncodeArgs, ExifArgs, ExtractArgs, HashArgs, HexdumpArgs, HidingMethod, HistoryArgs,
+        MigrateArgs, PicmesArgs, PrintArgs, RemoveArgs, RepairArgs, ScanArgs, SealArgs, SignArgs,
+        StatsArgs, StegoCommand, StripArgs, TimeCommand, UnsealArgs, VerifyArgs, VerifyFileArgs,
+    },
+    carrier::{Carrier, Placement, Section},
+    carve as carver,
+    chunk::{Chunk, MAX_CHUNK_LENGTH},
+    chunk_type::ChunkType,
+    compress,
+    crypto::{self, CryptoError, EncryptOptions, KdfParams},
+    detect::{self as detector, Evidence},
+    diff as differ,
+    envelope::is_envelope,
+    error::{Failure, PicmesError},
+    exif::ExifTags,
+    fec, history,
+    image_data::ImageData,
+    inputs::{self, Inputs},
+    passphrase,
+    plan::{self, Change, Plan},
+    png::{Png, PngError},
+    progress,
+    repair::{self as repairer, RepairOptions},
+    report::{FileFindings, FileProblems, FindingRow, Format, ProblemRow, Reporter, TypeRow},
+    scan as scanner,
+    seal::{self as sealer, SealError},
+    signing,
+    split::{self, SplitError},
+    stats as summarizer,
+    stego::{self as steganography},
+    text_chunk::{International, TextChunk},
+    time_chunk::LastModified,
+    verify as verifier, Result,
+};
+#[cfg(feature = "keyring")]
+use crate::{args::KeysCommand, keys::StoredKey};
+#[cfg(feature = "watch")]
+use crate::{args::WatchArgs, watch as watcher};
+
+/// Whether -q was given, leaving the exit code as the only output
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Prints a line to stdout unless -q was given
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if !QUIET.load(Ordering::Relaxed) {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// Prints to stdout without a newline unless -q was given
+macro_rules! say_inline {
+    ($($arg:tt)*) => {
+        if !QUIET.load(Ordering::Relaxed) {
+            print!($($arg)*);
+        }
+    };
+}
+
+/// Prints a line to stderr unless -q was given
+macro_rules! complain {
+    ($($arg:tt)*) => {
+        if !QUIET.load(Ordering::Relaxed) {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+#[derive(Debug)]
+pub enum CommandError {
+    CriticalChunk(String),
+    OutputWithBatch,
+    BatchFailed {
+        failed: usize,
+        total: usize,
+        /// The failure every failed file shares, Other when they differ
+        failure: Failure,
+    },
+    VerificationFailed {
+        errors: usize,
+        /// Checksum when a CRC mismatch is among the errors
+        failure: Failure,
+    },
+    MessageWithInput,
+    ShardsWithBatch,
+    EntryWithoutIcon(&'static str),
+    AppendWithoutPng(&'static str),
+    AppendWithOutput,
+    AppendWith(&'static str),
+    NoChunkAt {
+        index: usize,
+        count: usize,
+    },
+    NothingToMigrate,
+    TooLongToMigrate {
+        chunk_type: String,
+        length: usize,
+        format: &'static str,
+    },
+    RemoteTarget(PathBuf),
+    OutputWithClipboard,
+}
+
+impl std::error::Error for CommandError {}
+
+impl Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::CriticalChunk(chunk_type) => write!(
+                f,
+                "Refusing to remove critical chunk '{}' without --force",
+                chunk_type
+            ),
+            Self::OutputWithBatch => write!(
+                f,
+                "An output path can only be given for a single input file"
+            ),
+            Self::BatchFailed { failed, total, .. } => {
+                write!(f, "{} of {} files failed", failed, total)
+            }
+            Self::VerificationFailed { errors, .. } => write!(f, "Found {} error(s)", errors),
+            Self::MessageWithInput => write!(f, "A message cannot be given together with --input"),
+            Self::ShardsWithBatch => {
+                write!(f, "--split-across needs a single input file to start from")
+            }
+            Self::EntryWithoutIcon(format) => write!(
+                f,
+                "--entry only applies to icons and cursors, not {} files",
+                format
+            ),
+            Self::AppendWithoutPng(format) => {
+                write!(
+                    f,
+                    "--append only applies to png files, not {} files",
+                    format
+                )
+            }
+            Self::AppendWithOutput => {
+                write!(
+                    f,
+                    "--append changes the input file and takes no output path"
+                )
+            }
+            Self::AppendWith(flag) => write!(
+                f,
+                "--append only adds the payload chunks and can't be combined with {}",
+                flag
+            ),
+            Self::NoChunkAt { index, count } => write!(
+                f,
+                "There is no chunk at index {}, only {} match",
+                index, count
+            ),
+            Self::NothingToMigrate => write!(f, "The source holds no payloads to migrate"),
+            Self::TooLongToMigrate {
+                chunk_type,
+                length,
+                format,
+            } => write!(
+                f,
+                "The {} payload of {} bytes does not fit in a {} chunk",
+                chunk_type, length, format
+            ),
+            Self::RemoteTarget(path) => write!(
+                f,
+                "'{}' can't be written back to, save the result to a local file instead",
+                path.display()
+            ),
+            Self::OutputWithClipboard => write!(
+                f,
+                "--from-clipboard writes the result to FILE and takes no output path"
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+struct ChunkSummary {
+    chunk_type: String,
+    length: usize,
+    crc: u32,
+    critical: bool,
+    public: bool,
+    safe_to_copy: bool,
+    /// Only listed by print --verbose
+    #[serde(skip_serializing_if = "Option::is_none")]
+    entropy: Option<f64>,
+}
+
+impl From<&Chunk> for ChunkSummary {
+    fn from(chunk: &Chunk) -> Self {
+        let chunk_type = chunk.chunk_type();
+        Self {
+            chunk_type: chunk_type.to_string(),
+            length: chunk.length(),
+            crc: chunk.crc(),
+            critical: chunk_type.is_critical(),
+            public: chunk_type.is_public(),
+            safe_to_copy: chunk_type.is_safe_to_copy(),
+            entropy: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+struct SectionSummary {
+    name: String,
+    length: usize,
+    chunk_type: Option<String>,
+}
+
+impl From<Section> for SectionSummary {
+    fn from(section: Section) -> Self {
+        Self {
+            name: section.name,
+            length: section.length,
+            chunk_type: section.chunk_type.map(|t| t.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum Encoding {
+    Utf8,
+    Base64,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+struct DecodedPayload {
+    chunk_type: String,
+    encoding: Encoding,
+    data: String,
+}
+
+impl DecodedPayload {
+    /// Describes `payload`, the data of `chunk` after any decryption
+    fn new(chunk: &Chunk, payload: &[u8]) -> Self {
+        let (encoding, data) = match std::str::from_utf8(payload) {
+            Ok(text) => (Encoding::Utf8, text.to_string()),
+            Err(_) => (Encoding::Base64, BASE64.encode(payload)),
+        };
+        Self {
+            chunk_type: chunk.chunk_type().to_string(),
+            encoding,
+            data,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct FileChunks<'a> {
+    file: &'a Path,
+    chunks: Vec<ChunkSummary>,
+}
+
+#[derive(Debug, Serialize)]
+struct FileSections<'a> {
+    file: &'a Path,
+    sections: Vec<SectionSummary>,
+}
+
+#[derive(Debug, Serialize)]
+struct FileEvidence<'a> {
+    file: &'a Path,
+    likelihood: f64,
+    evidence: Vec<Evidence>,
+}
+
+#[derive(Debug, Serialize)]
+struct FilePayload<'a> {
+    file: &'a Path,
+    #[serde(flatten)]
+    payload: DecodedPayload,
+}
+
+/// Runs `op` on every input file. In batch mode a failing file is reported
+/// and processing continues with the next one, with a bar counting the files
+/// done
+fn for_each_file(inputs: &Inputs, mut op: impl FnMut(&Path) -> Result<()>) -> Result<()> {
+    if !inputs.batch {
+        return inputs.files.iter().try_for_each(|file| op(file));
+    }
+
+    let mut failed = 0;
+    let mut failure = None;
+    let bar = progress::files(inputs.files.len());
+    for file in &inputs.files {
+        // the bar is hidden while the file is processed, as that prints
+        if let Err(e) = bar.suspend(|| op(file)) {
+            bar.suspend(|| complain!("{}: error: {}", file.display(), e));
+            failed += 1;
+            failure = match failure {
+                Some(failure) if failure != e.failure() => Some(Failure::Other),
+                _ => Some(e.failure()),
+            };
+        }
+        bar.inc(1);
+    }
+
+    if let Some(failure) = failure {
+        return Err(CommandError::BatchFailed {
+            failed,
+            total: inputs.files.len(),
+            failure,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Fails on the first of `files` that can't be written back to. URLs never
+/// can, objects in a bucket only as a whole and so not with --append
+fn reject_read_only<'a>(files: impl IntoIterator<Item = &'a PathBuf>, append: bool) -> Result<()> {
+    let read_only = |file: &&PathBuf| match append {
+        true => inputs::is_remote(file),
+        false => file.to_str().is_some_and(inputs::is_url),
+    };
+    match files.into_iter().find(read_only) {
+        Some(file) => Err(CommandError::RemoteTarget(file.clone()).into()),
+        None => Ok(()),
+    }
+}
+
+/// Prints the plan for dry runs, otherwise writes it out
+fn apply_or_print(plan: Plan, dry_run: bool) -> Result<()> {
+    if dry_run {
+        say!("{}", plan);
+        Ok(())
+    } else if inputs::is_remote(plan.target()) {
+        let target = plan.target().to_path_buf();
+        inputs::write(&target, plan.into_bytes())
+    } else {
+        plan.apply()
+    }
+}
+
+/// Plans writing `carrier`, read from `file`, to `target`. A remote image
+/// has no file to take its size from, so `original_size` measures it as it
+/// was read
+fn plan_write(
+    file: &Path,
+    original_size: Option<u64>,
+    target: &Path,
+    carrier: impl Into<Carrier>,
+    changes: Vec<Change>,
+) -> Result<Plan> {
+    match original_size {
+        Some(size) => Ok(Plan::with_original_size(size, target, carrier, changes)),
+        None => Plan::new(file, target, carrier, changes),
+    }
+}
+
+/// Adds `operation` to the history chunk, recording the change
+fn record(png: &mut Png, operation: &str, changes: &mut Vec<Change>) -> Result<()> {
+    changes.push(history::record(png, &history::Entry::now(operation)?)?);
+    Ok(())
+}
+
+/// Sets the tIME chunk to the current time, recording the change
+fn touch(png: &mut Png, changes: &mut Vec<Change>) -> Result<()> {
+    changes.push(set_last_modified(png, LastModified::now()?));
+    Ok(())
+}
+
+fn set_last_modified(png: &mut Png, time: LastModified) -> Change {
+    match png.set_last_modified(time) {
+        Some(_) => Change::updated(&time.to_chunk(), format!("set to {}", time)),
+        None => Change::added(&time.to_chunk()),
+    }
+}
+
+/// Silences everything commands print for the rest of the process
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+/// Where commands write their output, nowhere with -q
+fn stdout() -> Box<dyn io::Write> {
+    match QUIET.load(Ordering::Relaxed) {
+        true => Box::new(io::sink()),
+        false => Box::new(io::stdout()),
+    }
+}
+
+/// Runs the command `args` describe
+pub fn run(args: PicmesArgs) -> Result<()> {
+    match args {
+        PicmesArgs::Encode(args) => encode(args),
+        PicmesArgs::Decode(args) => decode(args),
+        PicmesArgs::Remove(args) => remove(args),
+        PicmesArgs::Print(args) => print(args),
+        PicmesArgs::Scan(args) => scan(args),
+        PicmesArgs::Detect(args) => detect(args),
+        PicmesArgs::Stats(args) => stats(args),
+        PicmesArgs::Verify(args) => verify(args),
+        PicmesArgs::Diff(args) => diff(args),
+        PicmesArgs::Hash(args) => hash(args),
+        PicmesArgs::Sign(args) => sign(args),
+        PicmesArgs::VerifyFile(args) => verify_file(args),
+        PicmesArgs::Repair(args) => repair(args),
+        PicmesArgs::Strip(args) => strip(args),
+        PicmesArgs::Extract(args) => extract(args),
+        PicmesArgs::Carve(args) => carve(args),
+        PicmesArgs::Hexdump(args) => hexdump(args),
+        PicmesArgs::Migrate(args) => migrate(args),
+        PicmesArgs::Seal(args) => seal(args),
+        PicmesArgs::Unseal(args) => unseal(args),
+        PicmesArgs::Capacity(args) => capacity(args),
+        PicmesArgs::Stego(command) => stego(command),
+        PicmesArgs::History(args) => history(args),
+        PicmesArgs::Time(command) => time(command),
+        PicmesArgs::Exif(args) => exif(args),
+        #[cfg(feature = "keyring")]
+        PicmesArgs::Keys(command) => keys(command),
+        #[cfg(feature = "watch")]
+        PicmesArgs::Watch(args) => watch(args),
+        PicmesArgs::Completions(args) => completions(args),
+    }
+}
+
+/// Embeds the message in a new chunk, writing the result to the output path
+/// or back over the input file when no output is given
+pub fn encode(args: EncodeArgs) -> Result<()> {
+    let (mut payload, output) = encode_payload(&args)?;
+    let mut decoy = args.decoy.clone().map(String::into_bytes);
+    if let Some(compressor) = args.compress {
+        payload = compress::compress(&payload, compressor)?;
+        decoy = decoy
+            .map(|decoy| compress::compress(&decoy, compressor))
+            .transpose()?;
+    }
+    let mut payloads = vec![payload];
+    if args.encrypt {
+        let passphrase = passphrase::passphrase(args.passphrase.clone(), true)?;
+        let defaults = KdfParams::default();
+        let options = EncryptOptions {
+            cipher: args.cipher,
+            kdf: KdfParams {
+                memory_kib: args.kdf_memory.unwrap_or(defaults.memory_kib),
+                iterations: args.kdf_iterations.unwrap_or(defaults.iterations),
+                ..defaults
+            },
+            deterministic: args.deterministic,
+        };
+        payloads = match (&decoy, &args.decoy_passphrase) {
+            (Some(decoy), Some(decoy_passphrase)) => crypto::encrypt_with_decoy(
+                &payloads[0],
+                &passphrase,
+                decoy,
+                decoy_passphrase,
+                options,
+            )?
+            .to_vec(),
+            _ => vec![crypto::encrypt(&payloads[0], &passphrase, options)?],
+        };
+    } else if !args.recipient.is_empty() || args.recipients_file.is_some() {
+        let mut recipients = args.recipient.clone();
+        if let Some(path) = &args.recipients_file {
+            recipients.extend(crypto::read_recipients_file(path)?);
+        }
+        payloads = vec![crypto::encrypt_to_recipients(&payloads[0], &recipients)?];
+    }
+
+    let largest_part = MAX_CHUNK_LENGTH - split::PART_HEADER_LENGTH;
+    let part_size = args
+        .split_size
+        .map_or(largest_part, |size| (size as usize).min(largest_part));
+    for payload in &mut payloads {
+        if let Some(key) = &args.hmac_key {
+            *payload = signing::tag(payload, key.as_bytes());
+        }
+        if let Some(key) = &args.sign {
+            *payload = signing::sign(payload, &signing::read_signing_key(key)?);
+        }
+        if let Some(parity) = args.fec {
+            let protected = fec::protect(payload, parity as usize, 1)?;
+            // shards as long as a part mean a lost part damages at most two
+            *payload = match protected.len() > part_size {
+                true => fec::protect(payload, parity as usize, part_size)?,
+                false => protected,
+            };
+        }
+    }
+
+    let chunk_type = match &args.type_key {
+        Some(key) => signing::derive_chunk_type(key.as_bytes(), &args.chunk_type),
+        None => ChunkType::from_str(&args.chunk_type)?,
+    };
+    #[cfg(feature = "clipboard")]
+    if args.from_clipboard {
+        if output.is_some() {
+            return Err(CommandError::OutputWithClipboard.into());
+        }
+        let carrier = Carrier::Png(clipboard::paste_image()?);
+        let original_size = carrier.encoded_length() as u64;
+        let chunks = payload_chunks(&payloads, &chunk_type, part_size)?;
+        let target = Path::new(&args.file);
+        return embed(&args, target, Some(original_size), target, carrier, &chunks);
+    }
+
+    let inputs = inputs::expand(
+        std::slice::from_ref(&args.file),
+        args.recursive,
+        inputs::IMAGE_EXTENSIONS,
+    )?;
+    if inputs.batch && output.is_some() {
+        return Err(CommandError::OutputWithBatch.into());
+    }
+    if args.append && output.is_some() {
+        return Err(CommandError::AppendWithOutput.into());
+    }
+    if output.is_none() || args.append || !args.split_across.is_empty() {
+        reject_read_only(inputs.files.iter().chain(&args.split_across), args.append)?;
+    }
+    if !args.split_across.is_empty() {
+        if inputs.batch {
+            return Err(CommandError::ShardsWithBatch.into());
+        }
+        return encode_shards(&args, &inputs.files[0], chunk_type, &payloads[0]);
+    }
+
+    for_each_file(&inputs, |file| {
+        // appending only takes pngs and leaves the file unparsed
+        let read = match args.append {
+            true => None,
+            false => Some(read_for_writing(file)?),
+        };
+        let max_chunk_length = read
+            .as_ref()
+            .map_or(MAX_CHUNK_LENGTH, |(carrier, _)| carrier.max_chunk_length());
+        let largest_part = max_chunk_length - split::PART_HEADER_LENGTH;
+        let chunks = payload_chunks(&payloads, &chunk_type, part_size.min(largest_part))?;
+        match read {
+            None => append(&args, file, &chunks)?,
+            Some((carrier, original_size)) => embed(
+                &args,
+                file,
+                original_size,
+                output.as_deref().unwrap_or(file),
+                carrier,
+                &chunks,
+            )?,
+        }
+        if inputs.batch && !args.dry_run {
+            say!(
+                "{}: embedded {} {} chunk(s)",
+                file.display(),
+                chunks.len(),
+                chunk_type
+            );
+        }
+        Ok(())
+    })
+}
+
+/// Turns each payload into a chunk of `chunk_type`, or into a split payload
+/// when it is longer than `part_size`
+fn payload_chunks(
+    payloads: &[Vec<u8>],
+    chunk_type: &ChunkType,
+    part_size: usize,
+) -> Result<Vec<Chunk>> {
+    let mut chunks = Vec::new();
+    for payload in payloads {
+        match payload.len() > part_size {
+            true => {
+                for data in split::split(payload, part_size) {
+                    chunks.push(Chunk::try_new(chunk_type.clone(), data)?);
+                }
+            }
+            false => chunks.push(Chunk::try_new(chunk_type.clone(), payload.clone())?),
+        }
+    }
+    Ok(chunks)
+}
+
+/// Spreads the payload over `file` and the --split-across images, one shard
+/// each. Every image also gets the manifest, so any of them tells how many
+/// shards there are and which payload they belong to
+fn encode_shards(
+    args: &EncodeArgs,
+    file: &Path,
+    chunk_type: ChunkType,
+    payload: &[u8],
+) -> Result<()> {
+    let carriers: Vec<&Path> = std::iter::once(file)
+        .chain(args.split_across.iter().map(PathBuf::as_path))
+        .collect();
+    let mut pieces = split::split(payload, payload.len().div_ceil(carriers.len()))
+        .into_iter()
+        .map(|data| Chunk::try_new(chunk_type.clone(), data))
+        .collect::<Result<Vec<_>>>()?
+        .into_iter();
+    let manifest = pieces.next().expect("split always returns a manifest");
+
+    for carrier in carriers {
+        // a payload shorter than the image count leaves the last images
+        // holding only the manifest
+        let chunks: Vec<Chunk> = std::iter::once(manifest.clone())
+            .chain(pieces.next())
+            .collect();
+        let (image, original_size) = read_for_writing(carrier)?;
+        embed(args, carrier, original_size, carrier, image, &chunks)?;
+    }
+    Ok(())
+}
+
+/// Reads the image at `file`, with its size when it is remote and so has
+/// no file `Plan::new` could take the size from
+fn read_for_writing(file: &Path) -> Result<(Carrier, Option<u64>)> {
+    let carrier = inputs::read_carrier(file)?;
+    let original_size = inputs::is_remote(file).then(|| carrier.encoded_length() as u64);
+    Ok((carrier, original_size))
+}
+
+/// Inserts `chunks` into `carrier`, read from `file`, together or at --at,
+/// and writes the result to `target`. `original_size` is the size of an
+/// image that was not read from a local file, see `plan_write`
+fn embed(
+    args: &EncodeArgs,
+    file: &Path,
+    original_size: Option<u64>,
+    target: &Path,
+    mut carrier: Carrier,
+    chunks: &[Chunk],
+) -> Result<()> {
+    let mut changes: Vec<Change> = chunks.iter().map(Change::added).collect();
+    match (&mut carrier, args.entry) {
+        (Carrier::Ico(ico), Some(entry)) => ico.select(entry)?,
+        (carrier, Some(_)) => return Err(CommandError::EntryWithoutIcon(carrier.format()).into()),
+        _ => {}
+    }
+    let placement = Placement {
+        segment: args.segment,
+        region: args.bmp_region,
+    };
+    for (offset, chunk) in chunks.iter().enumerate() {
+        match args.at {
+            Some(index) => carrier
+                .png_mut("--at")?
+                .insert_chunk_at(index + offset, chunk.clone())?,
+            None => carrier.insert_chunk(chunk.clone(), placement)?,
+        }
+    }
+    if args.touch {
+        touch(carrier.png_mut("--touch")?, &mut changes)?;
+    }
+    if args.record {
+        record(carrier.png_mut("--record")?, "encode", &mut changes)?;
+    }
+    if let Carrier::Png(png) = &carrier {
+        for warning in png.validate().warnings() {
+            complain!("{}: warning: {}", file.display(), warning.message);
+        }
+    }
+    let plan = plan_write(file, original_size, target, carrier, changes)?;
+    apply_or_print(plan, args.dry_run)
+}
+
+/// Adds `chunks` right before the IEND chunk of the png at `file` for
+/// --append, without parsing, checking or rewriting anything before it
+fn append(args: &EncodeArgs, file: &Path, chunks: &[Chunk]) -> Result<()> {
+    // these change more than the end of the file
+    let flags = [
+        ("--at", args.at.is_some()),
+        ("--touch", args.touch),
+        ("--record", args.record),
+        ("--entry", args.entry.is_some()),
+    ];
+    if let Some((flag, _)) = flags.into_iter().find(|(_, given)| *given) {
+        return Err(CommandError::AppendWith(flag).into());
+    }
+    let mut signature = Vec::new();
+    fs::File::open(file)?
+        .take(Png::HEADER_LENGTH as u64)
+        .read_to_end(&mut signature)?;
+    if signature != Png::STANDARD_HEADER {
+        // only parsed to name the format it is in
+        let format = Carrier::from_file(file)?.format();
+        return Err(CommandError::AppendWithoutPng(format).into());
+    }
+
+    if args.dry_run {
+        let size = fs::metadata(file)?.len();
+        let added: usize = chunks
+            .iter()
+            .map(|chunk| chunk.length() + Chunk::META_DATA_LENGTH)
+            .sum();
+        say!(
+            "Would append to {} ({} -> {} bytes)",
+            file.display(),
+            size,
+            size + added as u64
+        );
+        for chunk in chunks {
+            say!("  {}", Change::added(chunk));
+        }
+        return Ok(());
+    }
+    Png::append_in_place(file, chunks)
+}
+
+/// Resolves the bytes to embed and the output path. With --input the message
+/// argument is omitted, so the positional after the chunk type is the output
+fn encode_payload(args: &EncodeArgs) -> Result<(Vec<u8>, Option<PathBuf>)> {
+    match (&args.input, &args.message) {
+        (Some(_), Some(_)) if args.output.is_some() => Err(CommandError::MessageWithInput.into()),
+        (Some(input), output) => Ok((fs::read(input)?, output.as_ref().map(PathBuf::from))),
+        (None, message) => {
+            let message = message.clone().unwrap_or_default();
+            let keyword = match &args.keyword {
+                Some(keyword) => Some(keyword.as_str()),
+                // an encrypted or trailed payload would not be valid text anyway
+                None if args.encrypt
+                    || !args.recipient.is_empty()
+                    || args.recipients_file.is_some()
+                    || args.sign.is_some()
+                    || args.hmac_key.is_some()
+                    || args.fec.is_some()
+                    || args.compress.is_some() =>
+                {
+                    None
+                }
+                None if args.type_key.is_none()
+                    && ["tEXt", "zTXt", "iTXt"].contains(&args.chunk_type.as_str()) =>
+                {
+                    Some("Comment")
+                }
+                None => None,
+            };
+            let payload = match keyword {
+                Some(keyword) => {
+                    let text = match args.chunk_type.as_str() {
+                        "iTXt" => {
+                            TextChunk::international(keyword, message, International::default())?
+                        }
+                        "zTXt" => TextChunk::new(keyword, message)?.compress(),
+                        _ => TextChunk::new(keyword, message)?,
+                    };
+                    text.to_chunk()?.into_data()
+                }
+                None => message.into_bytes(),
+            };
+            Ok((payload, args.output.clone()))
+        }
+    }
+}
+
+/// The keys decode may need to unwrap signed and encrypted payloads
+struct PayloadKeys {
+    passphrase: Option<String>,
+    identity: Option<PathBuf>,
+    verifying_key: Option<VerifyingKey>,
+    hmac_key: Option<String>,
+}
+
+impl PayloadKeys {
+    /// Returns the payload stored in a `chunk_type` chunk with any signature
+    /// or HMAC tag checked and removed and any encryption and compression
+    /// undone
+    fn open(&mut self, chunk_type: &ChunkType, stored: &[u8]) -> Result<Vec<u8>> {
+        // encode stores plain messages in text chunks with a keyword, see
+        // `encode_payload`
+        let text_types = [ChunkType::tEXt, ChunkType::zTXt, ChunkType::iTXt];
+        if text_types.contains(chunk_type) && !is_envelope(stored) {
+            let chunk = Chunk::new(chunk_type.clone(), stored.to_vec());
+            if let Ok(text) = TextChunk::try_from(&chunk) {
+                return Ok(text.text.into_bytes());
+            }
+        }
+        let recovered;
+        let stored = match fec::is_protected(stored) {
+            true => {
+                let corrected;
+                (recovered, corrected) = fec::recover(stored)?;
+                if corrected > 0 {
+                    complain!(
+                        "corrected {} damaged shard(s) of the {} payload",
+                        corrected,
+                        chunk_type
+                    );
+                }
+                &recovered
+            }
+            false => stored,
+        };
+        let data = match &self.verifying_key {
+            Some(key) => signing::verify(stored, key)?,
+            None => {
+                if signing::is_signed(stored) {
+                    complain!(
+                        "warning: the {} payload is signed, use --verify to check it",
+                        chunk_type
+                    );
+                }
+                signing::strip(stored)
+            }
+        };
+        let data = match &self.hmac_key {
+            Some(key) => signing::verify_tag(data, key.as_bytes())?,
+            None => {
+                if signing::is_tagged(data) {
+                    complain!(
+                        "warning: the {} payload has an HMAC tag, use --hmac-key to check it",
+                        chunk_type
+                    );
+                }
+                signing::strip_tag(data)
+            }
+        };
+
+        let data = self.decrypt(data)?;
+        match compress::is_compressed(&data) {
+            true => compress::decompress(&data),
+            false => Ok(data),
+        }
+    }
+
+    /// Undoes passphrase or age encryption, asking for the passphrase the
+    /// first time it is needed
+    fn decrypt(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+        if crypto::is_age_encrypted(data) {
+            let identity = self
+                .identity
+                .as_deref()
+                .ok_or(CryptoError::MissingIdentity)?;
+            return crypto::decrypt_with_identity_file(data, identity);
+        }
+        if !crypto::is_encrypted(data) {
+            return Ok(data.to_vec());
+        }
+        let passphrase = match &mut self.passphrase {
+            Some(passphrase) => passphrase,
+            None => self.passphrase.insert(passphrase::passphrase(None, false)?),
+        };
+        crypto::decrypt(data, passphrase)
+    }
+}
+
+/// Joins the parts of a split payload. When parts are missing or damaged
+/// they are zero filled instead, as long as error correction can repair that
+fn join_payload(manifest: &[u8], chunks: &[&[u8]]) -> Result<Vec<u8>> {
+    split::join(manifest, chunks.iter().copied()).or_else(|e| {
+        let payload = split::join_damaged(manifest, chunks.iter().copied())?;
+        match fec::is_protected(&payload) {
+            true => Ok(payload),
+            false => Err(e),
+        }
+    })
+}
+
+/// The payloads stored in the chunks of `chunk_type` in file order, paired
+/// with the chunk each starts in. Split payloads are reassembled from their
+/// parts and checked against the hash in their manifest
+fn stored_payloads<'a>(
+    carrier: &'a Carrier,
+    chunk_type: &'a str,
+) -> Result<Vec<(&'a Chunk, Vec<u8>)>> {
+    // collected once, as every manifest looks for its parts among them all
+    let chunks: Vec<&Chunk> = carrier.chunks_by_type(chunk_type).collect();
+    let parts: Vec<&[u8]> = chunks.iter().map(|chunk| chunk.data()).collect();
+    let mut payloads = Vec::new();
+    for (&chunk, &data) in chunks.iter().zip(&parts) {
+        if split::is_part(data) {
+            continue;
+        }
+        let payload = match split::is_manifest(data) {
+            true => join_payload(data, &parts)?,
+            false => data.to_vec(),
+        };
+        payloads.push((chunk, payload));
+    }
+    Ok(payloads)
+}
+
+/// Opens the first of `payloads`, or every one when `all` is set, skipping
+/// those the passphrase does not fit. That is what lets a decoy and the real
+/// payload share a chunk type. Fails when none opens, and on any other
+/// failure, like a bad signature
+fn open_payloads<'a>(
+    keys: &mut PayloadKeys,
+    payloads: Vec<(&'a Chunk, Vec<u8>)>,
+    all: bool,
+) -> Result<Vec<(&'a Chunk, Vec<u8>)>> {
+    let mut opened = Vec::new();
+    let mut first_error = None;
+    for (chunk, stored) in payloads {
+        match keys.open(chunk.chunk_type(), &stored) {
+            Ok(data) => {
+                opened.push((chunk, data));
+                if !all {
+                    break;
+                }
+            }
+            Err(e @ PicmesError::Crypto(CryptoError::DecryptionFailed)) => {
+                first_error.get_or_insert(e);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    match first_error {
+        Some(e) if opened.is_empty() => Err(e),
+        _ => Ok(opened),
+    }
+}
+
+/// Prints the payload of the first chunk of the requested type, or writes it
+/// to a file when an output path is given. Signatures are checked and
+/// encrypted payloads decrypted first
+pub fn decode(args: DecodeArgs) -> Result<()> {
+    let inputs = inputs::expand(&args.files, args.recursive, inputs::IMAGE_EXTENSIONS)?;
+    if inputs.batch && args.out.is_some() && !args.join {
+        return Err(CommandError::OutputWithBatch.into());
+    }
+    #[cfg(feature = "clipboard")]
+    if inputs.batch && args.to_clipboard && !args.join {
+        return Err(CommandError::OutputWithBatch.into());
+    }
+    #[cfg(feature = "qr")]
+    if inputs.batch && args.qr.is_some() && !args.join {
+        return Err(CommandError::OutputWithBatch.into());
+    }
+    let mut keys = PayloadKeys {
+        passphrase: args.passphrase.clone(),
+        identity: args.identity.clone(),
+        verifying_key: args
+            .verify
+            .as_deref()
+            .map(signing::read_verifying_key)
+            .transpose()?,
+        hmac_key: args.hmac_key.clone(),
+    };
+    let chunk_type = match &args.type_key {
+        Some(key) => signing::derive_chunk_type(key.as_bytes(), &args.chunk_type).to_string(),
+        None => args.chunk_type.clone(),
+    };
+    if args.join {
+        return decode_joined(&args, &chunk_type, &inputs.files, &mut keys);
+    }
+
+    for_each_file(&inputs, |file| {
+        let carrier = inputs::read_carrier(file)?;
+        let payloads = stored_payloads(&carrier, &chunk_type)?;
+        if payloads.is_empty() {
+            return Err(PngError::UnknownChunkType.into());
+        }
+        let opened = open_payloads(&mut keys, payloads, args.all)?;
+
+        if let Some(out) = &args.out {
+            let (_, data) = &opened[0];
+            plan::write_atomic(out, data)?;
+            say!("Wrote {} bytes to {}", data.len(), out.display());
+            return Ok(());
+        }
+        #[cfg(feature = "qr")]
+        if let Some(path) = &args.qr {
+            let (_, data) = &opened[0];
+            return write_qr(path, data);
+        }
+        #[cfg(feature = "clipboard")]
+        if args.to_clipboard {
+            let (_, data) = opened.into_iter().next().expect("a payload was opened");
+            return copy_to_clipboard(data, args.clear_after);
+        }
+        for (chunk, data) in opened {
+            if args.json {
+                let payload = DecodedPayload::new(chunk, &data);
+                if inputs.batch {
+                    say!("{}", serde_json::to_string(&FilePayload { file, payload })?);
+                } else {
+                    say!("{}", serde_json::to_string(&payload)?);
+                }
+            } else if inputs.batch {
+                say!("{}: {}", file.display(), String::from_utf8(data)?);
+            } else {
+                say!("{}", String::from_utf8(data)?);
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Reassembles the payload spread over `files` by encode --split-across and
+/// prints it or writes it to --out
+fn decode_joined(
+    args: &DecodeArgs,
+    chunk_type: &str,
+    files: &[PathBuf],
+    keys: &mut PayloadKeys,
+) -> Result<()> {
+    let carriers = files
+        .iter()
+        .map(|file| inputs::read_carrier(file))
+        .collect::<Result<Vec<_>>>()?;
+    let chunks: Vec<&Chunk> = carriers
+        .iter()
+        .flat_map(|carrier| carrier.chunks_by_type(chunk_type))
+        .collect();
+    let manifest = chunks
+        .iter()
+        .find(|chunk| split::is_manifest(chunk.data()))
+        .ok_or(SplitError::MissingManifest)?;
+    let parts: Vec<&[u8]> = chunks.iter().map(|c| c.data()).collect();
+    let stored = join_payload(manifest.data(), &parts)?;
+    let data = keys.open(manifest.chunk_type(), &stored)?;
+
+    #[cfg(feature = "qr")]
+    if let Some(path) = &args.qr {
+        return write_qr(path, &data);
+    }
+    #[cfg(feature = "clipboard")]
+    if args.to_clipboard {
+        return copy_to_clipboard(data, args.clear_after);
+    }
+    if let Some(out) = &args.out {
+        plan::write_atomic(out, &data)?;
+        say!("Wrote {} bytes to {}", data.len(), out.display());
+    } else if args.json {
+        let payload = DecodedPayload::new(manifest, &data);
+        say!("{}", serde_json::to_string(&payload)?);
+    } else {
+        say!("{}", String::from_utf8(data)?);
+    }
+    Ok(())
+}
+
+/// Writes a decoded payload to `path` as a QR code
+#[cfg(feature = "qr")]
+fn write_qr(path: &Path, data: &[u8]) -> Result<()> {
+    qr::render(data)?.save(path)?;
+    say!(
+        "Wrote a QR code of {} bytes to {}",
+        data.len(),
+        path.display()
+    );
+    Ok(())
+}
+
+/// Copies a decoded message to the clipboard, waiting `clear_after` seconds
+/// to clear it again
+#[cfg(feature = "clipboard")]
+fn copy_to_clipboard(data: Vec<u8>, clear_after: u64) -> Result<()> {
+    let text = String::from_utf8(data)?;
+    say!(
+        "Copied to the clipboard, clearing it in {} seconds",
+        clear_after
+    );
+    clipboard::copy_secret(&text, std::time::Duration::from_secs(clear_after))
+}
+
+/// Removes the first chunk of the requested type, rewriting the file in
+/// place. Critical chunks are only removed when forced
+pub fn remove(args: RemoveArgs) -> Result<()> {
+    let mut carrier = Carrier::from_file(&args.file)?;
+
+    let chunk = carrier
+        .chunks_by_type(&args.chunk_type)
+        .next()
+        .ok_or(PngError::UnknownChunkType)?;
+    if chunk.chunk_type().is_critical() && !args.force {
+        return Err(CommandError::CriticalChunk(args.chunk_type).into());
+    }
+
+    let removed = if args.all {
+        carrier.remove_all_chunks(&args.chunk_type)
+    } else {
+        vec![carrier.remove_first_chunk(&args.chunk_type)?]
+    };
+    let mut changes = removed.iter().map(Change::removed).collect();
+    if args.touch {
+        touch(carrier.png_mut("--touch")?, &mut changes)?;
+    }
+    apply_or_print(
+        Plan::new(&args.file, &args.file, carrier, changes)?,
+        args.dry_run,
+    )?;
+    if !args.dry_run {
+        for chunk in &removed {
+            say!(
+                "Removed {} chunk ({} bytes)",
+                chunk.chunk_type(),
+                chunk.length()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Lists every chunk with its length, CRC and property flags, or the
+/// segments or blocks of other formats
+pub fn print(args: PrintArgs) -> Result<()> {
+    let inputs = inputs::expand(&args.files, args.recursive, inputs::IMAGE_EXTENSIONS)?;
+    for_each_file(&inputs, |file| {
+        let png = match inputs::read_carrier(file)? {
+            Carrier::Png(png) => png,
+            carrier => {
+                let sections = carrier.sections().into_iter().map(SectionSummary::from);
+                if args.json {
+                    let sections: Vec<SectionSummary> = sections.collect();
+                    if inputs.batch {
+                        let listing = FileSections { file, sections };
+                        say!("{}", serde_json::to_string(&listing)?);
+                    } else {
+                        say!("{}", serde_json::to_string(&sections)?);
+                    }
+                    return Ok(());
+                }
+                if inputs.batch {
+                    say!("{}:", file.display());
+                }
+                for section in sections {
+                    say!("{}", describe_section(&section));
+                }
+                return Ok(());
+            }
+        };
+        if args.json {
+            let chunks: Vec<ChunkSummary> = png
+                .chunks()
+                .map(|chunk| ChunkSummary {
+                    entropy: args.verbose.then(|| detector::entropy(chunk.data())),
+                    ..ChunkSummary::from(chunk)
+                })
+                .collect();
+            if inputs.batch {
+                say!("{}", serde_json::to_string(&FileChunks { file, chunks })?);
+            } else {
+                say!("{}", serde_json::to_string(&chunks)?);
+            }
+        } else {
+            if inputs.batch {
+                say!("{}:", file.display());
+            }
+            if let Ok(header) = png.header() {
+                say!("{}", header);
+            }
+            if let Ok(Some(animation)) = png.animation() {
+                say!("{}", animation);
+            }
+            if let Ok(intent) = png.rendering_intent() {
+                say!("{}", intent);
+            }
+            if let Ok(gamma) = png.gamma() {
+                say!("{}", gamma);
+            }
+            if let Ok(physical) = png.physical_dimensions() {
+                say!("{}", physical);
+            }
+            for chunk in png.chunks() {
+                match args.verbose {
+                    true => say!(
+                        "{}  entropy={:.2}",
+                        describe(chunk),
+                        detector::entropy(chunk.data())
+                    ),
+                    false => say!("{}", describe(chunk)),
+                }
+            }
+            for text in png.text_metadata() {
+                say!("  {}", text);
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Reports non-standard and duplicate chunks, trailing data and files of
+/// other formats hidden in them
+pub fn scan(args: ScanArgs) -> Result<()> {
+    let inputs = inputs::expand(&args.files, args.recursive, inputs::PNG_EXTENSIONS)?;
+    let format = args.format.or_json(args.json);
+    let mut reporter = Reporter::new(format, stdout());
+    let scanned = for_each_file(&inputs, |file| {
+        let findings = scanner::scan_bytes(&inputs::read(file)?)?;
+        if format != Format::Text {
+            let rows = findings.iter().map(|f| FindingRow::new(file, f));
+            return match inputs.batch {
+                true => {
+                    let document = FileFindings {
+                        file,
+                        findings: &findings,
+                    };
+                    reporter.write(&document, rows)
+                }
+                false => reporter.write(&findings, rows),
+            };
+        }
+
+        if findings.is_empty() {
+            say!("{}: nothing suspicious found", file.display());
+        }
+        for finding in findings {
+            say!(
+                "{}: offset {:#x}  {}  {} bytes  entropy {:.2}  {}",
+                file.display(),
+                finding.offset,
+                finding.chunk_type,
+                finding.length,
+                finding.entropy,
+                finding.reason
+            );
+        }
+        Ok(())
+    });
+    reporter.finish()?;
+    scanned
+}
+
+/// Reports how likely each part of each file is to hold hidden data, and
+/// the file as a whole as likely as its most suspicious part
+pub fn detect(args: DetectArgs) -> Result<()> {
+    let inputs = inputs::expand(&args.files, args.recursive, inputs::PNG_EXTENSIONS)?;
+    for_each_file(&inputs, |file| {
+        let evidence = detector::detect(&inputs::read_png(file)?);
+        let likelihood = evidence.iter().map(|e| e.likelihood).fold(0.0, f64::max);
+        if args.json {
+            let report = FileEvidence {
+                file,
+                likelihood,
+                evidence,
+            };
+            say!("{}", serde_json::to_string(&report)?);
+            return Ok(());
+        }
+
+        for evidence in &evidence {
+            say!(
+                "{}: {}  {:.2}  {}",
+                file.display(),
+                evidence.source,
+                evidence.likelihood,
+                evidence.detail
+            );
+        }
+        let verdict = match likelihood {
+            l if l >= 0.9 => "likely",
+            l if l >= 0.5 => "possible",
+            _ => "unlikely",
+        };
+        say!(
+            "{}: hidden data {} ({:.2})",
+            file.display(),
+            verdict,
+            likelihood
+        );
+        Ok(())
+    })
+}
+
+/// Validates the structure of each file, reporting every problem found
+pub fn verify(args: VerifyArgs) -> Result<()> {
+    let inputs = inputs::expand(&args.files, args.recursive, inputs::PNG_EXTENSIONS)?;
+    let format = args.format.or_json(args.json);
+    let mut reporter = Reporter::new(format, stdout());
+    let verified = for_each_file(&inputs, |file| {
+        let report = verifier::verify(&inputs::read(file)?);
+        if format != Format::Text {
+            let rows = report.problems.iter().map(|p| ProblemRow::new(file, p));
+            match inputs.batch {
+                true => {
+                    let document = FileProblems {
+                        file,
+                        problems: &report.problems,
+                    };
+                    reporter.write(&document, rows)?;
+                }
+                false => reporter.write(&report, rows)?,
+            }
+        } else if report.problems.is_empty() {
+            say!("{}: OK", file.display());
+        } else {
+            for problem in &report.problems {
+                say!(
+                    "{}: offset {:#x}: {}: {}",
+                    file.display(),
+                    problem.offset,
+                    problem.severity,
+                    problem.message
+                );
+            }
+        }
+
+        if !report.is_valid() {
+            let failure = match report.has_bad_checksum() {
+                true => Failure::Checksum,
+                false => Failure::Other,
+            };
+            return Err(CommandError::VerificationFailed {
+                errors: report.errors().count(),
+                failure,
+            }
+            .into());
+        }
+        Ok(())
+    });
+    reporter.finish()?;
+    verified
+}
+
+/// Writes every complete png found in a file of any kind to its own file
+pub fn carve(args: CarveArgs) -> Result<()> {
+    let carved = carver::carve(&progress::read(&args.file)?);
+    if carved.is_empty() {
+        say!("{}: no PNG files found", args.file.display());
+        return Ok(());
+    }
+    if !args.dry_run {
+        fs::create_dir_all(&args.out)?;
+    }
+    let stem = args.file.file_stem().unwrap_or_default().to_string_lossy();
+    for found in carved {
+        let out = args.out.join(format!("{}-{:08x}.png", stem, found.offset));
+        let header = found
+            .png
+            .header()
+            .map(|h| h.to_string())
+            .unwrap_or_default();
+        say!(
+            "offset {:#x}  {} bytes  {}  -> {}",
+            found.offset,
+            found.length,
+            header,
+            out.display()
+        );
+        if !args.dry_run {
+            plan::write_atomic(&out, &found.png.as_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+/// Prints how many chunks of each type a file has, the bytes they take up
+/// and its largest chunks
+pub fn stats(args: StatsArgs) -> Result<()> {
+    let stats = summarizer::stats(&inputs::read_png(&args.file)?, args.top);
+    match args.format.or_json(args.json) {
+        Format::Text => say!("{}", stats),
+        format => {
+            let mut reporter = Reporter::new(format, stdout());
+            reporter.write(&stats, TypeRow::rows(&args.file, &stats))?;
+            reporter.finish()?;
+        }
+    }
+    Ok(())
+}
+
+/// Prints a hash of each file's chunks, leaving out its file signature, in
+/// the format of sha256sum
+pub fn hash(args: HashArgs) -> Result<()> {
+    let inputs = inputs::expand(&args.files, args.recursive, inputs::PNG_EXTENSIONS)?;
+    for_each_file(&inputs, |file| {
+        let hash = signing::file_hash(&inputs::read_png(file)?, args.algorithm);
+        let hex: String = hash.iter().map(|b| format!("{:02x}", b)).collect();
+        say!("{}  {}", hex, file.display());
+        Ok(())
+    })
+}
+
+/// Signs a whole file in place, replacing any earlier file signature
+pub fn sign(args: SignArgs) -> Result<()> {
+    let key = signing::read_signing_key(&args.key)?;
+    let mut png = Png::from_file(&args.file)?;
+    let chunk = signing::sign_file(&mut png, &key);
+    let changes = vec![Change::added(&chunk)];
+    apply_or_print(
+        Plan::new(&args.file, &args.file, png, changes)?,
+        args.dry_run,
+    )
+}
+
+/// Checks the file signature of a file against a public key
+pub fn verify_file(args: VerifyFileArgs) -> Result<()> {
+    let key = signing::read_verifying_key(&args.key)?;
+    signing::verify_file(&inputs::read_png(&args.file)?, &key)?;
+    say!("{}: signature OK", args.file.display());
+    Ok(())
+}
+
+/// Prints how the chunks of the new file differ from those of the old one
+pub fn diff(args: DiffArgs) -> Result<()> {
+    let differences = differ::diff(&inputs::read_png(&args.old)?, &inputs::read_png(&args.new)?);
+    if args.json {
+        say!("{}", serde_json::to_string(&differences)?);
+    } else if differences.is_empty() {
+        say!("No differences");
+    } else {
+        for difference in &differences {
+            say!("{}", difference);
+        }
+    }
+    Ok(())
+}
+
+/// Writes a copy of a damaged file with CRCs recomputed, trailing data
+/// removed and unrecoverable chunks truncated or dropped on request
+pub fn repair(args: RepairArgs) -> Result<()> {
+    let options = RepairOptions {
+        truncate: args.truncate,
+        drop: args.drop,
+        dedup: args.dedup,
+    };
+    let (mut png, mut changes) = repairer::repair(&progress::read(&args.file)?, options)?;
+    if args.touch {
+        touch(&mut png, &mut changes)?;
+    }
+    if args.record {
+        record(&mut png, "repair", &mut changes)?;
+    }
+
+    let target = args
+        .output
+        .clone()
+        .unwrap_or_else(|| repaired_path(&args.file));
+    if args.dry_run {
+        say!("{}", Plan::new(&args.file, &target, png, changes)?);
+        return Ok(());
+    }
+
+    if changes.is_empty() {
+        say!("{}: nothing to repair", args.file.display());
+    }
+    for change in &changes {
+        say!("{}", change);
+    }
+    Plan::new(&args.file, &target, png, changes)?.apply()?;
+    say!("Wrote {}", target.display());
+    Ok(())
+}
+
+/// Removes every ancillary chunk except the kept types. The animation
+/// chunks of an animated png are part of the image and always kept
+pub fn strip(args: StripArgs) -> Result<()> {
+    let inputs = inputs::expand(&args.files, args.recursive, inputs::PNG_EXTENSIONS)?;
+    reject_read_only(&inputs.files, false)?;
+    for_each_file(&inputs, |file| {
+        let mut png = inputs::read_png(file)?;
+        let original_size = inputs::is_remote(file).then(|| png.encoded_length() as u64);
+        let removed = png.remove_chunks_where(|chunk| {
+            let chunk_type = chunk.chunk_type();
+            // the history goes on when the strip itself is recorded
+            let keeps_history = args.record && chunk_type == &history::HISTORY;
+            !chunk_type.is_critical()
+                && !ANIMATION_CHUNKS.contains(chunk_type)
+                && !args.keep.contains(&chunk_type.to_string())
+                && !keeps_history
+        });
+
+        let mut changes = removed.iter().map(Change::removed).collect();
+        if args.touch {
+            touch(&mut png, &mut changes)?;
+        }
+        if args.record {
+            record(&mut png, "strip", &mut changes)?;
+        }
+        let plan = plan_write(file, original_size, file, png, changes)?;
+        apply_or_print(plan, args.dry_run)?;
+        if !args.dry_run {
+            say!(
+                "{}: removed {} ancillary chunk(s)",
+                file.display(),
+                removed.len()
+            );
+        }
+        Ok(())
+    })
+}
+
+/// Prints the changes recorded in the history chunk, oldest first
+pub fn history(args: HistoryArgs) -> Result<()> {
+    let entries = history::history(&inputs::read_png(&args.file)?)?;
+    if entries.is_empty() {
+        say!("{}: no recorded history", args.file.display());
+    }
+    for entry in entries {
+        say!("{}", entry);
+    }
+    Ok(())
+}
+
+/// Prints or sets the last modification time stored in the tIME chunk
+pub fn time(command: TimeCommand) -> Result<()> {
+    match command {
+        TimeCommand::Get(args) => {
+            say!("{}", Png::from_file(&args.file)?.last_modified()?);
+        }
+        TimeCommand::Set(args) => {
+            let time = match &args.time {
+                Some(time) => LastModified::from_str(time)?,
+                None => LastModified::now()?,
+            };
+            let mut png = Png::from_file(&args.file)?;
+            let change = set_last_modified(&mut png, time);
+            apply_or_print(
+                Plan::new(&args.file, &args.file, png, vec![change])?,
+                args.dry_run,
+            )?;
+            if !args.dry_run {
+                say!("Set tIME to {}", time);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Prints the common EXIF tags, writes the raw EXIF blob to a file or
+/// replaces it with the contents of another file
+pub fn exif(args: ExifArgs) -> Result<()> {
+    let mut png = Png::from_file(&args.file)?;
+    let Some(input) = &args.set else {
+        let data = png.exif()?;
+        match &args.out {
+            Some(out) => {
+                plan::write_atomic(out, data)?;
+                say!("Wrote {} bytes to {}", data.len(), out.display());
+            }
+            None => say!("{}", ExifTags::parse(data)?),
+        }
+        return Ok(());
+    };
+
+    let replaced = png.set_exif(fs::read(input)?)?;
+    let chunk = png
+        .chunk_by_type("eXIf")
+        .ok_or(PngError::UnknownChunkType)?;
+    let mut changes = vec![match replaced {
+        Some(old) => Change::updated(
+            chunk,
+            format!("replaced {} bytes with {}", old.length(), chunk.length()),
+        ),
+        None => Change::added(chunk),
+    }];
+    if args.touch {
+        touch(&mut png, &mut changes)?;
+    }
+    apply_or_print(
+        Plan::new(&args.file, &args.file, png, changes)?,
+        args.dry_run,
+    )?;
+    if !args.dry_run {
+        say!("Updated EXIF data in {}", args.file.display());
+    }
+    Ok(())
+}
+
+/// Writes the data of the first chunk of the requested type to a file,
+/// byte for byte
+pub fn extract(args: ExtractArgs) -> Result<()> {
+    let png = Png::from_file(&args.file)?;
+    let chunk = png
+        .chunk_by_type(&args.chunk_type)
+        .ok_or(PngError::UnknownChunkType)?;
+
+    let length = if args.whole {
+        plan::write_atomic_with(&args.out, |file| chunk.write_to(file))?;
+        chunk.length() + Chunk::META_DATA_LENGTH
+    } else {
+        plan::write_atomic(&args.out, chunk.data())?;
+        chunk.length()
+    };
+    say!("Wrote {} bytes to {}", length, args.out.display());
+    Ok(())
+}
+
+/// Dumps the data of every chunk, or of every chunk of the given type, or
+/// only the one at --index among those
+pub fn hexdump(args: HexdumpArgs) -> Result<()> {
+    let carrier = Carrier::from_file(&args.file)?;
+    let chunks: Vec<&Chunk> = match &args.chunk_type {
+        Some(chunk_type) => carrier.chunks_by_type(chunk_type).collect(),
+        None => carrier.chunks().collect(),
+    };
+    if chunks.is_empty() {
+        return Err(PngError::UnknownChunkType.into());
+    }
+    let chunks = match args.index {
+        Some(index) => vec![*chunks.get(index).ok_or(CommandError::NoChunkAt {
+            index,
+            count: chunks.len(),
+        })?],
+        None => chunks,
+    };
+
+    for (i, chunk) in chunks.iter().enumerate() {
+        if i > 0 {
+            say!();
+        }
+        say!("{}", describe(chunk));
+        say_inline!("{}", hex_dump(chunk.data()));
+    }
+    Ok(())
+}
+
+/// Copies every payload chunk of one carrier into another as it is, so
+/// encrypted and signed payloads keep their envelopes
+pub fn migrate(args: MigrateArgs) -> Result<()> {
+    let is_payload = |chunk: &Chunk| {
+        let chunk_type = chunk.chunk_type();
+        args.chunk_types.contains(&chunk_type.to_string())
+            || (!chunk_type.is_registered() && is_envelope(chunk.data()))
+    };
+    let mut source = Carrier::from_file(&args.source)?;
+    let chunks: Vec<Chunk> = source.chunks().filter(|c| is_payload(c)).cloned().collect();
+    if chunks.is_empty() {
+        return Err(CommandError::NothingToMigrate.into());
+    }
+
+    let mut target = Carrier::from_file(&args.target)?;
+    for chunk in &chunks {
+        if chunk.length() > target.max_chunk_length() {
+            return Err(CommandError::TooLongToMigrate {
+                chunk_type: chunk.chunk_type().to_string(),
+                length: chunk.length(),
+                format: target.format(),
+            }
+            .into());
+        }
+        target.insert_chunk(chunk.clone(), Placement::default())?;
+    }
+    let out = args.out.as_ref().unwrap_or(&args.target);
+    let changes = chunks.iter().map(Change::added).collect();
+    apply_or_print(Plan::new(&args.target, out, target, changes)?, args.dry_run)?;
+
+    if args.remove {
+        let removed = source.remove_chunks_where(is_payload);
+        let changes = removed.iter().map(Change::removed).collect();
+        apply_or_print(
+            Plan::new(&args.source, &args.source, source, changes)?,
+            args.dry_run,
+        )?;
+    }
+    if !args.dry_run {
+        say!(
+            "Migrated {} payload chunk(s) to {}",
+            chunks.len(),
+            out.display()
+        );
+    }
+    Ok(())
+}
+
+/// Encrypts every custom chunk of a file into one sealed chunk
+pub fn seal(args: SealArgs) -> Result<()> {
+    let mut png = Png::from_file(&args.file)?;
+    if !png
+        .chunks()
+        .any(|c| sealer::is_sealable(c, &args.chunk_types))
+    {
+        return Err(SealError::NothingToSeal.into());
+    }
+    let passphrase = passphrase::passphrase(args.passphrase.clone(), true)?;
+    let options = EncryptOptions {
+        cipher: args.cipher,
+        ..Default::default()
+    };
+    let (sealed, container) = sealer::seal(&mut png, &args.chunk_types, &passphrase, options)?;
+
+    let mut changes: Vec<Change> = sealed.iter().map(Change::removed).collect();
+    changes.push(Change::added(&container));
+    if args.touch {
+        touch(&mut png, &mut changes)?;
+    }
+    apply_or_print(
+        Plan::new(&args.file, &args.file, png, changes)?,
+        args.dry_run,
+    )?;
+    if !args.dry_run {
+        say!(
+            "Sealed {} chunk(s) into {} ({} bytes)",
+            sealed.len(),
+            container.chunk_type(),
+            container.length()
+        );
+    }
+    Ok(())
+}
+
+/// Decrypts the sealed chunk of a file and puts the chunks it holds back
+pub fn unseal(args: UnsealArgs) -> Result<()> {
+    let mut png = Png::from_file(&args.file)?;
+    if !sealer::is_sealed(&png) {
+        return Err(SealError::NotSealed.into());
+    }
+    let passphrase = passphrase::passphrase(args.passphrase.clone(), false)?;
+    let (container, restored) = sealer::unseal(&mut png, &passphrase)?;
+
+    let mut changes = vec![Change::removed(&container)];
+    changes.extend(restored.iter().map(Change::added));
+    if args.touch {
+        touch(&mut png, &mut changes)?;
+    }
+    apply_or_print(
+        Plan::new(&args.file, &args.file, png, changes)?,
+        args.dry_run,
+    )?;
+    if !args.dry_run {
+        say!("Unsealed {} chunk(s)", restored.len());
+    }
+    Ok(())
+}
+
+/// Reports how many bytes fit in a file with each hiding method. When every
+/// method is listed, one that does not suit the image is reported rather
+/// than failing the command
+pub fn capacity(args: CapacityArgs) -> Result<()> {
+    let png = Png::from_file(&args.file)?;
+    let methods = match args.method {
+        Some(method) => vec![method],
+        None => vec![HidingMethod::Chunk, HidingMethod::Lsb],
+    };
+    for method in methods {
+        match method {
+            HidingMethod::Chunk => say!("chunk: {} bytes per chunk", MAX_CHUNK_LENGTH),
+            HidingMethod::Lsb => {
+                let bytes =
+                    ImageData::from_png(&png).and_then(|image| steganography::capacity(&image));
+                match bytes {
+                    Ok(bytes) => say!(
+                        "lsb: {} bytes in the pixel data of the {} image",
+                        bytes,
+                        png.header()?
+                    ),
+                    Err(e) if args.method.is_none() => say!("lsb: unavailable, {}", e),
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Hides a payload in, or reveals it from, the least significant bits of
+/// the image samples
+pub fn stego(command: StegoCommand) -> Result<()> {
+    match command {
+        StegoCommand::Hide(args) => {
+            let payload = match &args.input {
+                Some(input) => fs::read(input)?,
+                None => args.message.clone().unwrap_or_default().into_bytes(),
+            };
+            let mut png = Png::from_file(&args.file)?;
+            let mut image = ImageData::from_png(&png)?;
+            steganography::hide(&mut image, &payload)?;
+            let idat = image.to_chunk()?;
+            let change = Change::updated(
+                &idat,
+                format!("{} bytes hidden in the pixel data", payload.len()),
+            );
+            png.replace_image_data(idat)?;
+
+            let target = args.output.as_deref().unwrap_or(&args.file);
+            apply_or_print(
+                Plan::new(&args.file, target, png, vec![change])?,
+                args.dry_run,
+            )?;
+            if !args.dry_run {
+                say!("Hid {} bytes in the pixel data", payload.len());
+            }
+        }
+        StegoCommand::Reveal(args) => {
+            let image = ImageData::from_png(&Png::from_file(&args.file)?)?;
+            let payload = steganography::reveal(&image)?;
+            match &args.out {
+                Some(out) => {
+                    plan::write_atomic(out, &payload)?;
+                    say!("Wrote {} bytes to {}", payload.len(), out.display());
+                }
+                None => say!("{}", String::from_utf8_lossy(&payload)),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Stores, prints or deletes a secret kept in the platform keychain
+#[cfg(feature = "keyring")]
+pub fn keys(command: KeysCommand) -> Result<()> {
+    match command {
+        KeysCommand::Store(args) => {
+            let secret = match &args.file {
+                Some(file) => fs::read(file)?,
+                None => passphrase::passphrase(None, true)?.into_bytes(),
+            };
+            StoredKey::open(&args.name)?.store(&secret)?;
+            say!("Stored {} in the keychain", args.name);
+        }
+        KeysCommand::Load(args) => {
+            let secret = StoredKey::open(&args.name)?.load()?;
+            match &args.out {
+                Some(out) => {
+                    plan::write_atomic(out, &secret)?;
+                    say!("Wrote {} bytes to {}", secret.len(), out.display());
+                }
+                None => io::Write::write_all(&mut stdout(), &secret)?,
+            }
+        }
+        KeysCommand::Delete(args) => {
+            StoredKey::open(&args.name)?.delete()?;
+            say!("Deleted {} from the keychain", args.name);
+        }
+    }
+    Ok(())
+}
+
+/// Runs --on-add on every image added to the directory until interrupted.
+/// A run that fails is reported and watching carries on
+#[cfg(feature = "watch")]
+pub fn watch(args: WatchArgs) -> Result<()> {
+    // a mistyped command fails now rather than on the first image
+    on_add_args(&args.on_add, Path::new("image.png"))?;
+    let existing = match args.existing {
+        true => inputs::images_in(&args.dir, args.recursive, inputs::IMAGE_EXTENSIONS)?,
+        false => Vec::new(),
+    };
+    say!(
+        "Watching {} for new images, stop with Ctrl-C",
+        args.dir.display()
+    );
+    watcher::watch(
+        &args.dir,
+        args.recursive,
+        std::time::Duration::from_millis(args.settle),
+        inputs::IMAGE_EXTENSIONS,
+        existing,
+        |file| {
+            if let Err(error) = on_add_args(&args.on_add, file).and_then(run) {
+                complain!("{}: {}", file.display(), error);
+            }
+        },
+        |error| complain!("warning: {}", error),
+    )
+}
+
+/// Parses the --on-add command of watch for `file`
+#[cfg(feature = "watch")]
+fn on_add_args(command: &str, file: &Path) -> Result<PicmesArgs> {
+    use clap::Parser;
+
+    let args = watcher::command_line(command, file)?;
+    Ok(Cli::try_parse_from(std::iter::once("picmes".to_string()).chain(args))?.command)
+}
+
+pub fn completions(args: CompletionsArgs) -> Result<()> {
+    let mut command = Cli::command();
+    let name = command.get_name().to_string();
+    clap_complete::generate(args.shell, &mut command, name, &mut stdout());
+    Ok(())
+}
+
+/// `image.png` becomes `image-repaired.png` in the same directory
+fn repaired_path(file: &Path) -> PathBuf {
+    let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+    file.with_file_name(format!("{}-repaired.png", stem))
+}
+
+fn describe_section(section: &SectionSummary) -> String {
+    let line = format!("{:<9}  length={}", section.name, section.length);
+    match &section.chunk_type {
+        Some(chunk_type) => format!("{:<26}  payload={}", line, chunk_type),
+        None => line,
+    }
+}
+
+/// Formats `bytes` like `hexdump -C`: sixteen bytes per line after their
+/// offset, then the same bytes as ASCII with a dot for anything unprintable,
+/// and finally the total length
+fn hex_dump(bytes: &[u8]) -> String {
+    let mut dump = String::new();
+    for (line, row) in bytes.chunks(16).enumerate() {
+        let mut hex = String::new();
+        for column in 0..16 {
+            if column == 8 {
+                hex.push(' ');
+            }
+            match row.get(column) {
+                Some(byte) => hex.push_str(&format!("{:02x} ", byte)),
+                None => hex.push_str("   "),
+            }
+        }
+        let ascii: String = row
+            .iter()
+            .map(|&byte| match byte.is_ascii_graphic() || byte == b' ' {
+                true => byte as char,
+                false => '.',
+            })
+            .collect();
+        dump.push_str(&format!("{:08x}  {} |{}|\n", line * 16, hex, ascii));
+    }
+    dump.push_str(&format!("{:08x}\n", bytes.len()));
+    dump
+}
+
+fn describe(chunk: &Chunk) -> String {
+    let chunk_type = chunk.chunk_type();
+    format!(
+        "{}  length={:<8} crc={:#010x}  {} {} {}",
+        chunk_type,
+        chunk.length(),
+        chunk.crc(),
+        if chunk_type.is_critical() {
+            "critical"
+        } else {
+            "ancillary"
+        },
+        if chunk_type.is_public() {
+            "public"
+        } else {
+            "private"
+        },
+        if chunk_type.is_safe_to_copy() {
+            "safe-to-copy"
+        } else {
+            "unsafe-to-copy"
+        },
+    )
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::apng::tests::{actl_chunk, fctl_chunk};
+    use crate::args::TimeSetArgs;
+    use crate::bmp::{tests::testing_bmp_bytes, Region};
+    use crate::compress::Compressor;
+    use crate::crypto::Cipher;
+    use crate::gif::tests::testing_gif_bytes;
+    use crate::ico::tests::testing_ico_bytes;
+    use crate::jpeg::{tests::testing_jpeg_bytes, Jpeg, SegmentKind};
+    pub(crate) use crate::png::tests::{testing_file, testing_png_bytes};
+    use crate::tiff::tests::testing_tiff_bytes;
+    use crate::webp::tests::testing_webp_bytes;
+    use clap_complete::Shell;
+
+    fn spec(path: &Path) -> String {
+        path.to_string_lossy().into_owned()
+    }
+
+    fn encode_args(
+        file: &Path,
+        chunk_type: &str,
+        message: &str,
+        output: Option<&Path>,
+    ) -> EncodeArgs {
+        EncodeArgs {
+            file: spec(file),
+            chunk_type: chunk_type.to_string(),
+            message: Some(message.to_string()),
+            output: output.map(Path::to_path_buf),
+            ..Default::default()
+        }
+    }
+
+    fn remove_args(file: &Path, chunk_type: &str, force: bool) -> RemoveArgs {
+        RemoveArgs {
+            file: file.to_path_buf(),
+            chunk_type: chunk_type.to_string(),
+            force,
+            ..Default::default()
+        }
+    }
+
+    fn message(png: &Png, chunk_type: &str) -> Result<String> {
+        let chunk = png
+            .chunk_by_type(chunk_type)
+            .ok_or(PngError::UnknownChunkType)?;
+        chunk.data_as_string()
+    }
+
+    fn chunk_types(png: &Png) -> Vec<String> {
+        png.chunks().map(|c| c.chunk_type().to_string()).collect()
+    }
+
+    #[test]
+    fn test_encode_placement() {
+        let file = testing_file("encode-placement");
+        encode(encode_args(&file, "pHYs", "placed", None)).unwrap();
+        encode(EncodeArgs {
+            at: Some(1),
+            ..encode_args(&file, "ruSt", "first", None)
+        })
+        .unwrap();
+
+        let png = Png::from_file(&file).unwrap();
+        assert_eq!(chunk_types(&png), ["IHDR", "ruSt", "pHYs", "IDAT", "IEND"]);
+        assert!(encode(EncodeArgs {
+            at: Some(9),
+            ..encode_args(&file, "ruSt", "lost", None)
+        })
+        .is_err());
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_encode_text_keyword() {
+        let file = testing_file("encode-keyword");
+        encode(encode_args(&file, "tEXt", "plain", None)).unwrap();
+        encode(EncodeArgs {
+            keyword: Some("Title".to_string()),
+            ..encode_args(&file, "tEXt", "Dice", None)
+        })
+        .unwrap();
+        encode(encode_args(&file, "zTXt", "squeezed", None)).unwrap();
+        encode(encode_args(&file, "iTXt", "caf\u{e9} \u{2615}", None)).unwrap();
+
+        let png = Png::from_file(&file).unwrap();
+        let metadata: Vec<String> = png.text_metadata().iter().map(|t| t.to_string()).collect();
+        assert_eq!(
+            metadata,
+            [
+                "Comment: plain",
+                "Title: Dice",
+                "Comment: squeezed",
+                "Comment: caf\u{e9} \u{2615}"
+            ]
+        );
+        assert!(encode(EncodeArgs {
+            keyword: Some(" bad".to_string()),
+            ..encode_args(&file, "tEXt", "Dice", None)
+        })
+        .is_err());
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_decode_text_chunks() {
+        let file = testing_file("decode-text");
+        let out = file.with_extension("txt");
+        for (chunk_type, message) in [
+            ("tEXt", "plain"),
+            ("zTXt", "squeezed"),
+            ("iTXt", "caf\u{e9} \u{2615}"),
+        ] {
+            encode(encode_args(&file, chunk_type, message, None)).unwrap();
+            decode(DecodeArgs {
+                files: vec![spec(&file)],
+                chunk_type: chunk_type.to_string(),
+                out: Some(out.clone()),
+                ..Default::default()
+            })
+            .unwrap();
+            assert_eq!(fs::read_to_string(&out).unwrap(), message);
+        }
+        fs::remove_file(file).unwrap();
+        fs::remove_file(out).unwrap();
+    }
+
+    #[test]
+    fn test_encode_in_place() {
+        let file = testing_file("encode-in-place");
+        encode(encode_args(&file, "ruSt", "hidden", None)).unwrap();
+
+        let png = Png::from_file(&file).unwrap();
+        assert_eq!(chunk_types(&png), ["IHDR", "IDAT", "ruSt", "IEND"]);
+        assert_eq!(
+            png.chunks().nth(2).unwrap().data_as_string().unwrap(),
+            "hidden"
+        );
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_encode_append() {
+        let file = testing_file("encode-append");
+        let mut args = encode_args(&file, "ruSt", "hidden", None);
+        args.append = true;
+        encode(args).unwrap();
+
+        let png = Png::from_file(&file).unwrap();
+        assert_eq!(chunk_types(&png), ["IHDR", "IDAT", "ruSt", "IEND"]);
+        assert_eq!(message(&png, "ruSt").unwrap(), "hidden");
+
+        let mut args = encode_args(&file, "pHYs", "hidden", None);
+        args.append = true;
+        assert!(encode(args).is_err());
+        let mut args = encode_args(
+            &file,
+            "ruSt",
+            "hidden",
+            Some(&file.with_extension("out.png")),
+        );
+        args.append = true;
+        assert!(encode(args).is_err());
+        let flags: [fn(&mut EncodeArgs); 3] = [
+            |args| args.at = Some(1),
+            |args| args.touch = true,
+            |args| args.record = true,
+        ];
+        for set in flags {
+            let mut args = encode_args(&file, "ruSt", "hidden", None);
+            args.append = true;
+            set(&mut args);
+            assert!(matches!(
+                encode(args),
+                Err(PicmesError::Command(CommandError::AppendWith(_)))
+            ));
+        }
+        assert_eq!(chunk_types(&Png::from_file(&file).unwrap()).len(), 4);
+        fs::remove_file(file).unwrap();
+
+        let jpeg = testing_jpeg_file("encode-append-jpeg");
+        let mut args = encode_args(&jpeg, "ruSt", "hidden", None);
+        args.append = true;
+        assert!(matches!(
+            encode(args),
+            Err(PicmesError::Command(CommandError::AppendWithoutPng("jpeg")))
+        ));
+        fs::remove_file(jpeg).unwrap();
+    }
+
+    #[test]
+    fn test_encode_to_output() {
+        let file = testing_file("encode-output-src");
+        let output = file.with_extension("out.png");
+        encode(encode_args(&file, "ruSt", "hidden", Some(&output))).unwrap();
+
+        assert_eq!(fs::read(&file).unwrap(), testing_png_bytes());
+        assert_eq!(Png::from_file(&output).unwrap().chunks().len(), 4);
+        fs::remove_file(file).unwrap();
+        fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_encode_from_input_file() {
+        let file = testing_file("encode-input");
+        let payload_file = file.with_extension("payload");
+        let output = file.with_extension("out.png");
+        let payload: Vec<u8> = (0..=255).collect();
+        fs::write(&payload_file, &payload).unwrap();
+
+        encode(EncodeArgs {
+            file: spec(&file),
+            chunk_type: "ruSt".to_string(),
+            message: Some(spec(&output)),
+            input: Some(payload_file.clone()),
+            ..Default::default()
+        })
+        .unwrap();
+        let png = Png::from_file(&output).unwrap();
+        assert_eq!(png.chunk_by_type("ruSt").unwrap().data(), payload);
+        assert_eq!(fs::read(&file).unwrap(), testing_png_bytes());
+
+        let result = encode(EncodeArgs {
+            file: spec(&file),
+            chunk_type: "ruSt".to_string(),
+            message: Some("message".to_string()),
+            output: Some(output.clone()),
+            input: Some(payload_file.clone()),
+            ..Default::default()
+        });
+        assert!(result.is_err());
+
+        for path in [file, payload_file, output] {
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_encode_then_decode() {
+        let file = testing_file("encode-decode");
+        encode(encode_args(&file, "ruSt", "first", None)).unwrap();
+        encode(encode_args(&file, "ruSt", "second", None)).unwrap();
+
+        let png = Png::from_file(&file).unwrap();
+        assert_eq!(message(&png, "ruSt").unwrap(), "first");
+        assert!(message(&png, "miSs").is_err());
+        for json in [false, true] {
+            assert!(decode(DecodeArgs {
+                files: vec![spec(&file)],
+                chunk_type: "ruSt".to_string(),
+                json,
+                ..Default::default()
+            })
+            .is_ok());
+        }
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_decode_all() {
+        let file = testing_file("decode-all");
+        encode(encode_args(&file, "ruSt", "first", None)).unwrap();
+        encode(encode_args(&file, "ruSt", "second", None)).unwrap();
+
+        let args = |chunk_type: &str| DecodeArgs {
+            files: vec![spec(&file)],
+            chunk_type: chunk_type.to_string(),
+            all: true,
+            ..Default::default()
+        };
+        decode(args("ruSt")).unwrap();
+        assert!(decode(args("miSs")).is_err());
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_encode_encrypted() {
+        let file = testing_file("encrypted");
+        let out = file.with_extension("txt");
+        encode(EncodeArgs {
+            encrypt: true,
+            passphrase: Some("hunter2".to_string()),
+            cipher: Cipher::ChaCha20Poly1305,
+            kdf_memory: Some(64),
+            kdf_iterations: Some(1),
+            ..encode_args(&file, "ruSt", "attack at dawn", None)
+        })
+        .unwrap();
+        let png = Png::from_file(&file).unwrap();
+        let data = png.chunk_by_type("ruSt").unwrap().data();
+        assert!(crypto::is_encrypted(data));
+        assert!(!data.windows(6).any(|w| w == b"attack"));
+
+        let args = |passphrase: &str| DecodeArgs {
+            files: vec![spec(&file)],
+            chunk_type: "ruSt".to_string(),
+            out: Some(out.clone()),
+            passphrase: Some(passphrase.to_string()),
+            ..Default::default()
+        };
+        assert!(decode(args("hunter3")).is_err());
+        decode(args("hunter2")).unwrap();
+        assert_eq!(fs::read(&out).unwrap(), b"attack at dawn");
+        fs::remove_file(file).unwrap();
+        fs::remove_file(out).unwrap();
+    }
+
+    #[test]
+    fn test_encode_compressed() {
+        let file = testing_file("compressed");
+        let out = file.with_extension("txt");
+        let message = "the same line over and over\n".repeat(200);
+        encode(EncodeArgs {
+            compress: Some(Compressor::Deflate),
+            encrypt: true,
+            passphrase: Some("hunter2".to_string()),
+            kdf_memory: Some(64),
+            kdf_iterations: Some(1),
+            ..encode_args(&file, "ruSt", &message, None)
+        })
+        .unwrap();
+        let png = Png::from_file(&file).unwrap();
+        assert!(png.chunk_by_type("ruSt").unwrap().length() < message.len() / 10);
+
+        decode(DecodeArgs {
+            files: vec![spec(&file)],
+            chunk_type: "ruSt".to_string(),
+            out: Some(out.clone()),
+            passphrase: Some("hunter2".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(fs::read_to_string(&out).unwrap(), message);
+        fs::remove_file(file).unwrap();
+        fs::remove_file(out).unwrap();
+    }
+
+    #[test]
+    fn test_encode_with_decoy() {
+        let file = testing_file("decoy");
+        let out = file.with_extension("txt");
+        encode(EncodeArgs {
+            encrypt: true,
+            passphrase: Some("real".to_string()),
+            kdf_memory: Some(64),
+            kdf_iterations: Some(1),
+            decoy: Some("grocery list".to_string()),
+            decoy_passphrase: Some("decoy".to_string()),
+            ..encode_args(&file, "ruSt", "meet at midnight", None)
+        })
+        .unwrap();
+        let png = Png::from_file(&file).unwrap();
+        let lengths: Vec<usize> = png.chunks_by_type("ruSt").map(Chunk::length).collect();
+        assert_eq!(lengths.len(), 2);
+        assert_eq!(lengths[0], lengths[1]);
+
+        let args = |passphrase: &str| DecodeArgs {
+            files: vec![spec(&file)],
+            chunk_type: "ruSt".to_string(),
+            out: Some(out.clone()),
+            passphrase: Some(passphrase.to_string()),
+            ..Default::default()
+        };
+        decode(args("real")).unwrap();
+        assert_eq!(fs::read(&out).unwrap(), b"meet at midnight");
+        decode(args("decoy")).unwrap();
+        assert_eq!(fs::read(&out).unwrap(), b"grocery list");
+        assert!(decode(args("guess")).is_err());
+        fs::remove_file(file).unwrap();
+        fs::remove_file(out).unwrap();
+    }
+
+    #[test]
+    fn test_decode_all_with_decoy() {
+        let file = testing_file("decoy-all");
+        let out = file.with_extension("txt");
+        encode(EncodeArgs {
+            encrypt: true,
+            passphrase: Some("real".to_string()),
+            kdf_memory: Some(64),
+            kdf_iterations: Some(1),
+            decoy: Some("grocery list".to_string()),
+            decoy_passphrase: Some("cats".to_string()),
+            ..encode_args(&file, "ruSt", "meet at midnight", None)
+        })
+        .unwrap();
+
+        let args = |passphrase: &str| DecodeArgs {
+            files: vec![spec(&file)],
+            chunk_type: "ruSt".to_string(),
+            out: Some(out.clone()),
+            passphrase: Some(passphrase.to_string()),
+            all: true,
+            ..Default::default()
+        };
+        decode(args("cats")).unwrap();
+        assert_eq!(fs::read(&out).unwrap(), b"grocery list");
+        decode(args("real")).unwrap();
+        assert_eq!(fs::read(&out).unwrap(), b"meet at midnight");
+        assert!(decode(args("guess")).is_err());
+        fs::remove_file(file).unwrap();
+        fs::remove_file(out).unwrap();
+    }
+
+    #[test]
+    fn test_plain_payload_keeps_padding_trailer() {
+        let file = testing_file("padding-trailer");
+        let (input, out) = (file.with_extension("in"), file.with_extension("txt"));
+        let mut message = b"hello world".to_vec();
+        message.extend(5u64.to_be_bytes());
+        message.extend(b"pmD\x01");
+        fs::write(&input, &message).unwrap();
+        encode(EncodeArgs {
+            input: Some(input.clone()),
+            message: None,
+            ..encode_args(&file, "ruSt", "", None)
+        })
+        .unwrap();
+        decode(DecodeArgs {
+            files: vec![spec(&file)],
+            chunk_type: "ruSt".to_string(),
+            out: Some(out.clone()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(fs::read(&out).unwrap(), message);
+        for file in [file, input, out] {
+            fs::remove_file(file).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_encode_deterministic() {
+        let file = testing_file("deterministic");
+        let outputs = [file.with_extension("a.png"), file.with_extension("b.png")];
+        for output in &outputs {
+            encode(EncodeArgs {
+                encrypt: true,
+                passphrase: Some("hunter2".to_string()),
+                kdf_memory: Some(64),
+                kdf_iterations: Some(1),
+                deterministic: true,
+                ..encode_args(&file, "ruSt", "same every time", Some(output))
+            })
+            .unwrap();
+        }
+        assert_eq!(
+            fs::read(&outputs[0]).unwrap(),
+            fs::read(&outputs[1]).unwrap()
+        );
+        for path in [file, outputs[0].clone(), outputs[1].clone()] {
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_encode_to_age_recipient() {
+        use age::secrecy::ExposeSecret;
+
+        let file = testing_file("age");
+        let out = file.with_extension("txt");
+        let identity_file = file.with_extension("key");
+        let identity = age::x25519::Identity::generate();
+        fs::write(&identity_file, identity.to_string().expose_secret()).unwrap();
+
+        encode(EncodeArgs {
+            recipient: vec![identity.to_public().to_string()],
+            ..encode_args(&file, "ruSt", "attack at dusk", None)
+        })
+        .unwrap();
+        let args = |identity: Option<&Path>| DecodeArgs {
+            files: vec![spec(&file)],
+            chunk_type: "ruSt".to_string(),
+            out: Some(out.clone()),
+            identity: identity.map(Path::to_path_buf),
+            ..Default::default()
+        };
+        assert!(decode(args(None)).is_err());
+        decode(args(Some(&identity_file))).unwrap();
+        assert_eq!(fs::read(&out).unwrap(), b"attack at dusk");
+        for path in [file, out, identity_file] {
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_encode_to_several_recipients() {
+        use age::secrecy::ExposeSecret;
+
+        let file = testing_file("age-team");
+        let out = file.with_extension("txt");
+        let recipients_file = file.with_extension("recipients");
+        let identity_file = file.with_extension("key");
+        let alice = age::x25519::Identity::generate();
+        let bob = age::x25519::Identity::generate();
+        fs::write(
+            &recipients_file,
+            format!("# the team\n{}\n\n", bob.to_public()),
+        )
+        .unwrap();
+
+        encode(EncodeArgs {
+            recipient: vec![alice.to_public().to_string()],
+            recipients_file: Some(recipients_file.clone()),
+            ..encode_args(&file, "ruSt", "for both of you", None)
+        })
+        .unwrap();
+        for identity in [&alice, &bob] {
+            fs::write(&identity_file, identity.to_string().expose_secret()).unwrap();
+            decode(DecodeArgs {
+                files: vec![spec(&file)],
+                chunk_type: "ruSt".to_string(),
+                out: Some(out.clone()),
+                identity: Some(identity_file.clone()),
+                ..Default::default()
+            })
+            .unwrap();
+            assert_eq!(fs::read(&out).unwrap(), b"for both of you");
+        }
+        for path in [file, out, recipients_file, identity_file] {
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_seal_and_unseal() {
+        let file = testing_file("seal");
+        for (chunk_type, message) in [("ruSt", "first"), ("prVt", "second")] {
+            encode(EncodeArgs {
+                compress: Some(Default::default()),
+                ..encode_args(&file, chunk_type, message, None)
+            })
+            .unwrap();
+        }
+        // a plain payload can't be told from other software's chunks
+        encode(encode_args(&file, "plAn", "third", None)).unwrap();
+        encode(encode_args(&file, "naMd", "fourth", None)).unwrap();
+        let original = fs::read(&file).unwrap();
+
+        seal(SealArgs {
+            file: file.clone(),
+            passphrase: Some("secret".to_string()),
+            chunk_types: vec!["naMd".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+        let png = Png::from_file(&file).unwrap();
+        assert!(png.chunk_by_type("ruSt").is_none());
+        assert!(png.chunk_by_type("prVt").is_none());
+        assert!(png.chunk_by_type("naMd").is_none());
+        assert!(png.chunk_by_type("plAn").is_some());
+        assert!(png.chunk_by_type("seAl").is_some());
+
+        let unseal_args = |passphrase: &str| UnsealArgs {
+            file: file.clone(),
+            passphrase: Some(passphrase.to_string()),
+            ..Default::default()
+        };
+        assert!(unseal(unseal_args("guess")).is_err());
+        unseal(unseal_args("secret")).unwrap();
+        assert_eq!(fs::read(&file).unwrap(), original);
+        assert!(unseal(unseal_args("secret")).is_err());
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_capacity() {
+        let file = testing_file("capacity");
+        let args = |method| CapacityArgs {
+            file: file.clone(),
+            method,
+        };
+        // the testing png has no real pixel data, so only chunks apply
+        capacity(args(None)).unwrap();
+        capacity(args(Some(HidingMethod::Chunk))).unwrap();
+        assert!(capacity(args(Some(HidingMethod::Lsb))).is_err());
+
+        crate::image_data::tests::testing_image(8, 8, crate::ihdr::ColorType::Rgb)
+            .save(&file)
+            .unwrap();
+        capacity(args(Some(HidingMethod::Lsb))).unwrap();
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_stego_hide_and_reveal() {
+        use crate::args::{StegoHideArgs, StegoRevealArgs};
+        use crate::ihdr::ColorType;
+
+        let file = testing_file("stego");
+        let hidden = file.with_extension("hidden.png");
+        let out = file.with_extension("txt");
+        crate::image_data::tests::testing_image(32, 16, ColorType::Rgb)
+            .save(&file)
+            .unwrap();
+
+        stego(StegoCommand::Hide(StegoHideArgs {
+            file: file.clone(),
+            message: Some("between the pixels".to_string()),
+            output: Some(hidden.clone()),
+            ..Default::default()
+        }))
+        .unwrap();
+        let before = Png::from_file(&file).unwrap();
+        let after = Png::from_file(&hidden).unwrap();
+        assert_eq!(chunk_types(&after), chunk_types(&before));
+
+        stego(StegoCommand::Reveal(StegoRevealArgs {
+            file: hidden.clone(),
+            out: Some(out.clone()),
+        }))
+        .unwrap();
+        assert_eq!(fs::read(&out).unwrap(), b"between the pixels");
+        assert!(stego(StegoCommand::Reveal(StegoRevealArgs {
+            file: file.clone(),
+            out: None,
+        }))
+        .is_err());
+
+        let too_long = StegoHideArgs {
+            file: file.clone(),
+            message: Some("x".repeat(200)),
+            ..Default::default()
+        };
+        assert!(stego(StegoCommand::Hide(too_long)).is_err());
+        for path in [file, hidden, out] {
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_encode_signed() {
+        let file = testing_file("signed");
+        let out = file.with_extension("txt");
+        let (private, public) = crate::signing::tests::write_testing_keys(&file);
+        encode(EncodeArgs {
+            sign: Some(private.clone()),
+            ..encode_args(&file, "ruSt", "signed, sealed", None)
+        })
+        .unwrap();
+
+        let args = |verify: Option<&Path>| DecodeArgs {
+            files: vec![spec(&file)],
+            chunk_type: "ruSt".to_string(),
+            out: Some(out.clone()),
+            verify: verify.map(Path::to_path_buf),
+            ..Default::default()
+        };
+        decode(args(Some(&public))).unwrap();
+        assert_eq!(fs::read(&out).unwrap(), b"signed, sealed");
+
+        // an unsigned chunk of the same type comes first now
+        let mut png = Png::from_file(&file).unwrap();
+        png.insert_chunk_at(
+            1,
+            Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"forged".to_vec()),
+        )
+        .unwrap();
+        png.save(&file).unwrap();
+        assert!(decode(args(Some(&public))).is_err());
+        decode(args(None)).unwrap();
+        assert_eq!(fs::read(&out).unwrap(), b"forged");
+        for path in [file, out, private, public] {
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_sign_and_verify_file() {
+        let file = testing_file("sign_file");
+        let (private, public) = crate::signing::tests::write_testing_keys(&file);
+        let verify_args = || VerifyFileArgs {
+            file: file.clone(),
+            key: public.clone(),
+        };
+        assert!(verify_file(verify_args()).is_err());
+        hash(HashArgs {
+            files: vec![spec(&file)],
+            ..Default::default()
+        })
+        .unwrap();
+
+        let sign_args = |dry_run| SignArgs {
+            file: file.clone(),
+            key: private.clone(),
+            dry_run,
+        };
+        sign(sign_args(true)).unwrap();
+        assert_eq!(fs::read(&file).unwrap(), testing_png_bytes());
+        sign(sign_args(false)).unwrap();
+        verify_file(verify_args()).unwrap();
+
+        encode(encode_args(&file, "ruSt", "after signing", None)).unwrap();
+        assert!(matches!(
+            verify_file(verify_args()),
+            Err(crate::Error::Signing(
+                signing::SigningError::FileSignatureMismatch
+            ))
+        ));
+        for path in [file, private, public] {
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_carve() {
+        let blob = std::env::temp_dir().join(format!("picmes-{}-carve.bin", std::process::id()));
+        let out = std::env::temp_dir().join(format!("picmes-{}-carved", std::process::id()));
+        let png = testing_png_bytes();
+        fs::write(&blob, [b"header".as_slice(), &png, b"footer"].concat()).unwrap();
+
+        let args = |dry_run| CarveArgs {
+            file: blob.clone(),
+            out: out.clone(),
+            dry_run,
+        };
+        carve(args(true)).unwrap();
+        assert!(!out.exists());
+        carve(args(false)).unwrap();
+        let stem = blob.file_stem().unwrap().to_string_lossy();
+        let carved = out.join(format!("{}-00000006.png", stem));
+        assert_eq!(fs::read(&carved).unwrap(), png);
+
+        fs::remove_dir_all(out).unwrap();
+        fs::remove_file(blob).unwrap();
+    }
+
+    #[test]
+    fn test_encode_hmac_tagged() {
+        let file = testing_file("hmac");
+        let out = file.with_extension("txt");
+        encode(EncodeArgs {
+            hmac_key: Some("shared".to_string()),
+            ..encode_args(&file, "ruSt", "untampered", None)
+        })
+        .unwrap();
+
+        let args = |key: &str| DecodeArgs {
+            files: vec![spec(&file)],
+            chunk_type: "ruSt".to_string(),
+            out: Some(out.clone()),
+            hmac_key: Some(key.to_string()),
+            ..Default::default()
+        };
+        decode(args("shared")).unwrap();
+        assert_eq!(fs::read(&out).unwrap(), b"untampered");
+        assert!(decode(args("guess")).is_err());
+
+        let mut png = Png::from_file(&file).unwrap();
+        let chunk = png.chunks_mut().find(|c| c.length() > 32).unwrap();
+        let mut data = chunk.data().to_vec();
+        data[0] ^= 1;
+        chunk.set_data(data);
+        png.save(&file).unwrap();
+        assert!(decode(args("shared")).is_err());
+        fs::remove_file(file).unwrap();
+        fs::remove_file(out).unwrap();
+    }
+
+    #[test]
+    fn test_encode_split() {
+        let file = testing_file("split");
+        let out = file.with_extension("txt");
+        encode(EncodeArgs {
+            split_size: Some(4),
+            ..encode_args(&file, "ruSt", "spread over parts", None)
+        })
+        .unwrap();
+        let png = Png::from_file(&file).unwrap();
+        assert_eq!(png.chunks_by_type("ruSt").count(), 6);
+
+        let args = || DecodeArgs {
+            files: vec![spec(&file)],
+            chunk_type: "ruSt".to_string(),
+            out: Some(out.clone()),
+            ..Default::default()
+        };
+        decode(args()).unwrap();
+        assert_eq!(fs::read(&out).unwrap(), b"spread over parts");
+
+        let mut png = Png::from_file(&file).unwrap();
+        let part = png
+            .chunks_mut()
+            .filter(|c| c.data().starts_with(b"pmP"))
+            .nth(2);
+        let part = part.unwrap();
+        let mut data = part.data().to_vec();
+        *data.last_mut().unwrap() ^= 1;
+        part.set_data(data);
+        png.save(&file).unwrap();
+        assert!(decode(args()).is_err());
+        fs::remove_file(file).unwrap();
+        fs::remove_file(out).unwrap();
+    }
+
+    #[test]
+    fn test_encode_with_error_correction() {
+        let file = testing_file("fec");
+        let out = file.with_extension("txt");
+        encode(EncodeArgs {
+            fec: Some(2),
+            split_size: Some(16),
+            ..encode_args(&file, "ruSt", "survives a little damage", None)
+        })
+        .unwrap();
+
+        let mut png = Png::from_file(&file).unwrap();
+        let parts: Vec<Vec<u8>> = png
+            .chunks_by_type("ruSt")
+            .map(|c| c.data().to_vec())
+            .filter(|data| split::is_part(data))
+            .collect();
+        png.remove_chunks_where(|c| c.data() == parts[1]);
+        let part = png.chunks_mut().find(|c| c.data() == parts[3]).unwrap();
+        let mut data = part.data().to_vec();
+        data[split::PART_HEADER_LENGTH + 3] ^= 1;
+        part.set_data(data);
+        png.save(&file).unwrap();
+
+        decode(DecodeArgs {
+            files: vec![spec(&file)],
+            chunk_type: "ruSt".to_string(),
+            out: Some(out.clone()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(fs::read(&out).unwrap(), b"survives a little damage");
+        fs::remove_file(file).unwrap();
+        fs::remove_file(out).unwrap();
+    }
+
+    #[test]
+    fn test_encode_with_derived_chunk_type() {
+        let file = testing_file("type-key");
+        encode(EncodeArgs {
+            type_key: Some("stealthy".to_string()),
+            ..encode_args(&file, "notes", "hard to find", None)
+        })
+        .unwrap();
+        let png = Png::from_file(&file).unwrap();
+        let derived = signing::derive_chunk_type(b"stealthy", "notes");
+        assert_eq!(
+            chunk_types(&png),
+            ["IHDR", "IDAT", &derived.to_string(), "IEND"]
+        );
+
+        let args = |key: &str| DecodeArgs {
+            files: vec![spec(&file)],
+            chunk_type: "notes".to_string(),
+            type_key: Some(key.to_string()),
+            ..Default::default()
+        };
+        decode(args("stealthy")).unwrap();
+        assert!(decode(args("guess")).is_err());
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_encode_split_across_images() {
+        let files: Vec<PathBuf> = ["shard-a", "shard-b", "shard-c"]
+            .into_iter()
+            .map(testing_file)
+            .collect();
+        let out = files[0].with_extension("txt");
+        encode(EncodeArgs {
+            encrypt: true,
+            passphrase: Some("hunter2".to_string()),
+            kdf_memory: Some(64),
+            kdf_iterations: Some(1),
+            split_across: files[1..].to_vec(),
+            ..encode_args(&files[0], "ruSt", "one message, three images", None)
+        })
+        .unwrap();
+        for file in &files {
+            let png = Png::from_file(file).unwrap();
+            let data: Vec<&[u8]> = png.chunks_by_type("ruSt").map(Chunk::data).collect();
+            assert_eq!(data.len(), 2);
+            assert!(split::is_manifest(data[0]) && split::is_part(data[1]));
+        }
+
+        let args = |files: &[PathBuf]| DecodeArgs {
+            files: files.iter().map(|f| spec(f)).collect(),
+            chunk_type: "ruSt".to_string(),
+            out: Some(out.clone()),
+            passphrase: Some("hunter2".to_string()),
+            join: true,
+            ..Default::default()
+        };
+        let shuffled = [files[2].clone(), files[0].clone(), files[1].clone()];
+        decode(args(&shuffled)).unwrap();
+        assert_eq!(fs::read(&out).unwrap(), b"one message, three images");
+        assert!(decode(args(&files[..2])).is_err());
+        for file in files {
+            fs::remove_file(file).unwrap();
+        }
+        fs::remove_file(out).unwrap();
+    }
+
+    #[test]
+    fn test_decode_binary_to_file() {
+        let file = testing_file("decode-out");
+        let out = file.with_extension("bin");
+        let payload = vec![0x00, 0xff, 0xc3, 0x28];
+        let mut png = Png::from_file(&file).unwrap();
+        png.append_chunk(Chunk::new(
+            ChunkType::from_str("ruSt").unwrap(),
+            payload.clone(),
+        ));
+        png.save(&file).unwrap();
+
+        decode(DecodeArgs {
+            files: vec![spec(&file)],
+            chunk_type: "ruSt".to_string(),
+            out: Some(out.clone()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(fs::read(&out).unwrap(), payload);
+        fs::remove_file(file).unwrap();
+        fs::remove_file(out).unwrap();
+    }
+
+    #[test]
+    fn test_remove() {
+        let file = testing_file("remove");
+        encode(encode_args(&file, "ruSt", "first", None)).unwrap();
+        encode(encode_args(&file, "ruSt", "second", None)).unwrap();
+
+        remove(remove_args(&file, "ruSt", false)).unwrap();
+        let png = Png::from_file(&file).unwrap();
+        assert_eq!(chunk_types(&png), ["IHDR", "IDAT", "ruSt", "IEND"]);
+        assert_eq!(message(&png, "ruSt").unwrap(), "second");
+
+        assert!(remove(remove_args(&file, "miSs", false)).is_err());
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_remove_all() {
+        let file = testing_file("remove-all");
+        encode(encode_args(&file, "ruSt", "first", None)).unwrap();
+        encode(encode_args(&file, "ruSt", "second", None)).unwrap();
+
+        remove(RemoveArgs {
+            all: true,
+            ..remove_args(&file, "ruSt", false)
+        })
+        .unwrap();
+        assert_eq!(fs::read(&file).unwrap(), testing_png_bytes());
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_remove_critical_requires_force() {
+        let file = testing_file("remove-critical");
+        assert!(remove(remove_args(&file, "IDAT", false)).is_err());
+        assert_eq!(fs::read(&file).unwrap(), testing_png_bytes());
+
+        remove(remove_args(&file, "IDAT", true)).unwrap();
+        let png = Png::from_file(&file).unwrap();
+        assert_eq!(chunk_types(&png), ["IHDR", "IEND"]);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_describe() {
+        let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"hidden".to_vec());
+        assert_eq!(
+            describe(&chunk),
+            format!(
+                "ruSt  length=6        crc={:#010x}  ancillary private safe-to-copy",
+                chunk.crc()
+            )
+        );
+
+        let chunk = Chunk::new(ChunkType::IHDR, vec![0; 13]);
+        assert!(describe(&chunk).ends_with("critical public unsafe-to-copy"));
+    }
+
+    #[test]
+    fn test_chunk_summary_json() {
+        let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"hidden".to_vec());
+        let json = serde_json::to_value(ChunkSummary::from(&chunk)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "chunk_type": "ruSt",
+                "length": 6,
+                "crc": chunk.crc(),
+                "critical": false,
+                "public": false,
+                "safe_to_copy": true,
+            })
+        );
+
+        let summary = ChunkSummary {
+            entropy: Some(1.5),
+            ..ChunkSummary::from(&chunk)
+        };
+        let json = serde_json::to_value(summary).unwrap();
+        assert_eq!(json["entropy"], 1.5);
+    }
+
+    #[test]
+    fn test_decoded_payload_json() {
+        let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"hidden".to_vec());
+        let json = serde_json::to_value(DecodedPayload::new(&chunk, chunk.data())).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"chunk_type": "ruSt", "encoding": "utf8", "data": "hidden"})
+        );
+
+        let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), vec![0xff, 0x00, 0xfe]);
+        let json = serde_json::to_value(DecodedPayload::new(&chunk, chunk.data())).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"chunk_type": "ruSt", "encoding": "base64", "data": "/wD+"})
+        );
+    }
+
+    #[test]
+    fn test_print() {
+        let file = testing_file("print");
+        for json in [false, true] {
+            assert!(print(PrintArgs {
+                files: vec![spec(&file)],
+                json,
+                ..Default::default()
+            })
+            .is_ok());
+        }
+        fs::remove_file(file).unwrap();
+    }
+
+    /// Writes `bytes` to a unique path with `extension` in the temp directory
+    fn testing_image_file(name: &str, extension: &str, bytes: &[u8]) -> PathBuf {
+        let path = testing_file(name).with_extension(extension);
+        fs::remove_file(path.with_extension("png")).unwrap();
+        fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    fn testing_jpeg_file(name: &str) -> PathBuf {
+        testing_image_file(name, "jpg", &testing_jpeg_bytes())
+    }
+
+    fn stored_chunks(file: &Path) -> Vec<(String, Vec<u8>)> {
+        Carrier::from_file(file)
+            .unwrap()
+            .chunks()
+            .map(|c| (c.chunk_type().to_string(), c.data().to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn test_jpeg_encode_decode_remove() {
+        let file = testing_jpeg_file("jpeg-round-trip");
+        encode(encode_args(&file, "ruSt", "first", None)).unwrap();
+        encode(EncodeArgs {
+            segment: SegmentKind::Com,
+            encrypt: true,
+            passphrase: Some("hunter2".to_string()),
+            ..encode_args(&file, "ruSt", "second", None)
+        })
+        .unwrap();
+        let payloads = stored_chunks(&file);
+        assert_eq!(payloads.len(), 2);
+        assert_eq!(payloads[0], ("ruSt".to_string(), b"first".to_vec()));
+
+        let out = file.with_extension("bin");
+        let decode_args = |all: bool| DecodeArgs {
+            files: vec![spec(&file)],
+            chunk_type: "ruSt".to_string(),
+            passphrase: Some("hunter2".to_string()),
+            all,
+            ..Default::default()
+        };
+        decode(decode_args(true)).unwrap();
+        remove(remove_args(&file, "ruSt", false)).unwrap();
+        decode(DecodeArgs {
+            out: Some(out.clone()),
+            ..decode_args(false)
+        })
+        .unwrap();
+        assert_eq!(fs::read(&out).unwrap(), b"second");
+
+        remove(remove_args(&file, "ruSt", false)).unwrap();
+        assert_eq!(fs::read(&file).unwrap(), testing_jpeg_bytes());
+        assert!(remove(remove_args(&file, "ruSt", false)).is_err());
+        fs::remove_file(file).unwrap();
+        fs::remove_file(out).unwrap();
+    }
+
+    #[test]
+    fn test_jpeg_split_payload() {
+        let file = testing_jpeg_file("jpeg-split");
+        let input = file.with_extension("in");
+        let payload: Vec<u8> = (0..=255)
+            .cycle()
+            .take(3 * Jpeg::MAX_PAYLOAD_LENGTH)
+            .collect();
+        fs::write(&input, &payload).unwrap();
+        encode(EncodeArgs {
+            file: spec(&file),
+            chunk_type: "ruSt".to_string(),
+            input: Some(input.clone()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(stored_chunks(&file).len(), 5);
+
+        decode(DecodeArgs {
+            files: vec![spec(&file)],
+            chunk_type: "ruSt".to_string(),
+            out: Some(input.clone()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(fs::read(&input).unwrap(), payload);
+        fs::remove_file(file).unwrap();
+        fs::remove_file(input).unwrap();
+    }
+
+    #[test]
+    fn test_jpeg_rejects_png_only_options() {
+        let file = testing_jpeg_file("jpeg-png-only");
+        for args in [
+            EncodeArgs {
+                at: Some(1),
+                ..encode_args(&file, "ruSt", "placed", None)
+            },
+            EncodeArgs {
+                touch: true,
+                ..encode_args(&file, "ruSt", "touched", None)
+            },
+        ] {
+            assert!(encode(args).is_err());
+        }
+        assert_eq!(fs::read(&file).unwrap(), testing_jpeg_bytes());
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_gif_encode_decode_remove() {
+        let file = testing_image_file("gif-round-trip", "gif", &testing_gif_bytes());
+        encode(encode_args(&file, "ruSt", "first", None)).unwrap();
+        encode(EncodeArgs {
+            compress: Some(Compressor::Deflate),
+            ..encode_args(&file, "ruSt", &"second ".repeat(100), None)
+        })
+        .unwrap();
+        assert_eq!(stored_chunks(&file).len(), 2);
+
+        let out = file.with_extension("txt");
+        remove(remove_args(&file, "ruSt", false)).unwrap();
+        decode(DecodeArgs {
+            files: vec![spec(&file)],
+            chunk_type: "ruSt".to_string(),
+            out: Some(out.clone()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(fs::read_to_string(&out).unwrap(), "second ".repeat(100));
+        print(PrintArgs {
+            files: vec![spec(&file)],
+            ..Default::default()
+        })
+        .unwrap();
+
+        remove(remove_args(&file, "ruSt", false)).unwrap();
+        assert_eq!(fs::read(&file).unwrap(), testing_gif_bytes());
+        fs::remove_file(file).unwrap();
+        fs::remove_file(out).unwrap();
+    }
+
+    #[test]
+    fn test_webp_encode_decode_remove() {
+        let file = testing_image_file("webp-round-trip", "webp", &testing_webp_bytes());
+        encode(encode_args(&file, "ruSt", "hidden", None)).unwrap();
+        assert!(encode(encode_args(&file, "ICCP", "reserved", None)).is_err());
+        assert_eq!(
+            stored_chunks(&file),
+            [("ruSt".to_string(), b"hidden".to_vec())]
+        );
+        decode(DecodeArgs {
+            files: vec![spec(&file)],
+            chunk_type: "ruSt".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+        remove(RemoveArgs {
+            all: true,
+            ..remove_args(&file, "ruSt", false)
+        })
+        .unwrap();
+        assert_eq!(fs::read(&file).unwrap(), testing_webp_bytes());
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_bmp_encode_decode_remove() {
+        let file = testing_image_file("bmp-round-trip", "bmp", &testing_bmp_bytes());
+        encode(encode_args(&file, "ruSt", "in the gap", None)).unwrap();
+        encode(EncodeArgs {
+            bmp_region: Region::Append,
+            ..encode_args(&file, "ruSt", "appended", None)
+        })
+        .unwrap();
+        let bytes = fs::read(&file).unwrap();
+        assert_eq!(&bytes[2..6], &(bytes.len() as u32).to_le_bytes());
+        assert_eq!(stored_chunks(&file).len(), 2);
+
+        let out = file.with_extension("txt");
+        remove(remove_args(&file, "ruSt", false)).unwrap();
+        decode(DecodeArgs {
+            files: vec![spec(&file)],
+            chunk_type: "ruSt".to_string(),
+            out: Some(out.clone()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(fs::read_to_string(&out).unwrap(), "appended");
+
+        remove(remove_args(&file, "ruSt", false)).unwrap();
+        assert_eq!(fs::read(&file).unwrap(), testing_bmp_bytes());
+        fs::remove_file(file).unwrap();
+        fs::remove_file(out).unwrap();
+    }
+
+    #[test]
+    fn test_tiff_encode_decode_remove() {
+        let file = testing_image_file("tiff-round-trip", "tiff", &testing_tiff_bytes());
+        encode(encode_args(&file, "ruSt", "in a private tag", None)).unwrap();
+        encode(encode_args(&file, "ruSt", "next to it", None)).unwrap();
+        assert_eq!(stored_chunks(&file).len(), 2);
+
+        let out = file.with_extension("txt");
+        remove(remove_args(&file, "ruSt", false)).unwrap();
+        decode(DecodeArgs {
+            files: vec![spec(&file)],
+            chunk_type: "ruSt".to_string(),
+            out: Some(out.clone()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(fs::read_to_string(&out).unwrap(), "next to it");
+
+        remove(remove_args(&file, "ruSt", false)).unwrap();
+        assert!(stored_chunks(&file).is_empty());
+        assert!(remove(remove_args(&file, "ruSt", false)).is_err());
+        fs::remove_file(file).unwrap();
+        fs::remove_file(out).unwrap();
+    }
+
+    #[test]
+    fn test_ico_encode_decode_remove() {
+        let file = testing_image_file("ico-round-trip", "ico", &testing_ico_bytes());
+        encode(encode_args(&file, "ruSt", "in the png entry", None)).unwrap();
+        for entry in [0, 2] {
+            let args = EncodeArgs {
+                entry: Some(entry),
+                ..encode_args(&file, "ruSt", "nowhere", None)
+            };
+            assert!(encode(args).is_err(), "{}", entry);
+        }
+        encode(EncodeArgs {
+            entry: Some(1),
+            at: Some(1),
+            ..encode_args(&file, "ruSt", "after IHDR", None)
+        })
+        .unwrap();
+        let sections = Carrier::from_file(&file).unwrap().sections();
+        assert_eq!(sections[3].name, "entry 1 IHDR");
+        assert_eq!(sections[4].name, "entry 1 ruSt");
+
+        let out = file.with_extension("txt");
+        decode(DecodeArgs {
+            files: vec![spec(&file)],
+            chunk_type: "ruSt".to_string(),
+            out: Some(out.clone()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(fs::read_to_string(&out).unwrap(), "after IHDR");
+
+        remove(remove_args(&file, "ruSt", false)).unwrap();
+        remove(remove_args(&file, "ruSt", false)).unwrap();
+        assert_eq!(fs::read(&file).unwrap(), testing_ico_bytes());
+
+        let png = testing_file("entry-png");
+        let args = EncodeArgs {
+            entry: Some(0),
+            ..encode_args(&png, "ruSt", "not an icon", None)
+        };
+        assert!(encode(args).is_err());
+        fs::remove_file(png).unwrap();
+        fs::remove_file(file).unwrap();
+        fs::remove_file(out).unwrap();
+    }
+
+    #[test]
+    fn test_migrate() {
+        let source = testing_file("migrate-source");
+        let target = testing_jpeg_file("migrate-target");
+        let out = target.with_extension("gif");
+        assert!(migrate(MigrateArgs {
+            source: source.clone(),
+            target: target.clone(),
+            ..Default::default()
+        })
+        .is_err());
+
+        encode(EncodeArgs {
+            encrypt: true,
+            passphrase: Some("hunter2".to_string()),
+            kdf_memory: Some(64),
+            kdf_iterations: Some(1),
+            ..encode_args(&source, "ruSt", "attack at dawn", None)
+        })
+        .unwrap();
+        encode(encode_args(&source, "tEXt", "a comment", None)).unwrap();
+        let png_chunks = stored_chunks(&source);
+        // chunks of other software stay, even when they share a payload's type
+        // and where they are, here ahead of the payloads
+        let mut png = Png::from_file(&source).unwrap();
+        let foreign = [
+            Chunk::new(ChunkType::from_str("soFt").unwrap(), b"not ours".to_vec()),
+            Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"nor this".to_vec()),
+        ];
+        for chunk in &foreign {
+            png.insert_chunk_at(1, chunk.clone()).unwrap();
+        }
+        png.save(&source).unwrap();
+
+        migrate(MigrateArgs {
+            source: source.clone(),
+            target: target.clone(),
+            chunk_types: vec!["tEXt".to_string()],
+            remove: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let migrated = stored_chunks(&target);
+        assert_eq!(migrated.len(), 2);
+        assert!(png_chunks.contains(&migrated[0]) && png_chunks.contains(&migrated[1]));
+        let mut png = Png::try_from(testing_png_bytes().as_slice()).unwrap();
+        for chunk in foreign {
+            png.insert_chunk_at(1, chunk).unwrap();
+        }
+        assert_eq!(fs::read(&source).unwrap(), png.as_bytes());
+        decode(DecodeArgs {
+            files: vec![spec(&target)],
+            chunk_type: "ruSt".to_string(),
+            out: Some(out.clone()),
+            passphrase: Some("hunter2".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(fs::read(&out).unwrap(), b"attack at dawn");
+
+        // into a gif written next to the jpeg, which stays as it is
+        fs::write(&out, testing_gif_bytes()).unwrap();
+        let copy = out.with_extension("copy.gif");
+        migrate(MigrateArgs {
+            source: target.clone(),
+            target: out.clone(),
+            out: Some(copy.clone()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(fs::read(&out).unwrap(), testing_gif_bytes());
+        assert_eq!(stored_chunks(&copy), [migrated[0].clone()]);
+        for file in [source, target, out, copy] {
+            fs::remove_file(file).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_migrate_too_long_for_jpeg() {
+        let source = testing_file("migrate-long-source");
+        let target = testing_jpeg_file("migrate-long");
+        let mut png = Png::from_file(&source).unwrap();
+        png.insert_chunk(Chunk::new(
+            ChunkType::from_str("ruSt").unwrap(),
+            vec![0; Jpeg::MAX_PAYLOAD_LENGTH + 1],
+        ));
+        png.save(&source).unwrap();
+        let error = migrate(MigrateArgs {
+            source: source.clone(),
+            target: target.clone(),
+            chunk_types: vec!["ruSt".to_string()],
+            ..Default::default()
+        })
+        .unwrap_err();
+        assert!(error.to_string().contains("does not fit in a jpeg chunk"));
+        assert_eq!(fs::read(&target).unwrap(), testing_jpeg_bytes());
+        fs::remove_file(source).unwrap();
+        fs::remove_file(target).unwrap();
+    }
+
+    #[test]
+    fn test_print_jpeg() {
+        let file = testing_jpeg_file("print-jpeg");
+        encode(encode_args(&file, "ruSt", "listed", None)).unwrap();
+        for json in [false, true] {
+            print(PrintArgs {
+                files: vec![spec(&file)],
+                json,
+                ..Default::default()
+            })
+            .unwrap();
+        }
+
+        let section = SectionSummary {
+            name: "APP15".to_string(),
+            length: 21,
+            chunk_type: Some("ruSt".to_string()),
+        };
+        assert_eq!(
+            describe_section(&section),
+            "APP15      length=21        payload=ruSt"
+        );
+        assert_eq!(
+            serde_json::to_value(&section).unwrap(),
+            serde_json::json!({"name": "APP15", "length": 21, "chunk_type": "ruSt"})
+        );
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_batch_encode_decode_print() {
+        let dir = std::env::temp_dir().join(format!("picmes-{}-batch", std::process::id()));
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        let files = [dir.join("a.png"), dir.join("nested/b.png")];
+        for file in &files {
+            fs::write(file, testing_png_bytes()).unwrap();
+        }
+
+        let mut args = encode_args(&dir, "ruSt", "hidden", None);
+        args.recursive = true;
+        encode(args).unwrap();
+        for file in &files {
+            assert_eq!(
+                message(&Png::from_file(file).unwrap(), "ruSt").unwrap(),
+                "hidden"
+            );
+        }
+
+        let glob = spec(&dir.join("**/*.png"));
+        for json in [false, true] {
+            assert!(decode(DecodeArgs {
+                files: vec![glob.clone()],
+                chunk_type: "ruSt".to_string(),
+                json,
+                ..Default::default()
+            })
+            .is_ok());
+            assert!(print(PrintArgs {
+                files: vec![spec(&files[0]), spec(&files[1])],
+                json,
+                ..Default::default()
+            })
+            .is_ok());
+        }
+
+        assert!(encode(encode_args(&dir, "ruSt", "x", Some(&dir.join("out.png")))).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_batch_reports_failures() {
+        let file = testing_file("batch-failures");
+        let result = decode(DecodeArgs {
+            files: vec![spec(&file), "missing.png".to_string()],
+            chunk_type: "IHDR".to_string(),
+            ..Default::default()
+        });
+        let err = result.unwrap_err();
+        assert_eq!(err.to_string(), "1 of 2 files failed");
+        assert_eq!(err.failure(), Failure::NotFound);
+
+        let failure_of = |files: Vec<String>| {
+            decode(DecodeArgs {
+                files,
+                chunk_type: "ruSt".to_string(),
+                ..Default::default()
+            })
+            .unwrap_err()
+            .failure()
+        };
+        let (file, missing) = (spec(&file), "missing.png".to_string());
+        assert_eq!(
+            failure_of(vec![file.clone(), file.clone()]),
+            Failure::NoPayload
+        );
+        assert_eq!(failure_of(vec![file.clone(), missing]), Failure::Other);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_dry_run_leaves_file_untouched() {
+        let file = testing_file("dry-run");
+        let mut args = encode_args(&file, "ruSt", "hidden", None);
+        args.dry_run = true;
+        encode(args).unwrap();
+        assert_eq!(fs::read(&file).unwrap(), testing_png_bytes());
+
+        let mut args = remove_args(&file, "IDAT", true);
+        args.dry_run = true;
+        remove(args).unwrap();
+        assert_eq!(fs::read(&file).unwrap(), testing_png_bytes());
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_scan() {
+        let file = testing_file("scan");
+        encode(encode_args(&file, "ruSt", "hidden", None)).unwrap();
+        for json in [false, true] {
+            assert!(scan(ScanArgs {
+                files: vec![spec(&file)],
+                json,
+                ..Default::default()
+            })
+            .is_ok());
+        }
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_detect() {
+        let file = testing_file("detect");
+        encode(EncodeArgs {
+            encrypt: true,
+            passphrase: Some("hunter2".to_string()),
+            kdf_memory: Some(64),
+            kdf_iterations: Some(1),
+            ..encode_args(&file, "ruSt", "hidden in plain sight", None)
+        })
+        .unwrap();
+        detect(DetectArgs {
+            files: vec![spec(&file)],
+            ..Default::default()
+        })
+        .unwrap();
+        detect(DetectArgs {
+            files: vec![spec(&file)],
+            json: true,
+            ..Default::default()
+        })
+        .unwrap();
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_verify() {
+        let file = testing_file("verify");
+        let args = || VerifyArgs {
+            files: vec![spec(&file)],
+            ..Default::default()
+        };
+        verify(args()).unwrap();
+
+        // trailing data is only a warning
+        let mut bytes = testing_png_bytes();
+        bytes.push(0);
+        fs::write(&file, &bytes).unwrap();
+        verify(args()).unwrap();
+
+        bytes[Png::HEADER_LENGTH + 20] ^= 0xff;
+        fs::write(&file, &bytes).unwrap();
+        let err = verify(args()).unwrap_err();
+        assert_eq!(err.to_string(), "Found 1 error(s)");
+        assert_eq!(err.failure(), Failure::Checksum);
+
+        bytes[Png::HEADER_LENGTH + 20] ^= 0xff;
+        bytes[0] = 13;
+        fs::write(&file, bytes).unwrap();
+        assert_eq!(verify(args()).unwrap_err().failure(), Failure::Other);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_repair() {
+        let file = testing_file("repair");
+        let mut bytes = testing_png_bytes();
+        bytes.extend_from_slice(b"garbage");
+        fs::write(&file, &bytes).unwrap();
+
+        repair(RepairArgs {
+            file: file.clone(),
+            ..Default::default()
+        })
+        .unwrap();
+        let repaired = repaired_path(&file);
+        assert_eq!(fs::read(&repaired).unwrap(), testing_png_bytes());
+        assert_eq!(fs::read(&file).unwrap(), bytes);
+        fs::remove_file(file).unwrap();
+        fs::remove_file(repaired).unwrap();
+    }
+
+    #[test]
+    fn test_strip() {
+        let file = testing_file("strip");
+        for (chunk_type, message) in [("tEXt", "a"), ("ruSt", "b"), ("pHYs", "c")] {
+            encode(encode_args(&file, chunk_type, message, None)).unwrap();
+        }
+
+        strip(StripArgs {
+            files: vec![spec(&file)],
+            keep: vec!["tEXt".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+        let png = Png::from_file(&file).unwrap();
+        assert_eq!(chunk_types(&png), ["IHDR", "IDAT", "tEXt", "IEND"]);
+
+        strip(StripArgs {
+            files: vec![spec(&file)],
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(fs::read(&file).unwrap(), testing_png_bytes());
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_strip_keeps_animation() {
+        let file = testing_file("strip-apng");
+        let mut png = Png::from_file(&file).unwrap();
+        png.insert_chunk(actl_chunk(1, 0));
+        png.insert_chunk_at(2, fctl_chunk(0, 1, 10)).unwrap();
+        png.insert_chunk(Chunk::new(ChunkType::tEXt, b"a".to_vec()));
+        png.save(&file).unwrap();
+
+        strip(StripArgs {
+            files: vec![spec(&file)],
+            ..Default::default()
+        })
+        .unwrap();
+        let png = Png::from_file(&file).unwrap();
+        assert_eq!(chunk_types(&png), ["IHDR", "acTL", "fcTL", "IDAT", "IEND"]);
+        assert_eq!(png.animation().unwrap().unwrap().frames.len(), 1);
+        print(PrintArgs {
+            files: vec![spec(&file)],
+            ..Default::default()
+        })
+        .unwrap();
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_remote_files_are_not_written_back() {
+        let url = "https://example.com/image.png";
+        let error = encode(encode_args(Path::new(url), "ruSt", "hidden", None)).unwrap_err();
+        assert!(matches!(
+            error,
+            crate::Error::Command(CommandError::RemoteTarget(_))
+        ));
+        let error = strip(StripArgs {
+            files: vec![url.to_string()],
+            dry_run: true,
+            ..Default::default()
+        })
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "'https://example.com/image.png' can't be written back to, \
+             save the result to a local file instead"
+        );
+    }
+
+    #[cfg(feature = "watch")]
+    #[test]
+    fn test_on_add_command() {
+        let file = testing_file("watch-on-add");
+        encode(encode_args(&file, "tEXt", "from the camera", None)).unwrap();
+        on_add_args("strip --record", &file).and_then(run).unwrap();
+        let png = Png::from_file(&file).unwrap();
+        assert_eq!(chunk_types(&png), ["IHDR", "IDAT", "prVn", "IEND"]);
+
+        let PicmesArgs::Encode(args) = on_add_args("encode {} ruSt 'seen'", &file).unwrap() else {
+            panic!("expected encode");
+        };
+        assert_eq!(
+            (args.file, args.message),
+            (spec(&file), Some("seen".to_string()))
+        );
+        let error = on_add_args("ingest", &file).unwrap_err();
+        assert!(matches!(error, crate::Error::Args(_)));
+        fs::remove_file(file).unwrap();
+    }
+
+    #[cfg(feature = "qr")]
+    #[test]
+    fn test_decode_qr() {
+        let file = testing_file("decode-qr");
+        let code = file.with_extension("qr.png");
+        encode(encode_args(&file, "ruSt", "recovered key", None)).unwrap();
+        decode(DecodeArgs {
+            files: vec![spec(&file)],
+            chunk_type: "ruSt".to_string(),
+            qr: Some(code.clone()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(
+            fs::read(&code).unwrap(),
+            qr::render(b"recovered key").unwrap().as_bytes()
+        );
+        fs::remove_file(file).unwrap();
+        fs::remove_file(code).unwrap();
+    }
+
+    #[cfg(feature = "clipboard")]
+    #[test]
+    fn test_clipboard_image_takes_no_output() {
+        let input = testing_file("clipboard-input");
+        let error = encode(EncodeArgs {
+            file: "pasted.png".to_string(),
+            chunk_type: "ruSt".to_string(),
+            input: Some(input.clone()),
+            // with --input the output takes the place of the message
+            message: Some("out.png".to_string()),
+            from_clipboard: true,
+            ..Default::default()
+        })
+        .unwrap_err();
+        assert!(matches!(
+            error,
+            crate::Error::Command(CommandError::OutputWithClipboard)
+        ));
+        fs::remove_file(input).unwrap();
+    }
+
+    #[cfg(not(feature = "net"))]
+    #[test]
+    fn test_urls_need_the_net_feature() {
+        let error = print(PrintArgs {
+            files: vec!["http://example.com/image.png".to_string()],
+            ..Default::default()
+        })
+        .unwrap_err();
+        assert!(matches!(
+            error,
+            crate::Error::Input(inputs::InputError::NeedsFeature { feature: "net", .. })
+        ));
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn test_remote_inputs() {
+        let url = crate::net::tests::serve("200 OK", testing_png_bytes(), 3, "image.png");
+        print(PrintArgs {
+            files: vec![url.clone()],
+            ..Default::default()
+        })
+        .unwrap();
+
+        let output = testing_file("remote-encode");
+        encode(encode_args(
+            Path::new(&url),
+            "ruSt",
+            "fetched",
+            Some(&output),
+        ))
+        .unwrap();
+        let png = Png::from_file(&output).unwrap();
+        assert_eq!(png.chunk_by_type("ruSt").unwrap().data(), b"fetched");
+
+        encode(EncodeArgs {
+            dry_run: true,
+            ..encode_args(Path::new(&url), "ruSt", "planned", Some(&output))
+        })
+        .unwrap();
+        fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_time_set() {
+        let file = testing_file("time-set");
+        let set_args = |time: &str| TimeSetArgs {
+            file: file.clone(),
+            time: Some(time.to_string()),
+            ..Default::default()
+        };
+        time(TimeCommand::Set(set_args("2024-02-29T13:05:09Z"))).unwrap();
+        time(TimeCommand::Set(set_args("2025-01-01 00:00:00"))).unwrap();
+        assert!(time(TimeCommand::Set(set_args("yesterday"))).is_err());
+
+        let png = Png::from_file(&file).unwrap();
+        assert_eq!(chunk_types(&png), ["IHDR", "IDAT", "tIME", "IEND"]);
+        assert_eq!(
+            png.last_modified().unwrap().to_string(),
+            "2025-01-01T00:00:00Z"
+        );
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_touch() {
+        let file = testing_file("touch");
+        encode(encode_args(&file, "ruSt", "hidden", None)).unwrap();
+        assert!(Png::from_file(&file).unwrap().last_modified().is_err());
+
+        strip(StripArgs {
+            files: vec![spec(&file)],
+            touch: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let png = Png::from_file(&file).unwrap();
+        assert_eq!(chunk_types(&png), ["IHDR", "IDAT", "tIME", "IEND"]);
+        assert!(png.last_modified().unwrap().year >= 2024);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_record_history() {
+        let file = testing_file("record-history");
+        history(HistoryArgs { file: file.clone() }).unwrap();
+        encode(EncodeArgs {
+            record: true,
+            ..encode_args(&file, "ruSt", "hidden", None)
+        })
+        .unwrap();
+        strip(StripArgs {
+            files: vec![spec(&file)],
+            record: true,
+            ..Default::default()
+        })
+        .unwrap();
+
+        let png = Png::from_file(&file).unwrap();
+        assert_eq!(chunk_types(&png), ["IHDR", "IDAT", "prVn", "IEND"]);
+        let operations: Vec<String> = history::history(&png)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.operation)
+            .collect();
+        assert_eq!(operations, ["encode", "strip"]);
+        history(HistoryArgs { file: file.clone() }).unwrap();
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_exif() {
+        let file = testing_file("exif");
+        let blob = std::env::temp_dir().join(format!("picmes-{}-exif.bin", std::process::id()));
+        let exif_args = |set: Option<&Path>, out: Option<&Path>| ExifArgs {
+            file: file.clone(),
+            set: set.map(Path::to_path_buf),
+            out: out.map(Path::to_path_buf),
+            ..Default::default()
+        };
+        assert!(exif(exif_args(None, None)).is_err());
+
+        fs::write(&blob, crate::exif::tests::testing_exif()).unwrap();
+        exif(exif_args(Some(&blob), None)).unwrap();
+        exif(exif_args(Some(&blob), None)).unwrap();
+        let png = Png::from_file(&file).unwrap();
+        assert_eq!(chunk_types(&png), ["IHDR", "eXIf", "IDAT", "IEND"]);
+        exif(exif_args(None, None)).unwrap();
+
+        fs::remove_file(&blob).unwrap();
+        exif(exif_args(None, Some(&blob))).unwrap();
+        assert_eq!(fs::read(&blob).unwrap(), crate::exif::tests::testing_exif());
+
+        fs::write(&blob, b"not exif").unwrap();
+        assert!(exif(exif_args(Some(&blob), None)).is_err());
+        fs::remove_file(blob).unwrap();
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_extract() {
+        let file = testing_file("extract");
+        let out = file.with_extension("bin");
+        let data = vec![0xff, 0x00, 0x89, b'P'];
+        let mut png = Png::from_file(&file).unwrap();
+        png.append_chunk(Chunk::new(
+            ChunkType::from_str("ruSt").unwrap(),
+            data.clone(),
+        ));
+        png.save(&file).unwrap();
+
+        let args = |whole| ExtractArgs {
+            file: file.clone(),
+            chunk_type: "ruSt".to_string(),
+            out: out.clone(),
+            whole,
+        };
+        extract(args(false)).unwrap();
+        assert_eq!(fs::read(&out).unwrap(), data);
+
+        extract(args(true)).unwrap();
+        let chunk = Chunk::try_from(fs::read(&out).unwrap().as_ref()).unwrap();
+        assert_eq!(chunk.data(), data);
+        fs::remove_file(file).unwrap();
+        fs::remove_file(out).unwrap();
+    }
+
+    #[test]
+    fn test_stats() {
+        let file = testing_file("stats");
+        for format in [Format::Text, Format::Json, Format::Csv, Format::Yaml] {
+            stats(StatsArgs {
+                file: file.clone(),
+                top: 5,
+                format,
+                ..Default::default()
+            })
+            .unwrap();
+        }
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = testing_file("diff-old");
+        let new = testing_file("diff-new");
+        let mut png = Png::from_file(&new).unwrap();
+        png.insert_chunk(Chunk::new(ChunkType::tEXt, b"Comment\0added".to_vec()));
+        png.save(&new).unwrap();
+
+        for json in [false, true] {
+            diff(DiffArgs {
+                old: old.clone(),
+                new: new.clone(),
+                json,
+            })
+            .unwrap();
+        }
+        fs::remove_file(&new).unwrap();
+        assert!(diff(DiffArgs {
+            old: old.clone(),
+            new,
+            json: false
+        })
+        .is_err());
+        fs::remove_file(old).unwrap();
+    }
+
+    #[test]
+    fn test_hex_dump() {
+        let bytes = [&Png::STANDARD_HEADER[..], b"\0\0\0\rIHDR abc"].concat();
+        assert_eq!(
+            hex_dump(&bytes),
+            "00000000  89 50 4e 47 0d 0a 1a 0a  00 00 00 0d 49 48 44 52  |.PNG........IHDR|\n\
+             00000010  20 61 62 63                                       | abc|\n\
+             00000014\n"
+        );
+        assert_eq!(hex_dump(&[]), "00000000\n");
+    }
+
+    #[test]
+    fn test_hexdump() {
+        let file = testing_file("hexdump");
+        let args = |chunk_type: Option<&str>, index| HexdumpArgs {
+            file: file.clone(),
+            chunk_type: chunk_type.map(str::to_string),
+            index,
+        };
+        hexdump(args(None, None)).unwrap();
+        hexdump(args(Some("IDAT"), Some(0))).unwrap();
+        hexdump(args(None, Some(2))).unwrap();
+        assert!(matches!(
+            hexdump(args(Some("IDAT"), Some(1))),
+            Err(crate::Error::Command(CommandError::NoChunkAt {
+                index: 1,
+                count: 1
+            }))
+        ));
+        assert!(hexdump(args(Some("tEXt"), None)).is_err());
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_completions_cover_subcommands() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish, Shell::PowerShell] {
+            let mut script = Vec::new();
+            clap_complete::generate(shell, &mut Cli::command(), "picmes", &mut script);
+            let script = String::from_utf8(script).unwrap();
+            assert!(script.contains("picmes"), "{shell}");
+            assert!(script.contains("extract"), "{shell}");
+        }
+    }
+
+    #[test]
+    fn test_repaired_path() {
+        assert_eq!(
+            repaired_path(Path::new("dir/image.png")),
+            PathBuf::from("dir/image-repaired.png")
+        );
+    }
+
+    #[test]
+    fn test_encode_invalid_chunk_type() {
+        let file = testing_file("encode-invalid-type");
+        assert!(encode(encode_args(&file, "Rust", "hidden", None)).is_err());
+        assert!(encode(encode_args(&file, "ru5t", "hidden", None)).is_err());
+        assert_eq!(fs::read(&file).unwrap(), testing_png_bytes());
+        fs::remove_file(file).unwrap();
+    }
+}
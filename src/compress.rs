@@ -0,0 +1,223 @@
+use std::{
+    fmt::Display,
+    io::{Read, Write},
+};
+
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+
+use crate::Result;
+
+#[derive(Debug)]
+pub enum CompressError {
+    NotCompressed,
+    UnknownCompressor(u8),
+    InvalidLength { expected: u64, length: usize },
+    TooLong(u64),
+    TooLongFor { expected: u64, compressed: usize },
+}
+
+impl std::error::Error for CompressError {}
+
+impl Display for CompressError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotCompressed => write!(f, "The payload was not compressed by picmes"),
+            Self::UnknownCompressor(id) => write!(f, "Unknown compression algorithm {}", id),
+            Self::InvalidLength { expected, length } => write!(
+                f,
+                "The payload should decompress to {} bytes but holds {}",
+                expected, length
+            ),
+            Self::TooLong(length) => write!(
+                f,
+                "A payload of {} bytes is over the {} bytes picmes compresses",
+                length, MAX_LENGTH
+            ),
+            Self::TooLongFor {
+                expected,
+                compressed,
+            } => write!(
+                f,
+                "A payload of {} bytes can't come out of {} compressed bytes",
+                expected, compressed
+            ),
+        }
+    }
+}
+
+/// Starts a compressed payload. The last byte is the format version
+const MAGIC: &[u8] = b"pmZ\x01";
+/// `MAGIC | compressor | uncompressed length`
+const HEADER_LENGTH: usize = MAGIC.len() + 1 + 8;
+const ZSTD_LEVEL: i32 = 19;
+/// The most a payload may decompress to. The length is read from the
+/// payload, so without a limit of its own a forged one could ask for any
+/// amount of memory
+pub const MAX_LENGTH: u64 = 4 << 30;
+/// The most a payload may decompress to per compressed byte. Neither
+/// algorithm gets this far, zstd spends at least 4 bytes on a block of
+/// 128 KiB and deflate at least one on 1032 bytes, so a small forged chunk
+/// can't ask for much more than it holds
+const MAX_RATIO: u64 = 1 << 15;
+
+/// The algorithms a payload can be compressed with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Compressor {
+    #[default]
+    Zstd,
+    /// zlib wrapped deflate, as used by zTXt and the image data
+    Deflate,
+}
+
+impl Compressor {
+    fn id(self) -> u8 {
+        match self {
+            Self::Zstd => 0,
+            Self::Deflate => 1,
+        }
+    }
+
+    fn from_id(id: u8) -> Result<Self> {
+        match id {
+            0 => Ok(Self::Zstd),
+            1 => Ok(Self::Deflate),
+            _ => Err(CompressError::UnknownCompressor(id).into()),
+        }
+    }
+}
+
+/// Compresses `payload`, recording the algorithm and the original length so
+/// `decompress` needs nothing else
+pub fn compress(payload: &[u8], compressor: Compressor) -> Result<Vec<u8>> {
+    if payload.len() as u64 > MAX_LENGTH {
+        return Err(CompressError::TooLong(payload.len() as u64).into());
+    }
+    let mut compressed = MAGIC.to_vec();
+    compressed.push(compressor.id());
+    compressed.extend((payload.len() as u64).to_be_bytes());
+    match compressor {
+        Compressor::Zstd => zstd::stream::copy_encode(payload, &mut compressed, ZSTD_LEVEL)?,
+        Compressor::Deflate => {
+            let mut encoder = ZlibEncoder::new(compressed, Compression::best());
+            encoder.write_all(payload)?;
+            compressed = encoder.finish()?;
+        }
+    }
+    Ok(compressed)
+}
+
+pub fn is_compressed(data: &[u8]) -> bool {
+    data.len() >= HEADER_LENGTH && data.starts_with(MAGIC)
+}
+
+/// Undoes `compress`. Recorded lengths over `MAX_LENGTH`, or over
+/// `MAX_RATIO` times the compressed length, are refused and reading stops
+/// one byte past the recorded length, so a forged payload cannot inflate
+/// past either
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>> {
+    if !is_compressed(data) {
+        return Err(CompressError::NotCompressed.into());
+    }
+    let compressor = Compressor::from_id(data[MAGIC.len()])?;
+    let expected = u64::from_be_bytes(data[MAGIC.len() + 1..HEADER_LENGTH].try_into()?);
+    if expected > MAX_LENGTH {
+        return Err(CompressError::TooLong(expected).into());
+    }
+    let compressed = &data[HEADER_LENGTH..];
+    if expected > (compressed.len() as u64).saturating_mul(MAX_RATIO) {
+        return Err(CompressError::TooLongFor {
+            expected,
+            compressed: compressed.len(),
+        }
+        .into());
+    }
+
+    let mut payload = Vec::new();
+    match compressor {
+        Compressor::Zstd => zstd::stream::Decoder::new(compressed)?
+            .take(expected.saturating_add(1))
+            .read_to_end(&mut payload)?,
+        Compressor::Deflate => ZlibDecoder::new(compressed)
+            .take(expected.saturating_add(1))
+            .read_to_end(&mut payload)?,
+    };
+    if payload.len() as u64 != expected {
+        return Err(CompressError::InvalidLength {
+            expected,
+            length: payload.len(),
+        }
+        .into());
+    }
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compress_and_decompress() {
+        let payload = "All work and no play makes Jack a dull boy. ".repeat(100);
+        for compressor in [Compressor::Zstd, Compressor::Deflate] {
+            let compressed = compress(payload.as_bytes(), compressor).unwrap();
+            assert!(is_compressed(&compressed));
+            assert!(compressed.len() < payload.len() / 10);
+            assert_eq!(decompress(&compressed).unwrap(), payload.as_bytes());
+        }
+        assert_eq!(
+            decompress(&compress(b"", Compressor::Zstd).unwrap()).unwrap(),
+            b""
+        );
+        assert!(!is_compressed(b"plain text"));
+    }
+
+    #[test]
+    fn test_decompress_checks_length() {
+        let mut compressed = compress(b"exactly this long", Compressor::Deflate).unwrap();
+        compressed[HEADER_LENGTH - 1] -= 1;
+        assert!(decompress(&compressed).is_err());
+
+        compressed[MAGIC.len()] = 7;
+        assert!(decompress(&compressed).is_err());
+
+        for data in [&compressed[..HEADER_LENGTH - 1], b"", b"plain text"] {
+            assert!(matches!(
+                decompress(data),
+                Err(crate::Error::Compress(CompressError::NotCompressed))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_decompress_refuses_forged_length() {
+        for compressor in [Compressor::Zstd, Compressor::Deflate] {
+            let mut compressed = compress(b"tiny", compressor).unwrap();
+            for length in [u64::MAX, MAX_LENGTH + 1] {
+                compressed[MAGIC.len() + 1..HEADER_LENGTH].copy_from_slice(&length.to_be_bytes());
+                assert!(matches!(
+                    decompress(&compressed),
+                    Err(crate::Error::Compress(CompressError::TooLong(l))) if l == length
+                ));
+            }
+
+            // under the cap, but far more than a few bytes can hold
+            let length = (compressed.len() - HEADER_LENGTH) as u64 * MAX_RATIO + 1;
+            compressed[MAGIC.len() + 1..HEADER_LENGTH].copy_from_slice(&length.to_be_bytes());
+            assert!(matches!(
+                decompress(&compressed),
+                Err(crate::Error::Compress(CompressError::TooLongFor { expected, .. }))
+                    if expected == length
+            ));
+        }
+    }
+
+    #[test]
+    fn test_decompress_highly_compressible() {
+        let payload = vec![0; 1 << 22];
+        for compressor in [Compressor::Zstd, Compressor::Deflate] {
+            let compressed = compress(&payload, compressor).unwrap();
+            assert_eq!(decompress(&compressed).unwrap(), payload);
+        }
+    }
+}
@@ -0,0 +1,639 @@
+use std::{
+    fmt::Display,
+    io::{Read, Write},
+    path::Path,
+    str::FromStr,
+};
+
+use aes_gcm::{
+    aead::{rand_core::RngCore, Aead, KeyInit, OsRng, Payload},
+    Aes256Gcm, Nonce,
+};
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::ChaCha20Poly1305;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+use crate::Result;
+
+#[derive(Debug)]
+pub enum CryptoError {
+    NotEncrypted,
+    Truncated,
+    UnsupportedVersion(u8),
+    UnknownCipher(u8),
+    KdfTooExpensive(KdfParams),
+    KeyDerivation(String),
+    EncryptionFailed,
+    DecryptionFailed,
+    InvalidRecipient(String, String),
+    MissingIdentity,
+    SameDecoyPassphrase,
+}
+
+impl std::error::Error for CryptoError {}
+
+impl Display for CryptoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotEncrypted => write!(f, "The payload is not encrypted"),
+            Self::Truncated => write!(f, "The encrypted payload is truncated"),
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "Encrypted payload format {} is not supported by this version of picmes",
+                version
+            ),
+            Self::UnknownCipher(id) => write!(f, "Unknown cipher {} in encrypted payload", id),
+            Self::KdfTooExpensive(params) => write!(
+                f,
+                "Key derivation parameters {} exceed the limit of {} KiB and {} iterations",
+                params,
+                KdfParams::MAX_MEMORY_KIB,
+                KdfParams::MAX_ITERATIONS
+            ),
+            Self::KeyDerivation(reason) => write!(f, "Could not derive a key: {}", reason),
+            Self::EncryptionFailed => write!(f, "Encryption failed"),
+            Self::DecryptionFailed => write!(
+                f,
+                "Decryption failed: wrong passphrase or the payload was modified"
+            ),
+            Self::InvalidRecipient(recipient, reason) => {
+                write!(f, "Invalid age recipient {:?}: {}", recipient, reason)
+            }
+            Self::MissingIdentity => write!(
+                f,
+                "The payload is encrypted to an age recipient, use --identity to decrypt it"
+            ),
+            Self::SameDecoyPassphrase => {
+                write!(f, "The decoy passphrase must differ from the real one")
+            }
+        }
+    }
+}
+
+/// Every encrypted payload starts with this, followed by a format version
+const MAGIC: &[u8] = b"pmE";
+const VERSION: u8 = 1;
+/// Version 2 is laid out like version 1, with a plaintext padded by
+/// `encrypt_with_decoy`
+const VERSION_PADDED: u8 = 2;
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 12;
+const TAG_LENGTH: usize = 16;
+const KEY_LENGTH: usize = 32;
+
+/// The authenticated ciphers a payload can be encrypted with. Both take a
+/// 256-bit key and a 96-bit nonce
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Cipher {
+    #[default]
+    #[cfg_attr(feature = "cli", value(name = "aes"))]
+    Aes256Gcm,
+    #[cfg_attr(feature = "cli", value(name = "chacha20"))]
+    ChaCha20Poly1305,
+}
+
+impl Cipher {
+    fn id(self) -> u8 {
+        match self {
+            Self::Aes256Gcm => 0,
+            Self::ChaCha20Poly1305 => 1,
+        }
+    }
+
+    fn from_id(id: u8) -> Result<Self> {
+        match id {
+            0 => Ok(Self::Aes256Gcm),
+            1 => Ok(Self::ChaCha20Poly1305),
+            _ => Err(CryptoError::UnknownCipher(id).into()),
+        }
+    }
+
+    /// Encrypts `plaintext`, authenticating `aad` along with it
+    fn seal(self, key: &[u8], nonce: &[u8], plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        let nonce = Nonce::from_slice(nonce);
+        let payload = Payload {
+            msg: plaintext,
+            aad,
+        };
+        let sealed = match self {
+            Self::Aes256Gcm => Aes256Gcm::new(key.into()).encrypt(nonce, payload),
+            Self::ChaCha20Poly1305 => ChaCha20Poly1305::new(key.into()).encrypt(nonce, payload),
+        };
+        Ok(sealed.map_err(|_| CryptoError::EncryptionFailed)?)
+    }
+
+    fn open(self, key: &[u8], nonce: &[u8], ciphertext: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        let nonce = Nonce::from_slice(nonce);
+        let payload = Payload {
+            msg: ciphertext,
+            aad,
+        };
+        let opened = match self {
+            Self::Aes256Gcm => Aes256Gcm::new(key.into()).decrypt(nonce, payload),
+            Self::ChaCha20Poly1305 => ChaCha20Poly1305::new(key.into()).decrypt(nonce, payload),
+        };
+        Ok(opened.map_err(|_| CryptoError::DecryptionFailed)?)
+    }
+}
+
+/// Argon2id cost parameters. They are stored in the payload header, so
+/// decoding derives the same key whatever the encoder chose
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KdfParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u8,
+}
+
+impl KdfParams {
+    const LENGTH: usize = 9;
+    /// Upper bounds accepted when decoding, so a crafted header cannot make
+    /// picmes allocate or spin without limit
+    pub const MAX_MEMORY_KIB: u32 = 1024 * 1024;
+    pub const MAX_ITERATIONS: u32 = 64;
+
+    fn to_bytes(self) -> [u8; Self::LENGTH] {
+        let mut bytes = [0; Self::LENGTH];
+        bytes[..4].copy_from_slice(&self.memory_kib.to_be_bytes());
+        bytes[4..8].copy_from_slice(&self.iterations.to_be_bytes());
+        bytes[8] = self.parallelism;
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let bytes: [u8; Self::LENGTH] = bytes.try_into()?;
+        Ok(Self {
+            memory_kib: u32::from_be_bytes(bytes[..4].try_into()?),
+            iterations: u32::from_be_bytes(bytes[4..8].try_into()?),
+            parallelism: bytes[8],
+        })
+    }
+
+    fn argon2(self) -> Result<Argon2<'static>> {
+        if self.memory_kib > Self::MAX_MEMORY_KIB || self.iterations > Self::MAX_ITERATIONS {
+            return Err(CryptoError::KdfTooExpensive(self).into());
+        }
+        let params = Params::new(
+            self.memory_kib,
+            self.iterations,
+            self.parallelism as u32,
+            Some(KEY_LENGTH),
+        )
+        .map_err(|e| CryptoError::KeyDerivation(e.to_string()))?;
+        Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+    }
+}
+
+impl Default for KdfParams {
+    /// The OWASP recommended minimum
+    fn default() -> Self {
+        Self {
+            memory_kib: 19 * 1024,
+            iterations: 2,
+            parallelism: 1,
+        }
+    }
+}
+
+impl Display for KdfParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "m={} KiB, t={}, p={}",
+            self.memory_kib, self.iterations, self.parallelism
+        )
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct EncryptOptions {
+    pub cipher: Cipher,
+    pub kdf: KdfParams,
+    /// Derive the salt from the passphrase and the nonce from the key and
+    /// plaintext, so the same message and passphrase always give the same
+    /// bytes. This reveals when two payloads hold the same message
+    pub deterministic: bool,
+}
+
+/// Hashed into the salt of deterministic payloads. It leaves the passphrase
+/// out, so the stored salt gives nothing to test a guess against without
+/// running Argon2id
+const DETERMINISTIC_SALT: &[u8] = b"picmes deterministic salt";
+
+/// Encrypts `plaintext` under a key derived from `passphrase` with Argon2id.
+/// The result is laid out as
+/// `MAGIC | version | cipher | KDF parameters | salt | nonce | ciphertext and tag`,
+/// with everything before the nonce authenticated along with the ciphertext
+pub fn encrypt(plaintext: &[u8], passphrase: &str, options: EncryptOptions) -> Result<Vec<u8>> {
+    seal(plaintext, passphrase, options, VERSION)
+}
+
+/// Encrypts `plaintext` as `encrypt` does, recording `version` in the header
+fn seal(
+    plaintext: &[u8],
+    passphrase: &str,
+    options: EncryptOptions,
+    version: u8,
+) -> Result<Vec<u8>> {
+    let mut salt = [0; SALT_LENGTH];
+    let mut nonce = [0; NONCE_LENGTH];
+    let key = if options.deterministic {
+        let digest = Sha256::digest(DETERMINISTIC_SALT);
+        salt.copy_from_slice(&digest[..SALT_LENGTH]);
+        let key = derive_key(passphrase, &salt, options.kdf)?;
+        // a synthetic nonce only repeats when the plaintext does too
+        let tag = <Hmac<Sha256> as Mac>::new_from_slice(&key)?
+            .chain_update([options.cipher.id()])
+            .chain_update(plaintext)
+            .finalize()
+            .into_bytes();
+        nonce.copy_from_slice(&tag[..NONCE_LENGTH]);
+        key
+    } else {
+        OsRng.fill_bytes(&mut salt);
+        OsRng.fill_bytes(&mut nonce);
+        derive_key(passphrase, &salt, options.kdf)?
+    };
+    let mut data = MAGIC.to_vec();
+    data.extend([version, options.cipher.id()]);
+    data.extend(options.kdf.to_bytes());
+    data.extend(salt);
+    let ciphertext = options.cipher.seal(&key, &nonce, plaintext, &data)?;
+    data.extend(nonce);
+    data.extend(ciphertext);
+    Ok(data)
+}
+
+/// Reverses `encrypt` and `encrypt_with_decoy`, using the cipher and KDF
+/// parameters recorded in the header and removing the padding of decoy
+/// pairs. Fails if the passphrase is wrong or any byte of the payload was
+/// changed
+pub fn decrypt(data: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    let rest = data.strip_prefix(MAGIC).ok_or(CryptoError::NotEncrypted)?;
+    let (cipher, kdf, rest) = match rest {
+        [VERSION | VERSION_PADDED, id, rest @ ..] if rest.len() >= KdfParams::LENGTH => {
+            let (kdf, rest) = rest.split_at(KdfParams::LENGTH);
+            (Cipher::from_id(*id)?, KdfParams::from_bytes(kdf)?, rest)
+        }
+        [version, ..] if !is_encrypted(data) => {
+            return Err(CryptoError::UnsupportedVersion(*version).into())
+        }
+        _ => return Err(CryptoError::Truncated.into()),
+    };
+    if rest.len() < SALT_LENGTH + NONCE_LENGTH + TAG_LENGTH {
+        return Err(CryptoError::Truncated.into());
+    }
+    let (salt, rest) = rest.split_at(SALT_LENGTH);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LENGTH);
+    let header = &data[..data.len() - rest.len()];
+    let plaintext = cipher.open(
+        &derive_key(passphrase, salt, kdf)?,
+        nonce,
+        ciphertext,
+        header,
+    )?;
+    match data[MAGIC.len()] {
+        VERSION_PADDED => Ok(strip_padding(plaintext)),
+        _ => Ok(plaintext),
+    }
+}
+
+/// Ends a padded plaintext, after the length of the payload it holds
+const PADDING_MAGIC: &[u8] = b"pmD\x01";
+
+/// Pads `payload` with zeros to `length` bytes plus a trailer recording its
+/// real length
+fn pad(payload: &[u8], length: usize) -> Vec<u8> {
+    let mut padded = payload.to_vec();
+    padded.resize(length, 0);
+    padded.extend((payload.len() as u64).to_be_bytes());
+    padded.extend(PADDING_MAGIC);
+    padded
+}
+
+/// Removes the padding `pad` adds, returning plaintexts without a valid
+/// trailer unchanged
+fn strip_padding(mut plaintext: Vec<u8>) -> Vec<u8> {
+    let Some(rest) = plaintext.strip_suffix(PADDING_MAGIC) else {
+        return plaintext;
+    };
+    let Some(end) = rest.len().checked_sub(8) else {
+        return plaintext;
+    };
+    let (padded, length) = rest.split_at(end);
+    let length = u64::from_be_bytes(length.try_into().expect("eight bytes"));
+    if length > padded.len() as u64 {
+        return plaintext;
+    }
+    plaintext.truncate(length as usize);
+    plaintext
+}
+
+/// Encrypts `plaintext` and an innocuous `decoy` under two passphrases,
+/// returning the two payloads in random order. Both are padded to the same
+/// length first, so nothing but the passphrase tells them apart
+pub fn encrypt_with_decoy(
+    plaintext: &[u8],
+    passphrase: &str,
+    decoy: &[u8],
+    decoy_passphrase: &str,
+    options: EncryptOptions,
+) -> Result<[Vec<u8>; 2]> {
+    if passphrase == decoy_passphrase {
+        return Err(CryptoError::SameDecoyPassphrase.into());
+    }
+    let length = plaintext.len().max(decoy.len());
+    let real = seal(&pad(plaintext, length), passphrase, options, VERSION_PADDED)?;
+    let decoy = seal(
+        &pad(decoy, length),
+        decoy_passphrase,
+        options,
+        VERSION_PADDED,
+    )?;
+    Ok(match OsRng.next_u32() & 1 {
+        0 => [real, decoy],
+        _ => [decoy, real],
+    })
+}
+
+/// Whether `data` starts with the header `encrypt` writes
+pub fn is_encrypted(data: &[u8]) -> bool {
+    match data.strip_prefix(MAGIC) {
+        Some([version, ..]) => [VERSION, VERSION_PADDED].contains(version),
+        _ => false,
+    }
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], kdf: KdfParams) -> Result<[u8; KEY_LENGTH]> {
+    let mut key = [0; KEY_LENGTH];
+    kdf.argon2()?
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| CryptoError::KeyDerivation(e.to_string()))?;
+    Ok(key)
+}
+
+/// Every binary age file starts with this line
+const AGE_MAGIC: &[u8] = b"age-encryption.org/v1\n";
+
+/// Encrypts `plaintext` to age X25519 recipients, given as `age1...` public
+/// keys. A random file key encrypts the payload once and is wrapped for each
+/// recipient, so the identity of any one of them can decrypt it. No secret is
+/// needed to encrypt
+pub fn encrypt_to_recipients(plaintext: &[u8], recipients: &[String]) -> Result<Vec<u8>> {
+    let mut unique = Vec::new();
+    for recipient in recipients {
+        if !unique.contains(&recipient) {
+            unique.push(recipient);
+        }
+    }
+    let recipients = unique
+        .into_iter()
+        .map(|recipient| {
+            age::x25519::Recipient::from_str(recipient)
+                .map_err(|e| CryptoError::InvalidRecipient(recipient.to_string(), e.to_string()))
+        })
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    let encryptor =
+        age::Encryptor::with_recipients(recipients.iter().map(|r| r as &dyn age::Recipient))?;
+
+    let mut data = Vec::new();
+    let mut writer = encryptor.wrap_output(&mut data)?;
+    writer.write_all(plaintext)?;
+    writer.finish()?;
+    Ok(data)
+}
+
+/// Reads age public keys from a recipients file, skipping blank lines and
+/// `#` comments
+pub fn read_recipients_file(path: &Path) -> Result<Vec<String>> {
+    Ok(std::fs::read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+/// Decrypts an age payload with the identities in `identity_file`, in the
+/// format `age-keygen` writes
+pub fn decrypt_with_identity_file(data: &[u8], identity_file: &Path) -> Result<Vec<u8>> {
+    let identities = age::IdentityFile::from_file(identity_file.to_string_lossy().into_owned())?
+        .into_identities()?;
+    let decryptor = age::Decryptor::new_buffered(data)?;
+    let mut reader = decryptor.decrypt(identities.iter().map(|i| i.as_ref()))?;
+
+    let mut plaintext = Vec::new();
+    reader.read_to_end(&mut plaintext)?;
+    Ok(plaintext)
+}
+
+/// Whether `data` is a binary age file
+pub fn is_age_encrypted(data: &[u8]) -> bool {
+    data.starts_with(AGE_MAGIC)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER_LENGTH: usize = 5 + KdfParams::LENGTH + SALT_LENGTH + NONCE_LENGTH;
+
+    /// Cheap parameters so the tests do not spend their time hashing
+    fn fast_kdf() -> KdfParams {
+        KdfParams {
+            memory_kib: 64,
+            iterations: 1,
+            parallelism: 1,
+        }
+    }
+
+    fn options(cipher: Cipher) -> EncryptOptions {
+        EncryptOptions {
+            cipher,
+            kdf: fast_kdf(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_encrypt_round_trip() {
+        for cipher in [Cipher::Aes256Gcm, Cipher::ChaCha20Poly1305] {
+            let data = encrypt(b"hidden message", "hunter2", options(cipher)).unwrap();
+            assert!(is_encrypted(&data));
+            assert_eq!(data[4], cipher.id());
+            assert_eq!(data.len(), HEADER_LENGTH + 14 + TAG_LENGTH);
+            assert_eq!(decrypt(&data, "hunter2").unwrap(), b"hidden message");
+
+            // a fresh salt and nonce every time
+            let again = encrypt(b"hidden message", "hunter2", options(cipher)).unwrap();
+            assert_ne!(again, data);
+        }
+    }
+
+    #[test]
+    fn test_deterministic_encryption() {
+        for cipher in [Cipher::Aes256Gcm, Cipher::ChaCha20Poly1305] {
+            let options = EncryptOptions {
+                deterministic: true,
+                ..options(cipher)
+            };
+            let data = encrypt(b"hidden message", "hunter2", options).unwrap();
+            assert_eq!(
+                encrypt(b"hidden message", "hunter2", options).unwrap(),
+                data
+            );
+            assert_eq!(decrypt(&data, "hunter2").unwrap(), b"hidden message");
+
+            let other = encrypt(b"hidden massage", "hunter2", options).unwrap();
+            assert_eq!(
+                other[..HEADER_LENGTH - NONCE_LENGTH],
+                data[..HEADER_LENGTH - NONCE_LENGTH]
+            );
+            assert_ne!(
+                other[HEADER_LENGTH - NONCE_LENGTH..HEADER_LENGTH],
+                data[HEADER_LENGTH - NONCE_LENGTH..HEADER_LENGTH]
+            );
+            let other_passphrase = encrypt(b"hidden message", "hunter3", options).unwrap();
+            assert_ne!(other_passphrase, data);
+            let salt = 5 + KdfParams::LENGTH..5 + KdfParams::LENGTH + SALT_LENGTH;
+            assert_eq!(other_passphrase[salt.clone()], data[salt]);
+        }
+    }
+
+    #[test]
+    fn test_encrypt_with_decoy() {
+        let options = options(Cipher::ChaCha20Poly1305);
+        let payloads = encrypt_with_decoy(
+            b"the real plans",
+            "real",
+            b"shopping list",
+            "decoy",
+            options,
+        )
+        .unwrap();
+        assert_eq!(payloads[0].len(), payloads[1].len());
+        assert!(payloads.iter().all(|p| is_encrypted(p)));
+
+        let open = |passphrase: &str| -> Vec<Vec<u8>> {
+            payloads
+                .iter()
+                .filter_map(|p| decrypt(p, passphrase).ok())
+                .collect()
+        };
+        assert_eq!(open("real"), [b"the real plans"]);
+        assert_eq!(open("decoy"), [b"shopping list"]);
+        assert!(open("guess").is_empty());
+
+        assert!(encrypt_with_decoy(b"a", "same", b"b", "same", options).is_err());
+    }
+
+    #[test]
+    fn test_strip_padding() {
+        assert_eq!(strip_padding(pad(b"short", 12)), b"short");
+        assert_eq!(strip_padding(pad(b"", 0)), b"");
+        assert_eq!(strip_padding(b"not padded".to_vec()), b"not padded");
+        assert_eq!(strip_padding(b"pmD\x01".to_vec()), b"pmD\x01");
+
+        let mut forged = pad(b"short", 12);
+        forged[12..20].copy_from_slice(&100u64.to_be_bytes());
+        assert_eq!(strip_padding(forged.clone()), forged);
+    }
+
+    #[test]
+    fn test_only_padded_payloads_lose_their_trailer() {
+        let message = pad(b"hello", 11);
+        let data = encrypt(&message, "hunter2", options(Cipher::default())).unwrap();
+        assert_eq!(decrypt(&data, "hunter2").unwrap(), message);
+    }
+
+    #[test]
+    fn test_kdf_params_are_stored() {
+        let data = encrypt(b"hidden", "hunter2", options(Cipher::default())).unwrap();
+        let stored = KdfParams::from_bytes(&data[5..5 + KdfParams::LENGTH]).unwrap();
+        assert_eq!(stored, fast_kdf());
+
+        let expensive = KdfParams {
+            memory_kib: KdfParams::MAX_MEMORY_KIB + 1,
+            ..fast_kdf()
+        };
+        let options = EncryptOptions {
+            kdf: expensive,
+            ..Default::default()
+        };
+        assert!(encrypt(b"hidden", "hunter2", options).is_err());
+
+        let mut crafted = data.clone();
+        crafted[5..5 + KdfParams::LENGTH].copy_from_slice(&expensive.to_bytes());
+        assert!(decrypt(&crafted, "hunter2").is_err());
+    }
+
+    #[test]
+    fn test_decrypt_rejects_unknown_header() {
+        let mut data = encrypt(b"hidden", "hunter2", options(Cipher::ChaCha20Poly1305)).unwrap();
+        data[4] = 9;
+        assert!(decrypt(&data, "hunter2").is_err());
+        data[3] = VERSION_PADDED + 1;
+        assert!(!is_encrypted(&data));
+        assert!(decrypt(&data, "hunter2").is_err());
+    }
+
+    #[test]
+    fn test_decrypt_rejects_changed_header() {
+        let data = encrypt(b"hidden", "hunter2", options(Cipher::default())).unwrap();
+        for (offset, value) in [
+            (MAGIC.len(), VERSION_PADDED),
+            (MAGIC.len() + 2 + 7, 2),
+            (MAGIC.len() + 2 + KdfParams::LENGTH, 0),
+        ] {
+            let mut changed = data.clone();
+            changed[offset] = value;
+            assert!(decrypt(&changed, "hunter2").is_err(), "{}", offset);
+        }
+        assert_eq!(decrypt(&data, "hunter2").unwrap(), b"hidden");
+    }
+
+    #[test]
+    fn test_decrypt_rejects_tampering() {
+        let data = encrypt(b"hidden message", "hunter2", options(Cipher::default())).unwrap();
+        assert!(decrypt(&data, "hunter3").is_err());
+
+        let mut tampered = data.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(decrypt(&tampered, "hunter2").is_err());
+
+        assert!(decrypt(&data[..HEADER_LENGTH + 4], "hunter2").is_err());
+        assert!(decrypt(&data[..6], "hunter2").is_err());
+        assert!(decrypt(b"hidden message", "hunter2").is_err());
+    }
+
+    #[test]
+    fn test_age_round_trip() {
+        use age::secrecy::ExposeSecret;
+
+        let identity = age::x25519::Identity::generate();
+        let other = age::x25519::Identity::generate();
+        let recipients = [
+            identity.to_public().to_string(),
+            other.to_public().to_string(),
+        ];
+        let data = encrypt_to_recipients(b"for your eyes only", &recipients).unwrap();
+        assert!(is_age_encrypted(&data));
+        assert!(!is_encrypted(&data));
+
+        let identity_file =
+            std::env::temp_dir().join(format!("picmes-{}-age-identity.txt", std::process::id()));
+        std::fs::write(&identity_file, identity.to_string().expose_secret()).unwrap();
+        let plaintext = decrypt_with_identity_file(&data, &identity_file).unwrap();
+        assert_eq!(plaintext, b"for your eyes only");
+
+        let stranger = age::x25519::Identity::generate();
+        std::fs::write(&identity_file, stranger.to_string().expose_secret()).unwrap();
+        assert!(decrypt_with_identity_file(&data, &identity_file).is_err());
+        std::fs::remove_file(identity_file).unwrap();
+
+        assert!(encrypt_to_recipients(b"hidden", &["age1nope".to_string()]).is_err());
+        assert!(encrypt_to_recipients(b"hidden", &[]).is_err());
+    }
+}
@@ -0,0 +1,259 @@
+use serde::Serialize;
+
+use crate::{chunk::Chunk, image_data::ImageData, png::Png, stego, Result};
+
+/// How likely one part of a file is to hold hidden data, from 0 to 1
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Evidence {
+    pub source: String,
+    pub likelihood: f64,
+    pub detail: String,
+}
+
+/// Chunks shorter than this are too small for their entropy to mean much
+const MIN_ENTROPY_LENGTH: usize = 16;
+/// Pairs of values seen fewer times than this are left out of the
+/// chi-square statistic, which is unreliable for small counts
+const MIN_PAIR_COUNT: usize = 10;
+const MIN_CHI_SQUARE_SAMPLES: usize = 256;
+
+/// Looks for hidden data in every ancillary chunk and in the low bits of
+/// the pixel data. Nothing here proves anything, the likelihoods only rank
+/// how unusual each part looks
+pub fn detect(png: &Png) -> Vec<Evidence> {
+    let mut evidence = Vec::new();
+    let mut offset = Png::HEADER_LENGTH;
+    for chunk in png.chunks() {
+        if !chunk.chunk_type().is_critical() {
+            evidence.push(chunk_evidence(chunk, offset));
+        }
+        offset += chunk.length() + Chunk::META_DATA_LENGTH;
+    }
+    evidence.push(lsb_evidence(png).unwrap_or_else(|e| Evidence {
+        source: "pixel data".to_string(),
+        likelihood: 0.0,
+        detail: format!("not analysed, {}", e),
+    }));
+    evidence
+}
+
+/// Shannon entropy in bits per byte, 0 for no data. Compressed and
+/// encrypted data come close to 8
+pub fn entropy(data: &[u8]) -> f64 {
+    let mut counts = [0usize; 256];
+    for &byte in data {
+        counts[byte as usize] += 1;
+    }
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| count as f64 / data.len() as f64)
+        // summing no values gives -0.0, which would print with a sign
+        .fold(0.0, |bits, p| bits - p * p.log2())
+}
+
+/// Roughly the entropy `length` random bytes have. Short samples fall well
+/// short of 8 bits per byte, so this corrects for the bias (Miller-Madow)
+fn random_entropy(length: usize) -> f64 {
+    let bins = length.min(256) as f64;
+    (length as f64).log2().min(8.0) - (bins - 1.0) / (2.0 * length as f64 * std::f64::consts::LN_2)
+}
+
+/// Unregistered chunks are suspect to begin with and more so when their data
+/// looks random, as encrypted payloads do. Registered chunks only count when
+/// they look random, and less, since some of them are compressed anyway
+fn chunk_evidence(chunk: &Chunk, offset: usize) -> Evidence {
+    let data = chunk.data();
+    let bits = entropy(data);
+    let randomness = match data.len() >= MIN_ENTROPY_LENGTH {
+        true => ((bits / random_entropy(data.len()) - 0.85) / 0.15).clamp(0.0, 1.0),
+        false => 0.0,
+    };
+    let likelihood = match chunk.chunk_type().is_registered() {
+        true => 0.25 * randomness,
+        false => 0.5 + 0.5 * randomness,
+    };
+    Evidence {
+        source: format!("chunk {} at offset {:#x}", chunk.chunk_type(), offset),
+        likelihood,
+        detail: format!(
+            "entropy {:.2} bits per byte over {} bytes",
+            bits,
+            data.len()
+        ),
+    }
+}
+
+/// Runs the chi-square attack of Westfeld and Pfitzmann on the bytes LSB
+/// embedding would use. Overwriting low bits evens out how often each pair
+/// of values 2k and 2k + 1 occurs, which natural images rarely do. Since
+/// payloads are usually written from the start, growing prefixes are tested
+/// and the most suspicious one reported
+fn lsb_evidence(png: &Png) -> Result<Evidence> {
+    let image = ImageData::from_png(png)?;
+    let samples = image.samples();
+    let values: Vec<u8> = stego::carriers(image.header())?
+        .map(|offset| samples[offset])
+        .collect();
+
+    let mut best = (0.0, 0);
+    for length in [8, 4, 2, 1].map(|divisor| values.len() / divisor) {
+        if length < MIN_CHI_SQUARE_SAMPLES {
+            continue;
+        }
+        if let Some(p) = chi_square_embedding(&values[..length]) {
+            if p > best.0 {
+                best = (p, length);
+            }
+        }
+    }
+    let (likelihood, length) = best;
+    Ok(Evidence {
+        source: "pixel data".to_string(),
+        likelihood,
+        detail: match length {
+            0 => format!("too few samples to test, {}", values.len()),
+            _ => format!(
+                "chi-square test over the first {} of {} samples",
+                length,
+                values.len()
+            ),
+        },
+    })
+}
+
+/// The probability that `values` had their low bits overwritten, or `None`
+/// when there are too few of them to tell
+fn chi_square_embedding(values: &[u8]) -> Option<f64> {
+    let mut counts = [0usize; 256];
+    for &value in values {
+        counts[value as usize] += 1;
+    }
+    let mut statistic = 0.0;
+    let mut pairs = 0;
+    for pair in counts.chunks_exact(2) {
+        let (even, odd) = (pair[0], pair[1]);
+        if even + odd < MIN_PAIR_COUNT {
+            continue;
+        }
+        let expected = (even + odd) as f64 / 2.0;
+        statistic += (even as f64 - expected).powi(2) / expected;
+        pairs += 1;
+    }
+    (pairs >= 2).then(|| chi_square_upper_tail(statistic, (pairs - 1) as f64))
+}
+
+/// P(X > x) for X chi-square distributed with `k` degrees of freedom, using
+/// the Wilson-Hilferty normal approximation
+fn chi_square_upper_tail(x: f64, k: f64) -> f64 {
+    let variance = 2.0 / (9.0 * k);
+    let z = ((x / k).cbrt() - (1.0 - variance)) / variance.sqrt();
+    0.5 * erfc(z / std::f64::consts::SQRT_2)
+}
+
+/// The complementary error function, accurate to about 1e-7 (Abramowitz and
+/// Stegun 7.1.26)
+fn erfc(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.3275911 * x.abs());
+    let polynomial = t
+        * (0.254829592
+            + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    let tail = polynomial * (-x * x).exp();
+    match x >= 0.0 {
+        true => tail,
+        false => 2.0 - tail,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chunk_type::ChunkType, ihdr::ColorType, image_data::tests::testing_image};
+    use sha2::{Digest, Sha256};
+    use std::str::FromStr;
+
+    /// Bytes that look random, like an encrypted payload
+    fn random_looking(length: usize) -> Vec<u8> {
+        (0..length.div_ceil(32))
+            .flat_map(|i| Sha256::digest((i as u32).to_be_bytes()))
+            .take(length)
+            .collect()
+    }
+
+    /// A png whose colour samples are all even, the opposite of what LSB
+    /// embedding leaves behind
+    fn even_image() -> Png {
+        let mut png = testing_image(64, 64, ColorType::Rgb);
+        let mut image = ImageData::from_png(&png).unwrap();
+        for sample in image.samples_mut() {
+            *sample &= !1;
+        }
+        png.replace_image_data(image.to_chunk().unwrap()).unwrap();
+        png
+    }
+
+    #[test]
+    fn test_entropy() {
+        assert_eq!(entropy(b"aaaa"), 0.0);
+        assert!(entropy(b"").is_sign_positive());
+        assert_eq!(entropy(b"abab"), 1.0);
+        let all: Vec<u8> = (0..=255).collect();
+        assert_eq!(entropy(&all), 8.0);
+    }
+
+    #[test]
+    fn test_chunk_evidence() {
+        let chunk = |chunk_type: &str, data: Vec<u8>| {
+            Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data)
+        };
+        let likelihood = |chunk: Chunk| chunk_evidence(&chunk, 0).likelihood;
+
+        assert!(likelihood(chunk("ruSt", random_looking(512))) > 0.9);
+        let text = b"a perfectly ordinary sentence about the weather".to_vec();
+        assert_eq!(likelihood(chunk("ruSt", text.clone())), 0.5);
+        assert_eq!(likelihood(chunk("tEXt", text)), 0.0);
+        assert!(likelihood(chunk("zTXt", random_looking(512))) <= 0.25);
+        assert_eq!(likelihood(chunk("ruSt", b"tiny".to_vec())), 0.5);
+    }
+
+    #[test]
+    fn test_chi_square_finds_lsb_payload() {
+        let mut png = even_image();
+        let clean = lsb_evidence(&png).unwrap();
+        assert!(clean.likelihood < 0.01, "{:?}", clean);
+
+        let mut image = ImageData::from_png(&png).unwrap();
+        let capacity = stego::capacity(&image).unwrap();
+        stego::hide(&mut image, &random_looking(capacity)).unwrap();
+        png.replace_image_data(image.to_chunk().unwrap()).unwrap();
+        let hidden = lsb_evidence(&png).unwrap();
+        assert!(hidden.likelihood > 0.5, "{:?}", hidden);
+    }
+
+    #[test]
+    fn test_detect() {
+        let mut png = even_image();
+        png.insert_chunk(Chunk::new(
+            ChunkType::from_str("ruSt").unwrap(),
+            random_looking(256),
+        ));
+        let evidence = detect(&png);
+        assert_eq!(evidence.len(), 2);
+        assert!(evidence[0].source.starts_with("chunk ruSt at offset 0x"));
+        assert!(evidence[0].likelihood > 0.9);
+        assert_eq!(evidence[1].source, "pixel data");
+
+        let indexed = testing_image(8, 8, ColorType::Indexed);
+        assert!(detect(&indexed)[0].detail.starts_with("not analysed"));
+    }
+
+    #[test]
+    fn test_chi_square_upper_tail() {
+        // the median of a chi-square distribution is close to k (1 - 2/9k)^3
+        assert!((chi_square_upper_tail(9.342, 10.0) - 0.5).abs() < 0.01);
+        assert!(chi_square_upper_tail(0.0, 10.0) > 0.99);
+        assert!(chi_square_upper_tail(100.0, 10.0) < 1e-6);
+        assert!((erfc(0.0) - 1.0).abs() < 1e-7);
+        assert!((erfc(1.0) - 0.157299).abs() < 1e-6);
+    }
+}
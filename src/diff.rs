@@ -0,0 +1,284 @@
+use std::{collections::HashMap, fmt::Display};
+
+use serde::Serialize;
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, png::Png};
+
+/// One way a chunk differs between two pngs. Chunks are matched by type and
+/// occurrence, so the second tEXt chunk of one file is compared with the
+/// second tEXt chunk of the other. Indexes count every chunk in the file
+#[derive(Debug, Serialize, PartialEq)]
+#[serde(tag = "change", rename_all = "lowercase")]
+pub enum Difference {
+    Added {
+        chunk_type: String,
+        occurrence: usize,
+        index: usize,
+        length: usize,
+    },
+    Removed {
+        chunk_type: String,
+        occurrence: usize,
+        index: usize,
+        length: usize,
+    },
+    Changed {
+        chunk_type: String,
+        occurrence: usize,
+        old_length: usize,
+        new_length: usize,
+        old_crc: u32,
+        new_crc: u32,
+    },
+    Moved {
+        chunk_type: String,
+        occurrence: usize,
+        from: usize,
+        to: usize,
+    },
+}
+
+impl Display for Difference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Added {
+                chunk_type,
+                occurrence,
+                index,
+                length,
+            } => write!(
+                f,
+                "+ {} #{} at {} ({} bytes)",
+                chunk_type, occurrence, index, length
+            ),
+            Self::Removed {
+                chunk_type,
+                occurrence,
+                index,
+                length,
+            } => write!(
+                f,
+                "- {} #{} at {} ({} bytes)",
+                chunk_type, occurrence, index, length
+            ),
+            Self::Changed {
+                chunk_type,
+                occurrence,
+                old_length,
+                new_length,
+                old_crc,
+                new_crc,
+            } => write!(
+                f,
+                "~ {} #{}: {} -> {} bytes, crc {:#010x} -> {:#010x}",
+                chunk_type, occurrence, old_length, new_length, old_crc, new_crc
+            ),
+            Self::Moved {
+                chunk_type,
+                occurrence,
+                from,
+                to,
+            } => write!(
+                f,
+                "> {} #{}: moved from {} to {}",
+                chunk_type, occurrence, from, to
+            ),
+        }
+    }
+}
+
+/// Lists the chunks removed from `old` and those whose data changed, in the
+/// order of `old`, then the chunks `new` added and finally the chunks that
+/// changed places. Chunks only shifted by insertions or removals around them
+/// don't count as moved, only those out of order with the rest
+pub fn diff(old: &Png, new: &Png) -> Vec<Difference> {
+    let old_keys = keys(old);
+    let new_keys = keys(new);
+    let old_positions: HashMap<&(ChunkType, usize), usize> = old_keys
+        .iter()
+        .enumerate()
+        .map(|(i, key)| (key, i))
+        .collect();
+    let new_positions: HashMap<&(ChunkType, usize), usize> = new_keys
+        .iter()
+        .enumerate()
+        .map(|(i, key)| (key, i))
+        .collect();
+    let old_chunks: Vec<&Chunk> = old.chunks().collect();
+    let new_chunks: Vec<&Chunk> = new.chunks().collect();
+
+    let mut differences = Vec::new();
+    let mut common = Vec::new();
+    for (index, key @ (chunk_type, occurrence)) in old_keys.iter().enumerate() {
+        let (old_chunk, occurrence) = (old_chunks[index], *occurrence);
+        let Some(&new_index) = new_positions.get(key) else {
+            differences.push(Difference::Removed {
+                chunk_type: chunk_type.to_string(),
+                occurrence,
+                index,
+                length: old_chunk.length(),
+            });
+            continue;
+        };
+        let new_chunk = new_chunks[new_index];
+        if old_chunk.data() != new_chunk.data() {
+            differences.push(Difference::Changed {
+                chunk_type: chunk_type.to_string(),
+                occurrence,
+                old_length: old_chunk.length(),
+                new_length: new_chunk.length(),
+                old_crc: old_chunk.crc(),
+                new_crc: new_chunk.crc(),
+            });
+        }
+        common.push((index, new_index));
+    }
+
+    for (index, key @ (chunk_type, occurrence)) in new_keys.iter().enumerate() {
+        if !old_positions.contains_key(key) {
+            differences.push(Difference::Added {
+                chunk_type: chunk_type.to_string(),
+                occurrence: *occurrence,
+                index,
+                length: new_chunks[index].length(),
+            });
+        }
+    }
+
+    let new_order: Vec<usize> = common.iter().map(|&(_, new_index)| new_index).collect();
+    let in_order = longest_increasing(&new_order);
+    for (&(from, to), in_order) in common.iter().zip(in_order) {
+        if !in_order {
+            let (chunk_type, occurrence) = &old_keys[from];
+            differences.push(Difference::Moved {
+                chunk_type: chunk_type.to_string(),
+                occurrence: *occurrence,
+                from,
+                to,
+            });
+        }
+    }
+    differences
+}
+
+/// The type of each chunk together with how many chunks of that type come
+/// before it
+fn keys(png: &Png) -> Vec<(ChunkType, usize)> {
+    let mut seen: HashMap<&ChunkType, usize> = HashMap::new();
+    png.chunks()
+        .map(|chunk| {
+            let count = seen.entry(chunk.chunk_type()).or_default();
+            *count += 1;
+            (chunk.chunk_type().clone(), *count - 1)
+        })
+        .collect()
+}
+
+/// Marks the values that make up a longest strictly increasing subsequence
+/// of `values`, found by patience sorting
+fn longest_increasing(values: &[usize]) -> Vec<bool> {
+    // tails[k] is the position of the smallest value ending an increasing
+    // run of length k + 1, previous links each position to the one before it
+    let mut tails: Vec<usize> = Vec::new();
+    let mut previous = vec![None; values.len()];
+    for (position, &value) in values.iter().enumerate() {
+        let length = tails.partition_point(|&tail| values[tail] < value);
+        previous[position] = length.checked_sub(1).map(|k| tails[k]);
+        match tails.get_mut(length) {
+            Some(tail) => *tail = position,
+            None => tails.push(position),
+        }
+    }
+
+    let mut kept = vec![false; values.len()];
+    let mut position = tails.last().copied();
+    while let Some(current) = position {
+        kept[current] = true;
+        position = previous[current];
+    }
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::tests::testing_png_bytes;
+
+    fn testing_png() -> Png {
+        Png::try_from(testing_png_bytes().as_slice()).unwrap()
+    }
+
+    fn text(data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::tEXt, data.to_vec())
+    }
+
+    #[test]
+    fn test_identical_pngs() {
+        assert!(diff(&testing_png(), &testing_png()).is_empty());
+    }
+
+    #[test]
+    fn test_added_removed_and_changed() {
+        let mut old = testing_png();
+        old.insert_chunk(text(b"a\0kept"));
+        old.insert_chunk(text(b"b\0dropped"));
+        let mut new = testing_png();
+        new.insert_chunk(text(b"a\0edited"));
+        new.insert_chunk(Chunk::new(ChunkType::tIME, vec![0; 7]));
+
+        let differences = diff(&old, &new);
+        assert_eq!(differences.len(), 3);
+        assert!(matches!(
+            &differences[0],
+            Difference::Changed { chunk_type, occurrence: 0, old_length: 6, new_length: 8, .. }
+                if chunk_type == "tEXt"
+        ));
+        assert_eq!(
+            differences[1],
+            Difference::Removed {
+                chunk_type: "tEXt".to_string(),
+                occurrence: 1,
+                index: 3,
+                length: 9
+            }
+        );
+        assert_eq!(differences[2].to_string(), "+ tIME #0 at 3 (7 bytes)");
+    }
+
+    #[test]
+    fn test_moved() {
+        let mut old = testing_png();
+        old.insert_chunk(Chunk::new(ChunkType::tIME, vec![0; 7]));
+        old.insert_chunk(text(b"a\0text"));
+        let mut new = testing_png();
+        new.insert_chunk_at(0, text(b"a\0text")).unwrap();
+        // shifted by the insertion above, but not out of order
+        new.insert_chunk(Chunk::new(ChunkType::tIME, vec![0; 7]));
+
+        assert_eq!(
+            diff(&old, &new),
+            [Difference::Moved {
+                chunk_type: "tEXt".to_string(),
+                occurrence: 0,
+                from: 3,
+                to: 0
+            }]
+        );
+    }
+
+    #[test]
+    fn test_longest_increasing() {
+        assert_eq!(
+            longest_increasing(&[3, 0, 1, 4, 2]),
+            [false, true, true, false, true]
+        );
+        assert_eq!(longest_increasing(&[]), Vec::<bool>::new());
+        assert_eq!(
+            longest_increasing(&[2, 1, 0])
+                .iter()
+                .filter(|&&k| k)
+                .count(),
+            1
+        );
+    }
+}
@@ -0,0 +1,33 @@
+use crate::{compress, crypto, fec, signing, split};
+
+/// Whether `data` is wrapped in one of the envelopes picmes writes: error
+/// correction, a signature or HMAC tag, encryption, compression or a split
+/// manifest or part. A plain payload looks like any other chunk, so it can
+/// only be told apart by its type
+pub fn is_envelope(data: &[u8]) -> bool {
+    fec::is_protected(data)
+        || signing::is_signed(data)
+        || signing::is_tagged(data)
+        || crypto::is_encrypted(data)
+        || crypto::is_age_encrypted(data)
+        || compress::is_compressed(data)
+        || split::is_manifest(data)
+        || split::is_part(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compress::Compressor;
+
+    #[test]
+    fn test_is_envelope() {
+        let compressed = compress::compress(b"hidden", Compressor::default()).unwrap();
+        assert!(is_envelope(&compressed));
+        for part in split::split(&[7; 100], 40) {
+            assert!(is_envelope(&part));
+        }
+        assert!(!is_envelope(b"hidden"));
+        assert!(!is_envelope(b""));
+    }
+}
@@ -0,0 +1,299 @@
+use thiserror::Error;
+
+#[cfg(feature = "ffi")]
+use crate::ffi::FfiError;
+#[cfg(feature = "keyring")]
+use crate::keys::KeyError;
+#[cfg(feature = "qr")]
+use crate::qr::QrError;
+#[cfg(feature = "cloud")]
+use crate::storage::StorageError;
+#[cfg(feature = "watch")]
+use crate::watch::WatchError;
+#[cfg(feature = "std")]
+use crate::{
+    apng::ApngError, bmp::BmpError, carrier::CarrierError, compress::CompressError,
+    crypto::CryptoError, exif::ExifError, fec::FecError, gif::GifError, history::HistoryError,
+    ico::IcoError, ihdr::IhdrError, image_data::ImageDataError, jpeg::JpegError,
+    metadata::MetadataError, png::PngError, repair::RepairError, seal::SealError,
+    signing::SigningError, split::SplitError, stego::StegoError, text_chunk::TextError,
+    tiff::TiffError, time_chunk::TimeError, webp::WebpError,
+};
+use crate::{chunk::ChunkError, chunk_type::ChunkTypeError};
+#[cfg(feature = "cli")]
+use crate::{commands::CommandError, inputs::InputError, passphrase::PassphraseError};
+
+/// Every way picmes can fail. Each module has an error type of its own,
+/// which this wraps without changing its message
+#[derive(Debug, Error)]
+pub enum PicmesError {
+    #[error(transparent)]
+    Chunk(#[from] ChunkError),
+    #[error(transparent)]
+    ChunkType(#[from] ChunkTypeError),
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    Png(#[from] PngError),
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    Ihdr(#[from] IhdrError),
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    Apng(#[from] ApngError),
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    Time(#[from] TimeError),
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    Text(#[from] TextError),
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    Metadata(#[from] MetadataError),
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    Exif(#[from] ExifError),
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    ImageData(#[from] ImageDataError),
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    Repair(#[from] RepairError),
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    History(#[from] HistoryError),
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    Carrier(#[from] CarrierError),
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    Jpeg(#[from] JpegError),
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    Gif(#[from] GifError),
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    Webp(#[from] WebpError),
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    Bmp(#[from] BmpError),
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    Tiff(#[from] TiffError),
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    Ico(#[from] IcoError),
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    Crypto(#[from] CryptoError),
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    Signing(#[from] SigningError),
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    Seal(#[from] SealError),
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    Split(#[from] SplitError),
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    Fec(#[from] FecError),
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    Compress(#[from] CompressError),
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    Stego(#[from] StegoError),
+    #[cfg(feature = "cli")]
+    #[error(transparent)]
+    Command(#[from] CommandError),
+    #[cfg(feature = "cli")]
+    #[error(transparent)]
+    Input(#[from] InputError),
+    #[cfg(feature = "cli")]
+    #[error(transparent)]
+    Passphrase(#[from] PassphraseError),
+    #[cfg(feature = "cloud")]
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+    #[cfg(feature = "qr")]
+    #[error(transparent)]
+    Qr(#[from] QrError),
+    #[cfg(feature = "watch")]
+    #[error(transparent)]
+    Watch(#[from] WatchError),
+    #[cfg(feature = "ffi")]
+    #[error(transparent)]
+    Ffi(#[from] FfiError),
+    #[cfg(feature = "keyring")]
+    #[error(transparent)]
+    Key(#[from] KeyError),
+    #[cfg(feature = "keyring")]
+    #[error(transparent)]
+    Keyring(#[from] keyring::Error),
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Slice(#[from] core::array::TryFromSliceError),
+    #[error(transparent)]
+    Utf8(#[from] core::str::Utf8Error),
+    #[error(transparent)]
+    FromUtf8(#[from] alloc::string::FromUtf8Error),
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    Clock(#[from] std::time::SystemTimeError),
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    Persist(#[from] tempfile::PersistError),
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    AgeEncrypt(#[from] age::EncryptError),
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    AgeDecrypt(#[from] age::DecryptError),
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    HmacKey(#[from] hmac::digest::InvalidLength),
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    ErasureCoding(#[from] reed_solomon_erasure::Error),
+    #[cfg(feature = "cli")]
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[cfg(feature = "cli")]
+    #[error(transparent)]
+    Csv(#[from] csv::Error),
+    #[cfg(feature = "cli")]
+    #[error(transparent)]
+    Yaml(#[from] serde_yaml::Error),
+    #[cfg(feature = "net")]
+    #[error(transparent)]
+    Http(#[from] ureq::Error),
+    #[cfg(feature = "clipboard")]
+    #[error(transparent)]
+    Clipboard(#[from] arboard::Error),
+    #[cfg(feature = "cloud")]
+    #[error(transparent)]
+    ObjectStore(#[from] object_store::Error),
+    #[cfg(feature = "watch")]
+    #[error(transparent)]
+    Notify(#[from] notify::Error),
+    #[cfg(feature = "cli")]
+    #[error(transparent)]
+    Args(#[from] clap::Error),
+    #[cfg(feature = "cli")]
+    #[error(transparent)]
+    Pattern(#[from] glob::PatternError),
+    #[cfg(feature = "cli")]
+    #[error(transparent)]
+    Glob(#[from] glob::GlobError),
+}
+
+/// The kinds of failure scripts can tell apart by the exit code of picmes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    /// Anything not covered below
+    Other = 1,
+    /// Invalid arguments, the code clap exits with too
+    Usage = 2,
+    /// A file, object, URL or key that doesn't exist
+    NotFound = 3,
+    /// A chunk whose CRC doesn't match its contents
+    Checksum = 4,
+    /// A passphrase or key that doesn't decrypt the payload
+    WrongPassphrase = 5,
+    /// A file without the chunk or payload asked for
+    NoPayload = 6,
+}
+
+impl Failure {
+    /// The code the process exits with
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+}
+
+impl PicmesError {
+    /// The kind of failure this is
+    pub fn failure(&self) -> Failure {
+        match self {
+            Self::Chunk(ChunkError::InvalidCheckSum(..)) => Failure::Checksum,
+            #[cfg(feature = "std")]
+            Self::Io(e) if e.kind() == std::io::ErrorKind::NotFound => Failure::NotFound,
+            #[cfg(feature = "std")]
+            Self::Png(PngError::UnknownChunkType)
+            | Self::Exif(ExifError::Missing)
+            | Self::Seal(SealError::NotSealed)
+            | Self::Split(SplitError::MissingManifest)
+            | Self::Stego(StegoError::NoPayload) => Failure::NoPayload,
+            #[cfg(feature = "std")]
+            Self::Crypto(CryptoError::DecryptionFailed)
+            | Self::AgeDecrypt(age::DecryptError::DecryptionFailed)
+            | Self::AgeDecrypt(age::DecryptError::NoMatchingKeys) => Failure::WrongPassphrase,
+            #[cfg(feature = "cli")]
+            Self::Command(
+                CommandError::BatchFailed { failure, .. }
+                | CommandError::VerificationFailed { failure, .. },
+            ) => *failure,
+            #[cfg(feature = "cli")]
+            Self::Command(CommandError::NoChunkAt { .. } | CommandError::NothingToMigrate) => {
+                Failure::NoPayload
+            }
+            #[cfg(feature = "cli")]
+            Self::Input(InputError::NoMatches(_)) => Failure::NotFound,
+            #[cfg(feature = "cli")]
+            Self::Args(_) => Failure::Usage,
+            #[cfg(feature = "net")]
+            Self::Http(ureq::Error::StatusCode(404)) => Failure::NotFound,
+            #[cfg(feature = "cloud")]
+            Self::ObjectStore(object_store::Error::NotFound { .. }) => Failure::NotFound,
+            #[cfg(feature = "keyring")]
+            Self::Keyring(keyring::Error::NoEntry) => Failure::NotFound,
+            _ => Failure::Other,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ChunkType;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_match_on_source() {
+        let error = ChunkType::from_str("ab1D").unwrap_err();
+        assert!(matches!(
+            error,
+            PicmesError::ChunkType(ChunkTypeError::InvalidCharacter)
+        ));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_match_on_png_source() {
+        let error = crate::Png::try_from([0u8; 4].as_slice()).unwrap_err();
+        assert!(matches!(error, PicmesError::Png(PngError::TooSmall)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_failure() {
+        let error: PicmesError = ChunkError::InvalidCheckSum(1, 2).into();
+        assert_eq!(error.failure().code(), 4);
+        let error: PicmesError = std::fs::read("/no/such/file.png").unwrap_err().into();
+        assert_eq!(error.failure(), Failure::NotFound);
+        let error: PicmesError = CryptoError::DecryptionFailed.into();
+        assert_eq!(error.failure(), Failure::WrongPassphrase);
+        let error: PicmesError = PngError::UnknownChunkType.into();
+        assert_eq!(error.failure(), Failure::NoPayload);
+        let error: PicmesError = PngError::TooSmall.into();
+        assert_eq!(error.failure(), Failure::Other);
+    }
+
+    #[test]
+    fn test_message_is_unchanged() {
+        let error: PicmesError = ChunkError::InvalidChunkType.into();
+        assert_eq!(error.to_string(), ChunkError::InvalidChunkType.to_string());
+    }
+}
@@ -0,0 +1,284 @@
+use std::fmt::Display;
+
+use crate::Result;
+
+#[derive(Debug)]
+pub enum ExifError {
+    Missing,
+    InvalidHeader,
+    Truncated(usize),
+}
+
+impl std::error::Error for ExifError {}
+
+impl Display for ExifError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Missing => write!(f, "The png has no eXIf chunk"),
+            Self::InvalidHeader => write!(
+                f,
+                "EXIF data must start with a TIFF header (II*\\0 or MM\\0*)"
+            ),
+            Self::Truncated(offset) => {
+                write!(f, "EXIF data ends before offset {:#x}", offset)
+            }
+        }
+    }
+}
+
+/// Some writers keep the JPEG APP1 marker in front of the TIFF header
+const APP1_PREFIX: &[u8] = b"Exif\0\0";
+
+const TAG_MAKE: u16 = 0x010f;
+const TAG_MODEL: u16 = 0x0110;
+const TAG_ORIENTATION: u16 = 0x0112;
+const TAG_SOFTWARE: u16 = 0x0131;
+const TAG_DATE_TIME: u16 = 0x0132;
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_DATE_TIME_ORIGINAL: u16 = 0x9003;
+
+const TYPE_ASCII: u16 = 2;
+const TYPE_SHORT: u16 = 3;
+const TYPE_LONG: u16 = 4;
+
+const ENTRY_LENGTH: usize = 12;
+
+/// The commonly used tags of an EXIF blob. Anything else in the blob is
+/// left alone
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ExifTags {
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub orientation: Option<u16>,
+    pub software: Option<String>,
+    pub date_time: Option<String>,
+    pub date_time_original: Option<String>,
+}
+
+impl ExifTags {
+    /// Reads the tags from IFD0 and the Exif sub-IFD of a TIFF structured
+    /// blob, as stored in the eXIf chunk
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let tiff = Tiff::new(data)?;
+        let mut tags = Self::default();
+        let mut exif_ifd = None;
+        for entry in tiff.entries(tiff.u32(4)? as usize)? {
+            match tiff.u16(entry)? {
+                TAG_MAKE => tags.make = tiff.ascii(entry)?,
+                TAG_MODEL => tags.model = tiff.ascii(entry)?,
+                TAG_ORIENTATION => tags.orientation = tiff.short(entry)?,
+                TAG_SOFTWARE => tags.software = tiff.ascii(entry)?,
+                TAG_DATE_TIME => tags.date_time = tiff.ascii(entry)?,
+                TAG_EXIF_IFD => exif_ifd = tiff.long(entry)?,
+                _ => {}
+            }
+        }
+
+        if let Some(offset) = exif_ifd {
+            for entry in tiff.entries(offset as usize)? {
+                if tiff.u16(entry)? == TAG_DATE_TIME_ORIGINAL {
+                    tags.date_time_original = tiff.ascii(entry)?;
+                }
+            }
+        }
+        Ok(tags)
+    }
+}
+
+/// Checks that `data` starts with a TIFF header, allowing the APP1 prefix
+pub fn validate(data: &[u8]) -> Result<()> {
+    Tiff::new(data).map(|_| ())
+}
+
+impl Display for ExifTags {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let orientation = self.orientation.map(|o| o.to_string());
+        let fields = [
+            ("Make", &self.make),
+            ("Model", &self.model),
+            ("Orientation", &orientation),
+            ("Software", &self.software),
+            ("DateTime", &self.date_time),
+            ("DateTimeOriginal", &self.date_time_original),
+        ];
+        let mut first = true;
+        for (name, value) in fields {
+            if let Some(value) = value {
+                if !first {
+                    writeln!(f)?;
+                }
+                write!(f, "{}: {}", name, value)?;
+                first = false;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Bounds checked reads from a TIFF structure in either byte order. Offsets
+/// are relative to the start of the TIFF header
+struct Tiff<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> Tiff<'a> {
+    fn new(data: &'a [u8]) -> Result<Self> {
+        let data = data.strip_prefix(APP1_PREFIX).unwrap_or(data);
+        let big_endian = match data.get(..4) {
+            Some(b"II*\0") => false,
+            Some(b"MM\0*") => true,
+            _ => return Err(ExifError::InvalidHeader.into()),
+        };
+        Ok(Self { data, big_endian })
+    }
+
+    fn bytes(&self, offset: usize, length: usize) -> Result<&'a [u8]> {
+        let end = offset
+            .checked_add(length)
+            .ok_or(ExifError::Truncated(offset))?;
+        Ok(self
+            .data
+            .get(offset..end)
+            .ok_or(ExifError::Truncated(end))?)
+    }
+
+    fn u16(&self, offset: usize) -> Result<u16> {
+        let bytes = self.bytes(offset, 2)?.try_into()?;
+        Ok(match self.big_endian {
+            true => u16::from_be_bytes(bytes),
+            false => u16::from_le_bytes(bytes),
+        })
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32> {
+        let bytes = self.bytes(offset, 4)?.try_into()?;
+        Ok(match self.big_endian {
+            true => u32::from_be_bytes(bytes),
+            false => u32::from_le_bytes(bytes),
+        })
+    }
+
+    /// Offsets of the entries of the IFD at `offset`
+    fn entries(&self, offset: usize) -> Result<Vec<usize>> {
+        let count = self.u16(offset)? as usize;
+        self.bytes(offset + 2, count * ENTRY_LENGTH)?;
+        Ok((0..count).map(|i| offset + 2 + i * ENTRY_LENGTH).collect())
+    }
+
+    fn field_type(&self, entry: usize) -> Result<u16> {
+        self.u16(entry + 2)
+    }
+
+    fn ascii(&self, entry: usize) -> Result<Option<String>> {
+        if self.field_type(entry)? != TYPE_ASCII {
+            return Ok(None);
+        }
+        let count = self.u32(entry + 4)? as usize;
+        // values of up to four bytes are stored in place of the offset
+        let offset = match count {
+            0..=4 => entry + 8,
+            _ => self.u32(entry + 8)? as usize,
+        };
+        let text = self.bytes(offset, count)?;
+        let text = text.split(|&b| b == 0).next().unwrap_or_default();
+        Ok(Some(String::from_utf8_lossy(text).trim_end().to_string()))
+    }
+
+    fn short(&self, entry: usize) -> Result<Option<u16>> {
+        match self.field_type(entry)? {
+            TYPE_SHORT => Ok(Some(self.u16(entry + 8)?)),
+            _ => Ok(None),
+        }
+    }
+
+    fn long(&self, entry: usize) -> Result<Option<u32>> {
+        match self.field_type(entry)? {
+            TYPE_LONG => Ok(Some(self.u32(entry + 8)?)),
+            _ => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A little endian EXIF blob with a make, an orientation and an Exif
+    /// sub-IFD holding DateTimeOriginal
+    pub(crate) fn testing_exif() -> Vec<u8> {
+        let mut data = b"II*\0".to_vec();
+        data.extend(8u32.to_le_bytes());
+        // IFD0 at 8 with three entries, followed by the next IFD offset
+        data.extend(3u16.to_le_bytes());
+        let entry = |tag: u16, field_type: u16, count: u32, value: u32| {
+            [
+                &tag.to_le_bytes()[..],
+                &field_type.to_le_bytes(),
+                &count.to_le_bytes(),
+                &value.to_le_bytes(),
+            ]
+            .concat()
+        };
+        let ifd0_end = 8 + 2 + 3 * 12 + 4;
+        let make_offset = ifd0_end as u32;
+        let exif_ifd_offset = make_offset + 6;
+        data.extend(entry(TAG_MAKE, TYPE_ASCII, 6, make_offset));
+        data.extend(entry(TAG_ORIENTATION, TYPE_SHORT, 1, 6));
+        data.extend(entry(TAG_EXIF_IFD, TYPE_LONG, 1, exif_ifd_offset));
+        data.extend(0u32.to_le_bytes());
+        data.extend(b"Ferro\0");
+
+        let date_offset = exif_ifd_offset + 2 + 12 + 4;
+        data.extend(1u16.to_le_bytes());
+        data.extend(entry(TAG_DATE_TIME_ORIGINAL, TYPE_ASCII, 20, date_offset));
+        data.extend(0u32.to_le_bytes());
+        data.extend(b"2024:02:29 13:05:09\0");
+        data
+    }
+
+    #[test]
+    fn test_parse_exif() {
+        let tags = ExifTags::parse(&testing_exif()).unwrap();
+        assert_eq!(tags.make.as_deref(), Some("Ferro"));
+        assert_eq!(tags.orientation, Some(6));
+        assert_eq!(tags.model, None);
+        assert_eq!(
+            tags.date_time_original.as_deref(),
+            Some("2024:02:29 13:05:09")
+        );
+        assert_eq!(
+            tags.to_string(),
+            "Make: Ferro\nOrientation: 6\nDateTimeOriginal: 2024:02:29 13:05:09"
+        );
+
+        let prefixed = [APP1_PREFIX, &testing_exif()].concat();
+        assert_eq!(ExifTags::parse(&prefixed).unwrap(), tags);
+    }
+
+    #[test]
+    fn test_parse_big_endian_exif() {
+        let mut data = b"MM\0*".to_vec();
+        data.extend(8u32.to_be_bytes());
+        data.extend(1u16.to_be_bytes());
+        data.extend(TAG_MODEL.to_be_bytes());
+        data.extend(TYPE_ASCII.to_be_bytes());
+        data.extend(3u32.to_be_bytes());
+        data.extend(b"X1\0\0");
+        data.extend(0u32.to_be_bytes());
+
+        let tags = ExifTags::parse(&data).unwrap();
+        assert_eq!(tags.model.as_deref(), Some("X1"));
+    }
+
+    #[test]
+    fn test_invalid_exif() {
+        assert!(ExifTags::parse(b"not exif").is_err());
+        assert!(validate(b"II*").is_err());
+
+        let exif = testing_exif();
+        for length in [8, 20, exif.len() - 10] {
+            assert!(ExifTags::parse(&exif[..length]).is_err(), "{}", length);
+        }
+    }
+}
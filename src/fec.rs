@@ -0,0 +1,234 @@
+use std::fmt::Display;
+
+use reed_solomon_erasure::galois_8::ReedSolomon;
+
+use crate::{chunk, Result};
+
+#[derive(Debug)]
+pub enum FecError {
+    InvalidParity(usize),
+    InvalidHeader,
+    TooDamaged { damaged: usize, parity: usize },
+}
+
+impl std::error::Error for FecError {}
+
+impl Display for FecError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidParity(parity) => write!(
+                f,
+                "Error correction needs between 1 and {} parity shards, not {}",
+                MAX_PARITY_SHARDS, parity
+            ),
+            Self::InvalidHeader => write!(f, "Both error correction headers are damaged"),
+            Self::TooDamaged { damaged, parity } => write!(
+                f,
+                "{} shards are damaged but only {} can be rebuilt",
+                damaged, parity
+            ),
+        }
+    }
+}
+
+/// Starts the header of an error corrected payload. The last byte is the
+/// format version
+const MAGIC: &[u8] = b"pmF\x01";
+/// `MAGIC | data shards | parity shards | payload length | CRC-32 of these`
+const HEADER_LENGTH: usize = MAGIC.len() + 1 + 1 + 4 + 4;
+/// Each shard is stored after the CRC-32 that tells whether it is intact
+const SHARD_CRC_LENGTH: usize = 4;
+/// The payload is cut into at most this many data shards
+const DATA_SHARDS: usize = 16;
+pub const MAX_PARITY_SHARDS: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Header {
+    data_shards: usize,
+    parity_shards: usize,
+    length: usize,
+}
+
+impl Header {
+    fn for_payload(length: usize, parity_shards: usize, min_shard_length: usize) -> Self {
+        Self {
+            data_shards: (length / min_shard_length.max(1)).clamp(1, DATA_SHARDS),
+            parity_shards,
+            length,
+        }
+    }
+
+    fn shard_length(&self) -> usize {
+        self.length.div_ceil(self.data_shards).max(1)
+    }
+
+    fn shards(&self) -> usize {
+        self.data_shards + self.parity_shards
+    }
+
+    /// The length of the whole protected payload, headers included
+    fn protected_length(&self) -> usize {
+        2 * HEADER_LENGTH + self.shards() * (SHARD_CRC_LENGTH + self.shard_length())
+    }
+
+    fn to_bytes(self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(self.data_shards as u8);
+        bytes.push(self.parity_shards as u8);
+        bytes.extend((self.length as u32).to_be_bytes());
+        bytes.extend(chunk::checksum(&bytes).to_be_bytes());
+        bytes
+    }
+
+    fn parse(bytes: &[u8]) -> Option<Self> {
+        let (fields, crc) = bytes.get(..HEADER_LENGTH)?.split_at(HEADER_LENGTH - 4);
+        if !fields.starts_with(MAGIC) || chunk::checksum(fields).to_be_bytes() != crc {
+            return None;
+        }
+        let header = Self {
+            data_shards: fields[MAGIC.len()] as usize,
+            parity_shards: fields[MAGIC.len() + 1] as usize,
+            length: u32::from_be_bytes(fields[MAGIC.len() + 2..].try_into().ok()?) as usize,
+        };
+        (header.data_shards > 0 && header.parity_shards > 0).then_some(header)
+    }
+}
+
+/// Adds `parity_shards` Reed-Solomon parity shards to `payload`. Any shards
+/// up to that many may later be damaged or lost and still be rebuilt. The
+/// header is stored at both ends so losing either end is survivable too.
+/// Shards are made at least `min_shard_length` long, so that losing a run of
+/// that many bytes damages no more than two of them
+pub fn protect(payload: &[u8], parity_shards: usize, min_shard_length: usize) -> Result<Vec<u8>> {
+    if !(1..=MAX_PARITY_SHARDS).contains(&parity_shards) {
+        return Err(FecError::InvalidParity(parity_shards).into());
+    }
+    let header = Header::for_payload(payload.len(), parity_shards, min_shard_length);
+    let shard_length = header.shard_length();
+    let mut shards: Vec<Vec<u8>> = (0..header.shards())
+        .map(|index| {
+            let start = (index * shard_length).min(payload.len());
+            let end = (start + shard_length).min(payload.len());
+            let mut shard = payload[start..end].to_vec();
+            shard.resize(shard_length, 0);
+            shard
+        })
+        .collect();
+    ReedSolomon::new(header.data_shards, header.parity_shards)?.encode(&mut shards)?;
+
+    let mut protected = header.to_bytes();
+    for shard in &shards {
+        protected.extend(chunk::checksum(shard).to_be_bytes());
+        protected.extend(shard);
+    }
+    protected.extend(header.to_bytes());
+    Ok(protected)
+}
+
+/// The most damaged bytes `recover` can restore next to `intact` ones, reached
+/// with a single data shard and every other shard parity
+pub fn max_recoverable(intact: usize) -> usize {
+    intact.saturating_mul(MAX_PARITY_SHARDS)
+}
+
+/// Whether `data` has an intact error correction header at either end
+pub fn is_protected(data: &[u8]) -> bool {
+    header(data).is_some()
+}
+
+fn header(data: &[u8]) -> Option<Header> {
+    Header::parse(data).or_else(|| Header::parse(&data[data.len().checked_sub(HEADER_LENGTH)?..]))
+}
+
+/// Rebuilds the payload `protect` wrapped, returning it and the number of
+/// damaged or missing shards that had to be corrected
+pub fn recover(data: &[u8]) -> Result<(Vec<u8>, usize)> {
+    let header = header(data).ok_or(FecError::InvalidHeader)?;
+    let record_length = SHARD_CRC_LENGTH + header.shard_length();
+    // without the leading header the shards are found from the end, so data
+    // cut off at the front only loses the shards it covered
+    let first = match Header::parse(data) {
+        Some(_) => HEADER_LENGTH as isize,
+        None => (data.len() + HEADER_LENGTH) as isize - header.protected_length() as isize,
+    };
+
+    let mut shards: Vec<Option<Vec<u8>>> = (0..header.shards())
+        .map(|index| {
+            let start = usize::try_from(first + (index * record_length) as isize).ok()?;
+            let record = data.get(start..start + record_length)?;
+            let (crc, shard) = record.split_at(SHARD_CRC_LENGTH);
+            (chunk::checksum(shard).to_be_bytes() == crc).then(|| shard.to_vec())
+        })
+        .collect();
+    let damaged = shards.iter().filter(|shard| shard.is_none()).count();
+    if damaged > header.parity_shards {
+        return Err(FecError::TooDamaged {
+            damaged,
+            parity: header.parity_shards,
+        }
+        .into());
+    }
+    ReedSolomon::new(header.data_shards, header.parity_shards)?.reconstruct_data(&mut shards)?;
+
+    let mut payload: Vec<u8> = shards
+        .into_iter()
+        .take(header.data_shards)
+        .flat_map(|shard| shard.expect("reconstructed"))
+        .collect();
+    payload.truncate(header.length);
+    Ok((payload, damaged))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_protect_and_recover() {
+        let payload: Vec<u8> = (0..=255).cycle().take(1000).collect();
+        let protected = protect(&payload, 4, 1).unwrap();
+        assert!(is_protected(&protected));
+        assert_eq!(
+            protected.len(),
+            Header::for_payload(1000, 4, 1).protected_length()
+        );
+        assert_eq!(recover(&protected).unwrap(), (payload.clone(), 0));
+
+        let mut flipped = protected.clone();
+        flipped[HEADER_LENGTH + 10] ^= 0x40;
+        flipped[500] ^= 1;
+        assert_eq!(recover(&flipped).unwrap(), (payload.clone(), 2));
+
+        // losing the front takes the leading header with it
+        let mut zeroed = protected.clone();
+        zeroed[..200].fill(0);
+        assert!(recover(&zeroed).unwrap().1 > 0);
+        assert_eq!(recover(&zeroed).unwrap().0, payload);
+        assert_eq!(recover(&protected[150..]).unwrap().0, payload);
+
+        // a lost run of min_shard_length bytes spans at most two shards
+        let protected = protect(&payload, 2, 300).unwrap();
+        let mut lost = protected.clone();
+        lost[400..700].fill(0);
+        assert_eq!(recover(&lost).unwrap(), (payload, 2));
+    }
+
+    #[test]
+    fn test_small_payloads() {
+        for payload in [&b""[..], b"x", b"hi there"] {
+            let protected = protect(payload, 2, 1).unwrap();
+            assert_eq!(recover(&protected).unwrap().0, payload);
+        }
+        assert!(protect(b"hi", 0, 1).is_err());
+        assert!(!is_protected(b"plain text"));
+    }
+
+    #[test]
+    fn test_too_much_damage() {
+        let protected = protect(b"not enough parity to go round", 1, 1).unwrap();
+        let mut damaged = protected.clone();
+        damaged[HEADER_LENGTH] ^= 1;
+        damaged[HEADER_LENGTH + SHARD_CRC_LENGTH + 2] ^= 1;
+        assert!(recover(&damaged).is_err());
+    }
+}
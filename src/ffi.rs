@@ -0,0 +1,382 @@
+use std::{
+    cell::RefCell,
+    ffi::{c_char, CStr, CString},
+    fmt::Display,
+    ptr, slice,
+    str::FromStr,
+};
+
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    png::{Png, PngError},
+    Result,
+};
+
+#[derive(Debug)]
+pub enum FfiError {
+    NullPointer(&'static str),
+    NoChunkAt { index: usize, count: usize },
+}
+
+impl std::error::Error for FfiError {}
+
+impl Display for FfiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NullPointer(name) => write!(f, "{} is a null pointer", name),
+            Self::NoChunkAt { index, count } => {
+                write!(f, "No chunk at index {}, the png has {}", index, count)
+            }
+        }
+    }
+}
+
+/// A png opened through the C API. It belongs to the caller until passed to
+/// `picmes_free`
+pub struct PicmesPng(Png);
+
+/// A chunk as `picmes_chunk_info` describes it
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct PicmesChunkInfo {
+    /// The four letters of the chunk type and a terminating nul
+    pub chunk_type: [c_char; 5],
+    pub length: usize,
+    pub crc: u32,
+}
+
+/// What the functions that can fail return. After an error
+/// `picmes_last_error` says what went wrong
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PicmesStatus {
+    Ok = 0,
+    Error = -1,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Keeps the message of `error` for `picmes_last_error`
+fn set_last_error(error: impl Display) {
+    // a message can't hold a nul, so any would cut it short
+    let message = error.to_string().replace('\0', "");
+    LAST_ERROR.with(|last| *last.borrow_mut() = CString::new(message).ok());
+}
+
+fn status(result: Result<()>) -> PicmesStatus {
+    match result {
+        Ok(()) => PicmesStatus::Ok,
+        Err(e) => {
+            set_last_error(e);
+            PicmesStatus::Error
+        }
+    }
+}
+
+/// Hands `bytes` over to C, to be freed with `picmes_free_bytes`
+fn into_raw_bytes(bytes: Vec<u8>, len: *mut usize) -> *mut u8 {
+    let bytes = bytes.into_boxed_slice();
+    if !len.is_null() {
+        // SAFETY: checked for null, the caller vouches for the rest
+        unsafe { *len = bytes.len() };
+    }
+    Box::into_raw(bytes).cast()
+}
+
+/// # Safety
+/// `pointer` is null or points to a live value of `T`
+unsafe fn reference<'a, T>(pointer: *const T, name: &'static str) -> Result<&'a T> {
+    unsafe { pointer.as_ref() }.ok_or_else(|| FfiError::NullPointer(name).into())
+}
+
+/// # Safety
+/// `pointer` is null or points to a live value of `T` nothing else uses
+unsafe fn mutable<'a, T>(pointer: *mut T, name: &'static str) -> Result<&'a mut T> {
+    unsafe { pointer.as_mut() }.ok_or_else(|| FfiError::NullPointer(name).into())
+}
+
+/// # Safety
+/// `string` is null or a nul terminated string
+unsafe fn string<'a>(string: *const c_char, name: &'static str) -> Result<&'a str> {
+    if string.is_null() {
+        return Err(FfiError::NullPointer(name).into());
+    }
+    Ok(unsafe { CStr::from_ptr(string) }.to_str()?)
+}
+
+/// # Safety
+/// `data` is null only when `len` is 0, otherwise it points to `len` bytes
+unsafe fn bytes<'a>(data: *const u8, len: usize, name: &'static str) -> Result<&'a [u8]> {
+    match (data.is_null(), len) {
+        (true, 0) => Ok(&[]),
+        (true, _) => Err(FfiError::NullPointer(name).into()),
+        (false, _) => Ok(unsafe { slice::from_raw_parts(data, len) }),
+    }
+}
+
+fn open(result: Result<Png>) -> *mut PicmesPng {
+    match result {
+        Ok(png) => Box::into_raw(Box::new(PicmesPng(png))),
+        Err(e) => {
+            set_last_error(e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// The message of the last error on this thread, or null if there was none.
+/// The string stays valid until the next call that fails
+#[no_mangle]
+pub extern "C" fn picmes_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |e| e.as_ptr()))
+}
+
+/// Reads and parses the png file at `path`, returning null on failure
+///
+/// # Safety
+/// `path` is a nul terminated UTF-8 string
+#[no_mangle]
+pub unsafe extern "C" fn picmes_open(path: *const c_char) -> *mut PicmesPng {
+    open(unsafe { string(path, "path") }.and_then(Png::from_file))
+}
+
+/// Parses the `len` bytes at `data` as a png, returning null on failure
+///
+/// # Safety
+/// `data` points to `len` readable bytes
+#[no_mangle]
+pub unsafe extern "C" fn picmes_parse(data: *const u8, len: usize) -> *mut PicmesPng {
+    open(unsafe { bytes(data, len, "data") }.and_then(Png::try_from))
+}
+
+/// Frees a png from `picmes_open` or `picmes_parse`. Null is ignored
+///
+/// # Safety
+/// `png` came from `picmes_open` or `picmes_parse` and isn't used afterwards
+#[no_mangle]
+pub unsafe extern "C" fn picmes_free(png: *mut PicmesPng) {
+    if !png.is_null() {
+        drop(unsafe { Box::from_raw(png) });
+    }
+}
+
+/// Frees bytes from `picmes_extract` or `picmes_to_bytes`. Null is ignored
+///
+/// # Safety
+/// `bytes` and `len` are exactly what one of those functions returned, and
+/// the bytes aren't used afterwards
+#[no_mangle]
+pub unsafe extern "C" fn picmes_free_bytes(bytes: *mut u8, len: usize) {
+    if !bytes.is_null() {
+        drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(bytes, len)) });
+    }
+}
+
+/// The number of chunks in `png`, 0 for null
+///
+/// # Safety
+/// `png` is null or a live png from this API
+#[no_mangle]
+pub unsafe extern "C" fn picmes_chunk_count(png: *const PicmesPng) -> usize {
+    unsafe { png.as_ref() }.map_or(0, |png| png.0.chunks().len())
+}
+
+/// Describes the chunk at `index` in file order
+///
+/// # Safety
+/// `png` is a live png from this API and `info` points to writable memory
+/// for a `PicmesChunkInfo`
+#[no_mangle]
+pub unsafe extern "C" fn picmes_chunk_info(
+    png: *const PicmesPng,
+    index: usize,
+    info: *mut PicmesChunkInfo,
+) -> PicmesStatus {
+    status((|| {
+        let png = &unsafe { reference(png, "png") }?.0;
+        let info = unsafe { mutable(info, "info") }?;
+        let count = png.chunks().len();
+        let chunk = png
+            .chunks()
+            .nth(index)
+            .ok_or(FfiError::NoChunkAt { index, count })?;
+        let mut chunk_type = [0; 5];
+        for (c, byte) in chunk_type.iter_mut().zip(chunk.chunk_type().bytes()) {
+            *c = byte as c_char;
+        }
+        *info = PicmesChunkInfo {
+            chunk_type,
+            length: chunk.length(),
+            crc: chunk.crc(),
+        };
+        Ok(())
+    })())
+}
+
+/// Adds a chunk of `chunk_type` holding the `len` bytes at `data` before
+/// IEND
+///
+/// # Safety
+/// `png` is a live png from this API, `chunk_type` a nul terminated string
+/// and `data` points to `len` readable bytes
+#[no_mangle]
+pub unsafe extern "C" fn picmes_embed(
+    png: *mut PicmesPng,
+    chunk_type: *const c_char,
+    data: *const u8,
+    len: usize,
+) -> PicmesStatus {
+    status((|| {
+        let png = &mut unsafe { mutable(png, "png") }?.0;
+        let chunk_type = ChunkType::from_str(unsafe { string(chunk_type, "chunk_type") }?)?;
+        let data = unsafe { bytes(data, len, "data") }?;
+        png.insert_chunk(Chunk::try_new(chunk_type, data.to_vec())?);
+        Ok(())
+    })())
+}
+
+/// Copies the data of the first chunk of `chunk_type`, storing its length in
+/// `len`. Returns null on failure, free the bytes with `picmes_free_bytes`
+///
+/// # Safety
+/// `png` is a live png from this API, `chunk_type` a nul terminated string
+/// and `len` points to writable memory for a size_t
+#[no_mangle]
+pub unsafe extern "C" fn picmes_extract(
+    png: *const PicmesPng,
+    chunk_type: *const c_char,
+    len: *mut usize,
+) -> *mut u8 {
+    let data = (|| -> Result<Vec<u8>> {
+        let png = &unsafe { reference(png, "png") }?.0;
+        let chunk_type = unsafe { string(chunk_type, "chunk_type") }?;
+        let chunk = png
+            .chunk_by_type(chunk_type)
+            .ok_or(PngError::UnknownChunkType)?;
+        Ok(chunk.data().to_vec())
+    })();
+    match data {
+        Ok(data) => into_raw_bytes(data, len),
+        Err(e) => {
+            set_last_error(e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Serializes `png`, storing the length in `len`. Returns null on failure,
+/// free the bytes with `picmes_free_bytes`
+///
+/// # Safety
+/// `png` is a live png from this API and `len` points to writable memory for
+/// a size_t
+#[no_mangle]
+pub unsafe extern "C" fn picmes_to_bytes(png: *const PicmesPng, len: *mut usize) -> *mut u8 {
+    match unsafe { reference(png, "png") } {
+        Ok(png) => into_raw_bytes(png.0.as_bytes(), len),
+        Err(e) => {
+            set_last_error(e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Writes `png` to the file at `path`, replacing it atomically
+///
+/// # Safety
+/// `png` is a live png from this API and `path` a nul terminated UTF-8
+/// string
+#[no_mangle]
+pub unsafe extern "C" fn picmes_save(png: *const PicmesPng, path: *const c_char) -> PicmesStatus {
+    status((|| {
+        let png = &unsafe { reference(png, "png") }?.0;
+        png.save(unsafe { string(path, "path") }?)
+    })())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::tests::{testing_file, testing_png_bytes};
+
+    fn last_error() -> String {
+        let error = picmes_last_error();
+        assert!(!error.is_null());
+        unsafe { CStr::from_ptr(error) }
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_embed_extract_and_list() {
+        let bytes = testing_png_bytes();
+        unsafe {
+            let png = picmes_parse(bytes.as_ptr(), bytes.len());
+            assert_eq!(picmes_chunk_count(png), 3);
+
+            let status = picmes_embed(png, c"ruSt".as_ptr(), b"from C".as_ptr(), 6);
+            assert_eq!(status, PicmesStatus::Ok);
+            let mut info = PicmesChunkInfo::default();
+            assert_eq!(picmes_chunk_info(png, 2, &mut info), PicmesStatus::Ok);
+            let chunk_type = CStr::from_ptr(info.chunk_type.as_ptr());
+            assert_eq!(chunk_type, c"ruSt");
+            assert_eq!(info.length, 6);
+
+            let mut len = 0;
+            let data = picmes_extract(png, c"ruSt".as_ptr(), &mut len);
+            assert_eq!(slice::from_raw_parts(data, len), b"from C");
+            picmes_free_bytes(data, len);
+
+            let data = picmes_to_bytes(png, &mut len);
+            let reparsed = Png::try_from(slice::from_raw_parts(data, len)).unwrap();
+            assert_eq!(reparsed.chunks().count(), 4);
+            picmes_free_bytes(data, len);
+            picmes_free(png);
+        }
+    }
+
+    #[test]
+    fn test_open_and_save() {
+        let path = testing_file("ffi");
+        let c_path = CString::new(path.to_str().unwrap()).unwrap();
+        unsafe {
+            let png = picmes_open(c_path.as_ptr());
+            assert!(!png.is_null());
+            picmes_embed(png, c"tEXt".as_ptr(), b"a\0b".as_ptr(), 3);
+            assert_eq!(picmes_save(png, c_path.as_ptr()), PicmesStatus::Ok);
+            picmes_free(png);
+        }
+        let png = Png::from_file(&path).unwrap();
+        assert_eq!(png.chunk_by_type("tEXt").unwrap().data(), b"a\0b");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_errors() {
+        unsafe {
+            assert!(picmes_parse(b"not a png".as_ptr(), 9).is_null());
+            assert!(picmes_parse(ptr::null(), 4).is_null());
+            assert_eq!(last_error(), "data is a null pointer");
+
+            let bytes = testing_png_bytes();
+            let png = picmes_parse(bytes.as_ptr(), bytes.len());
+            let mut info = PicmesChunkInfo::default();
+            assert_eq!(picmes_chunk_info(png, 3, &mut info), PicmesStatus::Error);
+            assert_eq!(last_error(), "No chunk at index 3, the png has 3");
+            let status = picmes_embed(png, c"ru5t".as_ptr(), ptr::null(), 0);
+            assert_eq!(status, PicmesStatus::Error);
+            let mut len = 0;
+            assert!(picmes_extract(png, c"ruSt".as_ptr(), &mut len).is_null());
+            assert_eq!(last_error(), "Unknown chunk type");
+            picmes_free(png);
+
+            assert_eq!(picmes_chunk_count(ptr::null()), 0);
+            picmes_free(ptr::null_mut());
+            picmes_free_bytes(ptr::null_mut(), 0);
+        }
+    }
+}
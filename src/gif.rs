@@ -0,0 +1,406 @@
+use std::{convert::TryFrom, fmt::Display, path::Path};
+
+use crate::{carrier::Section, chunk::Chunk, chunk_type::ChunkType, plan, Result};
+
+#[derive(Debug)]
+pub enum GifError {
+    InvalidHeader,
+    UnknownBlock(u8, usize),
+    Truncated,
+}
+
+impl std::error::Error for GifError {}
+
+impl Display for GifError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidHeader => write!(f, "A gif should start with GIF87a or GIF89a"),
+            Self::UnknownBlock(introducer, offset) => write!(
+                f,
+                "Unknown block introducer {:#04x} at offset {:#x}",
+                introducer, offset
+            ),
+            Self::Truncated => write!(f, "The file ends in the middle of a block"),
+        }
+    }
+}
+
+const EXTENSION: u8 = 0x21;
+const IMAGE: u8 = 0x2c;
+const TRAILER: u8 = 0x3b;
+const PLAIN_TEXT_LABEL: u8 = 0x01;
+const GRAPHIC_CONTROL_LABEL: u8 = 0xf9;
+const COMMENT_LABEL: u8 = 0xfe;
+const APPLICATION_LABEL: u8 = 0xff;
+
+/// The application identifier and authentication code of the extensions
+/// picmes writes. Their data starts with the chunk type
+const APPLICATION: &[u8; 11] = b"PICMES  1.0";
+/// Application data follows the introducer, label, identifier length and
+/// identifier
+const APPLICATION_DATA_START: usize = 3 + APPLICATION.len();
+/// Header, version and logical screen descriptor
+const SCREEN_DESCRIPTOR_END: usize = 13;
+/// Image separator, position, size and flags
+const IMAGE_DESCRIPTOR_LENGTH: usize = 10;
+const MAX_SUB_BLOCK_LENGTH: usize = 255;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Block {
+    /// An extension as read, introducer and terminator included
+    Extension { label: u8, bytes: Vec<u8> },
+    /// An image descriptor with its colour table and image data
+    Image(Vec<u8>),
+    /// An application extension written by picmes, holding a payload chunk
+    Payload(Chunk),
+}
+
+impl Block {
+    fn extension(label: u8, bytes: Vec<u8>) -> Self {
+        if label == APPLICATION_LABEL
+            && bytes.get(2) == Some(&(APPLICATION.len() as u8))
+            && bytes.get(3..APPLICATION_DATA_START) == Some(APPLICATION)
+        {
+            if let Some(chunk) = payload_chunk(&sub_block_data(&bytes[APPLICATION_DATA_START..])) {
+                return Self::Payload(chunk);
+            }
+        }
+        Self::Extension { label, bytes }
+    }
+
+    fn write_to(&self, bytes: &mut Vec<u8>) {
+        match self {
+            Self::Extension { bytes: block, .. } | Self::Image(block) => bytes.extend(block),
+            Self::Payload(chunk) => {
+                bytes.extend([EXTENSION, APPLICATION_LABEL, APPLICATION.len() as u8]);
+                bytes.extend(APPLICATION);
+                let data = [&chunk.chunk_type().bytes()[..], chunk.data()].concat();
+                for sub_block in data.chunks(MAX_SUB_BLOCK_LENGTH) {
+                    bytes.push(sub_block.len() as u8);
+                    bytes.extend(sub_block);
+                }
+                bytes.push(0);
+            }
+        }
+    }
+
+    /// The length of what `write_to` writes
+    fn encoded_length(&self) -> usize {
+        match self {
+            Self::Extension { bytes, .. } | Self::Image(bytes) => bytes.len(),
+            Self::Payload(chunk) => {
+                let data = chunk.chunk_type().bytes().len() + chunk.length();
+                // a length byte per sub-block and the terminator
+                APPLICATION_DATA_START + data + data.div_ceil(MAX_SUB_BLOCK_LENGTH) + 1
+            }
+        }
+    }
+
+    fn name(&self) -> String {
+        match self {
+            Self::Extension { label, bytes } => match *label {
+                PLAIN_TEXT_LABEL => "plain text".to_string(),
+                GRAPHIC_CONTROL_LABEL => "graphic control".to_string(),
+                COMMENT_LABEL => "comment".to_string(),
+                APPLICATION_LABEL => match bytes.get(3..APPLICATION_DATA_START) {
+                    Some(id) => format!("application {}", String::from_utf8_lossy(id)),
+                    None => "application".to_string(),
+                },
+                _ => format!("extension {:#04x}", label),
+            },
+            Self::Image(_) => "image".to_string(),
+            Self::Payload(_) => format!("application {}", String::from_utf8_lossy(APPLICATION)),
+        }
+    }
+}
+
+/// The chunk stored in the data of an application extension picmes wrote
+fn payload_chunk(data: &[u8]) -> Option<Chunk> {
+    let chunk_type = ChunkType::try_from(<[u8; 4]>::try_from(data.get(..4)?).ok()?).ok()?;
+    Some(Chunk::new(chunk_type, data[4..].to_vec()))
+}
+
+/// The offset just past the sub-blocks starting at `offset`, terminator
+/// included
+fn skip_sub_blocks(bytes: &[u8], mut offset: usize) -> Option<usize> {
+    loop {
+        let length = *bytes.get(offset)? as usize;
+        offset += 1 + length;
+        if length == 0 {
+            return Some(offset);
+        }
+    }
+}
+
+/// The data of the sub-blocks at the start of `bytes`, joined together
+fn sub_block_data(mut bytes: &[u8]) -> Vec<u8> {
+    let mut data = Vec::new();
+    while let Some((&length, rest)) = bytes.split_first() {
+        let length = (length as usize).min(rest.len());
+        if length == 0 {
+            break;
+        }
+        data.extend(&rest[..length]);
+        bytes = &rest[length..];
+    }
+    data
+}
+
+/// The length of the colour table a descriptor's flags announce
+fn colour_table_length(flags: u8) -> usize {
+    match flags & 0x80 {
+        0 => 0,
+        _ => 3 << ((flags & 0x07) + 1),
+    }
+}
+
+/// A gif as a list of blocks. Payloads are stored in application
+/// extensions, which every decoder skips when it does not know them
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gif {
+    /// Signature, logical screen descriptor and global colour table
+    header: Vec<u8>,
+    blocks: Vec<Block>,
+    /// The trailer byte and anything after it, kept as is
+    trailer: Vec<u8>,
+}
+
+impl Gif {
+    pub const SIGNATURE: &'static [u8] = b"GIF8";
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        Self::try_from(std::fs::read(path)?.as_slice())
+    }
+
+    /// The payload chunks stored in the gif, in file order
+    pub fn chunks(&self) -> impl Iterator<Item = &Chunk> {
+        self.blocks.iter().filter_map(|block| match block {
+            Block::Payload(chunk) => Some(chunk),
+            _ => None,
+        })
+    }
+
+    /// Lists the header, every block and the trailer with their lengths
+    pub fn sections(&self) -> Vec<Section> {
+        let section = |name: String, length: usize| Section {
+            name,
+            length,
+            chunk_type: None,
+        };
+        let mut sections = vec![section("header".to_string(), self.header.len())];
+        for block in &self.blocks {
+            let mut bytes = Vec::new();
+            block.write_to(&mut bytes);
+            sections.push(Section {
+                chunk_type: match block {
+                    Block::Payload(chunk) => Some(chunk.chunk_type().clone()),
+                    _ => None,
+                },
+                ..section(block.name(), bytes.len())
+            });
+        }
+        sections.push(section("trailer".to_string(), self.trailer.len()));
+        sections
+    }
+
+    /// Stores `chunk` in a new application extension after any leading
+    /// application and comment extensions, so a looping extension stays
+    /// first. Gif87a files are upgraded, since they predate extensions
+    pub fn insert_chunk(&mut self, chunk: Chunk) {
+        let index = self
+            .blocks
+            .iter()
+            .position(|block| match block {
+                Block::Extension { label, .. } => {
+                    !matches!(*label, APPLICATION_LABEL | COMMENT_LABEL)
+                }
+                Block::Image(_) => true,
+                Block::Payload(_) => false,
+            })
+            .unwrap_or(self.blocks.len());
+        self.blocks.insert(index, Block::Payload(chunk));
+        self.header[3..6].copy_from_slice(b"89a");
+    }
+
+    /// Removes the extensions holding payloads matching `predicate`,
+    /// returning their chunks
+    pub fn remove_chunks_where(&mut self, mut predicate: impl FnMut(&Chunk) -> bool) -> Vec<Chunk> {
+        let mut removed = Vec::new();
+        let mut kept = Vec::new();
+        for block in self.blocks.drain(..) {
+            match block {
+                Block::Payload(chunk) if predicate(&chunk) => removed.push(chunk),
+                block => kept.push(block),
+            }
+        }
+        self.blocks = kept;
+        removed
+    }
+
+    pub fn remove_first_chunk(&mut self, chunk_type: &str) -> Option<Chunk> {
+        let mut first = true;
+        self.remove_chunks_where(|chunk| {
+            chunk.chunk_type().to_string() == chunk_type && std::mem::take(&mut first)
+        })
+        .pop()
+    }
+
+    pub fn remove_all_chunks(&mut self, chunk_type: &str) -> Vec<Chunk> {
+        self.remove_chunks_where(|chunk| chunk.chunk_type().to_string() == chunk_type)
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.header.clone();
+        for block in &self.blocks {
+            block.write_to(&mut bytes);
+        }
+        bytes.extend(&self.trailer);
+        bytes
+    }
+
+    /// The size of the gif in bytes once written
+    pub fn encoded_length(&self) -> usize {
+        let blocks: usize = self.blocks.iter().map(Block::encoded_length).sum();
+        self.header.len() + blocks + self.trailer.len()
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        plan::write_atomic(path.as_ref(), &self.as_bytes())
+    }
+}
+
+impl TryFrom<&[u8]> for Gif {
+    type Error = crate::Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        if !bytes.starts_with(Self::SIGNATURE) || !matches!(bytes.get(4..6), Some(b"7a" | b"9a")) {
+            return Err(GifError::InvalidHeader.into());
+        }
+        let flags = *bytes
+            .get(SCREEN_DESCRIPTOR_END - 3)
+            .ok_or(GifError::Truncated)?;
+        let mut offset = SCREEN_DESCRIPTOR_END + colour_table_length(flags);
+        let header = bytes.get(..offset).ok_or(GifError::Truncated)?.to_vec();
+
+        let mut blocks = Vec::new();
+        loop {
+            let start = offset;
+            match *bytes.get(offset).ok_or(GifError::Truncated)? {
+                EXTENSION => {
+                    let label = *bytes.get(offset + 1).ok_or(GifError::Truncated)?;
+                    offset = skip_sub_blocks(bytes, offset + 2).ok_or(GifError::Truncated)?;
+                    let block = bytes.get(start..offset).ok_or(GifError::Truncated)?;
+                    blocks.push(Block::extension(label, block.to_vec()));
+                }
+                IMAGE => {
+                    let flags = *bytes
+                        .get(offset + IMAGE_DESCRIPTOR_LENGTH - 1)
+                        .ok_or(GifError::Truncated)?;
+                    // the colour table is followed by the LZW code size
+                    offset += IMAGE_DESCRIPTOR_LENGTH + colour_table_length(flags) + 1;
+                    offset = skip_sub_blocks(bytes, offset).ok_or(GifError::Truncated)?;
+                    let block = bytes.get(start..offset).ok_or(GifError::Truncated)?;
+                    blocks.push(Block::Image(block.to_vec()));
+                }
+                TRAILER => break,
+                introducer => return Err(GifError::UnknownBlock(introducer, offset).into()),
+            }
+        }
+        Ok(Self {
+            header,
+            blocks,
+            trailer: bytes[offset..].to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{
+        carrier::tests::{assert_chunks_round_trip, assert_round_trip},
+        png::tests::testing_chunk,
+    };
+
+    /// A two frame, looping 1x1 gif with a two colour global palette
+    pub(crate) fn testing_gif_bytes() -> Vec<u8> {
+        let mut bytes = b"GIF89a".to_vec();
+        bytes.extend([1, 0, 1, 0, 0x80, 0, 0]);
+        bytes.extend([0, 0, 0, 0xff, 0xff, 0xff]);
+        bytes.extend([EXTENSION, APPLICATION_LABEL, 11]);
+        bytes.extend(b"NETSCAPE2.0");
+        bytes.extend([3, 1, 0, 0, 0]);
+        for _ in 0..2 {
+            bytes.extend([EXTENSION, GRAPHIC_CONTROL_LABEL, 4, 0, 10, 0, 0, 0]);
+            bytes.extend([IMAGE, 0, 0, 0, 0, 1, 0, 1, 0, 0]);
+            bytes.extend([2, 2, 0x44, 0x01, 0]);
+        }
+        bytes.push(TRAILER);
+        bytes
+    }
+
+    fn names(gif: &Gif) -> Vec<String> {
+        gif.sections().into_iter().map(|s| s.name).collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let bytes = testing_gif_bytes();
+        let gif = Gif::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(
+            names(&gif),
+            [
+                "header",
+                "application NETSCAPE2.0",
+                "graphic control",
+                "image",
+                "graphic control",
+                "image",
+                "trailer"
+            ]
+        );
+        assert_round_trip(&bytes);
+        assert_round_trip(&[&bytes[..], b"appended"].concat());
+    }
+
+    #[test]
+    fn test_insert_and_remove_chunks() {
+        let bytes = testing_gif_bytes();
+        let mut gif = Gif::try_from(bytes.as_slice()).unwrap();
+        // long enough to need several sub-blocks
+        let long: Vec<u8> = (0..=255).cycle().take(1000).collect();
+        gif.insert_chunk(testing_chunk(b"first"));
+        gif.insert_chunk(testing_chunk(&long));
+        assert_eq!(
+            names(&gif)[1..4],
+            [
+                "application NETSCAPE2.0",
+                "application PICMES  1.0",
+                "application PICMES  1.0"
+            ]
+        );
+        let removed = assert_chunks_round_trip(gif, &[b"first", &long]);
+        assert_eq!(removed.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_insert_upgrades_gif87a() {
+        let mut bytes = testing_gif_bytes();
+        bytes[4] = b'7';
+        let mut gif = Gif::try_from(bytes.as_slice()).unwrap();
+        gif.insert_chunk(testing_chunk(b"hidden"));
+        assert!(gif.as_bytes().starts_with(b"GIF89a"));
+    }
+
+    #[test]
+    fn test_invalid_gifs() {
+        assert!(Gif::try_from(&b"GIF90a"[..]).is_err());
+        let bytes = testing_gif_bytes();
+        assert!(Gif::try_from(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Gif::try_from(&bytes[..30]).is_err());
+
+        let mut unknown = bytes.clone();
+        unknown[19] = 0x99;
+        assert!(Gif::try_from(unknown.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,156 @@
+use std::{fmt::Display, str::FromStr};
+
+use crate::{
+    chunk::Chunk, chunk_type::ChunkType, plan::Change, png::Png, time_chunk::LastModified, Result,
+};
+
+#[derive(Debug)]
+pub enum HistoryError {
+    MalformedEntry(String),
+}
+
+impl std::error::Error for HistoryError {}
+
+impl Display for HistoryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MalformedEntry(line) => write!(f, "Malformed history entry {:?}", line),
+        }
+    }
+}
+
+/// The private chunk picmes records its history in. Safe to copy, so other
+/// editors keep it
+pub const HISTORY: ChunkType = ChunkType(*b"prVn");
+
+/// One change picmes made to a file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    /// The version of picmes that made the change
+    pub version: String,
+    pub time: LastModified,
+    /// The command that made the change, e.g. encode
+    pub operation: String,
+}
+
+impl Entry {
+    /// An entry for `operation` made now by this version of picmes
+    pub fn now(operation: &str) -> Result<Self> {
+        Ok(Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            time: LastModified::now()?,
+            operation: operation.to_string(),
+        })
+    }
+
+    /// The entry as a line of the history chunk, its fields separated by tabs
+    fn to_line(&self) -> String {
+        format!("{}\t{}\t{}\n", self.version, self.time, self.operation)
+    }
+}
+
+impl FromStr for Entry {
+    type Err = crate::Error;
+
+    fn from_str(line: &str) -> Result<Self> {
+        let malformed = || HistoryError::MalformedEntry(line.to_string());
+        let mut fields = line.splitn(3, '\t');
+        let (Some(version), Some(time), Some(operation)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            return Err(malformed().into());
+        };
+        Ok(Self {
+            version: version.to_string(),
+            time: time.parse().map_err(|_| malformed())?,
+            operation: operation.to_string(),
+        })
+    }
+}
+
+impl Display for Entry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}  {}  picmes {}",
+            self.time, self.operation, self.version
+        )
+    }
+}
+
+/// Every entry recorded in `png`, oldest first. A png without a history
+/// chunk has an empty history
+pub fn history(png: &Png) -> Result<Vec<Entry>> {
+    let Some(chunk) = png.chunk_by_type(&HISTORY.to_string()) else {
+        return Ok(Vec::new());
+    };
+    std::str::from_utf8(chunk.data())?
+        .lines()
+        .map(Entry::from_str)
+        .collect()
+}
+
+/// Appends `entry` to the history chunk of `png`, adding the chunk before
+/// IEND when there is none yet
+pub fn record(png: &mut Png, entry: &Entry) -> Result<Change> {
+    let line = entry.to_line();
+    let action = format!("recorded {}", entry.operation);
+    match png.chunks_mut().find(|c| c.chunk_type() == &HISTORY) {
+        Some(chunk) => {
+            let data = [chunk.data(), line.as_bytes()].concat();
+            *chunk = Chunk::try_new(HISTORY, data)?;
+            Ok(Change::updated(chunk, action))
+        }
+        None => {
+            let chunk = Chunk::try_new(HISTORY, line.into_bytes())?;
+            let change = Change::added(&chunk);
+            png.insert_chunk(chunk);
+            Ok(change)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::tests::testing_png_bytes;
+
+    fn entry(operation: &str, second: u8) -> Entry {
+        Entry {
+            version: "0.1.0".to_string(),
+            time: LastModified::new(2024, 2, 29, 13, 5, second).unwrap(),
+            operation: operation.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_record_and_read_history() {
+        let mut png = Png::try_from(testing_png_bytes().as_slice()).unwrap();
+        assert!(history(&png).unwrap().is_empty());
+        assert!(HISTORY.is_valid() && !HISTORY.is_public() && HISTORY.is_safe_to_copy());
+
+        let change = record(&mut png, &entry("encode", 1)).unwrap();
+        assert_eq!(change.to_string(), "+ prVn (34 bytes)");
+        let change = record(&mut png, &entry("strip", 2)).unwrap();
+        assert_eq!(change.to_string(), "~ prVn: recorded strip");
+
+        assert_eq!(
+            history(&png).unwrap(),
+            [entry("encode", 1), entry("strip", 2)]
+        );
+        assert_eq!(png.chunks_by_type("prVn").count(), 1);
+        assert_eq!(
+            entry("repair", 3).to_string(),
+            "2024-02-29T13:05:03Z  repair  picmes 0.1.0"
+        );
+    }
+
+    #[test]
+    fn test_malformed_history() {
+        let mut png = Png::try_from(testing_png_bytes().as_slice()).unwrap();
+        png.insert_chunk(Chunk::new(HISTORY, b"0.1.0\tyesterday\tencode\n".to_vec()));
+        assert!(history(&png).is_err());
+        assert!("0.1.0\t2024-02-29T13:05:03Z".parse::<Entry>().is_err());
+        assert!(Entry::now("encode").is_ok());
+    }
+}
@@ -0,0 +1,399 @@
+use std::{convert::TryFrom, fmt::Display, path::Path};
+
+use crate::{
+    carrier::Section,
+    chunk::Chunk,
+    plan,
+    png::{Png, PngError},
+    Result,
+};
+
+#[derive(Debug)]
+pub enum IcoError {
+    InvalidHeader,
+    Truncated(usize),
+    NoPngEntry,
+    EntryOutOfRange(usize, usize),
+    NotPng(usize),
+}
+
+impl std::error::Error for IcoError {}
+
+impl Display for IcoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidHeader => write!(
+                f,
+                "An icon or cursor should start with 0, 1 or 2 and an entry count"
+            ),
+            Self::Truncated(index) => write!(f, "Entry {} lies past the end of the file", index),
+            Self::NoPngEntry => write!(f, "None of the entries is a PNG image"),
+            Self::EntryOutOfRange(index, count) => {
+                write!(f, "Entry {} is out of range, there are {}", index, count)
+            }
+            Self::NotPng(index) => write!(f, "Entry {} is a bitmap, not a PNG image", index),
+        }
+    }
+}
+
+/// `reserved | type | entry count`
+const HEADER_LENGTH: usize = 6;
+/// `width | height | colors | reserved | planes | bit count | size | offset`
+const ENTRY_LENGTH: usize = 16;
+/// The part of an entry that describes the image rather than where it is
+const DESCRIPTION_LENGTH: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Icon,
+    Cursor,
+}
+
+#[derive(Debug)]
+enum Image {
+    Png(Png),
+    /// A DIB without its file header, kept as is
+    Bitmap(Vec<u8>),
+}
+
+impl Image {
+    fn as_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::Png(png) => png.as_bytes(),
+            Self::Bitmap(bytes) => bytes.clone(),
+        }
+    }
+
+    fn encoded_length(&self) -> usize {
+        match self {
+            Self::Png(png) => png.encoded_length(),
+            Self::Bitmap(bytes) => bytes.len(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    /// Dimensions, palette size and planes and bit count, or the hotspot
+    /// of a cursor
+    description: [u8; DESCRIPTION_LENGTH],
+    image: Image,
+}
+
+/// An icon or cursor as the images in its directory. Chunks go into the
+/// PNG encoded entries, and the directory sizes and offsets are worked out
+/// again when it is written
+#[derive(Debug)]
+pub struct Ico {
+    kind: Kind,
+    entries: Vec<Entry>,
+    /// The entry new chunks go into
+    selected: Option<usize>,
+}
+
+impl Ico {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        Self::try_from(std::fs::read(path)?.as_slice())
+    }
+
+    /// Whether `bytes` start like an icon or cursor with at least one entry
+    pub fn has_signature(bytes: &[u8]) -> bool {
+        matches!(bytes.get(..4), Some([0, 0, 1 | 2, 0])) && bytes.get(4..6) != Some(&[0, 0])
+    }
+
+    pub fn format(&self) -> &'static str {
+        match self.kind {
+            Kind::Icon => "ico",
+            Kind::Cursor => "cur",
+        }
+    }
+
+    /// Makes new chunks go into entry `index`, which has to be a PNG image
+    pub fn select(&mut self, index: usize) -> Result<()> {
+        match self.entries.get(index) {
+            Some(Entry {
+                image: Image::Png(_),
+                ..
+            }) => {
+                self.selected = Some(index);
+                Ok(())
+            }
+            Some(_) => Err(IcoError::NotPng(index).into()),
+            None => Err(IcoError::EntryOutOfRange(index, self.entries.len()).into()),
+        }
+    }
+
+    /// The selected PNG entry, the first one unless another was selected
+    pub fn png_mut(&mut self) -> Result<&mut Png> {
+        let index = self.selected.ok_or(IcoError::NoPngEntry)?;
+        match &mut self.entries[index].image {
+            Image::Png(png) => Ok(png),
+            Image::Bitmap(_) => Err(IcoError::NotPng(index).into()),
+        }
+    }
+
+    fn pngs(&self) -> impl Iterator<Item = &Png> {
+        self.entries.iter().filter_map(|entry| match &entry.image {
+            Image::Png(png) => Some(png),
+            Image::Bitmap(_) => None,
+        })
+    }
+
+    /// The chunks of every PNG entry, in directory order
+    pub fn chunks(&self) -> impl Iterator<Item = &Chunk> {
+        self.pngs().flat_map(Png::chunks)
+    }
+
+    /// Every chunk of the given type, looked up in the index of each entry
+    pub fn chunks_by_type<'a>(&'a self, chunk_type: &'a str) -> impl Iterator<Item = &'a Chunk> {
+        self.pngs()
+            .flat_map(move |png| png.chunks_by_type(chunk_type))
+    }
+
+    /// Lists the header and directory, then every entry with the chunks of
+    /// the PNG encoded ones
+    pub fn sections(&self) -> Vec<Section> {
+        let section = |name: String, length: usize| Section {
+            name,
+            length,
+            chunk_type: None,
+        };
+        let mut sections = vec![
+            section("header".to_string(), HEADER_LENGTH),
+            section("directory".to_string(), self.entries.len() * ENTRY_LENGTH),
+        ];
+        for (index, entry) in self.entries.iter().enumerate() {
+            match &entry.image {
+                Image::Png(png) => sections.extend(png.chunks().map(|chunk| Section {
+                    chunk_type:
+                        (!chunk.chunk_type().is_critical()).then(|| chunk.chunk_type().clone()),
+                    ..section(
+                        format!("entry {} {}", index, chunk.chunk_type()),
+                        chunk.length() + Chunk::META_DATA_LENGTH,
+                    )
+                })),
+                Image::Bitmap(bytes) => {
+                    sections.push(section(format!("entry {} bitmap", index), bytes.len()))
+                }
+            }
+        }
+        sections
+    }
+
+    pub fn insert_chunk(&mut self, chunk: Chunk) -> Result<()> {
+        self.png_mut()?.insert_chunk(chunk);
+        Ok(())
+    }
+
+    /// Removes the first chunk of the given type from the first entry that
+    /// has one
+    pub fn remove_first_chunk(&mut self, chunk_type: &str) -> Result<Chunk> {
+        for entry in &mut self.entries {
+            if let Image::Png(png) = &mut entry.image {
+                if png.chunks_by_type(chunk_type).next().is_some() {
+                    return png.remove_first_chunk(chunk_type);
+                }
+            }
+        }
+        Err(PngError::UnknownChunkType.into())
+    }
+
+    pub fn remove_all_chunks(&mut self, chunk_type: &str) -> Vec<Chunk> {
+        let mut removed = Vec::new();
+        for entry in &mut self.entries {
+            if let Image::Png(png) = &mut entry.image {
+                removed.extend(png.remove_all_chunks(chunk_type));
+            }
+        }
+        removed
+    }
+
+    /// Removes every chunk matching `predicate` from each PNG entry
+    pub fn remove_chunks_where(&mut self, mut predicate: impl FnMut(&Chunk) -> bool) -> Vec<Chunk> {
+        let mut removed = Vec::new();
+        for entry in &mut self.entries {
+            if let Image::Png(png) = &mut entry.image {
+                removed.extend(png.remove_chunks_where(&mut predicate));
+            }
+        }
+        removed
+    }
+
+    /// Writes the images one after another behind the directory, with the
+    /// sizes and offsets in the directory to match
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let images: Vec<Vec<u8>> = self.entries.iter().map(|e| e.image.as_bytes()).collect();
+        let kind: u16 = match self.kind {
+            Kind::Icon => 1,
+            Kind::Cursor => 2,
+        };
+        let mut bytes = vec![0, 0];
+        bytes.extend(kind.to_le_bytes());
+        bytes.extend((self.entries.len() as u16).to_le_bytes());
+
+        let mut offset = HEADER_LENGTH + self.entries.len() * ENTRY_LENGTH;
+        for (entry, image) in self.entries.iter().zip(&images) {
+            bytes.extend(entry.description);
+            bytes.extend((image.len() as u32).to_le_bytes());
+            bytes.extend((offset as u32).to_le_bytes());
+            offset += image.len();
+        }
+        bytes.extend(images.concat());
+        bytes
+    }
+
+    /// The size of the icon in bytes once written
+    pub fn encoded_length(&self) -> usize {
+        let images: usize = self.entries.iter().map(|e| e.image.encoded_length()).sum();
+        HEADER_LENGTH + self.entries.len() * ENTRY_LENGTH + images
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        plan::write_atomic(path.as_ref(), &self.as_bytes())
+    }
+}
+
+impl TryFrom<&[u8]> for Ico {
+    type Error = crate::Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        if !Self::has_signature(bytes) || bytes.len() < HEADER_LENGTH {
+            return Err(IcoError::InvalidHeader.into());
+        }
+        let kind = match bytes[2] {
+            1 => Kind::Icon,
+            _ => Kind::Cursor,
+        };
+        let count = u16::from_le_bytes(bytes[4..6].try_into()?) as usize;
+
+        let mut entries = Vec::new();
+        for index in 0..count {
+            let start = HEADER_LENGTH + index * ENTRY_LENGTH;
+            let entry = bytes
+                .get(start..start + ENTRY_LENGTH)
+                .ok_or(IcoError::InvalidHeader)?;
+            let size = u32::from_le_bytes(entry[8..12].try_into()?) as usize;
+            let offset = u32::from_le_bytes(entry[12..16].try_into()?) as usize;
+            let data = bytes
+                .get(offset..offset + size)
+                .ok_or(IcoError::Truncated(index))?;
+            let image = match data.starts_with(&Png::STANDARD_HEADER) {
+                true => Image::Png(Png::try_from(data)?),
+                false => Image::Bitmap(data.to_vec()),
+            };
+            entries.push(Entry {
+                description: entry[..DESCRIPTION_LENGTH].try_into()?,
+                image,
+            });
+        }
+        let selected = entries
+            .iter()
+            .position(|entry| matches!(entry.image, Image::Png(_)));
+        Ok(Self {
+            kind,
+            entries,
+            selected,
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{
+        carrier::tests::{assert_chunks_round_trip, assert_round_trip},
+        png::tests::{testing_chunk, testing_png_bytes},
+    };
+
+    /// An icon with a bitmap entry followed by a PNG encoded one
+    pub(crate) fn testing_ico_bytes() -> Vec<u8> {
+        let bitmap = vec![0x28; 48];
+        let png = testing_png_bytes();
+        let mut bytes = vec![0, 0, 1, 0, 2, 0];
+        let mut offset = HEADER_LENGTH + 2 * ENTRY_LENGTH;
+        for (size, image) in [(16, &bitmap), (0, &png)] {
+            bytes.extend([size, size, 0, 0, 1, 0, 32, 0]);
+            bytes.extend((image.len() as u32).to_le_bytes());
+            bytes.extend((offset as u32).to_le_bytes());
+            offset += image.len();
+        }
+        bytes.extend(bitmap);
+        bytes.extend(png);
+        bytes
+    }
+
+    fn names(ico: &Ico) -> Vec<String> {
+        ico.sections().into_iter().map(|s| s.name).collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let bytes = testing_ico_bytes();
+        let ico = Ico::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(ico.format(), "ico");
+        assert_eq!(ico.selected, Some(1));
+        assert_eq!(
+            names(&ico),
+            [
+                "header",
+                "directory",
+                "entry 0 bitmap",
+                "entry 1 IHDR",
+                "entry 1 IDAT",
+                "entry 1 IEND"
+            ]
+        );
+        assert_round_trip(&bytes);
+
+        let mut cursor = bytes.clone();
+        cursor[2] = 2;
+        assert_eq!(Ico::try_from(cursor.as_slice()).unwrap().format(), "cur");
+    }
+
+    #[test]
+    fn test_insert_and_remove_chunks() {
+        let bytes = testing_ico_bytes();
+        let mut ico = Ico::try_from(bytes.as_slice()).unwrap();
+        ico.insert_chunk(testing_chunk(b"hidden")).unwrap();
+
+        let written = ico.as_bytes();
+        assert_eq!(written.len(), bytes.len() + 18);
+        // the size of the PNG entry grew, its offset stayed
+        assert_eq!(&written[..HEADER_LENGTH + ENTRY_LENGTH + 8], &bytes[..30]);
+        let size = u32::from_le_bytes(written[30..34].try_into().unwrap()) as usize;
+        let offset = u32::from_le_bytes(written[34..38].try_into().unwrap()) as usize;
+        assert_eq!(offset + size, written.len());
+
+        let types: Vec<String> = ico.chunks().map(|c| c.chunk_type().to_string()).collect();
+        assert_eq!(types, ["IHDR", "IDAT", "ruSt", "IEND"]);
+        let removed = assert_chunks_round_trip(ico, &[b"hidden"]);
+        assert_eq!(removed.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_select() {
+        let bytes = testing_ico_bytes();
+        let mut ico = Ico::try_from(bytes.as_slice()).unwrap();
+        assert!(ico.select(0).is_err());
+        assert!(ico.select(2).is_err());
+        ico.select(1).unwrap();
+        assert!(ico.png_mut().is_ok());
+
+        // an icon of bitmaps only has nowhere to put chunks
+        let mut bitmaps = bytes[..HEADER_LENGTH + ENTRY_LENGTH].to_vec();
+        bitmaps[4] = 1;
+        bitmaps.extend(&bytes[HEADER_LENGTH + 2 * ENTRY_LENGTH..][..48]);
+        bitmaps[18..22].copy_from_slice(&22u32.to_le_bytes());
+        let mut ico = Ico::try_from(bitmaps.as_slice()).unwrap();
+        assert!(ico.insert_chunk(testing_chunk(b"nowhere")).is_err());
+    }
+
+    #[test]
+    fn test_invalid_icos() {
+        assert!(!Ico::has_signature(&[0, 0, 1, 0, 0, 0]));
+        assert!(!Ico::has_signature(&[0, 0, 3, 0, 1, 0]));
+        let bytes = testing_ico_bytes();
+        assert!(Ico::try_from(&bytes[..20]).is_err());
+        assert!(Ico::try_from(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,275 @@
+use std::fmt::Display;
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, Error, Result};
+
+#[derive(Debug)]
+pub enum IhdrError {
+    Missing,
+    InvalidLength(usize),
+    InvalidColorType(u8),
+    InvalidBitDepth(u8, ColorType),
+    InvalidMethod(&'static str, u8),
+}
+
+impl std::error::Error for IhdrError {}
+
+impl Display for IhdrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Missing => write!(f, "The png does not start with an IHDR chunk"),
+            Self::InvalidLength(length) => write!(
+                f,
+                "IHDR data should be {} bytes but is {}",
+                Ihdr::LENGTH,
+                length
+            ),
+            Self::InvalidColorType(value) => write!(f, "Unknown IHDR color type {}", value),
+            Self::InvalidBitDepth(depth, color_type) => {
+                write!(
+                    f,
+                    "A bit depth of {} is not allowed for {}",
+                    depth, color_type
+                )
+            }
+            Self::InvalidMethod(field, value) => {
+                write!(f, "Unknown IHDR {} method {}", field, value)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorType {
+    Grayscale,
+    Rgb,
+    Indexed,
+    GrayscaleAlpha,
+    Rgba,
+}
+
+impl ColorType {
+    /// Number of samples in each pixel
+    pub fn channels(&self) -> usize {
+        match self {
+            Self::Grayscale | Self::Indexed => 1,
+            Self::GrayscaleAlpha => 2,
+            Self::Rgb => 3,
+            Self::Rgba => 4,
+        }
+    }
+
+    /// The value stored for the color type in the IHDR chunk
+    pub fn code(&self) -> u8 {
+        match self {
+            Self::Grayscale => 0,
+            Self::Rgb => 2,
+            Self::Indexed => 3,
+            Self::GrayscaleAlpha => 4,
+            Self::Rgba => 6,
+        }
+    }
+
+    fn allowed_bit_depths(&self) -> &'static [u8] {
+        match self {
+            Self::Grayscale => &[1, 2, 4, 8, 16],
+            Self::Indexed => &[1, 2, 4, 8],
+            Self::Rgb | Self::GrayscaleAlpha | Self::Rgba => &[8, 16],
+        }
+    }
+}
+
+impl TryFrom<u8> for ColorType {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(Self::Grayscale),
+            2 => Ok(Self::Rgb),
+            3 => Ok(Self::Indexed),
+            4 => Ok(Self::GrayscaleAlpha),
+            6 => Ok(Self::Rgba),
+            _ => Err(IhdrError::InvalidColorType(value).into()),
+        }
+    }
+}
+
+impl Display for ColorType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Grayscale => "grayscale",
+            Self::Rgb => "rgb",
+            Self::Indexed => "indexed",
+            Self::GrayscaleAlpha => "grayscale+alpha",
+            Self::Rgba => "rgba",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The image header stored in the IHDR chunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ihdr {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: ColorType,
+    pub compression: u8,
+    pub filter: u8,
+    pub interlaced: bool,
+}
+
+impl Ihdr {
+    pub const LENGTH: usize = 13;
+
+    pub fn bits_per_pixel(&self) -> usize {
+        self.color_type.channels() * self.bit_depth as usize
+    }
+
+    /// Bytes in one scanline of a non-interlaced image, not counting the
+    /// filter type byte, or `None` if that does not fit in a `usize`
+    pub fn stride(&self) -> Option<usize> {
+        Some(
+            (self.width as usize)
+                .checked_mul(self.bits_per_pixel())?
+                .div_ceil(8),
+        )
+    }
+
+    /// A non-interlaced 8-bit RGBA header, the format of pixels handed over
+    /// by other programs
+    pub fn rgba(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            bit_depth: 8,
+            color_type: ColorType::Rgba,
+            compression: 0,
+            filter: 0,
+            interlaced: false,
+        }
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity(Self::LENGTH);
+        data.extend_from_slice(&self.width.to_be_bytes());
+        data.extend_from_slice(&self.height.to_be_bytes());
+        data.extend_from_slice(&[
+            self.bit_depth,
+            self.color_type.code(),
+            self.compression,
+            self.filter,
+            self.interlaced as u8,
+        ]);
+        Chunk::new(ChunkType::IHDR, data)
+    }
+}
+
+impl TryFrom<&Chunk> for Ihdr {
+    type Error = Error;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        if chunk.chunk_type() != &ChunkType::IHDR {
+            return Err(IhdrError::Missing.into());
+        }
+        let data = chunk.data();
+        if data.len() != Ihdr::LENGTH {
+            return Err(IhdrError::InvalidLength(data.len()).into());
+        }
+
+        let color_type = ColorType::try_from(data[9])?;
+        let bit_depth = data[8];
+        if !color_type.allowed_bit_depths().contains(&bit_depth) {
+            return Err(IhdrError::InvalidBitDepth(bit_depth, color_type).into());
+        }
+        // deflate and adaptive filtering are the only methods the spec defines
+        for (field, value) in [("compression", data[10]), ("filter", data[11])] {
+            if value != 0 {
+                return Err(IhdrError::InvalidMethod(field, value).into());
+            }
+        }
+        let interlaced = match data[12] {
+            0 => false,
+            1 => true,
+            value => return Err(IhdrError::InvalidMethod("interlace", value).into()),
+        };
+
+        Ok(Self {
+            width: u32::from_be_bytes(data[0..4].try_into()?),
+            height: u32::from_be_bytes(data[4..8].try_into()?),
+            bit_depth,
+            color_type,
+            compression: data[10],
+            filter: data[11],
+            interlaced,
+        })
+    }
+}
+
+impl Display for Ihdr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}x{} {}-bit {}",
+            self.width, self.height, self.bit_depth, self.color_type
+        )?;
+        if self.interlaced {
+            write!(f, ", interlaced")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ihdr_chunk(data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::IHDR, data.to_vec())
+    }
+
+    #[test]
+    fn test_parse_ihdr() {
+        let chunk = ihdr_chunk(&[0, 0, 0, 50, 0, 0, 1, 0, 8, 6, 0, 0, 1]);
+        let ihdr = Ihdr::try_from(&chunk).unwrap();
+        assert_eq!(ihdr.width, 50);
+        assert_eq!(ihdr.height, 256);
+        assert_eq!(ihdr.bit_depth, 8);
+        assert_eq!(ihdr.color_type, ColorType::Rgba);
+        assert_eq!((ihdr.compression, ihdr.filter), (0, 0));
+        assert!(ihdr.interlaced);
+        assert_eq!(ihdr.to_string(), "50x256 8-bit rgba, interlaced");
+        assert_eq!((ihdr.bits_per_pixel(), ihdr.stride()), (32, Some(200)));
+
+        let chunk = ihdr_chunk(&[0, 0, 0, 13, 0, 0, 0, 1, 2, 0, 0, 0, 0]);
+        let ihdr = Ihdr::try_from(&chunk).unwrap();
+        assert_eq!((ihdr.bits_per_pixel(), ihdr.stride()), (2, Some(4)));
+    }
+
+    #[test]
+    fn test_ihdr_to_chunk() {
+        let data = [0, 0, 0, 50, 0, 0, 1, 0, 8, 6, 0, 0, 1];
+        let ihdr = Ihdr::try_from(&ihdr_chunk(&data)).unwrap();
+        assert_eq!(ihdr.to_chunk().data(), data);
+        assert_eq!(
+            Ihdr::try_from(&Ihdr::rgba(3, 2).to_chunk()).unwrap(),
+            Ihdr::rgba(3, 2)
+        );
+    }
+
+    #[test]
+    fn test_invalid_ihdr() {
+        let valid = [0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0];
+        assert!(Ihdr::try_from(&ihdr_chunk(&valid)).is_ok());
+        assert!(Ihdr::try_from(&ihdr_chunk(&valid[..12])).is_err());
+
+        // (index, value) pairs that each make the header invalid
+        for (index, value) in [(9, 5), (8, 4), (10, 1), (11, 1), (12, 2)] {
+            let mut data = valid;
+            data[index] = value;
+            assert!(Ihdr::try_from(&ihdr_chunk(&data)).is_err(), "{}", index);
+        }
+
+        let chunk = Chunk::new(ChunkType::IDAT, valid.to_vec());
+        assert!(Ihdr::try_from(&chunk).is_err());
+    }
+}
@@ -0,0 +1,332 @@
+use std::{
+    fmt::Display,
+    io::{Read, Write},
+};
+
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, ihdr::Ihdr, png::Png, Result};
+
+#[derive(Debug)]
+pub enum ImageDataError {
+    Missing,
+    Interlaced,
+    InvalidLength(u64, usize),
+    InvalidFilter(u8),
+    TooLarge(u32, u32),
+}
+
+impl std::error::Error for ImageDataError {}
+
+impl Display for ImageDataError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Missing => write!(f, "The png has no IDAT chunks"),
+            Self::Interlaced => write!(f, "Interlaced images are not supported"),
+            Self::InvalidLength(expected, length) => write!(
+                f,
+                "The image data should inflate to {} bytes but holds {}",
+                expected, length
+            ),
+            Self::InvalidFilter(filter) => write!(f, "Unknown scanline filter type {}", filter),
+            Self::TooLarge(width, height) => write!(f, "A {}x{} image is too large", width, height),
+        }
+    }
+}
+
+const FILTER_NONE: u8 = 0;
+const FILTER_SUB: u8 = 1;
+const FILTER_UP: u8 = 2;
+const FILTER_AVERAGE: u8 = 3;
+const FILTER_PAETH: u8 = 4;
+
+/// The decompressed samples of a non-interlaced image, with the scanline
+/// filters undone
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageData {
+    header: Ihdr,
+    /// The filter type of each scanline, reused when compressing again so the
+    /// file size stays close to the original
+    filters: Vec<u8>,
+    /// Scanlines back to back, without their filter type bytes
+    samples: Vec<u8>,
+}
+
+impl ImageData {
+    /// Inflates and unfilters the concatenated IDAT chunks of `png`
+    pub fn from_png(png: &Png) -> Result<Self> {
+        let header = png.header()?;
+        if header.interlaced {
+            return Err(ImageDataError::Interlaced.into());
+        }
+        let mut idat = png.chunks_by_type("IDAT").peekable();
+        if idat.peek().is_none() {
+            return Err(ImageDataError::Missing.into());
+        }
+        let compressed: Vec<u8> = idat.flat_map(|c| c.data().iter().copied()).collect();
+
+        // read one byte past the expected length to notice surplus data,
+        // without letting a forged stream inflate without limit
+        let stride = stride(&header)?;
+        let expected = (stride as u64 + 1)
+            .checked_mul(header.height as u64)
+            .ok_or(ImageDataError::TooLarge(header.width, header.height))?;
+        let mut filtered = Vec::new();
+        ZlibDecoder::new(compressed.as_slice())
+            .take(expected.saturating_add(1))
+            .read_to_end(&mut filtered)?;
+        if filtered.len() as u64 != expected {
+            return Err(ImageDataError::InvalidLength(expected, filtered.len()).into());
+        }
+
+        let step = filter_step(&header);
+        let zero = vec![0; stride];
+        let mut filters = Vec::with_capacity(header.height as usize);
+        let mut samples = Vec::with_capacity(filtered.len());
+        for row in filtered.chunks_exact(stride + 1) {
+            let start = samples.len();
+            samples.extend_from_slice(&row[1..]);
+            let (done, line) = samples.split_at_mut(start);
+            let previous = match start {
+                0 => &zero[..],
+                _ => &done[start - stride..],
+            };
+            unfilter(row[0], step, previous, line)?;
+            filters.push(row[0]);
+        }
+        Ok(Self {
+            header,
+            filters,
+            samples,
+        })
+    }
+
+    /// An image of unfiltered `samples`, scanline after scanline as
+    /// `header` lays them out
+    pub fn new(header: Ihdr, samples: Vec<u8>) -> Result<Self> {
+        if header.interlaced {
+            return Err(ImageDataError::Interlaced.into());
+        }
+        let expected = (stride(&header)? as u64)
+            .checked_mul(header.height as u64)
+            .ok_or(ImageDataError::TooLarge(header.width, header.height))?;
+        if samples.len() as u64 != expected {
+            return Err(ImageDataError::InvalidLength(expected, samples.len()).into());
+        }
+        Ok(Self {
+            // Paeth predicts photos and screenshots alike well enough
+            filters: vec![FILTER_PAETH; header.height as usize],
+            header,
+            samples,
+        })
+    }
+
+    pub fn header(&self) -> &Ihdr {
+        &self.header
+    }
+
+    pub fn samples(&self) -> &[u8] {
+        &self.samples
+    }
+
+    pub fn samples_mut(&mut self) -> &mut [u8] {
+        &mut self.samples
+    }
+
+    /// Filters and compresses the samples into a single IDAT chunk
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        let stride = stride(&self.header)?;
+        let step = filter_step(&self.header);
+        let zero = vec![0; stride];
+        let mut line = vec![0; stride];
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        for (row, &filter) in self.filters.iter().enumerate() {
+            let current = &self.samples[row * stride..(row + 1) * stride];
+            let previous = match row {
+                0 => &zero[..],
+                _ => &self.samples[(row - 1) * stride..row * stride],
+            };
+            apply_filter(filter, step, previous, current, &mut line);
+            encoder.write_all(&[filter])?;
+            encoder.write_all(&line)?;
+        }
+        Chunk::try_new(ChunkType::IDAT, encoder.finish()?)
+    }
+
+    /// A png holding only the image: IHDR, one IDAT and IEND
+    pub fn to_png(&self) -> Result<Png> {
+        Ok(Png::from_chunks(vec![
+            self.header.to_chunk(),
+            self.to_chunk()?,
+            Chunk::new(ChunkType::IEND, vec![]),
+        ]))
+    }
+}
+
+/// The scanline length of `header`, failing when it does not fit in memory
+fn stride(header: &Ihdr) -> Result<usize> {
+    Ok(header
+        .stride()
+        .ok_or(ImageDataError::TooLarge(header.width, header.height))?)
+}
+
+/// Distance in bytes to the corresponding byte of the pixel on the left,
+/// rounded up to one for bit depths below 8
+fn filter_step(header: &Ihdr) -> usize {
+    header.bits_per_pixel().div_ceil(8)
+}
+
+/// The value a filter predicts for a byte from its neighbours to the left,
+/// above and above left
+fn predict(filter: u8, left: u8, up: u8, up_left: u8) -> u8 {
+    match filter {
+        FILTER_SUB => left,
+        FILTER_UP => up,
+        FILTER_AVERAGE => ((left as u16 + up as u16) / 2) as u8,
+        FILTER_PAETH => {
+            let estimate = left as i16 + up as i16 - up_left as i16;
+            let distance = |value: u8| (estimate - value as i16).abs();
+            if distance(left) <= distance(up) && distance(left) <= distance(up_left) {
+                left
+            } else if distance(up) <= distance(up_left) {
+                up
+            } else {
+                up_left
+            }
+        }
+        _ => 0,
+    }
+}
+
+/// Reverses `filter` on `line` in place, given the unfiltered previous line
+fn unfilter(filter: u8, step: usize, previous: &[u8], line: &mut [u8]) -> Result<()> {
+    if filter > FILTER_PAETH {
+        return Err(ImageDataError::InvalidFilter(filter).into());
+    }
+    if filter == FILTER_NONE {
+        return Ok(());
+    }
+    for i in 0..line.len() {
+        let (left, up_left) = match i.checked_sub(step) {
+            Some(j) => (line[j], previous[j]),
+            None => (0, 0),
+        };
+        line[i] = line[i].wrapping_add(predict(filter, left, previous[i], up_left));
+    }
+    Ok(())
+}
+
+/// Writes `current` filtered with `filter` to `out`
+fn apply_filter(filter: u8, step: usize, previous: &[u8], current: &[u8], out: &mut [u8]) {
+    for i in 0..current.len() {
+        let (left, up_left) = match i.checked_sub(step) {
+            Some(j) => (current[j], previous[j]),
+            None => (0, 0),
+        };
+        out[i] = current[i].wrapping_sub(predict(filter, left, previous[i], up_left));
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::ihdr::ColorType;
+
+    /// A non-interlaced 8-bit png of the given size and colour type whose
+    /// scanlines cycle through every filter type
+    pub(crate) fn testing_image(width: u32, height: u32, color_type: ColorType) -> Png {
+        let mut ihdr = width.to_be_bytes().to_vec();
+        ihdr.extend(height.to_be_bytes());
+        ihdr.extend([8, color_type.code(), 0, 0, 0]);
+        let ihdr = Chunk::new(ChunkType::IHDR, ihdr);
+
+        let header = Ihdr::try_from(&ihdr).unwrap();
+        let length = header.stride().unwrap() * height as usize;
+        let image = ImageData {
+            header,
+            filters: (0..height as u8).map(|row| row % 5).collect(),
+            samples: (0..length).map(|i| (i * 7 % 251) as u8).collect(),
+        };
+        Png::from_chunks(vec![
+            ihdr,
+            image.to_chunk().unwrap(),
+            Chunk::new(ChunkType::IEND, vec![]),
+        ])
+    }
+
+    #[test]
+    fn test_png_from_samples() {
+        let samples: Vec<u8> = (0..3 * 2 * 4).map(|i| i as u8 * 10).collect();
+        let image = ImageData::new(Ihdr::rgba(3, 2), samples.clone()).unwrap();
+        let png = Png::try_from(image.to_png().unwrap().as_bytes().as_slice()).unwrap();
+        assert_eq!(png.header().unwrap(), Ihdr::rgba(3, 2));
+        assert_eq!(ImageData::from_png(&png).unwrap().samples(), samples);
+
+        assert!(ImageData::new(Ihdr::rgba(3, 2), vec![0; 23]).is_err());
+        let interlaced = Ihdr {
+            interlaced: true,
+            ..Ihdr::rgba(3, 2)
+        };
+        assert!(ImageData::new(interlaced, samples).is_err());
+    }
+
+    #[test]
+    fn test_image_data_round_trip() {
+        let png = testing_image(7, 10, ColorType::Rgb);
+        let image = ImageData::from_png(&png).unwrap();
+        assert_eq!(image.filters, [0, 1, 2, 3, 4, 0, 1, 2, 3, 4]);
+        assert_eq!(image.samples().len(), 7 * 3 * 10);
+        assert_eq!(image.samples()[..4], [0, 7, 14, 21]);
+
+        let again = Png::from_chunks(vec![
+            png.chunks().next().unwrap().clone(),
+            image.to_chunk().unwrap(),
+        ]);
+        assert_eq!(ImageData::from_png(&again).unwrap(), image);
+    }
+
+    #[test]
+    fn test_paeth_predictor() {
+        assert_eq!(predict(FILTER_PAETH, 10, 20, 15), 15);
+        assert_eq!(predict(FILTER_PAETH, 10, 20, 5), 20);
+        assert_eq!(predict(FILTER_PAETH, 10, 20, 30), 10);
+        assert_eq!(predict(FILTER_AVERAGE, 255, 255, 0), 255);
+    }
+
+    #[test]
+    fn test_invalid_image_data() {
+        let png = testing_image(4, 4, ColorType::Grayscale);
+        let ihdr = png.chunks().next().unwrap().clone();
+
+        let no_idat = Png::from_chunks(vec![ihdr.clone()]);
+        assert!(ImageData::from_png(&no_idat).is_err());
+
+        let short = ImageData::from_png(&png).unwrap();
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&short.samples()[..10]).unwrap();
+        let truncated = Chunk::new(ChunkType::IDAT, encoder.finish().unwrap());
+        assert!(ImageData::from_png(&Png::from_chunks(vec![ihdr.clone(), truncated])).is_err());
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&[5, 0, 0, 0, 0].repeat(4)).unwrap();
+        let bad_filter = Chunk::new(ChunkType::IDAT, encoder.finish().unwrap());
+        assert!(ImageData::from_png(&Png::from_chunks(vec![ihdr, bad_filter])).is_err());
+    }
+
+    #[test]
+    fn test_maximal_header() {
+        let header = Ihdr {
+            width: 0x7fff_ffff,
+            height: 0x7fff_ffff,
+            bit_depth: 16,
+            ..Ihdr::rgba(1, 1)
+        };
+        let png = Png::from_chunks(vec![header.to_chunk(), Chunk::new(ChunkType::IDAT, vec![])]);
+        assert!(matches!(
+            ImageData::from_png(&png),
+            Err(crate::Error::ImageData(ImageDataError::TooLarge(..)))
+        ));
+        assert!(ImageData::new(header.clone(), vec![]).is_err());
+        assert!(crate::stego::carriers(&header).is_err());
+    }
+}
@@ -0,0 +1,413 @@
+use std::{
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[cfg(feature = "cloud")]
+use crate::storage::{Bucket, ObjectUri};
+use crate::{carrier::Carrier, plan, png::Png, progress, Result};
+
+#[derive(Debug)]
+pub enum InputError {
+    NoMatches(String),
+    NeedsFeature {
+        input: String,
+        feature: &'static str,
+    },
+}
+
+impl std::error::Error for InputError {}
+
+impl Display for InputError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoMatches(spec) => write!(f, "No supported images found for '{}'", spec),
+            Self::NeedsFeature { input, feature } => write!(
+                f,
+                "Can't use '{}', picmes was built without the {} feature",
+                input, feature
+            ),
+        }
+    }
+}
+
+/// The extensions of the files in directories commands that only read
+/// pngs pick up
+pub const PNG_EXTENSIONS: &[&str] = &["png"];
+/// The extensions of every format a carrier can be read from
+pub const IMAGE_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "gif", "webp", "bmp", "tif", "tiff", "ico", "cur",
+];
+
+/// The files a command should operate on
+#[derive(Debug, PartialEq)]
+pub struct Inputs {
+    pub files: Vec<PathBuf>,
+    /// Set when the inputs came from more than one path, a glob or a
+    /// directory, so results should be reported per file
+    pub batch: bool,
+}
+
+/// Expands paths, glob patterns and directories into a list of files.
+/// Directories contribute the files with one of `extensions` directly
+/// inside them, or every such file below them when `recursive` is set.
+/// http and https URLs are kept as they are, to be fetched by `read`.
+/// Object URIs are too, unless their key is empty, ends in a `/` or is a
+/// glob pattern, which lists the objects it covers like a directory
+pub fn expand(specs: &[String], recursive: bool, extensions: &[&str]) -> Result<Inputs> {
+    let mut files = Vec::new();
+    let mut batch = specs.len() > 1;
+
+    for spec in specs {
+        let path = Path::new(spec);
+        let found = if is_url(spec) {
+            files.push(path.to_path_buf());
+            continue;
+        } else if is_object_uri(spec) {
+            let key = spec.splitn(4, '/').nth(3).unwrap_or("");
+            if !key.is_empty() && !key.ends_with('/') && !is_pattern(key) {
+                files.push(path.to_path_buf());
+                continue;
+            }
+            objects_in(spec, recursive, extensions)?
+        } else if path.is_dir() {
+            images_in(path, recursive, extensions)?
+        } else if !path.exists() && is_pattern(spec) {
+            let mut found = Vec::new();
+            for entry in glob::glob(spec)? {
+                let entry = entry?;
+                if entry.is_dir() {
+                    found.extend(images_in(&entry, recursive, extensions)?);
+                } else {
+                    found.push(entry);
+                }
+            }
+            found
+        } else {
+            files.push(path.to_path_buf());
+            continue;
+        };
+
+        if found.is_empty() {
+            return Err(InputError::NoMatches(spec.clone()).into());
+        }
+        batch = true;
+        files.extend(found);
+    }
+
+    Ok(Inputs { files, batch })
+}
+
+/// Whether `spec` is an http or https URL rather than a path
+pub fn is_url(spec: &str) -> bool {
+    spec.starts_with("http://") || spec.starts_with("https://")
+}
+
+/// Whether `spec` is an s3:// or gs:// URI of an object in a bucket
+pub fn is_object_uri(spec: &str) -> bool {
+    spec.starts_with("s3://") || spec.starts_with("gs://")
+}
+
+/// Whether `path` is a URL or object URI `expand` passed through rather
+/// than a local file. Objects can be written back to, URLs can't
+pub fn is_remote(path: &Path) -> bool {
+    path.to_str()
+        .is_some_and(|spec| is_url(spec) || is_object_uri(spec))
+}
+
+/// The contents of the file at `path`, fetched when it is a URL or an
+/// object URI
+pub fn read(path: &Path) -> Result<Vec<u8>> {
+    match path.to_str() {
+        Some(spec) if is_url(spec) => fetch(spec),
+        Some(spec) if is_object_uri(spec) => get_object(spec),
+        _ => progress::read(path),
+    }
+}
+
+/// Writes `contents` to `path`, uploading them when it is an object URI and
+/// replacing a local file atomically otherwise
+pub fn write(path: &Path, contents: Vec<u8>) -> Result<()> {
+    match path.to_str() {
+        Some(spec) if is_object_uri(spec) => put_object(spec, contents),
+        _ => plan::write_atomic(path, &contents),
+    }
+}
+
+/// Reads the png at `path`, which may be a URL
+pub fn read_png(path: &Path) -> Result<Png> {
+    match is_remote(path) {
+        true => Png::try_from(read(path)?.as_slice()),
+        false => Png::from_reader(progress::open(path)?),
+    }
+}
+
+/// Reads the image at `path`, which may be a URL
+pub fn read_carrier(path: &Path) -> Result<Carrier> {
+    match is_remote(path) {
+        true => Carrier::from_bytes(&read(path)?, path),
+        false => Carrier::from_reader(progress::open(path)?, path),
+    }
+}
+
+#[cfg(feature = "net")]
+fn fetch(url: &str) -> Result<Vec<u8>> {
+    crate::net::fetch(url)
+}
+
+#[cfg(not(feature = "net"))]
+fn fetch(url: &str) -> Result<Vec<u8>> {
+    Err(needs_feature(url, "net"))
+}
+
+#[cfg(feature = "cloud")]
+fn get_object(spec: &str) -> Result<Vec<u8>> {
+    let uri: ObjectUri = spec.parse()?;
+    Bucket::open(&uri)?.get(&uri.key)
+}
+
+#[cfg(feature = "cloud")]
+fn put_object(spec: &str, contents: Vec<u8>) -> Result<()> {
+    let uri: ObjectUri = spec.parse()?;
+    Bucket::open(&uri)?.put(&uri.key, contents)
+}
+
+/// The objects `spec` covers, as object URIs
+#[cfg(feature = "cloud")]
+fn objects_in(spec: &str, recursive: bool, extensions: &[&str]) -> Result<Vec<PathBuf>> {
+    let uri: ObjectUri = spec.parse()?;
+    let literal = match uri.key.find(['*', '?', '[']) {
+        Some(end) => &uri.key[..end],
+        None => &uri.key,
+    };
+    let prefix = &literal[..literal.rfind('/').map_or(0, |slash| slash + 1)];
+    // a pattern is matched against every key below its literal start
+    let recursive = recursive || is_pattern(&uri.key);
+    let keys = Bucket::open(&uri)?.list(prefix, recursive)?;
+    Ok(select_keys(keys, &uri.key, extensions)?
+        .into_iter()
+        .map(|key| PathBuf::from(uri.with_key(key).to_string()))
+        .collect())
+}
+
+/// The `keys` a listing for `key` keeps: those matching it as a pattern, or
+/// those with one of `extensions` when it names a prefix
+#[cfg(feature = "cloud")]
+fn select_keys(keys: Vec<String>, key: &str, extensions: &[&str]) -> Result<Vec<String>> {
+    let pattern = match is_pattern(key) {
+        true => Some(glob::Pattern::new(key)?),
+        false => None,
+    };
+    let options = glob::MatchOptions {
+        require_literal_separator: true,
+        ..Default::default()
+    };
+    Ok(keys
+        .into_iter()
+        .filter(|found| match &pattern {
+            Some(pattern) => pattern.matches_with(found, options),
+            None => has_extension(Path::new(found), extensions),
+        })
+        .collect())
+}
+
+#[cfg(not(feature = "cloud"))]
+fn get_object(spec: &str) -> Result<Vec<u8>> {
+    Err(needs_feature(spec, "cloud"))
+}
+
+#[cfg(not(feature = "cloud"))]
+fn put_object(spec: &str, _contents: Vec<u8>) -> Result<()> {
+    Err(needs_feature(spec, "cloud"))
+}
+
+#[cfg(not(feature = "cloud"))]
+fn objects_in(spec: &str, _recursive: bool, _extensions: &[&str]) -> Result<Vec<PathBuf>> {
+    Err(needs_feature(spec, "cloud"))
+}
+
+#[cfg(any(not(feature = "net"), not(feature = "cloud")))]
+fn needs_feature(input: &str, feature: &'static str) -> crate::Error {
+    InputError::NeedsFeature {
+        input: input.to_string(),
+        feature,
+    }
+    .into()
+}
+
+fn is_pattern(spec: &str) -> bool {
+    spec.contains(['*', '?', '['])
+}
+
+/// The files with one of `extensions` directly inside `dir`, or anywhere
+/// below it when `recursive` is set, sorted
+pub fn images_in(dir: &Path, recursive: bool, extensions: &[&str]) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            if recursive {
+                files.extend(images_in(&path, recursive, extensions)?);
+            }
+        } else if has_extension(&path, extensions) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Whether the extension of `path` is one of `extensions`, ignoring case
+pub fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| extensions.iter().any(|e| ext.eq_ignore_ascii_case(e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates a directory tree with images at the top level and in a subdirectory
+    fn testing_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("picmes-{}-{}", std::process::id(), name));
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        for file in ["a.png", "b.PNG", "d.jpg", "notes.txt", "nested/c.png"] {
+            fs::write(dir.join(file), []).unwrap();
+        }
+        dir
+    }
+
+    fn spec(path: &Path) -> String {
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn test_expand_single_file() {
+        let inputs = expand(&["image.png".to_string()], false, PNG_EXTENSIONS).unwrap();
+        assert_eq!(
+            inputs,
+            Inputs {
+                files: vec![PathBuf::from("image.png")],
+                batch: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_expand_multiple_files() {
+        let inputs = expand(
+            &["a.png".to_string(), "b.png".to_string()],
+            false,
+            PNG_EXTENSIONS,
+        )
+        .unwrap();
+        assert_eq!(inputs.files.len(), 2);
+        assert!(inputs.batch);
+    }
+
+    #[test]
+    fn test_expand_directory() {
+        let dir = testing_dir("expand-dir");
+
+        let inputs = expand(&[spec(&dir)], false, PNG_EXTENSIONS).unwrap();
+        assert_eq!(inputs.files, [dir.join("a.png"), dir.join("b.PNG")]);
+        assert!(inputs.batch);
+
+        let inputs = expand(&[spec(&dir)], true, PNG_EXTENSIONS).unwrap();
+        assert_eq!(
+            inputs.files,
+            [
+                dir.join("a.png"),
+                dir.join("b.PNG"),
+                dir.join("nested/c.png")
+            ]
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_expand_directory_of_images() {
+        let dir = testing_dir("expand-images");
+        let inputs = expand(&[spec(&dir)], false, IMAGE_EXTENSIONS).unwrap();
+        assert_eq!(
+            inputs.files,
+            [dir.join("a.png"), dir.join("b.PNG"), dir.join("d.jpg")]
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_expand_glob() {
+        let dir = testing_dir("expand-glob");
+
+        let inputs = expand(&[spec(&dir.join("*.png"))], false, PNG_EXTENSIONS).unwrap();
+        assert_eq!(inputs.files, [dir.join("a.png")]);
+        assert!(inputs.batch);
+
+        let inputs = expand(&[spec(&dir.join("**/*.png"))], false, PNG_EXTENSIONS).unwrap();
+        assert_eq!(inputs.files, [dir.join("a.png"), dir.join("nested/c.png")]);
+
+        assert!(expand(&[spec(&dir.join("*.gif"))], false, PNG_EXTENSIONS).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_expand_keeps_urls() {
+        let urls = [
+            "https://example.com/image.png?size=[large]".to_string(),
+            "http://example.com/".to_string(),
+        ];
+        let inputs = expand(&urls, true, PNG_EXTENSIONS).unwrap();
+        assert_eq!(
+            inputs.files,
+            [PathBuf::from(&urls[0]), PathBuf::from(&urls[1])]
+        );
+        assert!(inputs.files.iter().all(|file| is_remote(file)));
+        assert!(!is_remote(Path::new("http.png")) && !is_url("ftp://example.com/a.png"));
+    }
+
+    #[test]
+    fn test_expand_keeps_object_uris() {
+        let specs = ["s3://corpus/2024/a.png".to_string()];
+        let inputs = expand(&specs, false, PNG_EXTENSIONS).unwrap();
+        assert_eq!(inputs.files, [PathBuf::from(&specs[0])]);
+        assert!(!inputs.batch && is_remote(&inputs.files[0]));
+        assert!(is_object_uri("gs://corpus/a.png") && !is_object_uri("s3:/corpus/a.png"));
+    }
+
+    #[cfg(not(feature = "cloud"))]
+    #[test]
+    fn test_object_uris_need_the_cloud_feature() {
+        let error = expand(&["s3://corpus/".to_string()], false, PNG_EXTENSIONS).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Can't use 's3://corpus/', picmes was built without the cloud feature"
+        );
+        assert!(read(Path::new("gs://corpus/a.png")).is_err());
+    }
+
+    #[cfg(feature = "cloud")]
+    #[test]
+    fn test_select_keys() {
+        let keys = || {
+            ["2024/a.png", "2024/b.jpg", "2024/03/c.png", "2025/d.png"]
+                .map(String::from)
+                .to_vec()
+        };
+        // listing a prefix already leaves out the keys outside it
+        assert_eq!(
+            select_keys(keys(), "2024/", PNG_EXTENSIONS).unwrap(),
+            ["2024/a.png", "2024/03/c.png", "2025/d.png"]
+        );
+        assert_eq!(
+            select_keys(keys(), "2024/*.png", PNG_EXTENSIONS).unwrap(),
+            ["2024/a.png"]
+        );
+        assert_eq!(
+            select_keys(keys(), "**/*.png", PNG_EXTENSIONS).unwrap(),
+            ["2024/a.png", "2024/03/c.png", "2025/d.png"]
+        );
+    }
+}
@@ -0,0 +1,431 @@
+use std::{convert::TryFrom, fmt::Display, path::Path};
+
+use crate::{carrier::Section, chunk::Chunk, chunk_type::ChunkType, plan, Result};
+
+#[derive(Debug)]
+pub enum JpegError {
+    InvalidStartOfImage,
+    MissingMarker(usize),
+    TruncatedSegment(u8),
+    PayloadTooLong(usize),
+}
+
+impl std::error::Error for JpegError {}
+
+impl Display for JpegError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidStartOfImage => write!(f, "A jpeg should start with the bytes FF D8"),
+            Self::MissingMarker(offset) => write!(f, "Expected a marker at offset {:#x}", offset),
+            Self::TruncatedSegment(marker) => write!(
+                f,
+                "The file ends in the middle of a {} segment",
+                marker_name(*marker)
+            ),
+            Self::PayloadTooLong(length) => write!(
+                f,
+                "A jpeg segment can hold at most {} payload bytes, not {}",
+                Jpeg::MAX_PAYLOAD_LENGTH,
+                length
+            ),
+        }
+    }
+}
+
+const SOI: u8 = 0xd8;
+const EOI: u8 = 0xd9;
+const SOS: u8 = 0xda;
+const APP0: u8 = 0xe0;
+const APP15: u8 = 0xef;
+const COM: u8 = 0xfe;
+
+/// Starts the data of every segment picmes writes, followed by the chunk
+/// type. The NUL keeps it from being mistaken for a readable comment
+const TAG: &[u8] = b"picmes\0";
+/// Segment lengths count their own two bytes
+const LENGTH_LENGTH: usize = 2;
+const MAX_SEGMENT_DATA_LENGTH: usize = u16::MAX as usize - LENGTH_LENGTH;
+
+/// The segment kind payloads are written to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum SegmentKind {
+    /// An APP15 application segment, which no common software reads
+    #[default]
+    App15,
+    /// A comment segment
+    Com,
+}
+
+impl SegmentKind {
+    fn marker(self) -> u8 {
+        match self {
+            Self::App15 => APP15,
+            Self::Com => COM,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    /// A marker without a length or data, like SOI, EOI and RSTn
+    Standalone(u8),
+    /// A marker followed by its data, with the length bytes left out
+    Marker { marker: u8, data: Vec<u8> },
+    /// A segment written by picmes, holding a payload chunk
+    Payload { marker: u8, chunk: Chunk },
+    /// Entropy coded image data following a SOS segment, restart markers
+    /// included
+    ScanData(Vec<u8>),
+}
+
+impl Segment {
+    fn marker_data(marker: u8, data: Vec<u8>) -> Self {
+        if marker == COM || marker == APP15 {
+            if let Some(chunk) = payload_chunk(&data) {
+                return Self::Payload { marker, chunk };
+            }
+        }
+        Self::Marker { marker, data }
+    }
+
+    fn write_to(&self, bytes: &mut Vec<u8>) {
+        let mut write_marker = |marker: u8, parts: &[&[u8]]| {
+            let length: usize = parts.iter().map(|part| part.len()).sum();
+            bytes.extend([0xff, marker]);
+            bytes.extend(((length + LENGTH_LENGTH) as u16).to_be_bytes());
+            parts.iter().for_each(|part| bytes.extend(*part));
+        };
+        match self {
+            Self::Standalone(marker) => bytes.extend([0xff, *marker]),
+            Self::Marker { marker, data } => write_marker(*marker, &[data]),
+            Self::Payload { marker, chunk } => {
+                write_marker(*marker, &[TAG, &chunk.chunk_type().bytes(), chunk.data()])
+            }
+            Self::ScanData(data) => bytes.extend(data),
+        }
+    }
+
+    /// The length of what `write_to` writes
+    fn encoded_length(&self) -> usize {
+        match self {
+            Self::Standalone(_) => 2,
+            Self::Marker { data, .. } => 2 + LENGTH_LENGTH + data.len(),
+            Self::Payload { chunk, .. } => {
+                2 + LENGTH_LENGTH + TAG.len() + chunk.chunk_type().bytes().len() + chunk.length()
+            }
+            Self::ScanData(data) => data.len(),
+        }
+    }
+}
+
+/// The chunk stored in a segment written by picmes
+fn payload_chunk(data: &[u8]) -> Option<Chunk> {
+    let rest = data.strip_prefix(TAG)?;
+    let chunk_type = ChunkType::try_from(<[u8; 4]>::try_from(rest.get(..4)?).ok()?).ok()?;
+    Some(Chunk::new(chunk_type, rest[4..].to_vec()))
+}
+
+/// A short name for a marker, as used in the JPEG specification
+pub fn marker_name(marker: u8) -> String {
+    match marker {
+        SOI => "SOI".to_string(),
+        EOI => "EOI".to_string(),
+        SOS => "SOS".to_string(),
+        COM => "COM".to_string(),
+        0xc4 => "DHT".to_string(),
+        0xcc => "DAC".to_string(),
+        0xdb => "DQT".to_string(),
+        0xdd => "DRI".to_string(),
+        0xc0..=0xcf => format!("SOF{}", marker - 0xc0),
+        0xd0..=0xd7 => format!("RST{}", marker - 0xd0),
+        APP0..=APP15 => format!("APP{}", marker - APP0),
+        _ => format!("{:#04X}", marker),
+    }
+}
+
+/// Whether `marker` stands alone, without a length and data
+fn is_standalone(marker: u8) -> bool {
+    matches!(marker, SOI | EOI | 0x01 | 0xd0..=0xd7)
+}
+
+/// A jpeg as a list of segments. Payloads are stored in APP15 or comment
+/// segments tagged so they can be told apart from any others
+#[derive(Debug, Clone, PartialEq)]
+pub struct Jpeg {
+    segments: Vec<Segment>,
+    /// Anything after the EOI marker, kept as is
+    trailer: Vec<u8>,
+}
+
+impl Jpeg {
+    pub const START_OF_IMAGE: [u8; 2] = [0xff, SOI];
+    pub const MAX_PAYLOAD_LENGTH: usize = MAX_SEGMENT_DATA_LENGTH - TAG.len() - 4;
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        Self::try_from(std::fs::read(path)?.as_slice())
+    }
+
+    /// The payload chunks stored in the jpeg, in file order
+    pub fn chunks(&self) -> impl Iterator<Item = &Chunk> {
+        self.segments.iter().filter_map(|segment| match segment {
+            Segment::Payload { chunk, .. } => Some(chunk),
+            _ => None,
+        })
+    }
+
+    /// Lists every segment with its length
+    pub fn sections(&self) -> Vec<Section> {
+        self.segments
+            .iter()
+            .map(|segment| {
+                let mut bytes = Vec::new();
+                segment.write_to(&mut bytes);
+                let (name, chunk_type) = match segment {
+                    Segment::Standalone(marker) | Segment::Marker { marker, .. } => {
+                        (marker_name(*marker), None)
+                    }
+                    Segment::Payload { marker, chunk } => {
+                        (marker_name(*marker), Some(chunk.chunk_type().clone()))
+                    }
+                    Segment::ScanData(_) => ("scan data".to_string(), None),
+                };
+                Section {
+                    name,
+                    length: bytes.len(),
+                    chunk_type,
+                }
+            })
+            .collect()
+    }
+
+    /// Stores `chunk` in a new `kind` segment after the leading application
+    /// and comment segments, so JFIF and Exif headers stay first
+    pub fn insert_chunk(&mut self, chunk: Chunk, kind: SegmentKind) -> Result<()> {
+        if chunk.length() > Self::MAX_PAYLOAD_LENGTH {
+            return Err(JpegError::PayloadTooLong(chunk.length()).into());
+        }
+        let index = self
+            .segments
+            .iter()
+            .position(|segment| match segment {
+                Segment::Standalone(marker) => *marker != SOI,
+                Segment::Marker { marker, .. } => !matches!(*marker, APP0..=APP15 | COM),
+                Segment::Payload { .. } => false,
+                Segment::ScanData(_) => true,
+            })
+            .unwrap_or(self.segments.len());
+        let marker = kind.marker();
+        self.segments
+            .insert(index, Segment::Payload { marker, chunk });
+        Ok(())
+    }
+
+    /// Removes the segments holding payloads matching `predicate`, returning
+    /// their chunks
+    pub fn remove_chunks_where(&mut self, mut predicate: impl FnMut(&Chunk) -> bool) -> Vec<Chunk> {
+        let mut removed = Vec::new();
+        let mut kept = Vec::new();
+        for segment in self.segments.drain(..) {
+            match segment {
+                Segment::Payload { chunk, .. } if predicate(&chunk) => removed.push(chunk),
+                segment => kept.push(segment),
+            }
+        }
+        self.segments = kept;
+        removed
+    }
+
+    pub fn remove_first_chunk(&mut self, chunk_type: &str) -> Option<Chunk> {
+        let mut first = true;
+        self.remove_chunks_where(|chunk| {
+            chunk.chunk_type().to_string() == chunk_type && std::mem::take(&mut first)
+        })
+        .pop()
+    }
+
+    pub fn remove_all_chunks(&mut self, chunk_type: &str) -> Vec<Chunk> {
+        self.remove_chunks_where(|chunk| chunk.chunk_type().to_string() == chunk_type)
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for segment in &self.segments {
+            segment.write_to(&mut bytes);
+        }
+        bytes.extend(&self.trailer);
+        bytes
+    }
+
+    /// The size of the jpeg in bytes once written
+    pub fn encoded_length(&self) -> usize {
+        let segments: usize = self.segments.iter().map(Segment::encoded_length).sum();
+        segments + self.trailer.len()
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        plan::write_atomic(path.as_ref(), &self.as_bytes())
+    }
+}
+
+impl TryFrom<&[u8]> for Jpeg {
+    type Error = crate::Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        if !bytes.starts_with(&Self::START_OF_IMAGE) {
+            return Err(JpegError::InvalidStartOfImage.into());
+        }
+        let mut segments = Vec::new();
+        let mut offset = 0;
+        loop {
+            if bytes.get(offset) != Some(&0xff) {
+                return Err(JpegError::MissingMarker(offset).into());
+            }
+            // any number of fill bytes may precede a marker
+            while bytes.get(offset + 1) == Some(&0xff) {
+                offset += 1;
+            }
+            let marker = *bytes
+                .get(offset + 1)
+                .ok_or(JpegError::MissingMarker(offset))?;
+            offset += 2;
+
+            if is_standalone(marker) {
+                segments.push(Segment::Standalone(marker));
+                if marker == EOI {
+                    break;
+                }
+                continue;
+            }
+            let length = bytes
+                .get(offset..offset + LENGTH_LENGTH)
+                .map(|length| u16::from_be_bytes([length[0], length[1]]) as usize)
+                .filter(|length| *length >= LENGTH_LENGTH)
+                .ok_or(JpegError::TruncatedSegment(marker))?;
+            let data = bytes
+                .get(offset + LENGTH_LENGTH..offset + length)
+                .ok_or(JpegError::TruncatedSegment(marker))?;
+            segments.push(Segment::marker_data(marker, data.to_vec()));
+            offset += length;
+
+            if marker == SOS {
+                // scan data runs to the first marker that is neither a
+                // stuffed zero nor a restart marker
+                let start = offset;
+                while offset < bytes.len() {
+                    match (bytes[offset], bytes.get(offset + 1)) {
+                        (0xff, Some(0x00 | 0xd0..=0xd7)) => offset += 2,
+                        (0xff, _) => break,
+                        _ => offset += 1,
+                    }
+                }
+                segments.push(Segment::ScanData(bytes[start..offset].to_vec()));
+                if offset >= bytes.len() {
+                    return Err(JpegError::TruncatedSegment(SOS).into());
+                }
+            }
+        }
+        Ok(Self {
+            segments,
+            trailer: bytes[offset..].to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{
+        carrier::tests::{assert_chunks_round_trip, assert_round_trip},
+        png::tests::testing_chunk,
+    };
+
+    /// The segment structure of a small baseline jpeg with a JFIF header.
+    /// The tables and scan data are placeholders, nothing here decodes them
+    pub(crate) fn testing_jpeg_bytes() -> Vec<u8> {
+        let mut bytes = vec![0xff, SOI];
+        let mut segment = |marker: u8, data: &[u8]| {
+            bytes.extend([0xff, marker]);
+            bytes.extend(((data.len() + 2) as u16).to_be_bytes());
+            bytes.extend(data);
+        };
+        segment(APP0, b"JFIF\0\x01\x01\0\0\x01\0\x01\0\0");
+        segment(0xdb, &[0; 65]);
+        segment(0xc0, &[8, 0, 1, 0, 1, 1, 1, 0x11, 0]);
+        segment(0xc4, &[0; 20]);
+        segment(SOS, &[1, 1, 0, 0, 0x3f, 0]);
+        bytes.extend([0x12, 0xff, 0x00, 0x34, 0xff, 0xd0, 0x56]);
+        bytes.extend([0xff, EOI]);
+        bytes
+    }
+
+    fn markers(jpeg: &Jpeg) -> Vec<String> {
+        jpeg.sections().into_iter().map(|s| s.name).collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let bytes = testing_jpeg_bytes();
+        let jpeg = Jpeg::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(
+            markers(&jpeg),
+            [
+                "SOI",
+                "APP0",
+                "DQT",
+                "SOF0",
+                "DHT",
+                "SOS",
+                "scan data",
+                "EOI"
+            ]
+        );
+        assert_eq!(jpeg.sections()[6].length, 7);
+        assert_round_trip(&bytes);
+        assert_round_trip(&[&bytes[..], b"appended"].concat());
+    }
+
+    #[test]
+    fn test_insert_and_remove_chunks() {
+        let bytes = testing_jpeg_bytes();
+        let mut jpeg = Jpeg::try_from(bytes.as_slice()).unwrap();
+        jpeg.insert_chunk(testing_chunk(b"first"), SegmentKind::App15)
+            .unwrap();
+        jpeg.insert_chunk(testing_chunk(b"second"), SegmentKind::Com)
+            .unwrap();
+        assert_eq!(markers(&jpeg)[..5], ["SOI", "APP0", "APP15", "COM", "DQT"]);
+        let removed = assert_chunks_round_trip(jpeg, &[b"first", b"second"]);
+        assert_eq!(removed.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_foreign_segments_are_not_payloads() {
+        let mut bytes = testing_jpeg_bytes();
+        let comment = [0xff, COM, 0, 7, b'h', b'e', b'l', b'l', b'o'];
+        bytes.splice(2..2, comment);
+        let jpeg = Jpeg::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(jpeg.chunks().count(), 0);
+        assert_eq!(jpeg.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_payload_length_limit() {
+        let mut jpeg = Jpeg::try_from(testing_jpeg_bytes().as_slice()).unwrap();
+        let largest = vec![0; Jpeg::MAX_PAYLOAD_LENGTH];
+        jpeg.insert_chunk(testing_chunk(&largest), SegmentKind::App15)
+            .unwrap();
+        assert_eq!(Jpeg::try_from(jpeg.as_bytes().as_slice()).unwrap(), jpeg);
+        let too_long = vec![0; Jpeg::MAX_PAYLOAD_LENGTH + 1];
+        assert!(jpeg
+            .insert_chunk(testing_chunk(&too_long), SegmentKind::App15)
+            .is_err());
+    }
+
+    #[test]
+    fn test_invalid_jpegs() {
+        assert!(Jpeg::try_from(&b"not a jpeg"[..]).is_err());
+        let bytes = testing_jpeg_bytes();
+        assert!(Jpeg::try_from(&bytes[..bytes.len() - 2]).is_err());
+        assert!(Jpeg::try_from(&bytes[..10]).is_err());
+    }
+}
@@ -0,0 +1,124 @@
+//! Hides payloads in chunks of PNG files, or wherever other image formats
+//! leave room for extra data. The `cli` feature adds the command line
+//! arguments and the commands of the picmes binary, the `serde` feature
+//! makes `Png`, `Chunk` and `ChunkType` serializable, the `async` feature
+//! reads and writes pngs over tokio's async I/O traits and the `wasm`
+//! feature exposes encode, decode and listChunks to JavaScript. The `ffi`
+//! feature adds a C API, declared in include/picmes.h. Without the
+//! default `std` feature the crate is no_std, leaving the chunk types, the
+//! errors and the chunk stream parser in `parse`, which only need alloc
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod apng;
+#[cfg(feature = "cli")]
+pub mod args;
+#[cfg(feature = "async")]
+mod async_io;
+#[cfg(feature = "std")]
+pub mod bmp;
+#[cfg(feature = "std")]
+pub mod carrier;
+#[cfg(feature = "std")]
+pub mod carve;
+pub mod chunk;
+pub mod chunk_type;
+#[cfg(feature = "clipboard")]
+pub mod clipboard;
+#[cfg(feature = "cli")]
+pub mod commands;
+#[cfg(feature = "std")]
+pub mod compress;
+#[cfg(feature = "std")]
+pub mod crypto;
+#[cfg(feature = "std")]
+pub mod detect;
+#[cfg(feature = "std")]
+pub mod diff;
+#[cfg(feature = "std")]
+pub mod envelope;
+pub mod error;
+#[cfg(feature = "std")]
+pub mod exif;
+#[cfg(feature = "std")]
+pub mod fec;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "std")]
+pub mod gif;
+#[cfg(feature = "std")]
+pub mod history;
+#[cfg(feature = "std")]
+pub mod ico;
+#[cfg(feature = "std")]
+pub mod ihdr;
+#[cfg(feature = "std")]
+pub mod image_data;
+#[cfg(feature = "cli")]
+pub mod inputs;
+#[cfg(feature = "std")]
+pub mod jpeg;
+#[cfg(feature = "keyring")]
+pub mod keys;
+#[cfg(feature = "std")]
+pub mod metadata;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+#[cfg(feature = "net")]
+pub mod net;
+pub mod parse;
+#[cfg(feature = "cli")]
+pub mod passphrase;
+#[cfg(feature = "std")]
+pub mod plan;
+#[cfg(feature = "std")]
+pub mod png;
+#[cfg(feature = "cli")]
+pub mod progress;
+#[cfg(feature = "qr")]
+pub mod qr;
+#[cfg(feature = "std")]
+pub mod repair;
+#[cfg(feature = "cli")]
+pub mod report;
+#[cfg(feature = "std")]
+pub mod scan;
+#[cfg(feature = "std")]
+pub mod seal;
+#[cfg(feature = "std")]
+pub mod signing;
+#[cfg(feature = "std")]
+pub mod split;
+#[cfg(feature = "std")]
+pub mod stats;
+#[cfg(feature = "std")]
+pub mod stego;
+#[cfg(feature = "cloud")]
+pub mod storage;
+#[cfg(feature = "std")]
+pub mod text_chunk;
+#[cfg(feature = "std")]
+pub mod tiff;
+#[cfg(feature = "std")]
+pub mod time_chunk;
+#[cfg(feature = "std")]
+pub mod verify;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "watch")]
+pub mod watch;
+#[cfg(feature = "std")]
+pub mod webp;
+
+#[cfg(feature = "std")]
+pub use carrier::Carrier;
+pub use chunk::{Chunk, ChunkFilters, ChunkRef};
+pub use chunk_type::ChunkType;
+pub use error::{Failure, PicmesError};
+#[cfg(feature = "std")]
+pub use png::Png;
+
+pub type Error = PicmesError;
+pub type Result<T> = core::result::Result<T, Error>;
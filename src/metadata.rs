@@ -0,0 +1,233 @@
+use std::fmt::Display;
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, Error, Result};
+
+#[derive(Debug)]
+pub enum MetadataError {
+    Missing(ChunkType),
+    WrongChunkType(ChunkType, String),
+    InvalidLength(ChunkType, usize, usize),
+    InvalidValue(&'static str, u32),
+}
+
+impl std::error::Error for MetadataError {}
+
+impl Display for MetadataError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Missing(chunk_type) => write!(f, "The png has no {} chunk", chunk_type),
+            Self::WrongChunkType(expected, found) => {
+                write!(f, "Expected a {} chunk but found {}", expected, found)
+            }
+            Self::InvalidLength(chunk_type, expected, length) => write!(
+                f,
+                "{} data should be {} bytes but is {}",
+                chunk_type, expected, length
+            ),
+            Self::InvalidValue(field, value) => write!(f, "Invalid {} {}", field, value),
+        }
+    }
+}
+
+/// Checks the type and length of `chunk`, returning its data
+fn expect_chunk(chunk: &Chunk, chunk_type: ChunkType, length: usize) -> Result<&[u8]> {
+    if chunk.chunk_type() != &chunk_type {
+        return Err(
+            MetadataError::WrongChunkType(chunk_type, chunk.chunk_type().to_string()).into(),
+        );
+    }
+    if chunk.length() != length {
+        return Err(MetadataError::InvalidLength(chunk_type, length, chunk.length()).into());
+    }
+    Ok(chunk.data())
+}
+
+const METRES_PER_INCH: f64 = 0.0254;
+
+/// The intended pixel size or aspect ratio stored in the pHYs chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PhysicalDimensions {
+    pub pixels_per_unit_x: u32,
+    pub pixels_per_unit_y: u32,
+    /// When false the values only give the pixel aspect ratio
+    pub in_metres: bool,
+}
+
+impl PhysicalDimensions {
+    pub const LENGTH: usize = 9;
+
+    /// Horizontal and vertical resolution in dots per inch, if the unit is
+    /// known
+    pub fn dpi(&self) -> Option<(f64, f64)> {
+        self.in_metres.then_some((
+            self.pixels_per_unit_x as f64 * METRES_PER_INCH,
+            self.pixels_per_unit_y as f64 * METRES_PER_INCH,
+        ))
+    }
+}
+
+impl TryFrom<&Chunk> for PhysicalDimensions {
+    type Error = Error;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        let data = expect_chunk(chunk, ChunkType::pHYs, Self::LENGTH)?;
+        let in_metres = match data[8] {
+            0 => false,
+            1 => true,
+            unit => return Err(MetadataError::InvalidValue("pHYs unit", unit as u32).into()),
+        };
+        Ok(Self {
+            pixels_per_unit_x: u32::from_be_bytes(data[0..4].try_into()?),
+            pixels_per_unit_y: u32::from_be_bytes(data[4..8].try_into()?),
+            in_metres,
+        })
+    }
+}
+
+impl Display for PhysicalDimensions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.dpi() {
+            Some((x, y)) => write!(
+                f,
+                "{}x{} pixels per metre ({:.0}x{:.0} dpi)",
+                self.pixels_per_unit_x, self.pixels_per_unit_y, x, y
+            ),
+            None => write!(
+                f,
+                "{}:{} pixel aspect ratio",
+                self.pixels_per_unit_x, self.pixels_per_unit_y
+            ),
+        }
+    }
+}
+
+/// The image gamma stored in the gAMA chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gamma {
+    /// Gamma times 100000, as stored in the file
+    pub scaled: u32,
+}
+
+impl Gamma {
+    pub const LENGTH: usize = 4;
+    const SCALE: f64 = 100_000.0;
+
+    pub fn value(&self) -> f64 {
+        self.scaled as f64 / Self::SCALE
+    }
+}
+
+impl TryFrom<&Chunk> for Gamma {
+    type Error = Error;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        let data = expect_chunk(chunk, ChunkType::gAMA, Self::LENGTH)?;
+        let scaled = u32::from_be_bytes(data.try_into()?);
+        if scaled == 0 {
+            return Err(MetadataError::InvalidValue("gamma", scaled).into());
+        }
+        Ok(Self { scaled })
+    }
+}
+
+impl Display for Gamma {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "gamma {:.5}", self.value())
+    }
+}
+
+/// The rendering intent stored in the sRGB chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderingIntent {
+    Perceptual,
+    RelativeColorimetric,
+    Saturation,
+    AbsoluteColorimetric,
+}
+
+impl RenderingIntent {
+    pub const LENGTH: usize = 1;
+}
+
+impl TryFrom<&Chunk> for RenderingIntent {
+    type Error = Error;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        let data = expect_chunk(chunk, ChunkType::sRGB, Self::LENGTH)?;
+        match data[0] {
+            0 => Ok(Self::Perceptual),
+            1 => Ok(Self::RelativeColorimetric),
+            2 => Ok(Self::Saturation),
+            3 => Ok(Self::AbsoluteColorimetric),
+            value => Err(MetadataError::InvalidValue("sRGB rendering intent", value as u32).into()),
+        }
+    }
+}
+
+impl Display for RenderingIntent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Perceptual => "perceptual",
+            Self::RelativeColorimetric => "relative colorimetric",
+            Self::Saturation => "saturation",
+            Self::AbsoluteColorimetric => "absolute colorimetric",
+        };
+        write!(f, "sRGB, {} rendering intent", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_physical_dimensions() {
+        let chunk = Chunk::new(ChunkType::pHYs, vec![0, 0, 14, 195, 0, 0, 14, 195, 1]);
+        let physical = PhysicalDimensions::try_from(&chunk).unwrap();
+        assert_eq!(physical.pixels_per_unit_x, 3779);
+        assert!(physical.in_metres);
+        assert_eq!(
+            physical.to_string(),
+            "3779x3779 pixels per metre (96x96 dpi)"
+        );
+
+        let aspect = PhysicalDimensions {
+            pixels_per_unit_x: 2,
+            pixels_per_unit_y: 1,
+            in_metres: false,
+        };
+        assert_eq!(aspect.dpi(), None);
+        assert_eq!(aspect.to_string(), "2:1 pixel aspect ratio");
+
+        let bad_unit = Chunk::new(ChunkType::pHYs, vec![0, 0, 0, 1, 0, 0, 0, 1, 2]);
+        assert!(PhysicalDimensions::try_from(&bad_unit).is_err());
+        let short = Chunk::new(ChunkType::pHYs, vec![0; 8]);
+        assert!(PhysicalDimensions::try_from(&short).is_err());
+    }
+
+    #[test]
+    fn test_gamma() {
+        let chunk = Chunk::new(ChunkType::gAMA, vec![0, 0, 177, 143]);
+        let gamma = Gamma::try_from(&chunk).unwrap();
+        assert_eq!(gamma.scaled, 45455);
+        assert!((gamma.value() - 0.45455).abs() < f64::EPSILON);
+        assert_eq!(gamma.to_string(), "gamma 0.45455");
+
+        assert!(Gamma::try_from(&Chunk::new(ChunkType::gAMA, vec![0; 4])).is_err());
+        assert!(Gamma::try_from(&Chunk::new(ChunkType::sRGB, vec![0; 4])).is_err());
+    }
+
+    #[test]
+    fn test_rendering_intent() {
+        let chunk = Chunk::new(ChunkType::sRGB, vec![1]);
+        let intent = RenderingIntent::try_from(&chunk).unwrap();
+        assert_eq!(intent, RenderingIntent::RelativeColorimetric);
+        assert_eq!(
+            intent.to_string(),
+            "sRGB, relative colorimetric rendering intent"
+        );
+
+        assert!(RenderingIntent::try_from(&Chunk::new(ChunkType::sRGB, vec![4])).is_err());
+        assert!(RenderingIntent::try_from(&Chunk::new(ChunkType::sRGB, vec![])).is_err());
+    }
+}
@@ -0,0 +1,99 @@
+use std::{fs::File, path::Path};
+
+use memmap2::Mmap;
+
+use crate::{
+    chunk::ChunkRef,
+    parse::{self, ChunkRefs},
+    png::{Png, PngError},
+    Result,
+};
+
+/// A png mapped into memory instead of read. Chunk headers are parsed as
+/// they are iterated over and chunk data is only paged in when it is read,
+/// so even a file with hundreds of megabytes of IDAT costs next to nothing
+/// to list
+#[derive(Debug)]
+pub struct MappedPng {
+    map: Mmap,
+}
+
+impl MappedPng {
+    /// Maps the file at `path`, checking only its signature
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let file = File::open(path)?;
+        // SAFETY: the map is read only, the usual caveat applies that another
+        // process truncating or rewriting the file while it is mapped shows up
+        // as changed bytes or a SIGBUS
+        let map = unsafe { Mmap::map(&file)? };
+        if map.len() < Png::HEADER_LENGTH {
+            return Err(PngError::TooSmall.into());
+        }
+        if !parse::has_signature(&map) {
+            return Err(PngError::InvalidStandardHeader.into());
+        }
+        Ok(Self { map })
+    }
+
+    /// The whole file, signature included
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.map
+    }
+
+    /// Iterates over the chunks in file order without copying their data or
+    /// checking their CRCs
+    pub fn chunks(&self) -> ChunkRefs<'_> {
+        ChunkRefs::new(&self.map[Png::HEADER_LENGTH..])
+    }
+
+    /// The first chunk of the given type, stopping at the first chunk that
+    /// can't be parsed
+    pub fn chunk_by_type(&self, chunk_type: &str) -> Option<ChunkRef<'_>> {
+        self.chunks()
+            .map_while(|chunk| chunk.ok())
+            .find(|chunk| chunk.chunk_type().to_string() == chunk_type)
+    }
+
+    /// Copies every chunk into an owned png, checking their CRCs, for when the
+    /// chunks have to be changed
+    pub fn to_png(&self) -> Result<Png> {
+        Png::try_from(self.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::tests::{testing_file, testing_png_bytes};
+    use std::fs;
+
+    #[test]
+    fn test_open_mmap() {
+        let path = testing_file("mmap");
+        let mapped = Png::open_mmap(&path).unwrap();
+        assert_eq!(mapped.as_bytes(), testing_png_bytes());
+
+        let types: Vec<String> = mapped
+            .chunks()
+            .map(|chunk| chunk.unwrap().chunk_type().to_string())
+            .collect();
+        assert_eq!(types, ["IHDR", "IDAT", "IEND"]);
+
+        let idat = mapped.chunk_by_type("IDAT").unwrap();
+        assert_eq!(idat.data(), [1, 2, 3]);
+        assert!(idat.verify_crc().is_ok());
+        assert!(mapped.chunk_by_type("tEXt").is_none());
+        assert_eq!(mapped.to_png().unwrap().as_bytes(), testing_png_bytes());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_open_mmap_rejects_other_files() {
+        let path = testing_file("mmap-invalid");
+        fs::write(&path, b"GIF89a, not a png").unwrap();
+        assert!(Png::open_mmap(&path).is_err());
+        fs::write(&path, [137, 80]).unwrap();
+        assert!(Png::open_mmap(&path).is_err());
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,80 @@
+use std::time::Duration;
+
+use crate::Result;
+
+/// The largest image `fetch` downloads before giving up
+pub const MAX_DOWNLOAD: u64 = 256 * 1024 * 1024;
+/// How long a download may take altogether, connecting included
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Downloads the file at an http or https `url` into memory, following
+/// redirects. A response other than 2xx is an error
+pub fn fetch(url: &str) -> Result<Vec<u8>> {
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(TIMEOUT))
+        .build()
+        .into();
+    let bytes = agent
+        .get(url)
+        .call()?
+        .into_body()
+        .with_config()
+        .limit(MAX_DOWNLOAD)
+        .read_to_vec()?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::png::tests::testing_png_bytes;
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    /// Serves `body` with `status` to the next `requests` requests on a local
+    /// port and returns the URL of `path` on it
+    pub(crate) fn serve(
+        status: &'static str,
+        body: Vec<u8>,
+        requests: usize,
+        path: &str,
+    ) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/{}", listener.local_addr().unwrap(), path);
+        thread::spawn(move || {
+            for stream in listener.incoming().take(requests) {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(&stream);
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    body.len()
+                )
+                .unwrap();
+                stream.write_all(&body).unwrap();
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn test_fetch() {
+        let url = serve("200 OK", testing_png_bytes(), 1, "image.png");
+        assert_eq!(fetch(&url).unwrap(), testing_png_bytes());
+
+        let url = serve("404 Not Found", Vec::new(), 1, "missing.png");
+        let error = fetch(&url).unwrap_err();
+        assert!(matches!(
+            error,
+            crate::Error::Http(ureq::Error::StatusCode(404))
+        ));
+    }
+}
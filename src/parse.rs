@@ -0,0 +1,188 @@
+use crate::{
+    chunk::{Chunk, ChunkRef},
+    chunk_type::ChunkType,
+    Result,
+};
+
+/// The eight bytes every png starts with
+pub const SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+
+/// Whether `bytes` start with the png signature
+pub fn has_signature(bytes: &[u8]) -> bool {
+    bytes.starts_with(&SIGNATURE)
+}
+
+/// Length of the png at the start of `bytes`, from its signature to the end
+/// of its IEND chunk, walking the chunk headers without checking any CRCs.
+/// None when there is no signature or the chunks break off before IEND
+pub fn png_length(bytes: &[u8]) -> Option<usize> {
+    if !has_signature(bytes) {
+        return None;
+    }
+    let mut chunks = ChunkRefs::new(&bytes[SIGNATURE.len()..]);
+    while let Some(chunk) = chunks.next() {
+        if chunk.ok()?.chunk_type() == &ChunkType::IEND {
+            return Some(bytes.len() - chunks.remaining().len());
+        }
+    }
+    None
+}
+
+/// Parses a chunk stream one chunk at a time without copying anything,
+/// leaving crc checks to the caller. After the first error nothing more is
+/// yielded, since the boundaries past a damaged chunk can't be trusted
+#[derive(Debug, Clone)]
+pub struct ChunkRefs<'a> {
+    remaining: &'a [u8],
+}
+
+impl<'a> ChunkRefs<'a> {
+    /// Iterates over the chunks in `stream`, which starts right after the
+    /// signature
+    pub fn new(stream: &'a [u8]) -> Self {
+        Self { remaining: stream }
+    }
+
+    /// The bytes not parsed yet
+    pub fn remaining(&self) -> &'a [u8] {
+        self.remaining
+    }
+}
+
+impl<'a> Iterator for ChunkRefs<'a> {
+    type Item = Result<ChunkRef<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining.is_empty() {
+            return None;
+        }
+        match ChunkRef::try_from(self.remaining) {
+            Ok(chunk) => {
+                self.remaining = &self.remaining[chunk.length() + Chunk::META_DATA_LENGTH..];
+                Some(Ok(chunk))
+            }
+            Err(error) => {
+                self.remaining = &[];
+                Some(Err(error))
+            }
+        }
+    }
+}
+
+/// Like `ChunkRefs`, but copies each chunk's data out of the buffer and
+/// rejects chunks whose crc doesn't match
+#[derive(Debug, Clone)]
+pub struct Chunks<'a> {
+    refs: ChunkRefs<'a>,
+}
+
+impl<'a> Chunks<'a> {
+    /// Iterates over the chunks in `stream`, which starts right after the
+    /// signature
+    pub fn new(stream: &'a [u8]) -> Self {
+        Self {
+            refs: ChunkRefs::new(stream),
+        }
+    }
+
+    /// The bytes not parsed yet
+    pub fn remaining(&self) -> &'a [u8] {
+        self.refs.remaining()
+    }
+}
+
+impl Iterator for Chunks<'_> {
+    type Item = Result<Chunk>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let chunk = self.refs.next()?.and_then(Chunk::try_from);
+        if chunk.is_err() {
+            self.refs.remaining = &[];
+        }
+        Some(chunk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_stream() -> Vec<u8> {
+        [
+            Chunk::new(ChunkType::IHDR, vec![0; 13]),
+            Chunk::new(ChunkType::IDAT, vec![1, 2, 3]),
+            Chunk::new(ChunkType::IEND, vec![]),
+        ]
+        .iter()
+        .flat_map(Chunk::as_bytes)
+        .collect()
+    }
+
+    #[test]
+    fn test_signature() {
+        assert!(has_signature(&[SIGNATURE.as_slice(), &[0; 4]].concat()));
+        assert!(!has_signature(&SIGNATURE[..7]));
+        assert!(!has_signature(b"GIF89a.."));
+    }
+
+    #[test]
+    fn test_png_length() {
+        let png = [SIGNATURE.as_slice(), &testing_stream()].concat();
+        assert_eq!(png_length(&png), Some(png.len()));
+        assert_eq!(
+            png_length(&[png.as_slice(), b"junk"].concat()),
+            Some(png.len())
+        );
+        assert_eq!(png_length(&png[..png.len() - 1]), None);
+        assert_eq!(png_length(&png[1..]), None);
+    }
+
+    #[test]
+    fn test_chunks() {
+        let stream = testing_stream();
+        let chunks: Vec<Chunk> = Chunks::new(&stream).collect::<Result<_>>().unwrap();
+        let types: Vec<&ChunkType> = chunks.iter().map(Chunk::chunk_type).collect();
+        assert_eq!(
+            types,
+            [&ChunkType::IHDR, &ChunkType::IDAT, &ChunkType::IEND]
+        );
+        assert_eq!(chunks[1].data(), [1, 2, 3]);
+        assert_eq!(Chunks::new(&[]).count(), 0);
+    }
+
+    #[test]
+    fn test_chunks_stop_at_the_first_error() {
+        let mut stream = testing_stream();
+        // corrupt the crc of the IDAT chunk
+        let idat_crc = Chunk::META_DATA_LENGTH + 13 + Chunk::META_DATA_LENGTH + 3 - 1;
+        stream[idat_crc] ^= 0xff;
+
+        let mut chunks = Chunks::new(&stream);
+        assert!(chunks.next().unwrap().is_ok());
+        assert!(chunks.next().unwrap().is_err());
+        assert!(chunks.next().is_none());
+        assert!(chunks.remaining().is_empty());
+
+        let mut chunks = Chunks::new(&[0, 0]);
+        assert!(chunks.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_chunk_refs_borrow_the_stream() {
+        let mut stream = testing_stream();
+        let idat_crc = Chunk::META_DATA_LENGTH + 13 + Chunk::META_DATA_LENGTH + 3 - 1;
+        stream[idat_crc] ^= 0xff;
+
+        let chunks: Vec<ChunkRef> = ChunkRefs::new(&stream).collect::<Result<_>>().unwrap();
+        assert_eq!(chunks.len(), 3);
+        assert!(std::ptr::eq(chunks[1].data(), &stream[33..36]));
+        assert!(chunks[0].verify_crc().is_ok());
+        assert!(chunks[1].verify_crc().is_err());
+        assert!(Chunk::try_from(chunks[1].clone()).is_err());
+
+        let truncated = &stream[..stream.len() - 1];
+        let mut chunks = ChunkRefs::new(truncated);
+        assert_eq!(chunks.by_ref().filter(Result::is_ok).count(), 2);
+        assert!(chunks.remaining().is_empty());
+    }
+}
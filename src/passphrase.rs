@@ -0,0 +1,83 @@
+use std::{fmt::Display, io};
+
+use crate::Result;
+
+#[derive(Debug)]
+pub enum PassphraseError {
+    Empty,
+    Mismatch,
+}
+
+impl std::error::Error for PassphraseError {}
+
+impl Display for PassphraseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "The passphrase must not be empty"),
+            Self::Mismatch => write!(f, "The passphrases do not match"),
+        }
+    }
+}
+
+/// Returns the passphrase given on the command line, or asks for it on the
+/// terminal without echoing it. When `confirm` is set the passphrase has to be
+/// typed twice, which is what encoding wants
+pub fn passphrase(supplied: Option<String>, confirm: bool) -> Result<String> {
+    resolve(supplied, confirm, |prompt| {
+        rpassword::prompt_password(prompt)
+    })
+}
+
+fn resolve(
+    supplied: Option<String>,
+    confirm: bool,
+    mut prompt: impl FnMut(&str) -> io::Result<String>,
+) -> Result<String> {
+    if let Some(passphrase) = supplied {
+        return Ok(passphrase);
+    }
+
+    let passphrase = prompt("Passphrase: ")?;
+    if passphrase.is_empty() {
+        return Err(PassphraseError::Empty.into());
+    }
+    if confirm && prompt("Confirm passphrase: ")? != passphrase {
+        return Err(PassphraseError::Mismatch.into());
+    }
+    Ok(passphrase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A prompt that answers with `answers` in order
+    fn answers(answers: &[&str]) -> impl FnMut(&str) -> io::Result<String> {
+        let mut answers: Vec<String> = answers.iter().rev().map(|a| a.to_string()).collect();
+        move |_| Ok(answers.pop().expect("unexpected prompt"))
+    }
+
+    #[test]
+    fn test_supplied_passphrase_skips_prompt() {
+        let passphrase = resolve(Some("secret".to_string()), true, answers(&[])).unwrap();
+        assert_eq!(passphrase, "secret");
+    }
+
+    #[test]
+    fn test_prompted_passphrase() {
+        let passphrase = resolve(None, false, answers(&["secret"])).unwrap();
+        assert_eq!(passphrase, "secret");
+    }
+
+    #[test]
+    fn test_confirmed_passphrase() {
+        let passphrase = resolve(None, true, answers(&["secret", "secret"])).unwrap();
+        assert_eq!(passphrase, "secret");
+        assert!(resolve(None, true, answers(&["secret", "typo"])).is_err());
+    }
+
+    #[test]
+    fn test_empty_passphrase() {
+        assert!(resolve(None, false, answers(&[""])).is_err());
+    }
+}
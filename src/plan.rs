@@ -0,0 +1,252 @@
+use std::{
+    fmt::Display,
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use tempfile::NamedTempFile;
+
+use crate::{carrier::Carrier, chunk::Chunk, Result};
+
+/// A single chunk level change made by a command
+#[derive(Debug, PartialEq)]
+pub enum Change {
+    Added { chunk_type: String, length: usize },
+    Removed { chunk_type: String, length: usize },
+    Repaired { subject: String, action: String },
+    Updated { chunk_type: String, action: String },
+}
+
+impl Change {
+    pub fn added(chunk: &Chunk) -> Self {
+        Self::Added {
+            chunk_type: chunk.chunk_type().to_string(),
+            length: chunk.length(),
+        }
+    }
+
+    pub fn removed(chunk: &Chunk) -> Self {
+        Self::Removed {
+            chunk_type: chunk.chunk_type().to_string(),
+            length: chunk.length(),
+        }
+    }
+
+    pub fn updated(chunk: &Chunk, action: impl Into<String>) -> Self {
+        Self::Updated {
+            chunk_type: chunk.chunk_type().to_string(),
+            action: action.into(),
+        }
+    }
+
+    pub fn repaired(subject: impl Into<String>, action: impl Into<String>) -> Self {
+        Self::Repaired {
+            subject: subject.into(),
+            action: action.into(),
+        }
+    }
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Added { chunk_type, length } => write!(f, "+ {} ({} bytes)", chunk_type, length),
+            Self::Removed { chunk_type, length } => {
+                write!(f, "- {} ({} bytes)", chunk_type, length)
+            }
+            Self::Repaired { subject, action } => write!(f, "~ {}: {}", subject, action),
+            Self::Updated { chunk_type, action } => write!(f, "~ {}: {}", chunk_type, action),
+        }
+    }
+}
+
+/// A pending write of a modified image. Mutating commands build a plan and
+/// either apply it or, for dry runs, print it
+#[derive(Debug)]
+pub struct Plan {
+    target: PathBuf,
+    original_size: u64,
+    changes: Vec<Change>,
+    new_size: u64,
+    carrier: Carrier,
+}
+
+impl Plan {
+    /// Plans writing `carrier` to `target`. `source` is the file the image
+    /// was read from and is used to report the change in size
+    pub fn new(
+        source: &Path,
+        target: &Path,
+        carrier: impl Into<Carrier>,
+        changes: Vec<Change>,
+    ) -> Result<Self> {
+        let original_size = fs::metadata(source)?.len();
+        Ok(Self::with_original_size(
+            original_size,
+            target,
+            carrier,
+            changes,
+        ))
+    }
+
+    /// Plans writing `carrier` to `target` for an image that was not read
+    /// from a file, such as a download, that was `original_size` bytes long
+    pub fn with_original_size(
+        original_size: u64,
+        target: &Path,
+        carrier: impl Into<Carrier>,
+        changes: Vec<Change>,
+    ) -> Self {
+        let carrier = carrier.into();
+        Self {
+            target: target.to_path_buf(),
+            original_size,
+            changes,
+            new_size: carrier.encoded_length() as u64,
+            carrier,
+        }
+    }
+
+    pub fn new_size(&self) -> u64 {
+        self.new_size
+    }
+
+    pub fn target(&self) -> &Path {
+        &self.target
+    }
+
+    /// The image as it would be written, for targets `apply` can't write to
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.carrier.as_bytes()
+    }
+
+    pub fn apply(self) -> Result<()> {
+        self.carrier.save(&self.target)
+    }
+}
+
+/// Writes `contents` to `path` without ever leaving a partially written file
+/// behind
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    write_atomic_with(path, |file| Ok(file.write_all(contents)?))
+}
+
+/// Lets `write` fill a temporary file in the same directory as `path`, syncs
+/// it to disk and then renames it over `path`
+pub fn write_atomic_with(path: &Path, write: impl FnOnce(&mut File) -> Result<()>) -> Result<()> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    let mut temp = NamedTempFile::new_in(dir)?;
+    write(temp.as_file_mut())?;
+    if let Ok(metadata) = fs::metadata(path) {
+        temp.as_file().set_permissions(metadata.permissions())?;
+    }
+    temp.as_file().sync_all()?;
+    temp.persist(path)?;
+    Ok(())
+}
+
+impl Display for Plan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Would write {} ({} -> {} bytes)",
+            self.target.display(),
+            self.original_size,
+            self.new_size()
+        )?;
+        for change in &self.changes {
+            write!(f, "\n  {}", change)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use crate::png::tests::testing_file;
+    use crate::png::Png;
+    use std::str::FromStr;
+
+    fn testing_chunk() -> Chunk {
+        Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"hidden".to_vec())
+    }
+
+    #[test]
+    fn test_plan_display() {
+        let file = testing_file("plan-display");
+        let bytes = fs::read(&file).unwrap();
+        let mut png = Png::try_from(bytes.as_ref()).unwrap();
+        png.append_chunk(testing_chunk());
+
+        let plan = Plan::new(&file, &file, png, vec![Change::added(&testing_chunk())]).unwrap();
+        assert_eq!(plan.new_size(), bytes.len() as u64 + 18);
+        assert_eq!(
+            plan.to_string(),
+            format!(
+                "Would write {} ({} -> {} bytes)\n  + ruSt (6 bytes)",
+                file.display(),
+                bytes.len(),
+                bytes.len() + 18
+            )
+        );
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_plan_apply() {
+        let file = testing_file("plan-apply");
+        let target = file.with_extension("out.png");
+        let png = Png::from_chunks(vec![testing_chunk()]);
+        let expected = png.as_bytes();
+
+        let plan = Plan::new(&file, &target, png, vec![]).unwrap();
+        plan.apply().unwrap();
+        assert_eq!(fs::read(&target).unwrap(), expected);
+        fs::remove_file(file).unwrap();
+        fs::remove_file(target).unwrap();
+    }
+
+    #[test]
+    fn test_write_atomic_replaces_file() {
+        let file = testing_file("write-atomic");
+        write_atomic(&file, b"replaced").unwrap();
+        assert_eq!(fs::read(&file).unwrap(), b"replaced");
+        fs::remove_file(file).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_atomic_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let file = testing_file("write-atomic-permissions");
+        fs::set_permissions(&file, fs::Permissions::from_mode(0o640)).unwrap();
+        write_atomic(&file, b"replaced").unwrap();
+        let mode = fs::metadata(&file).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_change_display() {
+        assert_eq!(
+            Change::added(&testing_chunk()).to_string(),
+            "+ ruSt (6 bytes)"
+        );
+        assert_eq!(
+            Change::removed(&testing_chunk()).to_string(),
+            "- ruSt (6 bytes)"
+        );
+        assert_eq!(
+            Change::repaired("IDAT", "recomputed CRC").to_string(),
+            "~ IDAT: recomputed CRC"
+        );
+    }
+}
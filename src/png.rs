@@ -1,13 +1,37 @@
-use std::{convert::TryFrom, fmt::Display};
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryFrom,
+    fmt::Display,
+    fs::{File, OpenOptions},
+    io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::Path,
+    sync::OnceLock,
+};
 
-
-use crate::{chunk::Chunk, Error, Result};
+use crate::{
+    apng::Animation,
+    chunk::{Chunk, MAX_CHUNK_LENGTH},
+    chunk_type::ChunkType,
+    exif::{self, ExifError},
+    ihdr::{Ihdr, IhdrError},
+    metadata::{Gamma, MetadataError, PhysicalDimensions, RenderingIntent},
+    parse, plan,
+    text_chunk::TextChunk,
+    time_chunk::{LastModified, TimeError},
+    verify::{self, Severity, ValidationReport},
+    Error, Result,
+};
 
 #[derive(Debug)]
-enum PngError {
+pub enum PngError {
     InvalidStandardHeader,
     TooSmall,
-    UnknownChunkType
+    UnknownChunkType,
+    TruncatedChunk,
+    ChunkTooLong(usize),
+    IndexOutOfBounds(usize, usize),
+    OutOfSpec { offset: usize, message: String },
+    NotAppendable(ChunkType),
 }
 
 impl std::error::Error for PngError {}
@@ -21,6 +45,25 @@ impl Display for PngError {
             ),
             Self::TooSmall => write!(f, "File is too small"),
             PngError::UnknownChunkType => write!(f, "Unknown chunk type"),
+            Self::IndexOutOfBounds(index, len) => write!(
+                f,
+                "Cannot insert a chunk at index {} of a png with {} chunks",
+                index, len
+            ),
+            Self::TruncatedChunk => write!(f, "The file ends in the middle of a chunk"),
+            Self::ChunkTooLong(length) => write!(
+                f,
+                "A chunk declares {} data bytes, more than the allowed {}",
+                length, MAX_CHUNK_LENGTH
+            ),
+            Self::OutOfSpec { offset, message } => {
+                write!(f, "Out of spec at offset {:#x}: {}", offset, message)
+            }
+            Self::NotAppendable(chunk_type) => write!(
+                f,
+                "{} chunks belong before the end of the png and cannot be appended",
+                chunk_type
+            ),
         }
     }
 }
@@ -31,101 +74,758 @@ impl Display for Png {
     }
 }
 
-#[derive(Debug)]
+/// Ancillary chunks the specification requires to come before PLTE and IDAT
+pub const BEFORE_PLTE: [ChunkType; 8] = [
+    ChunkType::cHRM,
+    ChunkType::gAMA,
+    ChunkType::iCCP,
+    ChunkType::sBIT,
+    ChunkType::sRGB,
+    ChunkType::cICP,
+    ChunkType::mDCV,
+    ChunkType::cLLI,
+];
+
+/// Ancillary chunks the specification requires to come before IDAT
+pub const BEFORE_IDAT: [ChunkType; 10] = [
+    ChunkType::bKGD,
+    ChunkType::hIST,
+    ChunkType::tRNS,
+    ChunkType::pHYs,
+    ChunkType::sPLT,
+    ChunkType::eXIf,
+    ChunkType::oFFs,
+    ChunkType::pCAL,
+    ChunkType::sCAL,
+    ChunkType::acTL,
+];
+
+/// Chunk types the specification allows at most once in a png
+pub const AT_MOST_ONCE: [ChunkType; 22] = [
+    ChunkType::IHDR,
+    ChunkType::PLTE,
+    ChunkType::IEND,
+    ChunkType::cHRM,
+    ChunkType::gAMA,
+    ChunkType::iCCP,
+    ChunkType::sBIT,
+    ChunkType::sRGB,
+    ChunkType::cICP,
+    ChunkType::cLLI,
+    ChunkType::mDCV,
+    ChunkType::bKGD,
+    ChunkType::hIST,
+    ChunkType::tRNS,
+    ChunkType::pHYs,
+    ChunkType::tIME,
+    ChunkType::eXIf,
+    ChunkType::oFFs,
+    ChunkType::pCAL,
+    ChunkType::sCAL,
+    ChunkType::sTER,
+    ChunkType::acTL,
+];
+
+/// How strictly `Png::try_from_with` holds the input to the specification
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ParseOptions {
+    /// Keep every chunk that can still be delimited and report what is wrong
+    /// as warnings, instead of rejecting anything out of spec
+    pub lenient: bool,
+}
+
 pub struct Png {
     chunks: Vec<Chunk>,
+    /// Positions of the chunks of each type, built on the first lookup and
+    /// updated as chunks are inserted and removed
+    index: OnceLock<HashMap<ChunkType, Vec<usize>>>,
+}
+
+impl std::fmt::Debug for Png {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Png").field("chunks", &self.chunks).finish()
+    }
+}
+
+/// With the serde feature a png serializes as its list of chunks
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct SerializedPng<'a> {
+    chunks: &'a [Chunk],
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct DeserializedPng {
+    chunks: Vec<Chunk>,
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Png {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        SerializedPng {
+            chunks: &self.chunks,
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Png {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        Ok(Png::from_chunks(
+            DeserializedPng::deserialize(deserializer)?.chunks,
+        ))
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Png {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Png::from_chunks(u.arbitrary()?))
+    }
 }
 
 impl Png {
     pub const HEADER_LENGTH: usize = 8;
-    pub const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+    pub const STANDARD_HEADER: [u8; 8] = parse::SIGNATURE;
+
+    /// Reads and parses the png at `path`
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        Png::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    /// Maps the png at `path` into memory, leaving its chunks to be parsed
+    /// as they are read
+    #[cfg(feature = "mmap")]
+    pub fn open_mmap(path: impl AsRef<Path>) -> Result<crate::mmap::MappedPng> {
+        crate::mmap::MappedPng::open(path)
+    }
+
+    /// Parses a png from `reader` one chunk at a time, so only the chunk
+    /// being read is ever buffered on top of the parsed chunks
+    pub fn from_reader(mut reader: impl Read) -> Result<Self> {
+        let mut header = [0; Png::HEADER_LENGTH];
+        let filled = read_up_to(&mut reader, &mut header)?;
+        check_signature(&header[..filled])?;
+
+        let mut chunks = Vec::new();
+        loop {
+            let mut length = [0; Chunk::LEN_DATA_LENGTH];
+            let filled = read_up_to(&mut reader, &mut length)?;
+            let Some(remaining) = chunk_remaining(&length[..filled])? else {
+                break;
+            };
+
+            // Read through `take` rather than allocating the declared length
+            // up front, a corrupt length must not cost gigabytes of memory
+            let mut bytes = length.to_vec();
+            (&mut reader)
+                .take(remaining as u64)
+                .read_to_end(&mut bytes)?;
+            chunks.push(read_chunk(&bytes, remaining)?);
+        }
+
+        Ok(Png::from_chunks(chunks))
+    }
+
+    /// Parses `bytes` according to `options`. Strict parsing fails on the
+    /// first problem `verify` finds, warnings included. Lenient parsing only
+    /// fails on a file too small for a signature, keeping chunks with bad
+    /// CRCs or invalid types and stopping at a truncated chunk or IEND. Every
+    /// problem is then returned as a warning, and writing the png back out
+    /// fixes the CRCs
+    pub fn try_from_with(bytes: &[u8], options: ParseOptions) -> Result<(Self, ValidationReport)> {
+        let mut report = verify::verify(bytes);
+        if !options.lenient {
+            if let Some(problem) = report.problems.first() {
+                return Err(PngError::OutOfSpec {
+                    offset: problem.offset,
+                    message: problem.message.clone(),
+                }
+                .into());
+            }
+            return Ok((Png::try_from(bytes)?, report));
+        }
+
+        if bytes.len() < Png::HEADER_LENGTH {
+            return Err(PngError::TooSmall.into());
+        }
+        let mut chunks = Vec::new();
+        let mut rest = &bytes[Png::HEADER_LENGTH..];
+        while rest.len() >= Chunk::META_DATA_LENGTH {
+            let length = u32::from_be_bytes(rest[..4].try_into()?) as usize;
+            if length > MAX_CHUNK_LENGTH || rest.len() - Chunk::META_DATA_LENGTH < length {
+                break;
+            }
+            let chunk_type = ChunkType::new_unchecked(rest[4..8].try_into()?);
+            let chunk = Chunk::new(chunk_type, rest[8..8 + length].to_vec());
+            let is_iend = chunk.chunk_type() == &ChunkType::IEND;
+            chunks.push(chunk);
+            rest = &rest[length + Chunk::META_DATA_LENGTH..];
+            if is_iend {
+                break;
+            }
+        }
+
+        for problem in &mut report.problems {
+            problem.severity = Severity::Warning;
+        }
+        Ok((Png::from_chunks(chunks), report))
+    }
 
     pub fn from_chunks(chunks: Vec<Chunk>) -> Self {
-        Self { chunks }
+        Self {
+            chunks,
+            index: OnceLock::new(),
+        }
     }
 
-    pub fn chunks(&self) -> &Vec<Chunk> {
-        &self.chunks
+    /// Iterates over the chunks in file order
+    pub fn chunks(&self) -> std::slice::Iter<'_, Chunk> {
+        self.chunks.iter()
     }
 
-    fn header(&self) -> &[u8; 8] {
-        &Png::STANDARD_HEADER
+    /// Iterates over the chunks in file order, allowing each to be modified in
+    /// place. Chunks can't be added or removed this way, use `append_chunk`
+    /// and the `remove_*` methods for that
+    pub fn chunks_mut(&mut self) -> std::slice::IterMut<'_, Chunk> {
+        // a chunk replaced here can change type, so the index is rebuilt on
+        // the next lookup
+        self.index.take();
+        self.chunks.iter_mut()
     }
 
-    
-    fn append_chunk(&mut self, chunk: Chunk) {
-        self.chunks.push(chunk)
+    /// Inserts a chunk at `position`, shifting the indexed positions after it
+    fn insert_at(&mut self, position: usize, chunk: Chunk) {
+        if let Some(index) = self.index.get_mut() {
+            for later in index.values_mut().flatten().filter(|p| **p >= position) {
+                *later += 1;
+            }
+            let positions = index.entry(chunk.chunk_type().clone()).or_default();
+            positions.insert(positions.partition_point(|&p| p < position), position);
+        }
+        self.chunks.insert(position, chunk);
     }
 
-    fn remove_chunk(&mut self, chunk_type: &str) -> Result<Chunk> {
-        let index = self
+    /// Removes the chunks at the sorted `positions`, returning them in file
+    /// order, and shifts the indexed positions left after them
+    fn remove_at(&mut self, positions: &[usize]) -> Vec<Chunk> {
+        let mut position = 0;
+        let removed = self
             .chunks
+            .extract_if(.., |_| {
+                position += 1;
+                positions.binary_search(&(position - 1)).is_ok()
+            })
+            .collect();
+        if let Some(index) = self.index.get_mut() {
+            index.retain(|_, kept| {
+                kept.retain_mut(|p| match positions.binary_search(p) {
+                    Ok(_) => false,
+                    Err(before) => {
+                        *p -= before;
+                        true
+                    }
+                });
+                !kept.is_empty()
+            });
+        }
+        removed
+    }
+
+    fn index(&self) -> &HashMap<ChunkType, Vec<usize>> {
+        self.index.get_or_init(|| {
+            let mut index: HashMap<ChunkType, Vec<usize>> = HashMap::new();
+            for (position, chunk) in self.chunks.iter().enumerate() {
+                index
+                    .entry(chunk.chunk_type().clone())
+                    .or_default()
+                    .push(position);
+            }
+            index
+        })
+    }
+
+    /// Positions of every chunk of the given type, in file order
+    fn positions(&self, chunk_type: &ChunkType) -> &[usize] {
+        self.index().get(chunk_type).map_or(&[], Vec::as_slice)
+    }
+
+    fn signature(&self) -> &[u8; 8] {
+        &Png::STANDARD_HEADER
+    }
+
+    /// Checks the chunk layout against the PNG specification. CRCs are
+    /// recomputed on write, so only ordering problems can show up here
+    pub fn validate(&self) -> ValidationReport {
+        verify::verify(&self.as_bytes())
+    }
+
+    /// Keyword and text of every well formed tEXt, zTXt and iTXt chunk, in
+    /// file order
+    pub fn text_metadata(&self) -> Vec<TextChunk> {
+        self.chunks
             .iter()
-            .position(|c| (*c).chunk_type().to_string() == chunk_type)
+            .filter_map(|chunk| TextChunk::try_from(chunk).ok())
+            .collect()
+    }
+
+    /// Parses the IHDR chunk, which the specification requires to come first
+    pub fn header(&self) -> Result<Ihdr> {
+        let first = self.chunks.first().ok_or(IhdrError::Missing)?;
+        Ihdr::try_from(first)
+    }
+
+    fn find_chunk(&self, chunk_type: &ChunkType) -> Option<&Chunk> {
+        let &position = self.positions(chunk_type).first()?;
+        Some(&self.chunks[position])
+    }
+
+    /// Parses the animation control chunks, if the png is animated
+    pub fn animation(&self) -> Result<Option<Animation>> {
+        Animation::from_chunks(&self.chunks)
+    }
+
+    /// Parses the tIME chunk, if the png has one
+    pub fn last_modified(&self) -> Result<LastModified> {
+        let chunk = self
+            .find_chunk(&ChunkType::tIME)
+            .ok_or(TimeError::Missing)?;
+        LastModified::try_from(chunk)
+    }
+
+    /// Parses the pHYs chunk, if the png has one
+    pub fn physical_dimensions(&self) -> Result<PhysicalDimensions> {
+        let chunk = self
+            .find_chunk(&ChunkType::pHYs)
+            .ok_or(MetadataError::Missing(ChunkType::pHYs))?;
+        PhysicalDimensions::try_from(chunk)
+    }
+
+    /// Parses the gAMA chunk, if the png has one
+    pub fn gamma(&self) -> Result<Gamma> {
+        let chunk = self
+            .find_chunk(&ChunkType::gAMA)
+            .ok_or(MetadataError::Missing(ChunkType::gAMA))?;
+        Gamma::try_from(chunk)
+    }
+
+    /// Parses the sRGB chunk, if the png has one
+    pub fn rendering_intent(&self) -> Result<RenderingIntent> {
+        let chunk = self
+            .find_chunk(&ChunkType::sRGB)
+            .ok_or(MetadataError::Missing(ChunkType::sRGB))?;
+        RenderingIntent::try_from(chunk)
+    }
+
+    /// Replaces the tIME chunk, or inserts one when there is none. Returns
+    /// the chunk that was replaced
+    pub fn set_last_modified(&mut self, time: LastModified) -> Option<Chunk> {
+        self.replace_or_insert(time.to_chunk())
+    }
+
+    /// The raw EXIF blob stored in the eXIf chunk
+    pub fn exif(&self) -> Result<&[u8]> {
+        let chunk = self
+            .find_chunk(&ChunkType::eXIf)
+            .ok_or(ExifError::Missing)?;
+        Ok(chunk.data())
+    }
+
+    /// Replaces the eXIf chunk, or inserts one when there is none. Returns
+    /// the chunk that was replaced
+    pub fn set_exif(&mut self, data: Vec<u8>) -> Result<Option<Chunk>> {
+        exif::validate(&data)?;
+        Ok(self.replace_or_insert(Chunk::try_new(ChunkType::eXIf, data)?))
+    }
+
+    /// Replaces the first chunk of the same type, or inserts the chunk where
+    /// the specification allows it when there is none
+    fn replace_or_insert(&mut self, chunk: Chunk) -> Option<Chunk> {
+        match self.positions(chunk.chunk_type()).first() {
+            // the index stays valid, the replacement has the same type
+            Some(&position) => Some(std::mem::replace(&mut self.chunks[position], chunk)),
+            None => {
+                self.insert_chunk(chunk);
+                None
+            }
+        }
+    }
+
+    /// Inserts a chunk at the last position the PNG specification allows for
+    /// its type: palette related chunks go before PLTE, chunks such as pHYs
+    /// before the first IDAT and everything else just before IEND
+    pub fn insert_chunk(&mut self, chunk: Chunk) {
+        let index = self.last_legal_index(chunk.chunk_type());
+        self.insert_at(index, chunk)
+    }
+
+    /// Replaces every IDAT chunk with `chunk`, placed where the first one was
+    pub fn replace_image_data(&mut self, chunk: Chunk) -> Result<()> {
+        let image_data = self.positions(&ChunkType::IDAT).to_vec();
+        let &index = image_data.first().ok_or(PngError::UnknownChunkType)?;
+        self.remove_at(&image_data);
+        self.insert_at(index, chunk);
+        Ok(())
+    }
+
+    /// Inserts a chunk at `index`, ignoring the ordering rules
+    pub fn insert_chunk_at(&mut self, index: usize, chunk: Chunk) -> Result<()> {
+        if index > self.chunks.len() {
+            return Err(PngError::IndexOutOfBounds(index, self.chunks.len()).into());
+        }
+        self.insert_at(index, chunk);
+        Ok(())
+    }
+
+    /// Whether `insert_chunk` puts chunks of this type right before IEND
+    /// whatever else the png holds
+    fn goes_at_end(chunk_type: &ChunkType) -> bool {
+        ![ChunkType::IHDR, ChunkType::IDAT, ChunkType::IEND].contains(chunk_type)
+            && !BEFORE_PLTE.contains(chunk_type)
+            && !BEFORE_IDAT.contains(chunk_type)
+    }
+
+    fn last_legal_index(&self, chunk_type: &ChunkType) -> usize {
+        let position = |name: &ChunkType| self.positions(name).first().copied();
+        let last_idat = self.positions(&ChunkType::IDAT).last().copied();
+        let end = self.end_index();
+
+        // the frame control of an animated png that shows its IDAT as the
+        // first frame goes right before the IDAT too
+        let image = [position(&ChunkType::fcTL), position(&ChunkType::IDAT)];
+        let before = |limits: &[Option<usize>]| limits.iter().flatten().fold(end, |a, &b| a.min(b));
+        match *chunk_type {
+            ChunkType::IHDR => 0,
+            ChunkType::IEND => self.chunks.len(),
+            ChunkType::IDAT => last_idat.map_or(end, |i| i + 1),
+            _ if BEFORE_PLTE.contains(chunk_type) => {
+                before(&[position(&ChunkType::PLTE), image[0], image[1]])
+            }
+            _ if BEFORE_IDAT.contains(chunk_type) => before(&image),
+            _ => end,
+        }
+    }
+
+    /// Index of a trailing IEND chunk, or the number of chunks if there is none
+    pub(crate) fn end_index(&self) -> usize {
+        match self.chunks.last() {
+            Some(last) if last.chunk_type() == &ChunkType::IEND => self.chunks.len() - 1,
+            _ => self.chunks.len(),
+        }
+    }
+
+    /// Appends a chunk, keeping a trailing IEND chunk last
+    pub fn append_chunk(&mut self, chunk: Chunk) {
+        let index = self.end_index();
+        self.insert_at(index, chunk)
+    }
+
+    /// Removes the first chunk of the given type
+    pub fn remove_first_chunk(&mut self, chunk_type: &str) -> Result<Chunk> {
+        let &index = key(chunk_type)
+            .and_then(|key| self.positions(&key).first())
             .ok_or(PngError::UnknownChunkType)?;
-        let removed = self.chunks.remove(index);
-        Ok(removed)
+        Ok(self.remove_at(&[index]).remove(0))
     }
 
+    /// Removes every chunk of the given type, returning them in file order
+    pub fn remove_all_chunks(&mut self, chunk_type: &str) -> Vec<Chunk> {
+        let Some(key) = key(chunk_type) else {
+            return Vec::new();
+        };
+        let positions = self.positions(&key).to_vec();
+        self.remove_at(&positions)
+    }
 
-    fn chunk_by_type(&self, chunk_type: &str) -> Option<&Chunk> {
+    /// Removes every chunk matching `predicate`, returning them in file order
+    pub fn remove_chunks_where(&mut self, mut predicate: impl FnMut(&Chunk) -> bool) -> Vec<Chunk> {
+        let positions: Vec<usize> = self
+            .chunks
+            .iter()
+            .enumerate()
+            .filter(|(_, chunk)| predicate(chunk))
+            .map(|(position, _)| position)
+            .collect();
+        self.remove_at(&positions)
+    }
+
+    /// Positions of the ancillary chunks with the same type and data as an
+    /// earlier chunk, in file order
+    pub fn repeated_chunks(&self) -> Vec<usize> {
+        let mut seen = HashSet::new();
         self.chunks
             .iter()
-            .find(|&c| c.chunk_type().to_string() == chunk_type)
+            .enumerate()
+            .filter(|(_, chunk)| !chunk.chunk_type().is_critical())
+            .filter(|(_, chunk)| !seen.insert((chunk.chunk_type(), chunk.data())))
+            .map(|(position, _)| position)
+            .collect()
+    }
+
+    /// Removes the chunks `repeated_chunks` finds, keeping the first of each
+    /// set of identical chunks, and returns them in file order
+    pub fn remove_repeated_chunks(&mut self) -> Vec<Chunk> {
+        let repeated = self.repeated_chunks();
+        self.remove_at(&repeated)
+    }
+
+    /// The first chunk of the given type, looked up in constant time
+    pub fn chunk_by_type(&self, chunk_type: &str) -> Option<&Chunk> {
+        self.find_chunk(&key(chunk_type)?)
+    }
+
+    /// Iterates over every chunk of the given type in file order
+    pub fn chunks_by_type<'a>(&'a self, chunk_type: &'a str) -> impl Iterator<Item = &'a Chunk> {
+        let positions = key(chunk_type).map_or(&[][..], |key| self.positions(&key));
+        positions.iter().map(|&position| &self.chunks[position])
     }
 
-    fn as_bytes(&self) -> Vec<u8> {
-        let header: Vec<u8> = self.header().iter().copied().collect();
-        let body: Vec<u8> = self
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.encoded_length());
+        bytes.extend_from_slice(self.signature());
+        for chunk in &self.chunks {
+            chunk.extend_into(&mut bytes);
+        }
+        bytes
+    }
+
+    /// The size of the png in bytes once written, signature included
+    pub fn encoded_length(&self) -> usize {
+        let chunks: usize = self
             .chunks
             .iter()
-            .flat_map(|c| c.as_bytes().into_iter())
-            .collect::<Vec<_>>();
+            .map(|chunk| chunk.length() + Chunk::META_DATA_LENGTH)
+            .sum();
+        Png::HEADER_LENGTH + chunks
+    }
 
-        header.into_iter().chain(body.into_iter()).collect()
+    /// Writes the signature and then each chunk in turn to `writer`, never
+    /// holding more than a single chunk's worth of output in memory
+    pub fn write_to(&self, mut writer: impl Write) -> Result<()> {
+        writer.write_all(self.signature())?;
+        for chunk in &self.chunks {
+            chunk.write_to(&mut writer)?;
+        }
+        writer.flush()?;
+        Ok(())
     }
-}
 
-impl TryFrom<&[u8]> for Png {
-    type Error = Error;
+    /// Writes the png to `path` through a buffered writer. The file is
+    /// replaced atomically, so a failed save never leaves a half written png
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        plan::write_atomic_with(path.as_ref(), |file| self.write_to(BufWriter::new(file)))
+    }
 
-    fn try_from(value: &[u8]) -> Result<Self> {
-        if value.len() < Png::STANDARD_HEADER.len() {
-            return Err(Box::from(PngError::TooSmall));
+    /// Adds `chunks` right before the IEND chunk of the png at `path`,
+    /// writing over IEND and leaving everything before it untouched. Only the
+    /// chunk headers are read to find IEND, so the cost doesn't depend on the
+    /// size of the image data. Unlike `save` this is not atomic: a failed
+    /// write cuts the file back to end with IEND, but a crash part way
+    /// through leaves it without one
+    pub fn append_in_place(path: impl AsRef<Path>, chunks: &[Chunk]) -> Result<()> {
+        if let Some(chunk) = chunks.iter().find(|c| !Png::goes_at_end(c.chunk_type())) {
+            return Err(PngError::NotAppendable(chunk.chunk_type().clone()).into());
         }
 
-        let mut index = 0;
+        let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+        let end = end_offset(&mut file)?;
+        let mut iend = Vec::new();
+        file.seek(SeekFrom::Start(end))?;
+        file.read_to_end(&mut iend)?;
 
-        // validate header
-        let header = &value[index..index + Png::STANDARD_HEADER.len()];
-        index += Png::STANDARD_HEADER.len();
+        file.seek(SeekFrom::Start(end))?;
+        let written: Result<()> = (|| {
+            let mut writer = BufWriter::new(&mut file);
+            for chunk in chunks {
+                chunk.write_to(&mut writer)?;
+            }
+            writer.write_all(&iend)?;
+            writer.flush()?;
+            Ok(())
+        })();
+        if written.is_err() {
+            // best effort, the write error is the one worth reporting
+            let _ = file
+                .set_len(end)
+                .and_then(|_| file.seek(SeekFrom::Start(end)))
+                .and_then(|_| file.write_all(&iend));
+        }
+        written?;
+        file.sync_all()?;
+        Ok(())
+    }
+}
+
+/// Checks the start of a stream, as much of the signature as was read. Shared
+/// by the sync and async readers
+pub(crate) fn check_signature(filled: &[u8]) -> Result<()> {
+    if filled.len() < Png::HEADER_LENGTH {
+        return Err(PngError::TooSmall.into());
+    }
+    if filled != Png::STANDARD_HEADER {
+        return Err(PngError::InvalidStandardHeader.into());
+    }
+    Ok(())
+}
 
-        if Png::STANDARD_HEADER != header {
-            return Err(Box::from(PngError::InvalidStandardHeader));
+/// The number of bytes left in a chunk after its length field, of which
+/// `filled` holds as much as was read. `None` when the stream ended cleanly
+/// before the chunk
+pub(crate) fn chunk_remaining(filled: &[u8]) -> Result<Option<usize>> {
+    let length: [u8; Chunk::LEN_DATA_LENGTH] = match filled.len() {
+        0 => return Ok(None),
+        Chunk::LEN_DATA_LENGTH => filled.try_into()?,
+        _ => return Err(PngError::TruncatedChunk.into()),
+    };
+    let data_length = u32::from_be_bytes(length) as usize;
+    if data_length > MAX_CHUNK_LENGTH {
+        return Err(PngError::ChunkTooLong(data_length).into());
+    }
+    Ok(Some(
+        Chunk::CHUNK_TYPE_LENGTH + data_length + Chunk::CRC_LENGTH,
+    ))
+}
+
+/// Parses a chunk read as its length field followed by at most `remaining`
+/// more bytes, failing when the stream ended early
+pub(crate) fn read_chunk(bytes: &[u8], remaining: usize) -> Result<Chunk> {
+    if bytes.len() < Chunk::LEN_DATA_LENGTH + remaining {
+        return Err(PngError::TruncatedChunk.into());
+    }
+    Chunk::try_from(bytes)
+}
+
+/// Fills as much of `buf` as the reader can provide, returning how many bytes
+/// were read. Unlike `read_exact` a short read at the end of the stream is not
+/// an error, which lets the parser tell a clean end from a truncated chunk
+fn read_up_to(reader: &mut impl Read, buf: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
         }
+    }
+    Ok(filled)
+}
 
-        // parse one chunk at a time
-        let mut chunks = Vec::new();
+/// Offset of the IEND chunk in `file`, found by seeking from one chunk header
+/// to the next without reading any chunk data. IEND has to end the file
+fn end_offset(file: &mut File) -> Result<u64> {
+    let file_length = file.metadata()?.len();
+    let mut signature = [0; Png::HEADER_LENGTH];
+    if read_up_to(file, &mut signature)? < Png::HEADER_LENGTH {
+        return Err(PngError::TooSmall.into());
+    }
+    if !parse::has_signature(&signature) {
+        return Err(PngError::InvalidStandardHeader.into());
+    }
 
-        while index < value.len() {
-            let curr_chunk_len = u32::from_be_bytes((&value[index..(index + 4)]).try_into()?) as usize;
-            let chunk = Chunk::try_from(&value[index..(index + curr_chunk_len + Chunk::META_DATA_LENGTH)])?;
-            index += chunk.length() + Chunk::META_DATA_LENGTH;
-            chunks.push(chunk);
+    let mut offset = Png::HEADER_LENGTH as u64;
+    loop {
+        let mut header = [0; Chunk::LEN_DATA_LENGTH + Chunk::CHUNK_TYPE_LENGTH];
+        file.seek(SeekFrom::Start(offset))?;
+        match read_up_to(file, &mut header)? {
+            0 => {
+                return Err(PngError::OutOfSpec {
+                    offset: offset as usize,
+                    message: "the png has no IEND chunk".to_string(),
+                }
+                .into())
+            }
+            n if n < header.len() => return Err(PngError::TruncatedChunk.into()),
+            _ => {}
+        }
+        let (length, chunk_type) = header.split_at(Chunk::LEN_DATA_LENGTH);
+        let length = u32::from_be_bytes(length.try_into()?) as usize;
+        if length > MAX_CHUNK_LENGTH {
+            return Err(PngError::ChunkTooLong(length).into());
+        }
+        let next = offset + (length + Chunk::META_DATA_LENGTH) as u64;
+        if next > file_length {
+            return Err(PngError::TruncatedChunk.into());
+        }
+        if chunk_type == ChunkType::IEND.bytes() {
+            if next < file_length {
+                return Err(PngError::OutOfSpec {
+                    offset: next as usize,
+                    message: "data follows the IEND chunk".to_string(),
+                }
+                .into());
+            }
+            return Ok(offset);
+        }
+        offset = next;
+    }
+}
+
+/// The index key for a chunk type given as text. Chunks read leniently can
+/// have types that aren't letters, so the bytes are taken as they are
+fn key(chunk_type: &str) -> Option<ChunkType> {
+    Some(ChunkType::new_unchecked(
+        chunk_type.as_bytes().try_into().ok()?,
+    ))
+}
+
+impl TryFrom<&[u8]> for Png {
+    type Error = Error;
+
+    fn try_from(value: &[u8]) -> Result<Self> {
+        if value.len() < Png::HEADER_LENGTH {
+            return Err(PngError::TooSmall.into());
+        }
+
+        if !parse::has_signature(value) {
+            return Err(PngError::InvalidStandardHeader.into());
         }
 
-        Ok(Png { chunks })
+        let chunks = parse::Chunks::new(&value[Png::HEADER_LENGTH..]).collect::<Result<_>>()?;
+        Ok(Png::from_chunks(chunks))
     }
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
+    use crate::apng::tests::{actl_chunk, fctl_chunk, fdat_chunk};
     use crate::chunk::Chunk;
     use crate::chunk_type::ChunkType;
     use std::convert::TryFrom;
+    use std::fs;
+    use std::path::PathBuf;
     use std::str::FromStr;
 
+    /// A minimal png with an IHDR, IDAT and IEND chunk
+    pub(crate) fn testing_png_bytes() -> Vec<u8> {
+        let chunks = vec![
+            Chunk::new(ChunkType::IHDR, vec![0; 13]),
+            Chunk::new(ChunkType::IDAT, vec![1, 2, 3]),
+            Chunk::new(ChunkType::IEND, vec![]),
+        ];
+        Png::from_chunks(chunks).as_bytes()
+    }
+
+    /// Writes the testing png to a unique path in the temp directory
+    pub(crate) fn testing_file(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("picmes-{}-{}.png", std::process::id(), name));
+        fs::write(&path, testing_png_bytes()).unwrap();
+        path
+    }
+
+    /// A ruSt chunk holding `data`, as the other carriers store payloads
+    pub(crate) fn testing_chunk(data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str("ruSt").unwrap(), data.to_vec())
+    }
+
     fn chunk_from_strings(chunk_type: &str, data: &str) -> Result<Chunk> {
         let chunk_type = ChunkType::from_str(chunk_type)?;
         let data: Vec<u8> = data.bytes().collect();
@@ -134,13 +834,11 @@ mod tests {
     }
 
     fn testing_chunks() -> Vec<Chunk> {
-        let mut chunks = Vec::with_capacity(3);
-
-        chunks.push(chunk_from_strings("FrSt", "I am the first chunk").unwrap());
-        chunks.push(chunk_from_strings("miDl", "I am another chunk").unwrap());
-        chunks.push(chunk_from_strings("LASt", "I am the last chunk").unwrap());
-
-        chunks
+        vec![
+            chunk_from_strings("FrSt", "I am the first chunk").unwrap(),
+            chunk_from_strings("miDl", "I am another chunk").unwrap(),
+            chunk_from_strings("LASt", "I am the last chunk").unwrap(),
+        ]
     }
 
     fn testing_png() -> Png {
@@ -220,13 +918,66 @@ mod tests {
         assert_eq!(chunks.len(), 3);
     }
 
+    #[test]
+    fn test_chunks_mut() {
+        let mut png = testing_png();
+        for chunk in png.chunks_mut() {
+            chunk.set_data(Vec::new());
+        }
+        assert!(png.chunks().all(|c| c.length() == 0));
+    }
+
     #[test]
     fn test_chunk_by_type() {
         let png = testing_png();
         let chunk = png.chunk_by_type("FrSt").unwrap();
         assert_eq!(&chunk.chunk_type().to_string(), "FrSt");
         assert_eq!(&chunk.data_as_string().unwrap(), "I am the first chunk");
+    }
+
+    #[test]
+    fn test_validate() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        assert!(png.validate().problems.is_empty());
 
+        let report = testing_png().validate();
+        assert!(!report.is_valid());
+        assert!(report
+            .errors()
+            .any(|p| p.message == "First chunk is not IHDR"));
+    }
+
+    #[test]
+    fn test_text_metadata() {
+        let mut png = testing_png();
+        let text = |keyword, text| TextChunk::new(keyword, text).unwrap();
+        png.append_chunk(text("Title", "Dice").to_chunk().unwrap());
+        png.append_chunk(chunk_from_strings("tEXt", "no separator").unwrap());
+        png.append_chunk(text("Author", "Wikipedia").compress().to_chunk().unwrap());
+
+        let metadata: Vec<String> = png.text_metadata().iter().map(|t| t.to_string()).collect();
+        assert_eq!(metadata, ["Title: Dice", "Author: Wikipedia"]);
+    }
+
+    #[test]
+    fn test_header() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let header = png.header().unwrap();
+        assert_eq!((header.width, header.height), (50, 50));
+        assert!(testing_png().header().is_err());
+    }
+
+    #[test]
+    fn test_chunks_by_type() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("TeSt", "first").unwrap());
+        png.append_chunk(chunk_from_strings("TeSt", "second").unwrap());
+        let messages: Vec<String> = png
+            .chunks_by_type("TeSt")
+            .map(|c| c.data_as_string().unwrap())
+            .collect();
+        assert_eq!(messages, ["first", "second"]);
+        assert_eq!(png.chunks_by_type("miSs").count(), 0);
     }
 
     #[test]
@@ -238,13 +989,189 @@ mod tests {
         assert_eq!(&chunk.data_as_string().unwrap(), "Message");
     }
 
+    #[test]
+    fn test_append_chunk_before_iend() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("IEND", "").unwrap());
+        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap());
+        let types: Vec<String> = png.chunks().map(|c| c.chunk_type().to_string()).collect();
+        assert_eq!(types, ["FrSt", "miDl", "LASt", "TeSt", "IEND"]);
+    }
+
+    fn png_of(types: &[&str]) -> Png {
+        Png::from_chunks(
+            types
+                .iter()
+                .map(|t| chunk_from_strings(t, "").unwrap())
+                .collect(),
+        )
+    }
+
+    fn types_of(png: &Png) -> Vec<String> {
+        png.chunks().map(|c| c.chunk_type().to_string()).collect()
+    }
+
+    #[test]
+    fn test_insert_chunk_placement() {
+        let layout = ["IHDR", "PLTE", "IDAT", "IDAT", "IEND"];
+        let cases = [
+            ("tEXt", ["IHDR", "PLTE", "IDAT", "IDAT", "tEXt", "IEND"]),
+            ("pHYs", ["IHDR", "PLTE", "pHYs", "IDAT", "IDAT", "IEND"]),
+            ("gAMA", ["IHDR", "gAMA", "PLTE", "IDAT", "IDAT", "IEND"]),
+            ("IDAT", ["IHDR", "PLTE", "IDAT", "IDAT", "IDAT", "IEND"]),
+        ];
+        for (chunk_type, expected) in cases {
+            let mut png = png_of(&layout);
+            png.insert_chunk(chunk_from_strings(chunk_type, "").unwrap());
+            assert_eq!(types_of(&png), expected, "{}", chunk_type);
+        }
+
+        let mut png = png_of(&["IHDR", "IDAT"]);
+        png.insert_chunk(chunk_from_strings("gAMA", "").unwrap());
+        png.insert_chunk(chunk_from_strings("ruSt", "").unwrap());
+        assert_eq!(types_of(&png), ["IHDR", "gAMA", "IDAT", "ruSt"]);
+    }
+
+    #[test]
+    fn test_insert_chunk_into_apng() {
+        let mut png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "").unwrap(),
+            actl_chunk(2, 0),
+            fctl_chunk(0, 1, 10),
+            chunk_from_strings("IDAT", "").unwrap(),
+            fctl_chunk(1, 1, 10),
+            fdat_chunk(2),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]);
+        png.insert_chunk(chunk_from_strings("pHYs", "").unwrap());
+        png.insert_chunk(chunk_from_strings("ruSt", "").unwrap());
+        assert_eq!(
+            types_of(&png),
+            ["IHDR", "acTL", "pHYs", "fcTL", "IDAT", "fcTL", "fdAT", "ruSt", "IEND"]
+        );
+        assert!(png.validate().problems.is_empty());
+        let animation = png.animation().unwrap().unwrap();
+        assert_eq!(animation.frames.len(), 2);
+        assert_eq!(animation.duration(), 0.2);
+        assert_eq!(testing_png().animation().unwrap(), None);
+    }
+
+    #[test]
+    fn test_insert_chunk_at() {
+        let mut png = png_of(&["IHDR", "IDAT", "IEND"]);
+        png.insert_chunk_at(1, chunk_from_strings("tEXt", "").unwrap())
+            .unwrap();
+        assert_eq!(types_of(&png), ["IHDR", "tEXt", "IDAT", "IEND"]);
+        assert!(png
+            .insert_chunk_at(5, chunk_from_strings("tEXt", "").unwrap())
+            .is_err());
+    }
+
+    #[test]
+    fn test_last_modified() {
+        let mut png = png_of(&["IHDR", "IDAT", "IEND"]);
+        assert!(png.last_modified().is_err());
+
+        let first = LastModified::new(2020, 1, 2, 3, 4, 5).unwrap();
+        assert!(png.set_last_modified(first).is_none());
+        assert_eq!(types_of(&png), ["IHDR", "IDAT", "tIME", "IEND"]);
+        assert_eq!(png.last_modified().unwrap(), first);
+
+        let second = LastModified::new(2021, 6, 7, 8, 9, 10).unwrap();
+        let replaced = png.set_last_modified(second).unwrap();
+        assert_eq!(LastModified::try_from(&replaced).unwrap(), first);
+        assert_eq!(png.chunks_by_type("tIME").count(), 1);
+        assert_eq!(png.last_modified().unwrap(), second);
+    }
+
+    #[test]
+    fn test_color_and_physical_metadata() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        assert_eq!(png.rendering_intent().unwrap(), RenderingIntent::Perceptual);
+        assert_eq!(png.gamma().unwrap().scaled, 45455);
+        let physical = png.physical_dimensions().unwrap();
+        assert_eq!(physical.pixels_per_unit_x, 3778);
+        assert!(physical.in_metres);
+
+        let png = testing_png();
+        assert!(png.rendering_intent().is_err());
+        assert!(png.gamma().is_err());
+        assert!(png.physical_dimensions().is_err());
+    }
+
+    #[test]
+    fn test_set_exif() {
+        let mut png = png_of(&["IHDR", "IDAT", "IEND"]);
+        assert!(png.exif().is_err());
+        assert!(png.set_exif(b"not exif".to_vec()).is_err());
+
+        let exif = crate::exif::tests::testing_exif();
+        assert!(png.set_exif(exif.clone()).unwrap().is_none());
+        assert_eq!(types_of(&png), ["IHDR", "eXIf", "IDAT", "IEND"]);
+        assert_eq!(png.exif().unwrap(), exif);
+
+        let replaced = png.set_exif(b"MM\0*\0\0\0\x08\0\0".to_vec()).unwrap();
+        assert_eq!(replaced.unwrap().data(), exif);
+        assert_eq!(png.chunks_by_type("eXIf").count(), 1);
+    }
+
+    #[test]
+    fn test_replace_image_data() {
+        let mut png = png_of(&["IHDR", "IDAT", "IDAT", "tEXt", "IEND"]);
+        png.replace_image_data(chunk_from_strings("IDAT", "merged").unwrap())
+            .unwrap();
+        assert_eq!(types_of(&png), ["IHDR", "IDAT", "tEXt", "IEND"]);
+        assert_eq!(png.chunk_by_type("IDAT").unwrap().data(), b"merged");
+
+        let mut png = png_of(&["IHDR", "IEND"]);
+        assert!(png
+            .replace_image_data(chunk_from_strings("IDAT", "").unwrap())
+            .is_err());
+    }
+
     #[test]
     fn test_remove_chunk() {
         let mut png = testing_png();
         png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap());
-        png.remove_chunk("TeSt").unwrap();
+        png.remove_first_chunk("TeSt").unwrap();
         let chunk = png.chunk_by_type("TeSt");
         assert!(chunk.is_none());
+        assert!(png.remove_first_chunk("TeSt").is_err());
+    }
+
+    #[test]
+    fn test_remove_first_chunk_keeps_later_ones() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("TeSt", "first").unwrap());
+        png.append_chunk(chunk_from_strings("TeSt", "second").unwrap());
+        let removed = png.remove_first_chunk("TeSt").unwrap();
+        assert_eq!(removed.data_as_string().unwrap(), "first");
+        let chunk = png.chunk_by_type("TeSt").unwrap();
+        assert_eq!(chunk.data_as_string().unwrap(), "second");
+    }
+
+    #[test]
+    fn test_remove_all_chunks() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("TeSt", "first").unwrap());
+        png.append_chunk(chunk_from_strings("TeSt", "second").unwrap());
+        let removed = png.remove_all_chunks("TeSt");
+        assert_eq!(removed.len(), 2);
+        assert_eq!(removed[1].data_as_string().unwrap(), "second");
+        assert!(png.chunk_by_type("TeSt").is_none());
+        assert!(png.remove_all_chunks("TeSt").is_empty());
+    }
+
+    #[test]
+    fn test_remove_chunks_where() {
+        let mut png = testing_png();
+        let removed = png.remove_chunks_where(|c| !c.chunk_type().is_public());
+        let removed: Vec<String> = removed.iter().map(|c| c.chunk_type().to_string()).collect();
+        assert_eq!(removed, ["FrSt", "miDl"]);
+        assert_eq!(png.chunks().len(), 1);
+        assert!(png.chunk_by_type("LASt").is_some());
+
+        assert!(png.remove_chunks_where(|_| false).is_empty());
     }
 
     #[test]
@@ -279,6 +1206,55 @@ mod tests {
         let _png_string = format!("{}", png);
     }
 
+    #[test]
+    fn test_from_reader() {
+        let png = Png::from_reader(PNG_FILE.as_ref()).unwrap();
+        assert_eq!(png.as_bytes(), PNG_FILE);
+    }
+
+    #[test]
+    fn test_write_to() {
+        let png = testing_png();
+        let mut written = Vec::new();
+        png.write_to(&mut written).unwrap();
+        assert_eq!(written, png.as_bytes());
+        assert_eq!(&written[..Png::HEADER_LENGTH], Png::STANDARD_HEADER);
+    }
+
+    #[test]
+    fn test_from_reader_rejects_bad_input() {
+        assert!(Png::from_reader(&PNG_FILE[..4]).is_err());
+        assert!(Png::from_reader(&PNG_FILE[..PNG_FILE.len() - 3]).is_err());
+        assert!(Png::from_reader(&PNG_FILE[..Png::HEADER_LENGTH + 2]).is_err());
+
+        let mut bytes = PNG_FILE.to_vec();
+        bytes[0] = 0;
+        assert!(Png::from_reader(bytes.as_slice()).is_err());
+
+        let mut huge = Png::STANDARD_HEADER.to_vec();
+        huge.extend_from_slice(&[0xff, 0xff, 0xff, 0xff]);
+        huge.extend_from_slice(b"ruSt");
+        assert!(Png::from_reader(huge.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_save_and_from_file() {
+        let file = std::env::temp_dir().join(format!("picmes-{}-save.png", std::process::id()));
+        let png = testing_png();
+        png.save(&file).unwrap();
+
+        assert_eq!(fs::read(&file).unwrap(), png.as_bytes());
+        let read = Png::from_file(&file).unwrap();
+        assert_eq!(
+            read.chunks().collect::<Vec<_>>(),
+            png.chunks().collect::<Vec<_>>()
+        );
+
+        fs::write(&file, b"not a png at all").unwrap();
+        assert!(Png::from_file(&file).is_err());
+        fs::remove_file(file).unwrap();
+    }
+
     // This is the raw bytes for a shrunken version of the `dice.png` image on Wikipedia
     const PNG_FILE: [u8; 4803] = [
         137, 80, 78, 71, 13, 10, 26, 10, 0, 0, 0, 13, 73, 72, 68, 82, 0, 0, 0, 50, 0, 0, 0, 50, 8,
@@ -525,4 +1501,263 @@ mod tests {
         202, 28, 31, 66, 176, 235, 16, 0, 0, 0, 3, 82, 117, 83, 116, 104, 101, 121, 158, 176, 245,
         160, 0, 0, 0, 0, 73, 69, 78, 68, 174, 66, 96, 130,
     ];
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let png = Png::try_from(testing_png_bytes().as_slice()).unwrap();
+        let json = serde_json::to_string(&png).unwrap();
+        let png: Png = serde_json::from_str(&json).unwrap();
+        assert_eq!(png.as_bytes(), testing_png_bytes());
+    }
+
+    #[test]
+    fn test_try_from_with_strict() {
+        let strict = ParseOptions::default();
+        let (png, report) = Png::try_from_with(&testing_png_bytes(), strict).unwrap();
+        assert_eq!(png.as_bytes(), testing_png_bytes());
+        assert!(report.problems.is_empty());
+
+        // a misplaced gAMA chunk is only a warning, strict parsing still
+        // rejects it
+        let mut png = Png::try_from(testing_png_bytes().as_slice()).unwrap();
+        png.insert_chunk_at(2, Chunk::new(ChunkType::gAMA, vec![0, 0, 0xb1, 0x8f]))
+            .unwrap();
+        let bytes = png.as_bytes();
+        let error = Png::try_from_with(&bytes, strict).unwrap_err();
+        assert!(matches!(
+            error,
+            crate::Error::Png(PngError::OutOfSpec { offset, .. })
+                if offset == Png::HEADER_LENGTH + 25 + 15
+        ));
+        assert!(Png::try_from(bytes.as_slice()).is_ok());
+    }
+
+    #[test]
+    fn test_try_from_with_lenient() {
+        let lenient = ParseOptions { lenient: true };
+        let mut bytes = testing_png_bytes();
+        // corrupt the IDAT data, leaving its crc stale
+        let idat_data = Png::HEADER_LENGTH + 13 + Chunk::META_DATA_LENGTH + 8;
+        bytes[idat_data] ^= 0xff;
+        bytes.extend(b"junk");
+        assert!(Png::try_from(bytes.as_slice()).is_err());
+
+        let (png, report) = Png::try_from_with(&bytes, lenient).unwrap();
+        assert_eq!(png.chunks().count(), 3);
+        assert_eq!(png.chunk_by_type("IDAT").unwrap().data(), [0xfe, 2, 3]);
+        assert!(report.is_valid());
+        let warnings: Vec<&str> = report.warnings().map(|p| p.message.as_str()).collect();
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[0].starts_with("IDAT chunk CRC mismatch"));
+        assert_eq!(warnings[1], "4 bytes of trailing data after IEND");
+
+        // a cut off chunk ends parsing, keeping the chunks before it
+        let truncated = &testing_png_bytes()[..Png::HEADER_LENGTH + 25 + 10];
+        let (png, report) = Png::try_from_with(truncated, lenient).unwrap();
+        assert_eq!(png.chunks().count(), 1);
+        assert!(report.warnings().count() > 0);
+
+        assert!(Png::try_from_with(&[137, 80], lenient).is_err());
+    }
+
+    #[test]
+    fn test_lenient_chunk_types_that_are_not_letters() {
+        let mut bytes = testing_png_bytes();
+        let odd = Chunk::new(ChunkType::new_unchecked([0x49, 0x7f, 0xff, 0xff]), vec![1]);
+        let iend = bytes.len() - Chunk::META_DATA_LENGTH;
+        bytes.splice(iend..iend, odd.as_bytes());
+        let (png, _) = Png::try_from_with(&bytes, ParseOptions { lenient: true }).unwrap();
+        assert_eq!(png.chunks().nth(2).unwrap().chunk_type(), odd.chunk_type());
+
+        let stats = crate::stats::stats(&png, 5);
+        assert!(stats
+            .types
+            .iter()
+            .any(|t| t.chunk_type == "I\\x7f\\xff\\xff"));
+        crate::detect::detect(&png);
+        assert!(png.text_metadata().is_empty());
+    }
+
+    #[test]
+    fn test_adversarial_lengths_do_not_panic() {
+        let mut bytes = testing_png_bytes();
+        // the IDAT length now reaches far past the end of the file
+        let idat = Png::HEADER_LENGTH + 25;
+        bytes[idat..idat + 4].copy_from_slice(&0x7fff_fff0u32.to_be_bytes());
+        assert!(Png::try_from(bytes.as_slice()).is_err());
+
+        // a stream cut off inside a chunk header
+        let bytes = testing_png_bytes();
+        for end in Png::HEADER_LENGTH + 1..Png::HEADER_LENGTH + 4 {
+            assert!(Png::try_from(&bytes[..end]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_as_bytes_is_allocated_once() {
+        let png = Png::try_from(testing_png_bytes().as_slice()).unwrap();
+        let bytes = png.as_bytes();
+        assert_eq!(png.encoded_length(), bytes.len());
+        assert_eq!(bytes.capacity(), bytes.len());
+
+        let mut written = Vec::new();
+        png.write_to(&mut written).unwrap();
+        assert_eq!(written, bytes);
+    }
+
+    #[test]
+    fn test_index_follows_changes() {
+        let mut png = Png::try_from(testing_png_bytes().as_slice()).unwrap();
+        assert!(png.chunk_by_type("tEXt").is_none());
+
+        png.insert_chunk(Chunk::new(ChunkType::tEXt, b"first".to_vec()));
+        png.append_chunk(Chunk::new(ChunkType::tEXt, b"second".to_vec()));
+        png.insert_chunk_at(0, Chunk::new(ChunkType::tEXt, b"zeroth".to_vec()))
+            .unwrap();
+        let texts: Vec<&[u8]> = png.chunks_by_type("tEXt").map(Chunk::data).collect();
+        assert_eq!(texts, [&b"zeroth"[..], b"first", b"second"]);
+        assert_eq!(png.chunk_by_type("IDAT").unwrap().data(), [1, 2, 3]);
+
+        png.remove_first_chunk("tEXt").unwrap();
+        assert_eq!(png.chunk_by_type("tEXt").unwrap().data(), b"first");
+        assert_eq!(png.remove_all_chunks("tEXt").len(), 2);
+        assert!(png.chunk_by_type("tEXt").is_none());
+
+        // a chunk replaced through chunks_mut can change type
+        for chunk in png.chunks_mut() {
+            if chunk.chunk_type() == &ChunkType::IDAT {
+                *chunk = Chunk::new(ChunkType::pHYs, vec![0; 9]);
+            }
+        }
+        assert!(png.chunk_by_type("IDAT").is_none());
+        assert!(png.physical_dimensions().is_ok());
+
+        png.replace_image_data(Chunk::new(ChunkType::IDAT, vec![4]))
+            .unwrap_err();
+        assert!(png.chunk_by_type("long").is_none());
+        assert!(png.chunk_by_type("").is_none());
+    }
+
+    #[test]
+    fn test_index_updated_in_place() {
+        let mut png = Png::try_from(testing_png_bytes().as_slice()).unwrap();
+        let text = |data: &[u8]| Chunk::new(ChunkType::tEXt, data.to_vec());
+        let matches_scan = |png: &Png| {
+            let index = png.index.get().expect("the index was built by a lookup");
+            let mut scanned: HashMap<ChunkType, Vec<usize>> = HashMap::new();
+            for (position, chunk) in png.chunks.iter().enumerate() {
+                scanned
+                    .entry(chunk.chunk_type().clone())
+                    .or_default()
+                    .push(position);
+            }
+            *index == scanned
+        };
+        assert!(png.chunk_by_type("tEXt").is_none());
+
+        png.insert_chunk(text(b"a"));
+        assert!(matches_scan(&png));
+        png.insert_chunk_at(1, text(b"b")).unwrap();
+        png.append_chunk(Chunk::new(ChunkType::pHYs, vec![0; 9]));
+        png.insert_chunk(Chunk::new(ChunkType::gAMA, vec![0, 0, 0xb1, 0x8f]));
+        assert!(matches_scan(&png));
+        assert_eq!(png.chunk_by_type("tEXt").unwrap().data(), b"b");
+
+        png.remove_first_chunk("tEXt").unwrap();
+        assert!(matches_scan(&png));
+        assert_eq!(png.chunk_by_type("tEXt").unwrap().data(), b"a");
+        png.insert_chunk_at(0, text(b"c")).unwrap();
+        png.replace_image_data(Chunk::new(ChunkType::IDAT, vec![4]))
+            .unwrap();
+        assert!(matches_scan(&png));
+        assert_eq!(png.chunk_by_type("IDAT").unwrap().data(), [4]);
+
+        assert_eq!(png.remove_all_chunks("tEXt").len(), 2);
+        assert!(matches_scan(&png));
+        assert!(!png.index.get().unwrap().contains_key(&ChunkType::tEXt));
+        png.remove_chunks_where(|c| c.chunk_type() == &ChunkType::gAMA);
+        assert!(matches_scan(&png));
+        assert!(png.gamma().is_err());
+        assert!(png.physical_dimensions().is_ok());
+    }
+
+    #[test]
+    fn test_append_in_place() {
+        let path = testing_file("append-in-place");
+        let text = Chunk::new(ChunkType::tEXt, b"Comment\0appended".to_vec());
+        let private = Chunk::new(ChunkType::from_str("ruSt").unwrap(), vec![7; 5]);
+        Png::append_in_place(&path, &[text.clone(), private.clone()]).unwrap();
+
+        let mut expected = Png::try_from(testing_png_bytes().as_slice()).unwrap();
+        expected.insert_chunk(text);
+        expected.insert_chunk(private);
+        assert_eq!(fs::read(&path).unwrap(), expected.as_bytes());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_append_in_place_keeps_the_start() {
+        let path = testing_file("append-in-place-start");
+        let original = fs::read(&path).unwrap();
+        let end = end_offset(&mut File::open(&path).unwrap()).unwrap() as usize;
+        #[cfg(unix)]
+        let inode = std::os::unix::fs::MetadataExt::ino(&fs::metadata(&path).unwrap());
+
+        let private = Chunk::new(ChunkType::from_str("ruSt").unwrap(), vec![7; 5]);
+        Png::append_in_place(&path, &[private]).unwrap();
+        let appended = fs::read(&path).unwrap();
+        assert_eq!(appended[..end], original[..end]);
+        assert_eq!(appended[appended.len() - 12..], original[end..]);
+        // written in place rather than replaced by a new file
+        #[cfg(unix)]
+        assert_eq!(
+            std::os::unix::fs::MetadataExt::ino(&fs::metadata(&path).unwrap()),
+            inode
+        );
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_append_in_place_refuses() {
+        let path = testing_file("append-in-place-refuses");
+        let physical = Chunk::new(ChunkType::pHYs, vec![0; 9]);
+        assert!(matches!(
+            Png::append_in_place(&path, &[physical]),
+            Err(Error::Png(PngError::NotAppendable(ChunkType::pHYs)))
+        ));
+        assert_eq!(fs::read(&path).unwrap(), testing_png_bytes());
+
+        let text = Chunk::new(ChunkType::tEXt, b"Comment\0text".to_vec());
+        let mut trailing = testing_png_bytes();
+        trailing.extend_from_slice(b"junk");
+        fs::write(&path, &trailing).unwrap();
+        assert!(Png::append_in_place(&path, std::slice::from_ref(&text)).is_err());
+        assert_eq!(fs::read(&path).unwrap(), trailing);
+
+        let without_end = &testing_png_bytes()[..testing_png_bytes().len() - 12];
+        fs::write(&path, without_end).unwrap();
+        assert!(Png::append_in_place(&path, std::slice::from_ref(&text)).is_err());
+        fs::write(&path, &without_end[..without_end.len() - 1]).unwrap();
+        assert!(Png::append_in_place(&path, &[text]).is_err());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_repeated_chunks() {
+        let mut png = Png::try_from(testing_png_bytes().as_slice()).unwrap();
+        let text = Chunk::new(ChunkType::tEXt, b"Comment\0same".to_vec());
+        png.insert_chunk(text.clone());
+        png.insert_chunk(Chunk::new(ChunkType::tEXt, b"Comment\0other".to_vec()));
+        png.insert_chunk(text.clone());
+        png.insert_chunk(text.clone());
+        // critical chunks are never repeats, even identical IDAT chunks
+        png.insert_chunk(Chunk::new(ChunkType::IDAT, vec![1, 2, 3]));
+        assert_eq!(png.repeated_chunks(), [5, 6]);
+
+        assert_eq!(png.remove_repeated_chunks(), [text.clone(), text]);
+        assert!(png.repeated_chunks().is_empty());
+        assert_eq!(png.chunks_by_type("tEXt").count(), 2);
+        assert_eq!(png.chunks_by_type("IDAT").count(), 2);
+    }
 }
@@ -0,0 +1,85 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, IsTerminal, Read},
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use indicatif::{ProgressBar, ProgressBarIter, ProgressDrawTarget, ProgressFinish, ProgressStyle};
+
+use crate::Result;
+
+/// Local files at least this large are read behind a bar of their own
+pub const LARGE_FILE: u64 = 64 * 1024 * 1024;
+
+/// Cleared by --no-progress and -q
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Turns bars on or off for the rest of the process
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// A bar on stderr counting up to `total`, hidden when bars are turned off
+/// or stderr is not a terminal. It disappears once dropped
+fn bar(total: u64, template: &str) -> ProgressBar {
+    let bar = match ENABLED.load(Ordering::Relaxed) && io::stderr().is_terminal() {
+        true => ProgressBar::with_draw_target(Some(total), ProgressDrawTarget::stderr()),
+        false => ProgressBar::hidden(),
+    };
+    let style = ProgressStyle::with_template(template).expect("the templates are valid");
+    bar.with_style(style).with_finish(ProgressFinish::AndClear)
+}
+
+/// A bar counting the files of a batch done
+pub fn files(total: usize) -> ProgressBar {
+    bar(total as u64, "{wide_bar} {pos}/{len} files, {eta} left")
+}
+
+/// A bar counting the bytes of `path` read
+pub fn bytes(path: &Path, total: u64) -> ProgressBar {
+    let bar = bar(total, "{msg} {wide_bar} {bytes}/{total_bytes}, {eta} left");
+    bar.set_message(path.display().to_string());
+    bar
+}
+
+/// Opens the local file at `path`, showing how much of it was read when it
+/// is at least `LARGE_FILE` bytes
+pub fn open(path: &Path) -> Result<BufReader<ProgressBarIter<File>>> {
+    let file = File::open(path)?;
+    let bar = match file.metadata()?.len() {
+        length if length >= LARGE_FILE => bytes(path, length),
+        _ => ProgressBar::hidden(),
+    };
+    Ok(BufReader::new(bar.wrap_read(file)))
+}
+
+/// The contents of the local file at `path`, read as `open` does
+pub fn read(path: &Path) -> Result<Vec<u8>> {
+    let mut contents = Vec::new();
+    open(path)?.read_to_end(&mut contents)?;
+    Ok(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::tests::{testing_file, testing_png_bytes};
+
+    #[test]
+    fn test_read() {
+        let file = testing_file("progress-read");
+        assert_eq!(read(&file).unwrap(), testing_png_bytes());
+        assert!(read(Path::new("missing.png")).is_err());
+        std::fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_hidden_without_terminal() {
+        // stderr is only a terminal with --nocapture run from a shell
+        if !io::stderr().is_terminal() {
+            assert!(files(3).is_hidden());
+            assert!(bytes(Path::new("a.png"), LARGE_FILE).is_hidden());
+        }
+    }
+}
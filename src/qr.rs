@@ -0,0 +1,115 @@
+use std::fmt::Display;
+
+use qrcodegen::{QrCode, QrCodeEcc};
+
+use crate::{
+    ihdr::{ColorType, Ihdr},
+    image_data::ImageData,
+    png::Png,
+    Result,
+};
+
+#[derive(Debug)]
+pub enum QrError {
+    TooLong(usize),
+}
+
+impl std::error::Error for QrError {}
+
+impl Display for QrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooLong(length) => write!(
+                f,
+                "The payload of {} bytes does not fit in a QR code, which holds up to {}",
+                length, MAX_BYTES
+            ),
+        }
+    }
+}
+
+/// The most bytes a QR code holds at the medium error correction `render`
+/// uses. Text made of digits or upper case letters only packs tighter
+pub const MAX_BYTES: usize = 2331;
+/// Pixels on each side of a module
+pub const SCALE: i32 = 8;
+/// Light modules around the code, which readers need to find its edges
+const QUIET_ZONE: i32 = 4;
+
+const DARK: u8 = 0;
+const LIGHT: u8 = 255;
+
+/// Renders `payload` as a black on white QR code. Text is encoded as text so
+/// phones show it as such, anything else as bytes
+pub fn render(payload: &[u8]) -> Result<Png> {
+    let code = match std::str::from_utf8(payload) {
+        Ok(text) => QrCode::encode_text(text, QrCodeEcc::Medium),
+        Err(_) => QrCode::encode_binary(payload, QrCodeEcc::Medium),
+    }
+    .map_err(|_| QrError::TooLong(payload.len()))?;
+
+    let modules = code.size() + 2 * QUIET_ZONE;
+    let side = (modules * SCALE) as u32;
+    let mut samples = Vec::with_capacity(side as usize * side as usize);
+    for y in 0..modules {
+        let row: Vec<u8> = (0..modules)
+            .flat_map(|x| {
+                let dark = code.get_module(x - QUIET_ZONE, y - QUIET_ZONE);
+                [if dark { DARK } else { LIGHT }; SCALE as usize]
+            })
+            .collect();
+        for _ in 0..SCALE {
+            samples.extend_from_slice(&row);
+        }
+    }
+    let header = Ihdr {
+        color_type: ColorType::Grayscale,
+        ..Ihdr::rgba(side, side)
+    };
+    ImageData::new(header, samples)?.to_png()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Whether the module at `x`, `y` of the rendered `image`, counted from
+    /// the edge of the quiet zone, is dark
+    fn dark(image: &ImageData, x: i32, y: i32) -> bool {
+        let side = image.header().width as i32;
+        let pixel = (y * SCALE + SCALE / 2) * side + x * SCALE + SCALE / 2;
+        image.samples()[pixel as usize] == DARK
+    }
+
+    #[test]
+    fn test_render() {
+        let payload = b"AGE-SECRET-KEY-1QQQQQQQQQQQQQQQQQQQQQQQQQQQQQQQQ";
+        let png = Png::try_from(render(payload).unwrap().as_bytes().as_slice()).unwrap();
+        let image = ImageData::from_png(&png).unwrap();
+        let code =
+            QrCode::encode_text(std::str::from_utf8(payload).unwrap(), QrCodeEcc::Medium).unwrap();
+        let modules = code.size() + 2 * QUIET_ZONE;
+        assert_eq!(image.header().width, (modules * SCALE) as u32);
+        assert_eq!(image.header().color_type, ColorType::Grayscale);
+
+        for y in 0..modules {
+            for x in 0..modules {
+                let expected = code.get_module(x - QUIET_ZONE, y - QUIET_ZONE);
+                assert_eq!(dark(&image, x, y), expected, "module {}, {}", x, y);
+            }
+        }
+        // the corner of the top left finder pattern, right after the quiet zone
+        assert!(!dark(&image, QUIET_ZONE - 1, QUIET_ZONE) && dark(&image, QUIET_ZONE, QUIET_ZONE));
+    }
+
+    #[test]
+    fn test_render_binary_and_too_long() {
+        assert!(render(&[0xff, 0x00, 0x80]).is_ok());
+        assert!(render(&vec![0xff; MAX_BYTES]).is_ok());
+        let error = render(&vec![0xff; MAX_BYTES + 1]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The payload of 2332 bytes does not fit in a QR code, which holds up to 2331"
+        );
+    }
+}
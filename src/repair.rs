@@ -0,0 +1,277 @@
+use std::fmt::Display;
+
+use crate::{
+    chunk::{Chunk, MAX_CHUNK_LENGTH},
+    chunk_type::ChunkType,
+    plan::Change,
+    png::Png,
+    Result,
+};
+
+#[derive(Debug)]
+pub enum RepairError {
+    Unrecoverable { offset: usize, reason: String },
+}
+
+impl std::error::Error for RepairError {}
+
+impl Display for RepairError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unrecoverable { offset, reason } => write!(
+                f,
+                "Unrecoverable chunk at offset {:#x}: {} (use --truncate or --drop)",
+                offset, reason
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RepairOptions {
+    /// Keep the data that is present for a chunk cut off by the end of the file
+    pub truncate: bool,
+    /// Remove chunks that cannot be recovered instead of failing
+    pub drop: bool,
+    /// Remove ancillary chunks with the same type and data as an earlier one
+    pub dedup: bool,
+}
+
+/// Rebuilds a png from possibly damaged bytes. CRCs are recomputed, data
+/// after IEND is discarded and a missing IEND is added back. Chunks that
+/// cannot be recovered and repeated chunks are handled according to `options`
+pub fn repair(bytes: &[u8], options: RepairOptions) -> Result<(Png, Vec<Change>)> {
+    let mut chunks = Vec::new();
+    let mut changes = Vec::new();
+
+    if bytes.len() < Png::HEADER_LENGTH || bytes[..Png::HEADER_LENGTH] != Png::STANDARD_HEADER {
+        changes.push(Change::repaired("signature", "rewritten"));
+    }
+
+    let mut offset = Png::HEADER_LENGTH.min(bytes.len());
+    while offset < bytes.len() {
+        let rest = &bytes[offset..];
+        if rest.len() < Chunk::LEN_DATA_LENGTH + Chunk::CHUNK_TYPE_LENGTH {
+            changes.push(trailing_data(rest.len()));
+            break;
+        }
+
+        let length = u32::from_be_bytes(rest[..4].try_into()?) as usize;
+        let type_bytes: [u8; 4] = rest[4..8].try_into()?;
+        let name = String::from_utf8_lossy(&type_bytes).into_owned();
+        let data = &rest[8..];
+
+        let unrecoverable = if !type_bytes.iter().all(u8::is_ascii_alphabetic) {
+            Some(format!("invalid chunk type {:?}", type_bytes))
+        } else if length > MAX_CHUNK_LENGTH {
+            Some(format!("length {} exceeds the maximum", length))
+        } else if data.len() < length + Chunk::CRC_LENGTH && !options.truncate {
+            Some(format!(
+                "{} chunk truncated to {} of {} bytes",
+                name,
+                data.len(),
+                length
+            ))
+        } else {
+            None
+        };
+
+        if let Some(reason) = unrecoverable {
+            if !options.drop {
+                return Err(RepairError::Unrecoverable { offset, reason }.into());
+            }
+            // without a trustworthy length there is no way to find the next chunk
+            changes.push(Change::Removed {
+                chunk_type: name,
+                length: rest.len(),
+            });
+            break;
+        }
+
+        let chunk_type = ChunkType::try_from(type_bytes)?;
+        if data.len() < length + Chunk::CRC_LENGTH {
+            let kept = data.len().min(length);
+            let action = if kept == length {
+                "recomputed missing CRC".to_string()
+            } else {
+                format!("truncated from {} to {} bytes", length, kept)
+            };
+            changes.push(Change::repaired(&name, action));
+            chunks.push(Chunk::new(chunk_type, data[..kept].to_vec()));
+            break;
+        }
+
+        let chunk = Chunk::new(chunk_type, data[..length].to_vec());
+        let stored_crc = u32::from_be_bytes(data[length..length + Chunk::CRC_LENGTH].try_into()?);
+        if stored_crc != chunk.crc() {
+            changes.push(Change::repaired(&name, "recomputed CRC"));
+        }
+        let is_iend = chunk.chunk_type() == &ChunkType::IEND;
+        chunks.push(chunk);
+        offset += length + Chunk::META_DATA_LENGTH;
+
+        if is_iend {
+            if offset < bytes.len() {
+                changes.push(trailing_data(bytes.len() - offset));
+            }
+            break;
+        }
+    }
+
+    let has_iend = chunks
+        .last()
+        .is_some_and(|c| c.chunk_type() == &ChunkType::IEND);
+    if !has_iend {
+        let iend = Chunk::new(ChunkType::IEND, Vec::new());
+        changes.push(Change::added(&iend));
+        chunks.push(iend);
+    }
+
+    let mut png = Png::from_chunks(chunks);
+    if options.dedup {
+        changes.extend(png.remove_repeated_chunks().iter().map(Change::removed));
+    }
+    Ok((png, changes))
+}
+
+fn trailing_data(length: usize) -> Change {
+    Change::repaired("trailing data", format!("dropped {} bytes", length))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn valid_png() -> Vec<u8> {
+        Png::from_chunks(vec![
+            chunk("IHDR", &[0; 13]),
+            chunk("IDAT", &[1, 2, 3]),
+            chunk("IEND", &[]),
+        ])
+        .as_bytes()
+    }
+
+    fn descriptions(changes: &[Change]) -> Vec<String> {
+        changes.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn test_repair_valid_png_is_unchanged() {
+        let bytes = valid_png();
+        let (png, changes) = repair(&bytes, RepairOptions::default()).unwrap();
+        assert!(changes.is_empty());
+        assert_eq!(png.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_repair_bad_crc_and_trailing_data() {
+        let mut bytes = valid_png();
+        bytes[8 + 25 - 1] ^= 0xff;
+        bytes.extend_from_slice(b"garbage");
+
+        let (png, changes) = repair(&bytes, RepairOptions::default()).unwrap();
+        assert_eq!(
+            descriptions(&changes),
+            ["~ IHDR: recomputed CRC", "~ trailing data: dropped 7 bytes"]
+        );
+        assert_eq!(png.as_bytes(), valid_png());
+        assert!(png.validate().problems.is_empty());
+    }
+
+    #[test]
+    fn test_repair_dedup() {
+        let text = chunk("tEXt", b"a\0b");
+        let bytes = Png::from_chunks(vec![
+            chunk("IHDR", &[0; 13]),
+            chunk("IDAT", &[1, 2, 3]),
+            text.clone(),
+            text.clone(),
+            chunk("IEND", &[]),
+        ])
+        .as_bytes();
+
+        let (png, changes) = repair(&bytes, RepairOptions::default()).unwrap();
+        assert!(changes.is_empty());
+        assert_eq!(png.as_bytes(), bytes);
+
+        let options = RepairOptions {
+            dedup: true,
+            ..Default::default()
+        };
+        let (png, changes) = repair(&bytes, options).unwrap();
+        assert_eq!(descriptions(&changes), ["- tEXt (3 bytes)"]);
+        assert_eq!(png.chunks_by_type("tEXt").collect::<Vec<_>>(), [&text]);
+    }
+
+    #[test]
+    fn test_repair_bad_signature() {
+        let mut bytes = valid_png();
+        bytes[1] = b'X';
+        let (png, changes) = repair(&bytes, RepairOptions::default()).unwrap();
+        assert_eq!(descriptions(&changes), ["~ signature: rewritten"]);
+        assert_eq!(png.as_bytes(), valid_png());
+    }
+
+    #[test]
+    fn test_repair_truncated() {
+        let bytes = valid_png();
+        let truncated = &bytes[..8 + 25 + 10];
+
+        assert!(repair(truncated, RepairOptions::default()).is_err());
+
+        let options = RepairOptions {
+            truncate: true,
+            ..Default::default()
+        };
+        let (png, changes) = repair(truncated, options).unwrap();
+        assert_eq!(
+            descriptions(&changes),
+            ["~ IDAT: truncated from 3 to 2 bytes", "+ IEND (0 bytes)"]
+        );
+        assert_eq!(png.chunks().nth(1).unwrap().data(), [1, 2]);
+        assert!(png.validate().problems.is_empty());
+
+        let options = RepairOptions {
+            drop: true,
+            ..Default::default()
+        };
+        let (png, changes) = repair(truncated, options).unwrap();
+        assert_eq!(
+            descriptions(&changes),
+            ["- IDAT (10 bytes)", "+ IEND (0 bytes)"]
+        );
+        assert_eq!(png.chunks().len(), 2);
+    }
+
+    #[test]
+    fn test_repair_missing_crc() {
+        let bytes = valid_png();
+        let options = RepairOptions {
+            truncate: true,
+            ..Default::default()
+        };
+        let (png, changes) = repair(&bytes[..bytes.len() - 2], options).unwrap();
+        assert_eq!(descriptions(&changes), ["~ IEND: recomputed missing CRC"]);
+        assert_eq!(png.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_repair_invalid_chunk_type() {
+        let mut bytes = valid_png();
+        bytes[8 + 25 + 4] = b'1';
+        assert!(repair(&bytes, RepairOptions::default()).is_err());
+
+        let options = RepairOptions {
+            truncate: true,
+            drop: true,
+            ..Default::default()
+        };
+        let (png, _) = repair(&bytes, options).unwrap();
+        assert_eq!(png.chunks().len(), 2);
+    }
+}
@@ -0,0 +1,243 @@
+use std::{io::Write, path::Path};
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::{
+    scan::Finding,
+    stats::Stats,
+    verify::{Problem, Severity},
+    Result,
+};
+
+/// How scan, verify and stats print their results. The field names of the
+/// structs in this module, and of `Finding`, `Problem` and `Stats` they
+/// wrap, are kept stable for tools reading the output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Lines meant for people
+    #[default]
+    Text,
+    /// One JSON document per line and file
+    Json,
+    /// One row per record under a single header, with a file column
+    Csv,
+    /// One YAML document per file
+    Yaml,
+}
+
+impl Format {
+    /// The format asked for, with --json as a shorthand for --format json
+    pub fn or_json(self, json: bool) -> Self {
+        match json {
+            true => Self::Json,
+            false => self,
+        }
+    }
+}
+
+/// The findings of scan for one of several files, as JSON or YAML
+#[derive(Debug, Serialize)]
+pub struct FileFindings<'a> {
+    pub file: &'a Path,
+    pub findings: &'a [Finding],
+}
+
+/// The problems verify found in one of several files, as JSON or YAML
+#[derive(Debug, Serialize)]
+pub struct FileProblems<'a> {
+    pub file: &'a Path,
+    pub problems: &'a [Problem],
+}
+
+/// A CSV row for one finding of scan
+#[derive(Debug, Serialize, PartialEq)]
+pub struct FindingRow<'a> {
+    pub file: &'a Path,
+    pub offset: usize,
+    pub chunk_type: &'a str,
+    pub length: usize,
+    pub entropy: f64,
+    pub reason: &'a str,
+}
+
+impl<'a> FindingRow<'a> {
+    pub fn new(file: &'a Path, finding: &'a Finding) -> Self {
+        Self {
+            file,
+            offset: finding.offset,
+            chunk_type: &finding.chunk_type,
+            length: finding.length,
+            entropy: finding.entropy,
+            reason: finding.reason,
+        }
+    }
+}
+
+/// A CSV row for one problem verify found
+#[derive(Debug, Serialize, PartialEq)]
+pub struct ProblemRow<'a> {
+    pub file: &'a Path,
+    pub offset: usize,
+    pub severity: Severity,
+    pub message: &'a str,
+}
+
+impl<'a> ProblemRow<'a> {
+    pub fn new(file: &'a Path, problem: &'a Problem) -> Self {
+        Self {
+            file,
+            offset: problem.offset,
+            severity: problem.severity,
+            message: &problem.message,
+        }
+    }
+}
+
+/// A CSV row for one chunk type in the stats of a file. The largest chunks
+/// are only listed as JSON and YAML
+#[derive(Debug, Serialize, PartialEq)]
+pub struct TypeRow<'a> {
+    pub file: &'a Path,
+    pub chunk_type: &'a str,
+    pub count: usize,
+    pub bytes: usize,
+    /// `bytes` as a percentage of the file size
+    pub percent: f64,
+}
+
+impl<'a> TypeRow<'a> {
+    /// One row per chunk type, largest first
+    pub fn rows(file: &'a Path, stats: &'a Stats) -> impl Iterator<Item = Self> + 'a {
+        stats.types.iter().map(move |types| Self {
+            file,
+            chunk_type: &types.chunk_type,
+            count: types.count,
+            bytes: types.bytes,
+            percent: match stats.file_size {
+                0 => 0.0,
+                size => types.bytes as f64 * 100.0 / size as f64,
+            },
+        })
+    }
+}
+
+enum Sink<W: Write> {
+    Csv(Box<csv::Writer<W>>),
+    Document(Format, W),
+}
+
+/// Writes the results of one file after another in a structured format
+pub struct Reporter<W: Write> {
+    sink: Sink<W>,
+}
+
+impl<W: Write> Reporter<W> {
+    /// A reporter writing `format` to `writer`. Text is left to the caller,
+    /// a reporter for it writes JSON
+    pub fn new(format: Format, writer: W) -> Self {
+        let sink = match format {
+            Format::Csv => Sink::Csv(Box::new(csv::Writer::from_writer(writer))),
+            format => Sink::Document(format, writer),
+        };
+        Self { sink }
+    }
+
+    /// Writes the results of one file, `document` as JSON or YAML or `rows`
+    /// as CSV. The CSV header comes before the first row written
+    pub fn write<R: Serialize>(
+        &mut self,
+        document: &impl Serialize,
+        rows: impl IntoIterator<Item = R>,
+    ) -> Result<()> {
+        match &mut self.sink {
+            Sink::Csv(writer) => {
+                for row in rows {
+                    writer.serialize(row)?;
+                }
+            }
+            Sink::Document(Format::Yaml, writer) => {
+                writeln!(writer, "---")?;
+                serde_yaml::to_writer(&mut *writer, document)?;
+            }
+            Sink::Document(_, writer) => {
+                serde_json::to_writer(&mut *writer, document)?;
+                writeln!(writer)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Flushes everything written so far
+    pub fn finish(self) -> Result<()> {
+        match self.sink {
+            Sink::Csv(mut writer) => writer.flush()?,
+            Sink::Document(_, mut writer) => writer.flush()?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finding() -> Finding {
+        Finding {
+            offset: 33,
+            chunk_type: "ruSt".to_string(),
+            length: 6,
+            entropy: 2.5,
+            reason: "private chunk, possible hidden payload",
+        }
+    }
+
+    fn report(format: Format) -> String {
+        let findings = [finding()];
+        let file = Path::new("a, b.png");
+        let mut out = Vec::new();
+        let mut reporter = Reporter::new(format, &mut out);
+        for _ in 0..2 {
+            let rows = findings.iter().map(|f| FindingRow::new(file, f));
+            let document = FileFindings {
+                file,
+                findings: &findings,
+            };
+            reporter.write(&document, rows).unwrap();
+        }
+        reporter.finish().unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_csv_report() {
+        assert_eq!(
+            report(Format::Csv),
+            "file,offset,chunk_type,length,entropy,reason\n\
+             \"a, b.png\",33,ruSt,6,2.5,\"private chunk, possible hidden payload\"\n\
+             \"a, b.png\",33,ruSt,6,2.5,\"private chunk, possible hidden payload\"\n"
+        );
+    }
+
+    #[test]
+    fn test_json_and_yaml_reports() {
+        let json = report(Format::Json);
+        let lines: Vec<serde_json::Value> = json
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["file"], "a, b.png");
+        assert_eq!(lines[0]["findings"][0]["chunk_type"], "ruSt");
+
+        let yaml = report(Format::Yaml);
+        assert_eq!(yaml.matches("---\n").count(), 2);
+        assert!(yaml.starts_with("---\nfile: a, b.png\nfindings:\n- offset: 33\n"));
+    }
+
+    #[test]
+    fn test_json_shorthand() {
+        assert_eq!(Format::Csv.or_json(true), Format::Json);
+        assert_eq!(Format::Yaml.or_json(false), Format::Yaml);
+    }
+}
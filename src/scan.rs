@@ -0,0 +1,287 @@
+use std::collections::HashSet;
+
+use serde::Serialize;
+
+use crate::{
+    chunk::Chunk,
+    detect::entropy,
+    parse,
+    png::{Png, AT_MOST_ONCE},
+    Result,
+};
+
+/// A chunk that is not part of the registered PNG chunk set, a duplicate,
+/// data after IEND or a file of another format hidden in one of those
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Finding {
+    /// Byte offset of the chunk from the start of the file. For an embedded
+    /// file, the offset of its magic bytes
+    pub offset: usize,
+    /// The chunk type, or "trailing data" for data after IEND
+    pub chunk_type: String,
+    /// Length of the chunk data. For an embedded file, the bytes from its
+    /// magic bytes to the end of the data holding it
+    pub length: usize,
+    /// Entropy of the same data in bits per byte. Close to 8 in a chunk
+    /// other than IDAT is a strong hint of encrypted data
+    pub entropy: f64,
+    pub reason: &'static str,
+}
+
+/// What `scan_bytes` calls the data after IEND, in place of a chunk type
+pub const TRAILING_DATA: &str = "trailing data";
+
+/// A file format recognized by its magic bytes
+struct Magic {
+    bytes: &'static [u8],
+    reason: &'static str,
+    /// Look for the bytes anywhere in the data instead of only at its start.
+    /// Short magic bytes turn up by chance too often for that
+    anywhere: bool,
+}
+
+/// Formats smuggled inside pngs, mostly to make files that are valid as
+/// both a png and an archive or program. Archive readers look for their
+/// headers anywhere, so a ZIP still opens after a chunk header or some junk
+const MAGICS: [Magic; 7] = [
+    Magic {
+        bytes: b"PK\x03\x04",
+        reason: "embedded ZIP archive, possible polyglot",
+        anywhere: true,
+    },
+    Magic {
+        bytes: b"Rar!\x1a\x07",
+        reason: "embedded RAR archive, possible polyglot",
+        anywhere: true,
+    },
+    Magic {
+        bytes: b"7z\xbc\xaf\x27\x1c",
+        reason: "embedded 7z archive, possible polyglot",
+        anywhere: true,
+    },
+    Magic {
+        bytes: b"%PDF-",
+        reason: "embedded PDF document, possible polyglot",
+        anywhere: true,
+    },
+    Magic {
+        bytes: b"\x7fELF",
+        reason: "embedded ELF executable",
+        anywhere: true,
+    },
+    Magic {
+        bytes: b"MZ",
+        reason: "embedded Windows executable",
+        anywhere: false,
+    },
+    Magic {
+        bytes: b"#!",
+        reason: "embedded script",
+        anywhere: false,
+    },
+];
+
+/// Lists every chunk in `png` whose type is not a registered PNG chunk type.
+/// Private chunks are the usual place for hidden payloads, while unregistered
+/// public chunks are outright non-conforming. Also lists the chunks repeating
+/// a type the specification allows only once and the ancillary chunks
+/// identical to an earlier one, and any known file format inside the chunks
+/// listed
+pub fn scan(png: &Png) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut offset = Png::HEADER_LENGTH;
+    let repeated = png.repeated_chunks();
+    let mut seen = HashSet::new();
+
+    for (position, chunk) in png.chunks().enumerate() {
+        let chunk_type = chunk.chunk_type();
+        let reason = if !chunk_type.is_registered() {
+            Some(match chunk_type.is_public() {
+                true => "unregistered public chunk",
+                false => "private chunk, possible hidden payload",
+            })
+        } else if AT_MOST_ONCE.contains(chunk_type) && !seen.insert(chunk_type) {
+            Some("duplicate of a chunk allowed only once")
+        } else if repeated.binary_search(&position).is_ok() {
+            Some("identical to an earlier chunk")
+        } else {
+            None
+        };
+        if let Some(reason) = reason {
+            findings.push(Finding {
+                offset,
+                chunk_type: chunk_type.to_string(),
+                length: chunk.length(),
+                entropy: entropy(chunk.data()),
+                reason,
+            });
+            let data_offset = offset + Chunk::LEN_DATA_LENGTH + Chunk::CHUNK_TYPE_LENGTH;
+            embedded(
+                chunk.data(),
+                data_offset,
+                &chunk_type.to_string(),
+                &mut findings,
+            );
+        }
+        offset += chunk.length() + Chunk::META_DATA_LENGTH;
+    }
+
+    findings
+}
+
+/// Scans the raw bytes of a png like `scan`, also reporting any data after
+/// IEND and the known file formats in it. Everything up to the end of IEND
+/// has to parse
+pub fn scan_bytes(bytes: &[u8]) -> Result<Vec<Finding>> {
+    let end = parse::png_length(bytes).unwrap_or(bytes.len());
+    let mut findings = scan(&Png::try_from(&bytes[..end])?);
+    let trailing = &bytes[end..];
+    if !trailing.is_empty() {
+        findings.push(Finding {
+            offset: end,
+            chunk_type: TRAILING_DATA.to_string(),
+            length: trailing.len(),
+            entropy: entropy(trailing),
+            reason: "data after IEND",
+        });
+        embedded(trailing, end, TRAILING_DATA, &mut findings);
+    }
+    Ok(findings)
+}
+
+/// Adds a finding for each known file format in `data`, which starts at
+/// `offset` in the file
+fn embedded(data: &[u8], offset: usize, chunk_type: &str, findings: &mut Vec<Finding>) {
+    for magic in &MAGICS {
+        let position = match magic.anywhere {
+            true => data
+                .windows(magic.bytes.len())
+                .position(|window| window == magic.bytes),
+            false => data.starts_with(magic.bytes).then_some(0),
+        };
+        if let Some(position) = position {
+            findings.push(Finding {
+                offset: offset + position,
+                chunk_type: chunk_type.to_string(),
+                length: data.len() - position,
+                entropy: entropy(&data[position..]),
+                reason: magic.reason,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chunk_type::ChunkType, png::tests::testing_png_bytes};
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    #[test]
+    fn test_scan() {
+        let png = Png::from_chunks(vec![
+            chunk("IHDR", &[0; 13]),
+            chunk("ruSt", b"hidden"),
+            chunk("IDAT", &[1, 2, 3]),
+            chunk("ABCD", b""),
+            chunk("IEND", b""),
+        ]);
+
+        assert_eq!(
+            scan(&png),
+            [
+                Finding {
+                    offset: 8 + 25,
+                    chunk_type: "ruSt".to_string(),
+                    length: 6,
+                    entropy: entropy(b"hidden"),
+                    reason: "private chunk, possible hidden payload",
+                },
+                Finding {
+                    offset: 8 + 25 + 18 + 15,
+                    chunk_type: "ABCD".to_string(),
+                    length: 0,
+                    entropy: 0.0,
+                    reason: "unregistered public chunk",
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scan_clean_file() {
+        let png = Png::from_chunks(vec![chunk("IHDR", &[0; 13]), chunk("IEND", b"")]);
+        assert!(scan(&png).is_empty());
+    }
+
+    #[test]
+    fn test_scan_duplicates() {
+        let png = Png::from_chunks(vec![
+            chunk("IHDR", &[0; 13]),
+            chunk("pHYs", &[0; 9]),
+            chunk("pHYs", &[1; 9]),
+            chunk("IDAT", &[1, 2, 3]),
+            chunk("IDAT", &[1, 2, 3]),
+            chunk("tEXt", b"a\0b"),
+            chunk("tEXt", b"a\0b"),
+            chunk("IEND", b""),
+        ]);
+        let findings = scan(&png);
+        let reasons: Vec<(&str, &str)> = findings
+            .iter()
+            .map(|f| (f.chunk_type.as_str(), f.reason))
+            .collect();
+        assert_eq!(
+            reasons,
+            [
+                ("pHYs", "duplicate of a chunk allowed only once"),
+                ("tEXt", "identical to an earlier chunk"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scan_embedded_files() {
+        let png = Png::from_chunks(vec![
+            chunk("IHDR", &[0; 13]),
+            chunk("ruSt", b"junkPK\x03\x04zip"),
+            chunk("IDAT", b"PK\x03\x04"),
+            chunk("IEND", b""),
+        ]);
+        let mut bytes = png.as_bytes();
+        let end = bytes.len();
+        bytes.extend_from_slice(b"#!/bin/sh\necho hi\n");
+
+        let findings = scan_bytes(&bytes).unwrap();
+        let found: Vec<(usize, &str, usize, &str)> = findings
+            .iter()
+            .map(|f| (f.offset, f.chunk_type.as_str(), f.length, f.reason))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (8 + 25, "ruSt", 11, "private chunk, possible hidden payload"),
+                (
+                    8 + 25 + 8 + 4,
+                    "ruSt",
+                    7,
+                    "embedded ZIP archive, possible polyglot"
+                ),
+                (end, TRAILING_DATA, 18, "data after IEND"),
+                (end, TRAILING_DATA, 18, "embedded script"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scan_bytes_of_clean_and_damaged_files() {
+        let bytes = testing_png_bytes();
+        assert!(scan_bytes(&bytes).unwrap().is_empty());
+        assert!(scan_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(scan_bytes(&bytes[..7]).is_err());
+    }
+}
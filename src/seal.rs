@@ -0,0 +1,240 @@
+use std::fmt::Display;
+
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    crypto::{self, EncryptOptions},
+    envelope,
+    png::Png,
+    Result,
+};
+
+#[derive(Debug)]
+pub enum SealError {
+    NothingToSeal,
+    AlreadySealed,
+    NotSealed,
+    InvalidContainer,
+}
+
+impl std::error::Error for SealError {}
+
+impl Display for SealError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NothingToSeal => {
+                write!(f, "The png has no picmes payloads or named chunks to seal")
+            }
+            Self::AlreadySealed => {
+                write!(f, "The png already has a {} chunk, unseal it first", SEALED)
+            }
+            Self::NotSealed => write!(f, "The png has no {} chunk", SEALED),
+            Self::InvalidContainer => write!(f, "The sealed chunks are malformed"),
+        }
+    }
+}
+
+/// Ancillary, private and safe to copy, like the chunks it holds
+pub const SEALED: ChunkType = ChunkType(*b"seAl");
+
+/// Starts the decrypted contents of a container, which hold each chunk after
+/// its position in the png
+const CONTENTS_MAGIC: &[u8] = b"pmK\x01";
+const POSITION_LENGTH: usize = 4;
+
+/// Whether `chunk` holds a payload picmes wrapped in an envelope or is of
+/// one of `chunk_types`. Critical chunks change how the png is read, so they
+/// always stay where they are
+pub fn is_sealable(chunk: &Chunk, chunk_types: &[String]) -> bool {
+    let chunk_type = chunk.chunk_type();
+    !chunk_type.is_critical()
+        && chunk_type != &SEALED
+        && (chunk_types.contains(&chunk_type.to_string())
+            || (!chunk_type.is_registered() && envelope::is_envelope(chunk.data())))
+}
+
+pub fn is_sealed(png: &Png) -> bool {
+    png.chunks().any(|c| c.chunk_type() == &SEALED)
+}
+
+/// Moves every chunk of `png` sealable with `chunk_types` into a single
+/// encrypted container chunk, returning the chunks that were sealed and the
+/// container
+pub fn seal(
+    png: &mut Png,
+    chunk_types: &[String],
+    passphrase: &str,
+    options: EncryptOptions,
+) -> Result<(Vec<Chunk>, Chunk)> {
+    let is_sealable = |chunk: &Chunk| is_sealable(chunk, chunk_types);
+    if is_sealed(png) {
+        return Err(SealError::AlreadySealed.into());
+    }
+    if !png.chunks().any(is_sealable) {
+        return Err(SealError::NothingToSeal.into());
+    }
+
+    let positions: Vec<usize> = png
+        .chunks()
+        .enumerate()
+        .filter(|(_, chunk)| is_sealable(chunk))
+        .map(|(position, _)| position)
+        .collect();
+    let sealed = png.remove_chunks_where(is_sealable);
+    // the chunks are stored whole, so their CRCs are checked again on unseal
+    let mut contents = CONTENTS_MAGIC.to_vec();
+    for (position, chunk) in positions.iter().zip(&sealed) {
+        contents.extend((*position as u32).to_be_bytes());
+        chunk.extend_into(&mut contents);
+    }
+    let container = Chunk::try_new(SEALED, crypto::encrypt(&contents, passphrase, options)?)?;
+    png.insert_chunk(container.clone());
+    Ok((sealed, container))
+}
+
+/// Decrypts the container chunk of `png` and puts the chunks it holds back
+/// where they were, returning the container and the restored chunks
+pub fn unseal(png: &mut Png, passphrase: &str) -> Result<(Chunk, Vec<Chunk>)> {
+    let container = png
+        .remove_first_chunk(&SEALED.to_string())
+        .map_err(|_| SealError::NotSealed)?;
+    let contents = crypto::decrypt(container.data(), passphrase)?;
+
+    let mut chunks = Vec::new();
+    let mut positions = Vec::new();
+    let mut rest = contents
+        .strip_prefix(CONTENTS_MAGIC)
+        .ok_or(SealError::InvalidContainer)?;
+    while !rest.is_empty() {
+        let (position, after) = rest
+            .split_first_chunk::<POSITION_LENGTH>()
+            .ok_or(SealError::InvalidContainer)?;
+        positions.push(u32::from_be_bytes(*position) as usize);
+        rest = after;
+        let length = rest
+            .get(..Chunk::LEN_DATA_LENGTH)
+            .ok_or(SealError::InvalidContainer)?;
+        let length = u32::from_be_bytes(length.try_into()?) as usize;
+        let end = Chunk::META_DATA_LENGTH + length;
+        let bytes = rest.get(..end).ok_or(SealError::InvalidContainer)?;
+        chunks.push(Chunk::try_from(bytes)?);
+        rest = &rest[end..];
+    }
+
+    // in file order, each position counts the chunks restored before it
+    for (&position, chunk) in positions.iter().zip(&chunks) {
+        match position <= png.end_index() {
+            true => png.insert_chunk_at(position, chunk.clone())?,
+            false => png.insert_chunk(chunk.clone()),
+        }
+    }
+    Ok((container, chunks))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        compress::{self, Compressor},
+        crypto::KdfParams,
+    };
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn types_of(png: &Png) -> Vec<String> {
+        png.chunks().map(|c| c.chunk_type().to_string()).collect()
+    }
+
+    fn options() -> EncryptOptions {
+        EncryptOptions {
+            kdf: KdfParams {
+                memory_kib: 64,
+                iterations: 1,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    /// A chunk holding a compressed payload, as encode --compress writes
+    fn payload(chunk_type: &str, data: &[u8]) -> Chunk {
+        chunk(
+            chunk_type,
+            &compress::compress(data, Compressor::default()).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_seal_and_unseal() {
+        let original = vec![
+            chunk("IHDR", &[0; 13]),
+            payload("CgBI", b"critical, never sealed"),
+            payload("prVt", b"before the image"),
+            chunk("tEXt", b"Title\0kept"),
+            chunk("IDAT", &[1, 2, 3]),
+            payload("ruSt", b"hidden"),
+            chunk("ruSt", b"someone else's"),
+            payload("ruSt", b"hidden too"),
+            chunk("IEND", b""),
+        ];
+        let mut png = Png::from_chunks(original.clone());
+
+        let (sealed, container) = seal(&mut png, &[], "secret", options()).unwrap();
+        assert_eq!(
+            sealed,
+            [&original[2], &original[5], &original[7]].map(Clone::clone)
+        );
+        assert_eq!(
+            types_of(&png),
+            ["IHDR", "CgBI", "tEXt", "IDAT", "ruSt", "seAl", "IEND"]
+        );
+        assert!(crypto::is_encrypted(container.data()));
+        assert!(seal(&mut png, &[], "secret", options()).is_err());
+
+        let mut wrong = Png::from_chunks(png.chunks().cloned().collect());
+        assert!(unseal(&mut wrong, "guess").is_err());
+
+        let (_, restored) = unseal(&mut png, "secret").unwrap();
+        assert_eq!(restored, sealed);
+        assert_eq!(png.chunks().cloned().collect::<Vec<_>>(), original);
+        assert!(unseal(&mut png, "secret").is_err());
+    }
+
+    #[test]
+    fn test_seal_named_chunk_types() {
+        let original = vec![
+            chunk("IHDR", &[0; 13]),
+            chunk("ruSt", b"not wrapped"),
+            chunk("tEXt", b"Title\0sealed"),
+            chunk("IDAT", &[1, 2, 3]),
+            chunk("IEND", b""),
+        ];
+        let mut png = Png::from_chunks(original.clone());
+        assert!(seal(&mut png, &[], "secret", options()).is_err());
+        assert_eq!(png.chunks().cloned().collect::<Vec<_>>(), original);
+
+        let named = ["ruSt", "tEXt", "IDAT"].map(String::from);
+        let (sealed, _) = seal(&mut png, &named, "secret", options()).unwrap();
+        assert_eq!(sealed, original[1..3]);
+        assert_eq!(types_of(&png), ["IHDR", "IDAT", "seAl", "IEND"]);
+
+        unseal(&mut png, "secret").unwrap();
+        assert_eq!(png.chunks().cloned().collect::<Vec<_>>(), original);
+    }
+
+    #[test]
+    fn test_unseal_without_magic() {
+        let mut png = Png::from_chunks(vec![
+            chunk("IHDR", &[0; 13]),
+            chunk("IDAT", &[1, 2, 3]),
+            chunk("IEND", b""),
+        ]);
+        let hidden = payload("ruSt", b"hidden");
+        let contents = crypto::encrypt(&hidden.as_bytes(), "secret", options()).unwrap();
+        png.insert_chunk(Chunk::new(SEALED, contents));
+        assert!(unseal(&mut png, "secret").is_err());
+    }
+}
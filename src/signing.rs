@@ -0,0 +1,358 @@
+use std::{fmt::Display, fs, path::Path};
+
+use ed25519_dalek::{
+    pkcs8::{DecodePrivateKey, DecodePublicKey},
+    Signature, Signer, SigningKey, Verifier, VerifyingKey, SIGNATURE_LENGTH,
+};
+
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, png::Png, Result};
+
+#[derive(Debug)]
+pub enum SigningError {
+    Unsigned,
+    InvalidSignature,
+    InvalidKey(String),
+    Untagged,
+    TagMismatch,
+    FileUnsigned,
+    FileSignatureMismatch,
+}
+
+impl std::error::Error for SigningError {}
+
+impl Display for SigningError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unsigned => write!(f, "The payload is not signed"),
+            Self::InvalidSignature => write!(
+                f,
+                "The payload signature does not match, it was modified or signed with another key"
+            ),
+            Self::InvalidKey(reason) => write!(f, "Invalid Ed25519 key: {}", reason),
+            Self::Untagged => write!(f, "The payload has no HMAC tag"),
+            Self::TagMismatch => write!(
+                f,
+                "The payload HMAC does not match, the image was altered or the key is wrong"
+            ),
+            Self::FileUnsigned => write!(f, "The file has no signature chunk"),
+            Self::FileSignatureMismatch => write!(
+                f,
+                "The file signature does not match, it was modified or signed with another key"
+            ),
+        }
+    }
+}
+
+/// Ends every signed payload, after the signature. The last byte is the
+/// format version
+const MAGIC: &[u8] = b"pmS\x01";
+const TRAILER_LENGTH: usize = SIGNATURE_LENGTH + MAGIC.len();
+
+/// Appends a detached Ed25519 signature over `payload`, laid out as
+/// `payload | signature | MAGIC`
+pub fn sign(payload: &[u8], key: &SigningKey) -> Vec<u8> {
+    let mut data = payload.to_vec();
+    data.extend(key.sign(payload).to_bytes());
+    data.extend(MAGIC);
+    data
+}
+
+pub fn is_signed(data: &[u8]) -> bool {
+    data.len() >= TRAILER_LENGTH && data.ends_with(MAGIC)
+}
+
+/// The payload without its signature trailer. Unsigned data is returned
+/// unchanged
+pub fn strip(data: &[u8]) -> &[u8] {
+    match is_signed(data) {
+        true => &data[..data.len() - TRAILER_LENGTH],
+        false => data,
+    }
+}
+
+/// Checks the signature trailer against `key`, returning the signed payload
+pub fn verify<'a>(data: &'a [u8], key: &VerifyingKey) -> Result<&'a [u8]> {
+    if !is_signed(data) {
+        return Err(SigningError::Unsigned.into());
+    }
+    let (payload, trailer) = data.split_at(data.len() - TRAILER_LENGTH);
+    let signature = Signature::from_bytes(trailer[..SIGNATURE_LENGTH].try_into()?);
+    key.verify(payload, &signature)
+        .map_err(|_| SigningError::InvalidSignature)?;
+    Ok(payload)
+}
+
+/// Ends every HMAC tagged payload, after the tag
+const TAG_MAGIC: &[u8] = b"pmH\x01";
+const TAG_LENGTH: usize = 32;
+const TAG_TRAILER_LENGTH: usize = TAG_LENGTH + TAG_MAGIC.len();
+
+fn hmac(key: &[u8], payload: &[u8]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(payload);
+    mac
+}
+
+/// Appends an HMAC-SHA256 tag over `payload`, laid out as
+/// `payload | tag | TAG_MAGIC`. Unlike a signature the same key is needed
+/// to check it
+pub fn tag(payload: &[u8], key: &[u8]) -> Vec<u8> {
+    let mut data = payload.to_vec();
+    data.extend(hmac(key, payload).finalize().into_bytes());
+    data.extend(TAG_MAGIC);
+    data
+}
+
+pub fn is_tagged(data: &[u8]) -> bool {
+    data.len() >= TAG_TRAILER_LENGTH && data.ends_with(TAG_MAGIC)
+}
+
+/// The payload without its HMAC trailer. Untagged data is returned unchanged
+pub fn strip_tag(data: &[u8]) -> &[u8] {
+    match is_tagged(data) {
+        true => &data[..data.len() - TAG_TRAILER_LENGTH],
+        false => data,
+    }
+}
+
+/// Checks the HMAC trailer in constant time, returning the tagged payload
+pub fn verify_tag<'a>(data: &'a [u8], key: &[u8]) -> Result<&'a [u8]> {
+    if !is_tagged(data) {
+        return Err(SigningError::Untagged.into());
+    }
+    let (payload, trailer) = data.split_at(data.len() - TAG_TRAILER_LENGTH);
+    hmac(key, payload)
+        .verify_slice(&trailer[..TAG_LENGTH])
+        .map_err(|_| SigningError::TagMismatch)?;
+    Ok(payload)
+}
+
+/// Keeps derived chunk types apart from any other use of the same key
+const CHUNK_TYPE_CONTEXT: &[u8] = b"picmes chunk type\0";
+
+/// A chunk type only holders of `key` can find, derived from it and `label`
+/// with HMAC-SHA256. It is ancillary, private and safe to copy like the
+/// types picmes suggests, so it looks no different from a hand picked one
+pub fn derive_chunk_type(key: &[u8], label: &str) -> ChunkType {
+    let mut input = CHUNK_TYPE_CONTEXT.to_vec();
+    input.extend(label.as_bytes());
+    let bytes = hmac(key, &input).finalize().into_bytes();
+    let letter = |byte: u8| b'a' + byte % 26;
+    ChunkType([
+        letter(bytes[0]),
+        letter(bytes[1]),
+        letter(bytes[2]).to_ascii_uppercase(),
+        letter(bytes[3]),
+    ])
+}
+
+/// The hash functions `file_hash` can use
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    Blake3,
+}
+
+/// The private chunk holding a signature over the whole file. Unsafe to
+/// copy, so editors that change the image drop it
+pub const FILE_SIGNATURE: ChunkType = ChunkType(*b"pmSG");
+
+/// The bytes of `png` as written, leaving out any file signature chunk. CRCs
+/// are recomputed on write, so equal chunks always give equal bytes
+pub fn canonical_bytes(png: &Png) -> Vec<u8> {
+    let mut bytes = Png::STANDARD_HEADER.to_vec();
+    for chunk in png.chunks().filter(|c| c.chunk_type() != &FILE_SIGNATURE) {
+        chunk.extend_into(&mut bytes);
+    }
+    bytes
+}
+
+/// Hashes the canonical bytes of `png`, so signing a file doesn't change
+/// its hash
+pub fn file_hash(png: &Png, algorithm: HashAlgorithm) -> Vec<u8> {
+    let bytes = canonical_bytes(png);
+    match algorithm {
+        HashAlgorithm::Sha256 => Sha256::digest(&bytes).to_vec(),
+        HashAlgorithm::Blake3 => blake3::hash(&bytes).as_bytes().to_vec(),
+    }
+}
+
+/// Signs the canonical bytes of `png` with `key` and stores the signature
+/// in a chunk before IEND, replacing any earlier file signature. Returns
+/// the new chunk
+pub fn sign_file(png: &mut Png, key: &SigningKey) -> Chunk {
+    png.remove_chunks_where(|c| c.chunk_type() == &FILE_SIGNATURE);
+    let signature = key.sign(&canonical_bytes(png));
+    let chunk = Chunk::new(FILE_SIGNATURE, signature.to_bytes().to_vec());
+    png.insert_chunk(chunk.clone());
+    chunk
+}
+
+/// Checks the file signature chunk of `png` against `key`
+pub fn verify_file(png: &Png, key: &VerifyingKey) -> Result<()> {
+    let chunk = png
+        .chunk_by_type(&FILE_SIGNATURE.to_string())
+        .ok_or(SigningError::FileUnsigned)?;
+    let signature = <[u8; SIGNATURE_LENGTH]>::try_from(chunk.data())
+        .map_err(|_| SigningError::FileSignatureMismatch)?;
+    key.verify(&canonical_bytes(png), &Signature::from_bytes(&signature))
+        .map_err(|_| SigningError::FileSignatureMismatch)?;
+    Ok(())
+}
+
+/// Reads a PKCS#8 PEM private key, as written by
+/// `openssl genpkey -algorithm ed25519`
+pub fn read_signing_key(path: &Path) -> Result<SigningKey> {
+    Ok(SigningKey::from_pkcs8_pem(&fs::read_to_string(path)?)
+        .map_err(|e| SigningError::InvalidKey(e.to_string()))?)
+}
+
+/// Reads a PEM public key, as written by `openssl pkey -pubout`
+pub fn read_verifying_key(path: &Path) -> Result<VerifyingKey> {
+    Ok(
+        VerifyingKey::from_public_key_pem(&fs::read_to_string(path)?)
+            .map_err(|e| SigningError::InvalidKey(e.to_string()))?,
+    )
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use ed25519_dalek::pkcs8::{spki::der::pem::LineEnding, EncodePrivateKey, EncodePublicKey};
+    use std::path::PathBuf;
+
+    pub(crate) fn testing_key() -> SigningKey {
+        SigningKey::from_bytes(&[7; 32])
+    }
+
+    /// Writes the testing key pair as PEM files next to `base`, returning the
+    /// private and public key paths
+    pub(crate) fn write_testing_keys(base: &Path) -> (PathBuf, PathBuf) {
+        let key = testing_key();
+        let private = base.with_extension("key.pem");
+        let public = base.with_extension("pub.pem");
+        fs::write(
+            &private,
+            key.to_pkcs8_pem(LineEnding::LF).unwrap().as_bytes(),
+        )
+        .unwrap();
+        let public_pem = key
+            .verifying_key()
+            .to_public_key_pem(LineEnding::LF)
+            .unwrap();
+        fs::write(&public, public_pem).unwrap();
+        (private, public)
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let key = testing_key();
+        let data = sign(b"signed message", &key);
+        assert!(is_signed(&data));
+        assert_eq!(data.len(), 14 + TRAILER_LENGTH);
+        assert_eq!(strip(&data), b"signed message");
+        assert_eq!(
+            verify(&data, &key.verifying_key()).unwrap(),
+            b"signed message"
+        );
+
+        let mut tampered = data.clone();
+        tampered[0] ^= 1;
+        assert!(verify(&tampered, &key.verifying_key()).is_err());
+
+        let other = SigningKey::from_bytes(&[8; 32]);
+        assert!(verify(&data, &other.verifying_key()).is_err());
+        assert!(verify(b"signed message", &key.verifying_key()).is_err());
+        assert_eq!(strip(b"plain"), b"plain");
+    }
+
+    #[test]
+    fn test_tag_and_verify() {
+        let data = tag(b"tagged message", b"shared key");
+        assert!(is_tagged(&data));
+        assert!(!is_signed(&data));
+        assert_eq!(data.len(), 14 + TAG_TRAILER_LENGTH);
+        assert_eq!(strip_tag(&data), b"tagged message");
+        assert_eq!(verify_tag(&data, b"shared key").unwrap(), b"tagged message");
+
+        let mut tampered = data.clone();
+        tampered[3] ^= 1;
+        assert!(verify_tag(&tampered, b"shared key").is_err());
+        assert!(verify_tag(&data, b"other key").is_err());
+        assert!(verify_tag(b"tagged message", b"shared key").is_err());
+    }
+
+    #[test]
+    fn test_derive_chunk_type() {
+        let chunk_type = derive_chunk_type(b"stealthy", "ruSt");
+        assert_eq!(chunk_type, derive_chunk_type(b"stealthy", "ruSt"));
+        assert!(chunk_type.is_valid());
+        assert!(!chunk_type.is_critical());
+        assert!(!chunk_type.is_public());
+        assert!(chunk_type.is_safe_to_copy());
+
+        assert_ne!(chunk_type, derive_chunk_type(b"other key", "ruSt"));
+        assert_ne!(chunk_type, derive_chunk_type(b"stealthy", "notes"));
+    }
+
+    #[test]
+    fn test_file_hash() {
+        let bytes = crate::png::tests::testing_png_bytes();
+        let png = Png::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(canonical_bytes(&png), bytes);
+        assert_eq!(
+            file_hash(&png, HashAlgorithm::Sha256),
+            Sha256::digest(&bytes).to_vec()
+        );
+        assert_eq!(
+            file_hash(&png, HashAlgorithm::Blake3),
+            blake3::hash(&bytes).as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_sign_and_verify_file() {
+        let key = testing_key();
+        let bytes = crate::png::tests::testing_png_bytes();
+        let mut png = Png::try_from(bytes.as_slice()).unwrap();
+        assert!(matches!(
+            verify_file(&png, &key.verifying_key()),
+            Err(crate::Error::Signing(SigningError::FileUnsigned))
+        ));
+
+        let hash = file_hash(&png, HashAlgorithm::Sha256);
+        sign_file(&mut png, &key);
+        sign_file(&mut png, &key);
+        assert_eq!(png.chunks_by_type("pmSG").count(), 1);
+        assert_eq!(file_hash(&png, HashAlgorithm::Sha256), hash);
+        assert!(verify_file(&png, &key.verifying_key()).is_ok());
+        let other = SigningKey::from_bytes(&[8; 32]);
+        assert!(verify_file(&png, &other.verifying_key()).is_err());
+
+        png.insert_chunk(Chunk::new(ChunkType::tEXt, b"a\0b".to_vec()));
+        assert!(matches!(
+            verify_file(&png, &key.verifying_key()),
+            Err(crate::Error::Signing(SigningError::FileSignatureMismatch))
+        ));
+    }
+
+    #[test]
+    fn test_read_pem_keys() {
+        let base = std::env::temp_dir().join(format!("picmes-{}-signing", std::process::id()));
+        let (private, public) = write_testing_keys(&base);
+        assert_eq!(read_signing_key(&private).unwrap(), testing_key());
+        assert_eq!(
+            read_verifying_key(&public).unwrap(),
+            testing_key().verifying_key()
+        );
+        assert!(read_signing_key(&public).is_err());
+        assert!(read_verifying_key(&private).is_err());
+        fs::remove_file(private).unwrap();
+        fs::remove_file(public).unwrap();
+    }
+}
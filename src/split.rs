@@ -0,0 +1,278 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use sha2::{Digest, Sha256};
+
+use crate::{fec, Result};
+
+#[derive(Debug)]
+pub enum SplitError {
+    InvalidManifest,
+    MissingManifest,
+    MissingPart(u32),
+    Corrupted,
+}
+
+impl std::error::Error for SplitError {}
+
+impl Display for SplitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidManifest => write!(f, "The split payload manifest is malformed"),
+            Self::MissingManifest => write!(f, "None of the chunks is a split payload manifest"),
+            Self::MissingPart(index) => write!(f, "Part {} of the split payload is missing", index),
+            Self::Corrupted => write!(
+                f,
+                "The reassembled payload does not match the length and hash in its manifest"
+            ),
+        }
+    }
+}
+
+/// Starts the manifest chunk of a split payload. The last byte is the format
+/// version
+const MANIFEST_MAGIC: &[u8] = b"pmM\x01";
+/// Starts every part chunk of a split payload
+const PART_MAGIC: &[u8] = b"pmP\x01";
+/// Ties the parts to their manifest, taken from the start of the payload hash
+const ID_LENGTH: usize = 8;
+const HASH_LENGTH: usize = 32;
+/// `PART_MAGIC | id | index`
+pub const PART_HEADER_LENGTH: usize = PART_MAGIC.len() + ID_LENGTH + 4;
+/// `MANIFEST_MAGIC | id | part count | payload length | SHA-256 of the payload`
+const MANIFEST_LENGTH: usize = MANIFEST_MAGIC.len() + ID_LENGTH + 4 + 8 + HASH_LENGTH;
+
+/// Cuts `payload` into parts of at most `part_size` payload bytes, returning
+/// the manifest followed by the parts, each ready to be a chunk's data
+pub fn split(payload: &[u8], part_size: usize) -> Vec<Vec<u8>> {
+    let hash: [u8; HASH_LENGTH] = Sha256::digest(payload).into();
+    let id = &hash[..ID_LENGTH];
+    let parts: Vec<&[u8]> = payload.chunks(part_size.max(1)).collect();
+
+    let mut manifest = MANIFEST_MAGIC.to_vec();
+    manifest.extend(id);
+    manifest.extend((parts.len() as u32).to_be_bytes());
+    manifest.extend((payload.len() as u64).to_be_bytes());
+    manifest.extend(hash);
+
+    let mut chunks = vec![manifest];
+    for (index, part) in parts.into_iter().enumerate() {
+        let mut data = PART_MAGIC.to_vec();
+        data.extend(id);
+        data.extend((index as u32).to_be_bytes());
+        data.extend(part);
+        chunks.push(data);
+    }
+    chunks
+}
+
+pub fn is_manifest(data: &[u8]) -> bool {
+    data.len() == MANIFEST_LENGTH && data.starts_with(MANIFEST_MAGIC)
+}
+
+pub fn is_part(data: &[u8]) -> bool {
+    data.len() >= PART_HEADER_LENGTH && data.starts_with(PART_MAGIC)
+}
+
+struct Manifest<'a> {
+    id: &'a [u8],
+    count: u32,
+    length: u64,
+    hash: &'a [u8],
+}
+
+impl<'a> Manifest<'a> {
+    fn parse(manifest: &'a [u8]) -> Result<Self> {
+        if !is_manifest(manifest) {
+            return Err(SplitError::InvalidManifest.into());
+        }
+        let rest = &manifest[MANIFEST_MAGIC.len()..];
+        let (id, rest) = rest.split_at(ID_LENGTH);
+        let (count, rest) = rest.split_at(4);
+        let (length, hash) = rest.split_at(8);
+        Ok(Self {
+            id,
+            count: u32::from_be_bytes(count.try_into()?),
+            length: u64::from_be_bytes(length.try_into()?),
+            hash,
+        })
+    }
+
+    /// The parts among `chunks` that belong to this manifest by their index,
+    /// with duplicates dropped. Only parts that are present take up room, the
+    /// part count comes from the file and can be anything
+    fn parts<'b>(&self, chunks: impl Iterator<Item = &'b [u8]>) -> BTreeMap<u32, &'b [u8]> {
+        let mut parts = BTreeMap::new();
+        for data in chunks {
+            if !is_part(data) || &data[PART_MAGIC.len()..][..ID_LENGTH] != self.id {
+                continue;
+            }
+            let index = &data[PART_MAGIC.len() + ID_LENGTH..PART_HEADER_LENGTH];
+            let index = u32::from_be_bytes(index.try_into().expect("four bytes"));
+            if index < self.count {
+                parts.entry(index).or_insert(&data[PART_HEADER_LENGTH..]);
+            }
+        }
+        parts
+    }
+}
+
+/// Reassembles the payload `manifest` describes from the parts among
+/// `chunks`, checking its length and hash. Parts of other payloads and
+/// anything that is not a part are skipped
+pub fn join<'a>(manifest: &[u8], chunks: impl Iterator<Item = &'a [u8]>) -> Result<Vec<u8>> {
+    let manifest = Manifest::parse(manifest)?;
+    let parts = manifest.parts(chunks);
+    let mut payload = Vec::new();
+    for index in 0..manifest.count {
+        let part = parts.get(&index).ok_or(SplitError::MissingPart(index))?;
+        payload.extend_from_slice(part);
+    }
+    if payload.len() as u64 != manifest.length
+        || Sha256::digest(&payload).as_slice() != manifest.hash
+    {
+        return Err(SplitError::Corrupted.into());
+    }
+    Ok(payload)
+}
+
+/// Like `join`, but fills missing parts with zeros and skips the hash check,
+/// for payloads with their own error correction. Fails when the parts hold
+/// more than the manifest's length or when more is missing than error
+/// correction could restore
+pub fn join_damaged<'a>(
+    manifest: &[u8],
+    chunks: impl Iterator<Item = &'a [u8]>,
+) -> Result<Vec<u8>> {
+    let manifest = Manifest::parse(manifest)?;
+    let parts = manifest.parts(chunks);
+    let first_missing = (0..manifest.count)
+        .find(|index| !parts.contains_key(index))
+        .unwrap_or(manifest.count);
+    // every part but the last is full, so any of those gives the part size
+    let part_size = parts
+        .range(..manifest.count.saturating_sub(1))
+        .map(|(_, part)| part.len())
+        .next()
+        .ok_or(SplitError::MissingPart(first_missing))?;
+
+    let present: usize = parts.values().map(|part| part.len()).sum();
+    let length = usize::try_from(manifest.length).map_err(|_| SplitError::Corrupted)?;
+    if present > length {
+        return Err(SplitError::Corrupted.into());
+    }
+    if length - present > fec::max_recoverable(present) {
+        return Err(SplitError::MissingPart(first_missing).into());
+    }
+
+    // each run of missing parts is as long as that many full parts, but never
+    // runs past the length
+    let fill = |payload: &mut Vec<u8>, missing: u32| {
+        let missing = (missing as usize).saturating_mul(part_size);
+        payload.resize(length.min(payload.len().saturating_add(missing)), 0);
+    };
+    let mut payload = Vec::with_capacity(length);
+    let mut next = 0;
+    for (&index, part) in &parts {
+        fill(&mut payload, index - next);
+        payload.extend_from_slice(part);
+        if payload.len() > length {
+            return Err(SplitError::Corrupted.into());
+        }
+        next = index + 1;
+    }
+    fill(&mut payload, manifest.count - next);
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_and_join() {
+        let payload: Vec<u8> = (0..=255).cycle().take(1000).collect();
+        let chunks = split(&payload, 300);
+        assert_eq!(chunks.len(), 5);
+        assert!(is_manifest(&chunks[0]));
+        assert!(chunks[1..].iter().all(|c| is_part(c)));
+        assert_eq!(chunks[4].len(), PART_HEADER_LENGTH + 100);
+
+        let joined = join(&chunks[0], chunks[1..].iter().map(Vec::as_slice)).unwrap();
+        assert_eq!(joined, payload);
+
+        // parts may come in any order, mixed with unrelated chunks
+        let other = split(b"another payload", 4);
+        let mixed: [&[u8]; 6] = [
+            &chunks[3], &other[1], &chunks[1], b"plain", &chunks[4], &chunks[2],
+        ];
+        let joined = join(&chunks[0], mixed.into_iter()).unwrap();
+        assert_eq!(joined, payload);
+    }
+
+    #[test]
+    fn test_join_detects_damage() {
+        let chunks = split(b"split across several chunks", 8);
+        let parts = || chunks[1..].iter().map(Vec::as_slice);
+
+        assert!(join(&chunks[0], parts().skip(1)).is_err());
+        assert!(join(&chunks[1], parts()).is_err());
+
+        let mut tampered = chunks.clone();
+        *tampered[2].last_mut().unwrap() ^= 1;
+        assert!(join(&chunks[0], tampered[1..].iter().map(Vec::as_slice)).is_err());
+    }
+
+    #[test]
+    fn test_join_damaged() {
+        let chunks = split(b"split across several chunks", 8);
+        let parts = || chunks[1..].iter().map(Vec::as_slice);
+        assert_eq!(
+            join_damaged(&chunks[0], parts()).unwrap(),
+            b"split across several chunks"
+        );
+        assert_eq!(
+            join_damaged(&chunks[0], parts().skip(1)).unwrap(),
+            b"\0\0\0\0\0\0\0\0ross several chunks"
+        );
+        assert_eq!(
+            join_damaged(&chunks[0], parts().take(3)).unwrap(),
+            b"split across several chu\0\0\0"
+        );
+    }
+
+    /// A manifest with `count` parts and a payload of `length` bytes
+    fn forged_manifest(count: u32, length: u64) -> Vec<u8> {
+        let mut manifest = split(b"", 1).swap_remove(0);
+        manifest[MANIFEST_MAGIC.len() + ID_LENGTH..][..4].copy_from_slice(&count.to_be_bytes());
+        manifest[MANIFEST_MAGIC.len() + ID_LENGTH + 4..][..8]
+            .copy_from_slice(&length.to_be_bytes());
+        manifest
+    }
+
+    /// Part `index` of the payload `manifest` describes, holding `data`
+    fn forged_part(manifest: &[u8], index: u32, data: &[u8]) -> Vec<u8> {
+        let mut part = PART_MAGIC.to_vec();
+        part.extend(&manifest[MANIFEST_MAGIC.len()..][..ID_LENGTH]);
+        part.extend(index.to_be_bytes());
+        part.extend(data);
+        part
+    }
+
+    #[test]
+    fn test_join_refuses_forged_manifests() {
+        // the parts hold more than the manifest says
+        let manifest = forged_manifest(2, 5);
+        let part = forged_part(&manifest, 0, b"0123456789");
+        assert!(join_damaged(&manifest, [part.as_slice()].into_iter()).is_err());
+        assert!(join(&manifest, [part.as_slice()].into_iter()).is_err());
+
+        // a part count and length nothing in the file backs
+        let manifest = forged_manifest(u32::MAX, u64::MAX);
+        let part = forged_part(&manifest, 0, b"0123456789");
+        assert!(join_damaged(&manifest, [part.as_slice()].into_iter()).is_err());
+        assert!(matches!(
+            join(&manifest, [part.as_slice()].into_iter()),
+            Err(crate::Error::Split(SplitError::MissingPart(1)))
+        ));
+    }
+}
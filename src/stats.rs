@@ -0,0 +1,181 @@
+use std::{cmp::Reverse, collections::HashMap, fmt::Display};
+
+use serde::Serialize;
+
+use crate::{chunk::Chunk, png::Png};
+
+/// How many chunks of one type a png has and how many bytes they take up,
+/// their length, type and CRC fields included
+#[derive(Debug, Serialize, PartialEq)]
+pub struct TypeStats {
+    pub chunk_type: String,
+    pub count: usize,
+    pub bytes: usize,
+}
+
+/// A chunk and the bytes it takes up in the file
+#[derive(Debug, Serialize, PartialEq)]
+pub struct ChunkSize {
+    pub chunk_type: String,
+    pub index: usize,
+    pub bytes: usize,
+}
+
+/// Where the bytes of a png go
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Stats {
+    pub file_size: usize,
+    /// Ordered by bytes, largest first
+    pub types: Vec<TypeStats>,
+    pub ancillary_bytes: usize,
+    /// `ancillary_bytes` as a percentage of `file_size`
+    pub ancillary_percent: f64,
+    /// The `top` largest chunks passed to `stats`, largest first
+    pub largest: Vec<ChunkSize>,
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} bytes", self.file_size)?;
+        for types in &self.types {
+            writeln!(
+                f,
+                "  {}  {:>5} chunk(s)  {:>10} bytes  {:>5.1}%",
+                types.chunk_type,
+                types.count,
+                types.bytes,
+                percent(types.bytes, self.file_size)
+            )?;
+        }
+        write!(
+            f,
+            "ancillary chunks: {} bytes, {:.1}% of the file",
+            self.ancillary_bytes, self.ancillary_percent
+        )?;
+        if !self.largest.is_empty() {
+            write!(f, "\nlargest chunks:")?;
+        }
+        for chunk in &self.largest {
+            write!(
+                f,
+                "\n  {}  index {:<5} {:>10} bytes",
+                chunk.chunk_type, chunk.index, chunk.bytes
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Breaks the size of `png` down by chunk type and lists its `top` largest
+/// chunks
+pub fn stats(png: &Png, top: usize) -> Stats {
+    let file_size = png.encoded_length();
+    let bytes = |chunk: &Chunk| chunk.length() + Chunk::META_DATA_LENGTH;
+
+    let mut types: Vec<TypeStats> = Vec::new();
+    let mut positions = HashMap::new();
+    for chunk in png.chunks() {
+        let position = *positions.entry(chunk.chunk_type()).or_insert_with(|| {
+            types.push(TypeStats {
+                chunk_type: chunk.chunk_type().to_string(),
+                count: 0,
+                bytes: 0,
+            });
+            types.len() - 1
+        });
+        types[position].count += 1;
+        types[position].bytes += bytes(chunk);
+    }
+    // stable, so types of the same size stay in file order
+    types.sort_by_key(|types| Reverse(types.bytes));
+
+    let ancillary_bytes = png
+        .chunks()
+        .filter(|chunk| !chunk.chunk_type().is_critical())
+        .map(bytes)
+        .sum();
+
+    let mut largest: Vec<ChunkSize> = png
+        .chunks()
+        .enumerate()
+        .map(|(index, chunk)| ChunkSize {
+            chunk_type: chunk.chunk_type().to_string(),
+            index,
+            bytes: bytes(chunk),
+        })
+        .collect();
+    largest.sort_by_key(|chunk| Reverse(chunk.bytes));
+    largest.truncate(top);
+
+    Stats {
+        file_size,
+        types,
+        ancillary_bytes,
+        ancillary_percent: percent(ancillary_bytes, file_size),
+        largest,
+    }
+}
+
+fn percent(part: usize, whole: usize) -> f64 {
+    match whole {
+        0 => 0.0,
+        _ => part as f64 * 100.0 / whole as f64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chunk_type::ChunkType, png::tests::testing_png_bytes};
+
+    #[test]
+    fn test_stats() {
+        let mut png = Png::try_from(testing_png_bytes().as_slice()).unwrap();
+        png.insert_chunk(Chunk::new(ChunkType::tEXt, vec![0; 20]));
+        png.insert_chunk(Chunk::new(ChunkType::tEXt, vec![0; 8]));
+        let stats = stats(&png, 2);
+
+        // signature, IHDR 25, IDAT 15, two tEXt 52 and IEND 12
+        assert_eq!(stats.file_size, 112);
+        let types: Vec<(&str, usize, usize)> = stats
+            .types
+            .iter()
+            .map(|t| (t.chunk_type.as_str(), t.count, t.bytes))
+            .collect();
+        assert_eq!(
+            types,
+            [
+                ("tEXt", 2, 52),
+                ("IHDR", 1, 25),
+                ("IDAT", 1, 15),
+                ("IEND", 1, 12)
+            ]
+        );
+        assert_eq!(stats.ancillary_bytes, 52);
+        assert!((stats.ancillary_percent - 46.43).abs() < 0.01);
+        assert_eq!(
+            stats.largest,
+            [
+                ChunkSize {
+                    chunk_type: "tEXt".to_string(),
+                    index: 2,
+                    bytes: 32
+                },
+                ChunkSize {
+                    chunk_type: "IHDR".to_string(),
+                    index: 0,
+                    bytes: 25
+                }
+            ]
+        );
+        assert!(stats
+            .to_string()
+            .contains("ancillary chunks: 52 bytes, 46.4%"));
+    }
+
+    #[test]
+    fn test_percent_of_nothing() {
+        assert_eq!(percent(0, 0), 0.0);
+        assert_eq!(percent(1, 4), 25.0);
+    }
+}
@@ -0,0 +1,175 @@
+use std::fmt::Display;
+
+use crate::{
+    ihdr::{ColorType, Ihdr},
+    image_data::{ImageData, ImageDataError},
+    Result,
+};
+
+#[derive(Debug)]
+pub enum StegoError {
+    Unsupported(u8, ColorType),
+    TooLarge { length: usize, capacity: usize },
+    NoPayload,
+}
+
+impl std::error::Error for StegoError {}
+
+impl Display for StegoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unsupported(bit_depth, color_type) => write!(
+                f,
+                "Hiding data needs 8 or 16-bit grayscale or rgb samples, not {}-bit {}",
+                bit_depth, color_type
+            ),
+            Self::TooLarge { length, capacity } => write!(
+                f,
+                "The payload is {} bytes but the image can hide at most {}",
+                length, capacity
+            ),
+            Self::NoPayload => write!(f, "The pixel data holds no hidden payload"),
+        }
+    }
+}
+
+/// Starts the hidden bit stream, followed by the payload length as a u32
+const MAGIC: &[u8] = b"pmL";
+const HEADER_LENGTH: usize = MAGIC.len() + 4;
+
+/// Offsets of the sample bytes whose lowest bit carries the hidden stream:
+/// the low byte of every colour sample. Alpha is left alone so transparent
+/// pixels stay fully transparent
+pub fn carriers(header: &Ihdr) -> Result<impl Iterator<Item = usize>> {
+    let colours = match header.color_type {
+        ColorType::Grayscale | ColorType::GrayscaleAlpha => 1,
+        ColorType::Rgb | ColorType::Rgba => 3,
+        // a palette index or a packed sample changes colour with any bit
+        ColorType::Indexed => 0,
+    };
+    if colours == 0 || header.bit_depth < 8 {
+        return Err(StegoError::Unsupported(header.bit_depth, header.color_type).into());
+    }
+    let channels = header.color_type.channels();
+    let sample_length = header.bit_depth as usize / 8;
+    // every offset handed out must fit, not just the sample count
+    let samples = (header.width as usize)
+        .checked_mul(header.height as usize)
+        .and_then(|pixels| pixels.checked_mul(channels))
+        .filter(|samples| samples.checked_mul(sample_length).is_some())
+        .ok_or(ImageDataError::TooLarge(header.width, header.height))?;
+    Ok((0..samples)
+        .filter(move |sample| sample % channels < colours)
+        .map(move |sample| sample * sample_length + sample_length - 1))
+}
+
+/// How many payload bytes `image` can hide
+pub fn capacity(image: &ImageData) -> Result<usize> {
+    let bits = carriers(image.header())?.count();
+    Ok((bits / 8).saturating_sub(HEADER_LENGTH))
+}
+
+/// Writes `payload` into the least significant bits of the image samples,
+/// changing each sample by at most one
+pub fn hide(image: &mut ImageData, payload: &[u8]) -> Result<()> {
+    let capacity = capacity(image)?;
+    if payload.len() > capacity {
+        return Err(StegoError::TooLarge {
+            length: payload.len(),
+            capacity,
+        }
+        .into());
+    }
+
+    let mut stream = MAGIC.to_vec();
+    stream.extend((payload.len() as u32).to_be_bytes());
+    stream.extend(payload);
+    let bits = stream
+        .iter()
+        .flat_map(|byte| (0..8).rev().map(move |shift| (byte >> shift) & 1));
+    let carriers = carriers(image.header())?;
+    let samples = image.samples_mut();
+    for (offset, bit) in carriers.zip(bits) {
+        samples[offset] = (samples[offset] & !1) | bit;
+    }
+    Ok(())
+}
+
+/// Reads back a payload written by `hide`
+pub fn reveal(image: &ImageData) -> Result<Vec<u8>> {
+    let capacity = capacity(image)?;
+    let samples = image.samples();
+    let mut bits = carriers(image.header())?.map(|offset| samples[offset] & 1);
+    let mut read = |length: usize| -> Vec<u8> {
+        (0..length)
+            .map(|_| bits.by_ref().take(8).fold(0, |byte, bit| byte << 1 | bit))
+            .collect()
+    };
+
+    let header = read(HEADER_LENGTH);
+    if capacity == 0 || !header.starts_with(MAGIC) {
+        return Err(StegoError::NoPayload.into());
+    }
+    let length = u32::from_be_bytes(header[MAGIC.len()..].try_into()?) as usize;
+    if length > capacity {
+        return Err(StegoError::NoPayload.into());
+    }
+    Ok(read(length))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_data::tests::testing_image;
+
+    #[test]
+    fn test_hide_and_reveal() {
+        for color_type in [ColorType::Grayscale, ColorType::Rgb, ColorType::Rgba] {
+            let original = ImageData::from_png(&testing_image(16, 16, color_type)).unwrap();
+            let mut image = original.clone();
+            hide(&mut image, b"in plain sight").unwrap();
+            assert_eq!(reveal(&image).unwrap(), b"in plain sight");
+
+            let changed = original
+                .samples()
+                .iter()
+                .zip(image.samples())
+                .filter(|(a, b)| a != b)
+                .inspect(|(a, b)| assert!(a.abs_diff(**b) == 1))
+                .count();
+            assert!(changed > 0);
+        }
+    }
+
+    #[test]
+    fn test_alpha_is_untouched() {
+        let original = ImageData::from_png(&testing_image(8, 8, ColorType::Rgba)).unwrap();
+        let mut image = original.clone();
+        let capacity = capacity(&image).unwrap();
+        assert_eq!(capacity, 8 * 8 * 3 / 8 - HEADER_LENGTH);
+        hide(&mut image, &vec![0xa5; capacity]).unwrap();
+        for (a, b) in original
+            .samples()
+            .iter()
+            .zip(image.samples())
+            .skip(3)
+            .step_by(4)
+        {
+            assert_eq!(a, b);
+        }
+        assert!(hide(&mut image, &vec![0; capacity + 1]).is_err());
+    }
+
+    #[test]
+    fn test_reveal_without_payload() {
+        let image = ImageData::from_png(&testing_image(8, 8, ColorType::Rgb)).unwrap();
+        assert!(reveal(&image).is_err());
+
+        let tiny = ImageData::from_png(&testing_image(1, 1, ColorType::Rgb)).unwrap();
+        assert_eq!(capacity(&tiny).unwrap(), 0);
+        assert!(reveal(&tiny).is_err());
+
+        let indexed = ImageData::from_png(&testing_image(8, 8, ColorType::Indexed)).unwrap();
+        assert!(capacity(&indexed).is_err());
+    }
+}
@@ -0,0 +1,205 @@
+use std::{fmt::Display, str::FromStr, sync::Arc};
+
+use object_store::{
+    aws::AmazonS3Builder, gcp::GoogleCloudStorageBuilder, path::Path, ObjectStore, PutPayload,
+};
+use tokio::runtime::{Builder, Runtime};
+
+use crate::Result;
+
+#[derive(Debug)]
+pub enum StorageError {
+    InvalidUri(String),
+}
+
+impl std::error::Error for StorageError {}
+
+impl Display for StorageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidUri(uri) => write!(
+                f,
+                "'{}' is not an object URI like s3://bucket/key or gs://bucket/key",
+                uri
+            ),
+        }
+    }
+}
+
+/// The object storage services picmes can read from and write to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scheme {
+    S3,
+    Gcs,
+}
+
+impl Scheme {
+    pub const ALL: [Self; 2] = [Self::S3, Self::Gcs];
+
+    /// What URIs of the service start with
+    pub fn prefix(self) -> &'static str {
+        match self {
+            Self::S3 => "s3://",
+            Self::Gcs => "gs://",
+        }
+    }
+}
+
+/// An object in a bucket, or with an empty or partial key a place to list
+/// objects from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectUri {
+    pub scheme: Scheme,
+    pub bucket: String,
+    pub key: String,
+}
+
+impl ObjectUri {
+    /// The object at `key` in the same bucket
+    pub fn with_key(&self, key: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            ..self.clone()
+        }
+    }
+}
+
+impl FromStr for ObjectUri {
+    type Err = crate::Error;
+
+    fn from_str(uri: &str) -> Result<Self> {
+        let invalid = || StorageError::InvalidUri(uri.to_string());
+        let (scheme, rest) = Scheme::ALL
+            .into_iter()
+            .find_map(|scheme| Some((scheme, uri.strip_prefix(scheme.prefix())?)))
+            .ok_or_else(invalid)?;
+        let (bucket, key) = rest.split_once('/').unwrap_or((rest, ""));
+        if bucket.is_empty() {
+            return Err(invalid().into());
+        }
+        Ok(Self {
+            scheme,
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+        })
+    }
+}
+
+impl Display for ObjectUri {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}/{}", self.scheme.prefix(), self.bucket, self.key)
+    }
+}
+
+/// A bucket objects are read from, written to and listed, one request at a
+/// time
+pub struct Bucket {
+    store: Arc<dyn ObjectStore>,
+    runtime: Runtime,
+}
+
+impl Bucket {
+    /// Connects to the bucket of `uri` with the credentials in the
+    /// environment: AWS_ACCESS_KEY_ID, AWS_SECRET_ACCESS_KEY and AWS_REGION,
+    /// or AWS_ENDPOINT for other S3 compatible services, for s3 and
+    /// GOOGLE_APPLICATION_CREDENTIALS for gs
+    pub fn open(uri: &ObjectUri) -> Result<Self> {
+        let store: Arc<dyn ObjectStore> = match uri.scheme {
+            Scheme::S3 => Arc::new(
+                AmazonS3Builder::from_env()
+                    .with_bucket_name(&uri.bucket)
+                    .build()?,
+            ),
+            Scheme::Gcs => Arc::new(
+                GoogleCloudStorageBuilder::from_env()
+                    .with_bucket_name(&uri.bucket)
+                    .build()?,
+            ),
+        };
+        Self::new(store)
+    }
+
+    /// A bucket kept in `store`, which can be any object_store backend
+    pub fn new(store: Arc<dyn ObjectStore>) -> Result<Self> {
+        let runtime = Builder::new_current_thread().enable_all().build()?;
+        Ok(Self { store, runtime })
+    }
+
+    /// The contents of the object at `key`
+    pub fn get(&self, key: &str) -> Result<Vec<u8>> {
+        let key = Path::parse(key).map_err(object_store::Error::from)?;
+        let bytes = self
+            .runtime
+            .block_on(async { self.store.get(&key).await?.bytes().await })?;
+        Ok(bytes.to_vec())
+    }
+
+    /// Replaces the object at `key` with `contents` in a single request, so
+    /// readers see either the old or the new object
+    pub fn put(&self, key: &str, contents: Vec<u8>) -> Result<()> {
+        let key = Path::parse(key).map_err(object_store::Error::from)?;
+        self.runtime
+            .block_on(self.store.put(&key, PutPayload::from(contents)))?;
+        Ok(())
+    }
+
+    /// The keys of the objects directly under `prefix`, a key up to a `/`,
+    /// or of every object below it when `recursive` is set, sorted
+    pub fn list(&self, prefix: &str, recursive: bool) -> Result<Vec<String>> {
+        let mut keys = Vec::new();
+        let mut prefixes = vec![Path::parse(prefix).map_err(object_store::Error::from)?];
+        while let Some(prefix) = prefixes.pop() {
+            let listing = self
+                .runtime
+                .block_on(self.store.list_with_delimiter(Some(&prefix)))?;
+            keys.extend(listing.objects.into_iter().map(|o| o.location.to_string()));
+            if recursive {
+                prefixes.extend(listing.common_prefixes);
+            }
+        }
+        keys.sort();
+        Ok(keys)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use object_store::memory::InMemory;
+
+    #[test]
+    fn test_parse_object_uri() {
+        let uri: ObjectUri = "s3://corpus/2024/a.png".parse().unwrap();
+        assert_eq!(uri.scheme, Scheme::S3);
+        assert_eq!(
+            (uri.bucket.as_str(), uri.key.as_str()),
+            ("corpus", "2024/a.png")
+        );
+        assert_eq!(uri.with_key("b.png").to_string(), "s3://corpus/b.png");
+
+        let uri: ObjectUri = "gs://corpus".parse().unwrap();
+        assert_eq!((uri.scheme, uri.key.as_str()), (Scheme::Gcs, ""));
+        assert_eq!(uri.to_string(), "gs://corpus/");
+
+        for invalid in ["s3:///a.png", "azure://corpus/a.png", "corpus/a.png"] {
+            assert!(invalid.parse::<ObjectUri>().is_err());
+        }
+    }
+
+    #[test]
+    fn test_bucket() {
+        let bucket = Bucket::new(Arc::new(InMemory::new())).unwrap();
+        for key in ["a.png", "2024/b.png", "2024/03/c.png", "2025/d.png"] {
+            bucket.put(key, key.as_bytes().to_vec()).unwrap();
+        }
+        assert_eq!(bucket.get("2024/b.png").unwrap(), b"2024/b.png");
+        assert!(bucket.get("missing.png").is_err());
+
+        assert_eq!(bucket.list("2024/", false).unwrap(), ["2024/b.png"]);
+        assert_eq!(
+            bucket.list("2024", true).unwrap(),
+            ["2024/03/c.png", "2024/b.png"]
+        );
+        assert_eq!(bucket.list("", true).unwrap().len(), 4);
+    }
+}
@@ -0,0 +1,433 @@
+use std::{
+    fmt::Display,
+    io::{Read, Write},
+};
+
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, Error, Result};
+
+#[derive(Debug)]
+pub enum TextError {
+    WrongChunkType(String),
+    MissingSeparator,
+    InvalidKeyword(String),
+    NotLatin1,
+    UnknownCompressionMethod(u8),
+    InvalidCompressionFlag(u8),
+    InvalidLanguageTag(String),
+    TooLong,
+}
+
+impl std::error::Error for TextError {}
+
+impl Display for TextError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::WrongChunkType(chunk_type) => {
+                write!(
+                    f,
+                    "Expected a tEXt, zTXt or iTXt chunk but found {}",
+                    chunk_type
+                )
+            }
+            Self::MissingSeparator => {
+                write!(f, "Text chunk has no null byte after its keyword")
+            }
+            Self::InvalidKeyword(keyword) => write!(
+                f,
+                "Invalid keyword {:?}: keywords are 1 to 79 printable Latin-1 characters \
+                 without leading, trailing or consecutive spaces",
+                keyword
+            ),
+            Self::NotLatin1 => write!(
+                f,
+                "tEXt and zTXt chunks can only hold Latin-1 text, use iTXt for anything else"
+            ),
+            Self::UnknownCompressionMethod(method) => {
+                write!(f, "Unknown text compression method {}", method)
+            }
+            Self::InvalidCompressionFlag(flag) => {
+                write!(f, "iTXt compression flag must be 0 or 1 but is {}", flag)
+            }
+            Self::InvalidLanguageTag(tag) => write!(
+                f,
+                "Invalid language tag {:?}: only ASCII letters, digits and hyphens are allowed",
+                tag
+            ),
+            Self::TooLong => write!(
+                f,
+                "Compressed text can't be longer than {} bytes",
+                TextChunk::MAX_INFLATED_LENGTH
+            ),
+        }
+    }
+}
+
+/// A keyword and its text. tEXt chunks store the Latin-1 keyword, a null
+/// separator and the Latin-1 text. zTXt chunks add a compression method byte
+/// after the separator and deflate the text. iTXt chunks hold UTF-8 text
+/// along with a language tag and a translation of the keyword
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextChunk {
+    pub keyword: String,
+    pub text: String,
+    pub compressed: bool,
+    /// Set for iTXt chunks
+    pub international: Option<International>,
+}
+
+/// The iTXt specific fields of a text chunk
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct International {
+    /// RFC 3066 language tag such as `en-GB`, empty when unknown
+    pub language: String,
+    /// The keyword translated into `language`
+    pub translated_keyword: String,
+}
+
+impl TextChunk {
+    pub const MAX_KEYWORD_LENGTH: usize = 79;
+    /// Longest text a zTXt or compressed iTXt chunk may inflate to. Text
+    /// chunks are read from any file `print` or `scan` is given, so a small
+    /// forged chunk must not be able to take up much memory
+    pub const MAX_INFLATED_LENGTH: usize = 8 * 1024 * 1024;
+
+    /// Text for a tEXt chunk, or a zTXt chunk once compressed
+    pub fn new(keyword: impl Into<String>, text: impl Into<String>) -> Result<Self> {
+        let keyword = keyword.into();
+        let text = text.into();
+        validate_keyword(&keyword)?;
+        if !text.chars().all(|c| (c as u32) <= 0xff) {
+            return Err(TextError::NotLatin1.into());
+        }
+        Ok(Self {
+            keyword,
+            text,
+            compressed: false,
+            international: None,
+        })
+    }
+
+    /// UTF-8 text for an iTXt chunk
+    pub fn international(
+        keyword: impl Into<String>,
+        text: impl Into<String>,
+        international: International,
+    ) -> Result<Self> {
+        let keyword = keyword.into();
+        validate_keyword(&keyword)?;
+        validate_language(&international.language)?;
+        Ok(Self {
+            keyword,
+            text: text.into(),
+            compressed: false,
+            international: Some(international),
+        })
+    }
+
+    /// Stores the text deflated, in a zTXt chunk unless it is an iTXt one
+    pub fn compress(mut self) -> Self {
+        self.compressed = true;
+        self
+    }
+
+    pub fn chunk_type(&self) -> ChunkType {
+        match (&self.international, self.compressed) {
+            (Some(_), _) => ChunkType::iTXt,
+            (None, true) => ChunkType::zTXt,
+            (None, false) => ChunkType::tEXt,
+        }
+    }
+
+    /// The chunk holding this keyword and text
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        let mut data = latin1_bytes(&self.keyword);
+        data.push(0);
+
+        let text = match &self.international {
+            Some(international) => {
+                data.push(self.compressed as u8);
+                data.push(COMPRESSION_DEFLATE);
+                data.extend(international.language.as_bytes());
+                data.push(0);
+                data.extend(international.translated_keyword.as_bytes());
+                data.push(0);
+                self.text.as_bytes().to_vec()
+            }
+            None if self.compressed => {
+                data.push(COMPRESSION_DEFLATE);
+                latin1_bytes(&self.text)
+            }
+            None => latin1_bytes(&self.text),
+        };
+        if self.compressed {
+            // it couldn't be read back otherwise
+            if text.len() > TextChunk::MAX_INFLATED_LENGTH {
+                return Err(TextError::TooLong.into());
+            }
+            data.extend(deflate(&text)?);
+        } else {
+            data.extend(text);
+        }
+        Ok(Chunk::new(self.chunk_type(), data))
+    }
+}
+
+/// The only compression method the specification defines, zlib deflate
+const COMPRESSION_DEFLATE: u8 = 0;
+
+impl TryFrom<&Chunk> for TextChunk {
+    type Error = Error;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        let (keyword, rest) = split_at_null(chunk.data())?;
+        let keyword = latin1_string(keyword);
+        validate_keyword(&keyword)?;
+
+        match *chunk.chunk_type() {
+            ChunkType::tEXt => Ok(Self {
+                keyword,
+                text: latin1_string(rest),
+                compressed: false,
+                international: None,
+            }),
+            ChunkType::zTXt => {
+                let (&method, compressed) =
+                    rest.split_first().ok_or(TextError::MissingSeparator)?;
+                Ok(Self {
+                    keyword,
+                    text: latin1_string(&inflate(method, compressed)?),
+                    compressed: true,
+                    international: None,
+                })
+            }
+            ChunkType::iTXt => {
+                let [flag, method, rest @ ..] = rest else {
+                    return Err(TextError::MissingSeparator.into());
+                };
+                let (language, rest) = split_at_null(rest)?;
+                let (translated_keyword, text) = split_at_null(rest)?;
+                let text = match flag {
+                    0 => text.to_vec(),
+                    1 => inflate(*method, text)?,
+                    _ => return Err(TextError::InvalidCompressionFlag(*flag).into()),
+                };
+
+                let international = International {
+                    language: latin1_string(language),
+                    translated_keyword: String::from_utf8(translated_keyword.to_vec())?,
+                };
+                validate_language(&international.language)?;
+                Ok(Self {
+                    keyword,
+                    text: String::from_utf8(text)?,
+                    compressed: *flag == 1,
+                    international: Some(international),
+                })
+            }
+            _ => Err(TextError::WrongChunkType(chunk.chunk_type().to_string()).into()),
+        }
+    }
+}
+
+impl Display for TextChunk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.keyword)?;
+        if let Some(international) = &self.international {
+            if !international.translated_keyword.is_empty() {
+                write!(f, " ({})", international.translated_keyword)?;
+            }
+            if !international.language.is_empty() {
+                write!(f, " [{}]", international.language)?;
+            }
+        }
+        write!(f, ": {}", self.text)
+    }
+}
+
+/// Splits `bytes` around the first null byte
+fn split_at_null(bytes: &[u8]) -> Result<(&[u8], &[u8])> {
+    let separator = bytes
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or(TextError::MissingSeparator)?;
+    Ok((&bytes[..separator], &bytes[separator + 1..]))
+}
+
+fn validate_language(language: &str) -> Result<()> {
+    if !language
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-')
+    {
+        return Err(TextError::InvalidLanguageTag(language.to_string()).into());
+    }
+    Ok(())
+}
+
+fn deflate(data: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+
+/// Inflates zlib data, failing once it goes past `MAX_INFLATED_LENGTH`
+/// rather than letting a tiny chunk expand into gigabytes
+fn inflate(method: u8, data: &[u8]) -> Result<Vec<u8>> {
+    if method != COMPRESSION_DEFLATE {
+        return Err(TextError::UnknownCompressionMethod(method).into());
+    }
+    let mut inflated = Vec::new();
+    ZlibDecoder::new(data)
+        .take(TextChunk::MAX_INFLATED_LENGTH as u64 + 1)
+        .read_to_end(&mut inflated)?;
+    if inflated.len() > TextChunk::MAX_INFLATED_LENGTH {
+        return Err(TextError::TooLong.into());
+    }
+    Ok(inflated)
+}
+
+fn validate_keyword(keyword: &str) -> Result<()> {
+    let printable = keyword
+        .chars()
+        .all(|c| matches!(c as u32, 0x20..=0x7e | 0xa1..=0xff));
+    let valid = printable
+        && (1..=TextChunk::MAX_KEYWORD_LENGTH).contains(&keyword.chars().count())
+        && !keyword.starts_with(' ')
+        && !keyword.ends_with(' ')
+        && !keyword.contains("  ");
+    if !valid {
+        return Err(TextError::InvalidKeyword(keyword.to_string()).into());
+    }
+    Ok(())
+}
+
+// Latin-1 maps every byte to the code point of the same value
+fn latin1_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+fn latin1_bytes(text: &str) -> Vec<u8> {
+    text.chars().map(|c| c as u8).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_chunk_round_trip() {
+        let text = TextChunk::new("Comment", "Caf\u{e9} au lait").unwrap();
+        let chunk = text.to_chunk().unwrap();
+        assert_eq!(chunk.chunk_type(), &ChunkType::tEXt);
+        assert_eq!(chunk.data(), b"Comment\0Caf\xe9 au lait");
+        assert_eq!(TextChunk::try_from(&chunk).unwrap(), text);
+        assert_eq!(text.to_string(), "Comment: Caf\u{e9} au lait");
+    }
+
+    #[test]
+    fn test_invalid_keywords() {
+        for keyword in [
+            "",
+            " Title",
+            "Title ",
+            "Two  spaces",
+            "Tab\there",
+            "\u{263a}",
+        ] {
+            assert!(TextChunk::new(keyword, "text").is_err(), "{:?}", keyword);
+        }
+        assert!(TextChunk::new("k".repeat(80), "text").is_err());
+        assert!(TextChunk::new("Title", "\u{263a}").is_err());
+    }
+
+    #[test]
+    fn test_parse_invalid_chunks() {
+        let chunk = Chunk::new(ChunkType::tEXt, b"no separator".to_vec());
+        assert!(TextChunk::try_from(&chunk).is_err());
+
+        let chunk = Chunk::new(ChunkType::zTXt, b"Title\0\0not deflated".to_vec());
+        assert!(TextChunk::try_from(&chunk).is_err());
+
+        let chunk = Chunk::new(ChunkType::zTXt, b"Title\0\x07".to_vec());
+        assert!(TextChunk::try_from(&chunk).is_err());
+
+        let chunk = Chunk::new(ChunkType::IDAT, b"Title\0text".to_vec());
+        assert!(TextChunk::try_from(&chunk).is_err());
+    }
+
+    #[test]
+    fn test_compressed_text_round_trip() {
+        let text = TextChunk::new("Description", "na".repeat(500))
+            .unwrap()
+            .compress();
+        let chunk = text.to_chunk().unwrap();
+        assert_eq!(chunk.chunk_type(), &ChunkType::zTXt);
+        assert!(chunk.data().starts_with(b"Description\0\0"));
+        assert!(chunk.length() < 100);
+        assert_eq!(TextChunk::try_from(&chunk).unwrap(), text);
+    }
+
+    #[test]
+    fn test_inflating_is_bounded() {
+        let too_long = TextChunk::new("Comment", "a".repeat(TextChunk::MAX_INFLATED_LENGTH + 1))
+            .unwrap()
+            .compress();
+        assert!(too_long.to_chunk().is_err());
+
+        // a forged chunk of a few KiB that inflates past the limit
+        let mut data = b"Comment\0\0".to_vec();
+        data.extend(deflate(&vec![0; TextChunk::MAX_INFLATED_LENGTH + 1]).unwrap());
+        assert!(data.len() < 64 * 1024);
+        let forged = Chunk::new(ChunkType::zTXt, data);
+        assert!(matches!(
+            TextChunk::try_from(&forged),
+            Err(Error::Text(TextError::TooLong))
+        ));
+    }
+
+    #[test]
+    fn test_international_text_round_trip() {
+        let international = International {
+            language: "ja".to_string(),
+            translated_keyword: "\u{984c}\u{540d}".to_string(),
+        };
+        for compressed in [false, true] {
+            let mut text = TextChunk::international(
+                "Title",
+                "\u{30b5}\u{30a4}\u{30b3}\u{30ed}",
+                international.clone(),
+            )
+            .unwrap();
+            text.compressed = compressed;
+            let chunk = text.to_chunk().unwrap();
+            assert_eq!(chunk.chunk_type(), &ChunkType::iTXt);
+            assert_eq!(TextChunk::try_from(&chunk).unwrap(), text);
+        }
+
+        let text = TextChunk::international("Title", "Dice", international).unwrap();
+        assert_eq!(text.to_string(), "Title (\u{984c}\u{540d}) [ja]: Dice");
+        let chunk = text.to_chunk().unwrap();
+        assert_eq!(chunk.data()[..8], *b"Title\0\0\0");
+    }
+
+    #[test]
+    fn test_invalid_international_text() {
+        let bad_language = International {
+            language: "en GB".to_string(),
+            ..Default::default()
+        };
+        assert!(TextChunk::international("Title", "text", bad_language).is_err());
+
+        for data in [
+            &b"Title\0\x02\0\0\0text"[..],
+            b"Title\0\0\0en",
+            b"Title\0\0",
+        ] {
+            let chunk = Chunk::new(ChunkType::iTXt, data.to_vec());
+            assert!(TextChunk::try_from(&chunk).is_err(), "{:?}", data);
+        }
+        let chunk = Chunk::new(ChunkType::iTXt, b"Title\0\0\0\0\0\xff".to_vec());
+        assert!(TextChunk::try_from(&chunk).is_err());
+    }
+}
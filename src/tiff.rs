@@ -0,0 +1,418 @@
+use std::{convert::TryFrom, fmt::Display, path::Path};
+
+use crate::{
+    carrier::{self, Section},
+    chunk::Chunk,
+    plan, Result,
+};
+
+#[derive(Debug)]
+pub enum TiffError {
+    InvalidHeader,
+    BigTiff,
+    Truncated,
+}
+
+impl std::error::Error for TiffError {}
+
+impl Display for TiffError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidHeader => write!(f, "A tiff should start with II*\\0 or MM\\0*"),
+            Self::BigTiff => write!(f, "BigTIFF files are not supported"),
+            Self::Truncated => write!(f, "The file ends in the middle of the first IFD"),
+        }
+    }
+}
+
+/// `byte order | 42 | offset of the first IFD`
+const HEADER_LENGTH: usize = 8;
+const IFD_OFFSET_OFFSET: usize = 4;
+const BIG_TIFF_VERSION: u16 = 43;
+/// `tag | field type | count | value or offset`
+const ENTRY_LENGTH: usize = 12;
+/// Tags from 65000 up are free for private use without registration
+const PAYLOAD_TAG: u16 = 65000;
+const UNDEFINED: u16 = 7;
+/// Offsets in a tiff have to fall on a word boundary
+const WORD: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ByteOrder {
+    Little,
+    Big,
+}
+
+impl ByteOrder {
+    fn u16_at(self, bytes: &[u8], offset: usize) -> Option<u16> {
+        let field = bytes.get(offset..offset + 2)?.try_into().ok()?;
+        Some(match self {
+            Self::Little => u16::from_le_bytes(field),
+            Self::Big => u16::from_be_bytes(field),
+        })
+    }
+
+    fn u32_at(self, bytes: &[u8], offset: usize) -> Option<u32> {
+        let field = bytes.get(offset..offset + 4)?.try_into().ok()?;
+        Some(match self {
+            Self::Little => u32::from_le_bytes(field),
+            Self::Big => u32::from_be_bytes(field),
+        })
+    }
+
+    fn u16_bytes(self, value: u16) -> [u8; 2] {
+        match self {
+            Self::Little => value.to_le_bytes(),
+            Self::Big => value.to_be_bytes(),
+        }
+    }
+
+    fn u32_bytes(self, value: u32) -> [u8; 4] {
+        match self {
+            Self::Little => value.to_le_bytes(),
+            Self::Big => value.to_be_bytes(),
+        }
+    }
+}
+
+/// An IFD entry. The value field is kept as stored, since whatever it
+/// points to is never moved
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    tag: u16,
+    field_type: u16,
+    count: u32,
+    value: [u8; 4],
+}
+
+fn ifd_length(entries: usize) -> usize {
+    2 + entries * ENTRY_LENGTH + 4
+}
+
+/// A tiff with the chunks hidden in it held apart. They are stored in a
+/// container at the end of the file, which a private tag in the first IFD
+/// points to. Adding that tag means writing the IFD anew, so it follows
+/// the container and the header is pointed at it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tiff {
+    order: ByteOrder,
+    /// The file with the container and the IFD written after it taken out
+    bytes: Vec<u8>,
+    /// The first IFD, without the payload tag
+    entries: Vec<Entry>,
+    next_ifd: u32,
+    /// Whether the first IFD in `bytes` can be left where it is, which is
+    /// when the file has no payload tag
+    ifd_in_place: bool,
+    chunks: Vec<Chunk>,
+}
+
+impl Tiff {
+    pub const LITTLE_ENDIAN_SIGNATURE: &'static [u8] = b"II*\0";
+    pub const BIG_ENDIAN_SIGNATURE: &'static [u8] = b"MM\0*";
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        Self::try_from(std::fs::read(path)?.as_slice())
+    }
+
+    /// Whether `bytes` start like a tiff
+    pub fn has_signature(bytes: &[u8]) -> bool {
+        bytes.starts_with(Self::LITTLE_ENDIAN_SIGNATURE)
+            || bytes.starts_with(Self::BIG_ENDIAN_SIGNATURE)
+    }
+
+    pub fn chunks(&self) -> impl Iterator<Item = &Chunk> {
+        self.chunks.iter()
+    }
+
+    /// Lists the header, the first IFD and the hidden chunks
+    pub fn sections(&self) -> Vec<Section> {
+        let payload_entries = usize::from(!self.chunks.is_empty());
+        let mut sections = vec![
+            Section {
+                name: "header".to_string(),
+                length: HEADER_LENGTH,
+                chunk_type: None,
+            },
+            Section {
+                name: "IFD0".to_string(),
+                length: ifd_length(self.entries.len() + payload_entries),
+                chunk_type: None,
+            },
+        ];
+        sections.extend(self.chunks.iter().map(|chunk| Section {
+            name: format!("tag {}", PAYLOAD_TAG),
+            length: chunk.length() + Chunk::META_DATA_LENGTH,
+            chunk_type: Some(chunk.chunk_type().clone()),
+        }));
+        sections
+    }
+
+    pub fn insert_chunk(&mut self, chunk: Chunk) {
+        self.chunks.push(chunk);
+    }
+
+    /// Removes every chunk matching `predicate`, returning them in file order
+    pub fn remove_chunks_where(&mut self, mut predicate: impl FnMut(&Chunk) -> bool) -> Vec<Chunk> {
+        let mut removed = Vec::new();
+        let mut kept = Vec::new();
+        for chunk in self.chunks.drain(..) {
+            match predicate(&chunk) {
+                true => removed.push(chunk),
+                false => kept.push(chunk),
+            }
+        }
+        self.chunks = kept;
+        removed
+    }
+
+    pub fn remove_first_chunk(&mut self, chunk_type: &str) -> Option<Chunk> {
+        let mut first = true;
+        self.remove_chunks_where(|chunk| {
+            chunk.chunk_type().to_string() == chunk_type && std::mem::take(&mut first)
+        })
+        .pop()
+    }
+
+    pub fn remove_all_chunks(&mut self, chunk_type: &str) -> Vec<Chunk> {
+        self.remove_chunks_where(|chunk| chunk.chunk_type().to_string() == chunk_type)
+    }
+
+    /// Appends the container and a first IFD pointing at it, unless there
+    /// is nothing to hide and the original IFD is still in place
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.bytes.clone();
+        if self.ifd_in_place && self.chunks.is_empty() {
+            return bytes;
+        }
+        let order = self.order;
+        bytes.resize(bytes.len().next_multiple_of(WORD), 0);
+
+        let mut entries = self.entries.clone();
+        if !self.chunks.is_empty() {
+            let container = carrier::pack_chunks(&self.chunks, WORD);
+            let position = entries.partition_point(|entry| entry.tag < PAYLOAD_TAG);
+            entries.insert(
+                position,
+                Entry {
+                    tag: PAYLOAD_TAG,
+                    field_type: UNDEFINED,
+                    count: container.len() as u32,
+                    value: order.u32_bytes(bytes.len() as u32),
+                },
+            );
+            bytes.extend(container);
+        }
+
+        let ifd_offset = bytes.len() as u32;
+        bytes.extend(order.u16_bytes(entries.len() as u16));
+        for entry in &entries {
+            bytes.extend(order.u16_bytes(entry.tag));
+            bytes.extend(order.u16_bytes(entry.field_type));
+            bytes.extend(order.u32_bytes(entry.count));
+            bytes.extend(entry.value);
+        }
+        bytes.extend(order.u32_bytes(self.next_ifd));
+        bytes[IFD_OFFSET_OFFSET..HEADER_LENGTH].copy_from_slice(&order.u32_bytes(ifd_offset));
+        bytes
+    }
+
+    /// The size of the tiff in bytes once written
+    pub fn encoded_length(&self) -> usize {
+        if self.ifd_in_place && self.chunks.is_empty() {
+            return self.bytes.len();
+        }
+        let entries = self.entries.len() + usize::from(!self.chunks.is_empty());
+        // the entry count, the entries and the offset of the next IFD
+        self.bytes.len().next_multiple_of(WORD)
+            + carrier::packed_length(&self.chunks, WORD)
+            + 2
+            + entries * ENTRY_LENGTH
+            + 4
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        plan::write_atomic(path.as_ref(), &self.as_bytes())
+    }
+}
+
+impl TryFrom<&[u8]> for Tiff {
+    type Error = crate::Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        let order = match bytes.get(..2) {
+            Some(b"II") => ByteOrder::Little,
+            Some(b"MM") => ByteOrder::Big,
+            _ => return Err(TiffError::InvalidHeader.into()),
+        };
+        match order.u16_at(bytes, 2) {
+            Some(42) => {}
+            Some(BIG_TIFF_VERSION) => return Err(TiffError::BigTiff.into()),
+            _ => return Err(TiffError::InvalidHeader.into()),
+        }
+        let ifd_offset = order
+            .u32_at(bytes, IFD_OFFSET_OFFSET)
+            .ok_or(TiffError::Truncated)? as usize;
+        let count = order
+            .u16_at(bytes, ifd_offset)
+            .ok_or(TiffError::Truncated)? as usize;
+        let ifd_end = ifd_offset + ifd_length(count);
+        let next_ifd = order
+            .u32_at(bytes, ifd_end - 4)
+            .ok_or(TiffError::Truncated)?;
+
+        let mut entries = Vec::new();
+        let mut payload = None;
+        for index in 0..count {
+            let offset = ifd_offset + 2 + index * ENTRY_LENGTH;
+            let entry = Entry {
+                tag: order.u16_at(bytes, offset).ok_or(TiffError::Truncated)?,
+                field_type: order
+                    .u16_at(bytes, offset + 2)
+                    .ok_or(TiffError::Truncated)?,
+                count: order
+                    .u32_at(bytes, offset + 4)
+                    .ok_or(TiffError::Truncated)?,
+                value: bytes[offset + 8..offset + ENTRY_LENGTH].try_into()?,
+            };
+            if entry.tag == PAYLOAD_TAG && entry.field_type == UNDEFINED {
+                let start = order.u32_at(&entry.value, 0).ok_or(TiffError::Truncated)? as usize;
+                let data = bytes
+                    .get(start..start + entry.count as usize)
+                    .ok_or(TiffError::Truncated)?;
+                // a tag some other tool happened to use stays as it is
+                if let Some((chunks, _)) = carrier::unpack_chunks(data)? {
+                    payload = Some((start, entry.count as usize, chunks));
+                    continue;
+                }
+            }
+            entries.push(entry);
+        }
+
+        let (bytes, ifd_in_place, chunks) = match payload {
+            // what picmes wrote last, which the next save writes anew
+            Some((start, length, chunks))
+                if start + length == ifd_offset && ifd_end == bytes.len() =>
+            {
+                (bytes[..start].to_vec(), false, chunks)
+            }
+            Some((_, _, chunks)) => (bytes.to_vec(), false, chunks),
+            None => (bytes.to_vec(), true, Vec::new()),
+        };
+        Ok(Self {
+            order,
+            bytes,
+            entries,
+            next_ifd,
+            ifd_in_place,
+            chunks,
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{
+        carrier::tests::{assert_chunks_round_trip, assert_round_trip},
+        png::tests::testing_chunk,
+    };
+
+    /// A 1x1 grayscale tiff in `order`, with its single strip after the IFD
+    fn testing_tiff_bytes_in(order: ByteOrder) -> Vec<u8> {
+        let entries: [(u16, u16, u32); 8] = [
+            (256, 3, 1),
+            (257, 3, 1),
+            (258, 3, 8),
+            (259, 3, 1),
+            (262, 3, 1),
+            (273, 4, (HEADER_LENGTH + ifd_length(8)) as u32),
+            (278, 3, 1),
+            (279, 4, 1),
+        ];
+        let mut bytes = match order {
+            ByteOrder::Little => Tiff::LITTLE_ENDIAN_SIGNATURE.to_vec(),
+            ByteOrder::Big => Tiff::BIG_ENDIAN_SIGNATURE.to_vec(),
+        };
+        bytes.extend(order.u32_bytes(HEADER_LENGTH as u32));
+        bytes.extend(order.u16_bytes(entries.len() as u16));
+        for (tag, field_type, value) in entries {
+            bytes.extend(order.u16_bytes(tag));
+            bytes.extend(order.u16_bytes(field_type));
+            bytes.extend(order.u32_bytes(1));
+            match field_type {
+                // shorts are left justified in the value field
+                3 => bytes.extend([order.u16_bytes(value as u16), [0; 2]].concat()),
+                _ => bytes.extend(order.u32_bytes(value)),
+            }
+        }
+        bytes.extend([0; 4]);
+        bytes.push(0x80);
+        bytes
+    }
+
+    pub(crate) fn testing_tiff_bytes() -> Vec<u8> {
+        testing_tiff_bytes_in(ByteOrder::Little)
+    }
+
+    fn names(tiff: &Tiff) -> Vec<String> {
+        tiff.sections().into_iter().map(|s| s.name).collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for order in [ByteOrder::Little, ByteOrder::Big] {
+            let bytes = testing_tiff_bytes_in(order);
+            let tiff = Tiff::try_from(bytes.as_slice()).unwrap();
+            assert_eq!(tiff.order, order);
+            assert_eq!(tiff.entries.len(), 8);
+            assert_eq!(names(&tiff), ["header", "IFD0"]);
+            assert_eq!(tiff.sections()[1].length, 102);
+            assert_round_trip(&bytes);
+        }
+    }
+
+    #[test]
+    fn test_insert_and_remove_chunks() {
+        for order in [ByteOrder::Little, ByteOrder::Big] {
+            let bytes = testing_tiff_bytes_in(order);
+            let mut tiff = Tiff::try_from(bytes.as_slice()).unwrap();
+            tiff.insert_chunk(testing_chunk(b"first"));
+            tiff.insert_chunk(testing_chunk(b"second"));
+            assert_eq!(names(&tiff), ["header", "IFD0", "tag 65000", "tag 65000"]);
+
+            let written = tiff.as_bytes();
+            assert_eq!(
+                &written[HEADER_LENGTH..bytes.len()],
+                &bytes[HEADER_LENGTH..]
+            );
+            let ifd_offset = order.u32_at(&written, IFD_OFFSET_OFFSET).unwrap() as usize;
+            assert_eq!(ifd_offset % WORD, 0);
+            assert_eq!(order.u16_at(&written, ifd_offset), Some(9));
+            let last_tag = ifd_offset + 2 + 8 * ENTRY_LENGTH;
+            assert_eq!(order.u16_at(&written, last_tag), Some(PAYLOAD_TAG));
+
+            let reread = Tiff::try_from(written.as_slice()).unwrap();
+            assert_eq!(reread.bytes.len(), bytes.len().next_multiple_of(WORD));
+            // saving again replaces the container rather than adding one
+            let removed = assert_chunks_round_trip(tiff.clone(), &[b"first", b"second"]);
+            let cleared = Tiff::try_from(removed.as_bytes().as_slice()).unwrap();
+            assert_eq!(cleared.entries, tiff.entries);
+        }
+    }
+
+    #[test]
+    fn test_invalid_tiffs() {
+        assert!(Tiff::try_from(&b"II+\0\x08\0\0\0"[..]).is_err());
+        assert!(Tiff::try_from(&b"II*\0\xff\0\0\0"[..]).is_err());
+        let bytes = testing_tiff_bytes();
+        assert!(Tiff::try_from(&bytes[..50]).is_err());
+
+        let mut damaged = Tiff::try_from(bytes.as_slice()).unwrap();
+        damaged.insert_chunk(testing_chunk(b"damaged"));
+        let mut written = damaged.as_bytes();
+        // the length of the chunk inside the container
+        written[bytes.len() + 1 + 8 + 3] = 0xff;
+        assert!(Tiff::try_from(written.as_slice()).is_err());
+    }
+}
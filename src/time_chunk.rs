@@ -0,0 +1,240 @@
+use std::{
+    fmt::Display,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, Error, Result};
+
+#[derive(Debug)]
+pub enum TimeError {
+    Missing,
+    WrongChunkType(String),
+    InvalidLength(usize),
+    InvalidField(&'static str, u16),
+    InvalidFormat(String),
+}
+
+impl std::error::Error for TimeError {}
+
+impl Display for TimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Missing => write!(f, "The png has no tIME chunk"),
+            Self::WrongChunkType(chunk_type) => {
+                write!(f, "Expected a tIME chunk but found {}", chunk_type)
+            }
+            Self::InvalidLength(length) => write!(
+                f,
+                "tIME data should be {} bytes but is {}",
+                LastModified::LENGTH,
+                length
+            ),
+            Self::InvalidField(field, value) => write!(f, "Invalid tIME {} {}", field, value),
+            Self::InvalidFormat(time) => write!(
+                f,
+                "Invalid time {:?}, expected YYYY-MM-DDTHH:MM:SS in UTC",
+                time
+            ),
+        }
+    }
+}
+
+/// The last modification time stored in the tIME chunk, always in UTC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LastModified {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    /// Up to 60 to allow for leap seconds
+    pub second: u8,
+}
+
+impl LastModified {
+    pub const LENGTH: usize = 7;
+
+    pub fn new(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> Result<Self> {
+        for (field, value, range) in [
+            ("month", month, 1..=12),
+            ("day", day, 1..=31),
+            ("hour", hour, 0..=23),
+            ("minute", minute, 0..=59),
+            ("second", second, 0..=60),
+        ] {
+            if !range.contains(&value) {
+                return Err(TimeError::InvalidField(field, value as u16).into());
+            }
+        }
+        Ok(Self {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+        })
+    }
+
+    /// The time `seconds` after the unix epoch
+    pub fn from_unix(seconds: u64) -> Result<Self> {
+        let (days, rest) = (seconds / 86_400, seconds % 86_400);
+        let (year, month, day) = civil_from_days(days);
+        let year = u16::try_from(year).map_err(|_| TimeError::InvalidField("year", u16::MAX))?;
+        Self::new(
+            year,
+            month,
+            day,
+            (rest / 3600) as u8,
+            (rest % 3600 / 60) as u8,
+            (rest % 60) as u8,
+        )
+    }
+
+    pub fn now() -> Result<Self> {
+        Self::from_unix(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+    }
+
+    pub fn to_chunk(self) -> Chunk {
+        let mut data = self.year.to_be_bytes().to_vec();
+        data.extend([self.month, self.day, self.hour, self.minute, self.second]);
+        Chunk::new(ChunkType::tIME, data)
+    }
+}
+
+/// Converts days since 1970-01-01 to a (year, month, day) date in the
+/// proleptic Gregorian calendar
+fn civil_from_days(days: u64) -> (u64, u8, u8) {
+    // shift the epoch to 0000-03-01 so leap days fall at the end of a year
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u8;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u8;
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+impl TryFrom<&Chunk> for LastModified {
+    type Error = Error;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        if chunk.chunk_type() != &ChunkType::tIME {
+            return Err(TimeError::WrongChunkType(chunk.chunk_type().to_string()).into());
+        }
+        let data = chunk.data();
+        if data.len() != Self::LENGTH {
+            return Err(TimeError::InvalidLength(data.len()).into());
+        }
+        Self::new(
+            u16::from_be_bytes([data[0], data[1]]),
+            data[2],
+            data[3],
+            data[4],
+            data[5],
+            data[6],
+        )
+    }
+}
+
+impl FromStr for LastModified {
+    type Err = Error;
+
+    /// Parses `YYYY-MM-DDTHH:MM:SS`, optionally followed by `Z`. A space may
+    /// be used instead of the `T`
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || TimeError::InvalidFormat(s.to_string());
+        let trimmed = s.strip_suffix('Z').unwrap_or(s);
+        let (date, time) = trimmed.split_once(['T', ' ']).ok_or_else(invalid)?;
+
+        let numbers = date
+            .split('-')
+            .chain(time.split(':'))
+            .map(|field| field.parse::<u16>())
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|_| invalid())?;
+        let [year, month, day, hour, minute, second] = numbers[..] else {
+            return Err(invalid().into());
+        };
+        let byte = |value: u16| u8::try_from(value).map_err(|_| invalid());
+        Self::new(
+            year,
+            byte(month)?,
+            byte(day)?,
+            byte(hour)?,
+            byte(minute)?,
+            byte(second)?,
+        )
+    }
+}
+
+impl Display for LastModified {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_chunk_round_trip() {
+        let time = LastModified::new(2024, 2, 29, 13, 5, 9).unwrap();
+        let chunk = time.to_chunk();
+        assert_eq!(chunk.data(), [0x07, 0xe8, 2, 29, 13, 5, 9]);
+        assert_eq!(LastModified::try_from(&chunk).unwrap(), time);
+        assert_eq!(time.to_string(), "2024-02-29T13:05:09Z");
+    }
+
+    #[test]
+    fn test_invalid_time_chunk() {
+        let chunk = Chunk::new(ChunkType::tIME, vec![0x07, 0xe8, 13, 1, 0, 0, 0]);
+        assert!(LastModified::try_from(&chunk).is_err());
+        let chunk = Chunk::new(ChunkType::tIME, vec![0x07, 0xe8, 1, 1, 0, 0]);
+        assert!(LastModified::try_from(&chunk).is_err());
+        let chunk = Chunk::new(ChunkType::tEXt, vec![0x07, 0xe8, 1, 1, 0, 0, 0]);
+        assert!(LastModified::try_from(&chunk).is_err());
+    }
+
+    #[test]
+    fn test_time_from_unix() {
+        let epoch = LastModified::from_unix(0).unwrap();
+        assert_eq!(epoch.to_string(), "1970-01-01T00:00:00Z");
+        let leap_day = LastModified::from_unix(951_827_696).unwrap();
+        assert_eq!(leap_day.to_string(), "2000-02-29T12:34:56Z");
+        let new_year = LastModified::from_unix(1_704_067_199).unwrap();
+        assert_eq!(new_year.to_string(), "2023-12-31T23:59:59Z");
+    }
+
+    #[test]
+    fn test_parse_time() {
+        let time = LastModified::new(2024, 2, 29, 13, 5, 9).unwrap();
+        assert_eq!(
+            "2024-02-29T13:05:09Z".parse::<LastModified>().unwrap(),
+            time
+        );
+        assert_eq!("2024-02-29 13:05:09".parse::<LastModified>().unwrap(), time);
+        for invalid in [
+            "2024-02-29",
+            "2024-13-01T00:00:00",
+            "2024-02-29T13:05",
+            "now",
+        ] {
+            assert!(invalid.parse::<LastModified>().is_err(), "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,565 @@
+use std::{collections::HashMap, fmt::Display};
+
+use serde::Serialize;
+
+use crate::{
+    apng::{self, AnimationControl},
+    chunk::{checksum, Chunk, MAX_CHUNK_LENGTH},
+    chunk_type::ChunkType,
+    png::{Png, AT_MOST_ONCE, BEFORE_IDAT, BEFORE_PLTE},
+};
+
+/// Errors make a png invalid, warnings point at things decoders tolerate
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Warning => write!(f, "warning"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A structural problem found in a png, located by its byte offset
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Problem {
+    pub offset: usize,
+    pub severity: Severity,
+    pub message: String,
+    /// A stored CRC that doesn't match its chunk, which the exit code of
+    /// picmes tells apart from other errors
+    #[serde(skip)]
+    pub checksum: bool,
+}
+
+impl Problem {
+    fn error(offset: usize, message: impl Into<String>) -> Self {
+        Self {
+            offset,
+            severity: Severity::Error,
+            message: message.into(),
+            checksum: false,
+        }
+    }
+
+    fn checksum(offset: usize, message: impl Into<String>) -> Self {
+        Self {
+            checksum: true,
+            ..Self::error(offset, message)
+        }
+    }
+
+    fn warning(offset: usize, message: impl Into<String>) -> Self {
+        Self {
+            offset,
+            severity: Severity::Warning,
+            message: message.into(),
+            checksum: false,
+        }
+    }
+}
+
+/// Every problem found in a png, ordered by offset
+#[derive(Debug, Default, Serialize, PartialEq)]
+#[serde(transparent)]
+pub struct ValidationReport {
+    pub problems: Vec<Problem>,
+}
+
+impl ValidationReport {
+    /// A png is valid when it has no errors, warnings are allowed
+    pub fn is_valid(&self) -> bool {
+        self.errors().next().is_none()
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &Problem> {
+        self.with_severity(Severity::Error)
+    }
+
+    /// Whether any chunk has a CRC that doesn't match
+    pub fn has_bad_checksum(&self) -> bool {
+        self.problems.iter().any(|p| p.checksum)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &Problem> {
+        self.with_severity(Severity::Warning)
+    }
+
+    fn with_severity(&self, severity: Severity) -> impl Iterator<Item = &Problem> {
+        self.problems.iter().filter(move |p| p.severity == severity)
+    }
+}
+
+/// Checks the signature, every chunk's CRC and the placement of the
+/// critical and ordering sensitive ancillary chunks, collecting every problem
+/// instead of stopping at the first one
+pub fn verify(bytes: &[u8]) -> ValidationReport {
+    let mut problems = Vec::new();
+
+    if bytes.len() < Png::HEADER_LENGTH {
+        problems.push(Problem::error(
+            0,
+            "File is too small to hold a PNG signature",
+        ));
+        return ValidationReport { problems };
+    }
+    if bytes[..Png::HEADER_LENGTH] != Png::STANDARD_HEADER {
+        problems.push(Problem::error(0, "Invalid PNG signature"));
+    }
+
+    let mut offset = Png::HEADER_LENGTH;
+    let mut types: Vec<(usize, [u8; 4])> = Vec::new();
+    // the animation chunks are the only ones whose data matters here
+    let mut animation: Vec<(usize, Chunk)> = Vec::new();
+    // where each ancillary chunk was first seen, to spot identical copies
+    let mut first_seen: HashMap<([u8; 4], &[u8]), usize> = HashMap::new();
+
+    while offset < bytes.len() {
+        if let Some(&(_, last)) = types.last() {
+            if last == ChunkType::IEND.bytes() {
+                problems.push(Problem::warning(
+                    offset,
+                    format!("{} bytes of trailing data after IEND", bytes.len() - offset),
+                ));
+                break;
+            }
+        }
+
+        let rest = &bytes[offset..];
+        if rest.len() < Chunk::LEN_DATA_LENGTH + Chunk::CHUNK_TYPE_LENGTH {
+            problems.push(Problem::error(offset, "Truncated chunk header"));
+            break;
+        }
+
+        let length = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+        let chunk_type: [u8; 4] = rest[4..8].try_into().unwrap();
+        let name = String::from_utf8_lossy(&chunk_type).into_owned();
+
+        if !chunk_type.iter().all(u8::is_ascii_alphabetic) {
+            problems.push(Problem::error(
+                offset,
+                format!("Invalid chunk type {:?}", chunk_type),
+            ));
+        }
+        if length > MAX_CHUNK_LENGTH {
+            problems.push(Problem::error(
+                offset,
+                format!(
+                    "{} chunk length {} exceeds the maximum of {}",
+                    name, length, MAX_CHUNK_LENGTH
+                ),
+            ));
+            break;
+        }
+        if rest.len() < length + Chunk::META_DATA_LENGTH {
+            problems.push(Problem::error(
+                offset,
+                format!(
+                    "{} chunk is truncated: declares {} data bytes but only {} remain",
+                    name,
+                    length,
+                    rest.len() - Chunk::LEN_DATA_LENGTH - Chunk::CHUNK_TYPE_LENGTH
+                ),
+            ));
+            break;
+        }
+
+        let crc_start = Chunk::LEN_DATA_LENGTH + Chunk::CHUNK_TYPE_LENGTH + length;
+        let expected = u32::from_be_bytes(rest[crc_start..crc_start + 4].try_into().unwrap());
+        let actual = checksum(&rest[Chunk::LEN_DATA_LENGTH..crc_start]);
+        if expected != actual {
+            problems.push(Problem::checksum(
+                offset,
+                format!(
+                    "{} chunk CRC mismatch: stored {:#010x}, computed {:#010x}",
+                    name, expected, actual
+                ),
+            ));
+        }
+
+        let data = &rest[Chunk::LEN_DATA_LENGTH + Chunk::CHUNK_TYPE_LENGTH..crc_start];
+        match ChunkType::try_from(chunk_type) {
+            Ok(t) if apng::ANIMATION_CHUNKS.contains(&t) => {
+                animation.push((offset, Chunk::new(t, data.to_vec())))
+            }
+            // chunks allowed only once are reported as duplicates instead
+            Ok(t) if !t.is_critical() && !AT_MOST_ONCE.contains(&t) => {
+                let first = *first_seen.entry((chunk_type, data)).or_insert(offset);
+                if first != offset {
+                    problems.push(Problem::warning(
+                        offset,
+                        format!("{} chunk repeats the one at offset {:#x}", name, first),
+                    ));
+                }
+            }
+            _ => {}
+        }
+        types.push((offset, chunk_type));
+        offset += length + Chunk::META_DATA_LENGTH;
+    }
+
+    check_ordering(&types, offset, &mut problems);
+    check_duplicates(&types, &mut problems);
+    check_animation(&animation, &mut problems);
+    problems.sort_by_key(|p| p.offset);
+    ValidationReport { problems }
+}
+
+/// Checks the placement rules for the critical chunks. `end` is the offset
+/// just past the last chunk that could be read
+fn check_ordering(types: &[(usize, [u8; 4])], end: usize, problems: &mut Vec<Problem>) {
+    let positions = |name: &ChunkType| -> Vec<usize> {
+        types
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, t))| *t == name.bytes())
+            .map(|(i, _)| i)
+            .collect()
+    };
+
+    match types.first() {
+        Some((_, [b'I', b'H', b'D', b'R'])) => {}
+        Some((offset, _)) => problems.push(Problem::error(*offset, "First chunk is not IHDR")),
+        None => problems.push(Problem::error(end, "File contains no chunks")),
+    }
+    for &i in positions(&ChunkType::IHDR).iter().skip(1) {
+        problems.push(Problem::error(types[i].0, "Duplicate IHDR chunk"));
+    }
+
+    let idat = positions(&ChunkType::IDAT);
+    match (idat.first(), idat.last()) {
+        (Some(&first), Some(&last)) => {
+            if last - first + 1 != idat.len() {
+                problems.push(Problem::error(
+                    types[first].0,
+                    "IDAT chunks are not consecutive",
+                ));
+            }
+            for &i in positions(&ChunkType::PLTE).iter().filter(|&&i| i > first) {
+                problems.push(Problem::error(types[i].0, "PLTE chunk appears after IDAT"));
+            }
+        }
+        _ => problems.push(Problem::error(end, "Missing IDAT chunk")),
+    }
+
+    // anything after an IEND chunk is reported as trailing data
+    if positions(&ChunkType::IEND).is_empty() {
+        problems.push(Problem::error(end, "Missing IEND chunk"));
+    }
+
+    // misplaced ancillary chunks are still understood by most decoders
+    let first_of = |names: &[ChunkType]| {
+        names
+            .iter()
+            .filter_map(|name| positions(name).first().copied())
+            .min()
+    };
+    let rules = [
+        (
+            &BEFORE_PLTE[..],
+            first_of(&[ChunkType::PLTE, ChunkType::IDAT]),
+            "PLTE and IDAT",
+        ),
+        (&BEFORE_IDAT[..], first_of(&[ChunkType::IDAT]), "IDAT"),
+    ];
+    for (names, bound, limit) in rules {
+        let Some(bound) = bound else { continue };
+        for (offset, chunk_type) in &types[bound..] {
+            if names.iter().any(|name| name.bytes() == *chunk_type) {
+                problems.push(Problem::warning(
+                    *offset,
+                    format!(
+                        "{} chunk should appear before {}",
+                        String::from_utf8_lossy(chunk_type),
+                        limit
+                    ),
+                ));
+            }
+        }
+    }
+}
+
+/// Reports every chunk after the first of a type the specification allows
+/// only once. IHDR is checked with the ordering, a second IEND shows up as
+/// trailing data and acTL is checked with the rest of the animation
+fn check_duplicates(types: &[(usize, [u8; 4])], problems: &mut Vec<Problem>) {
+    let skipped = [ChunkType::IHDR, ChunkType::IEND, ChunkType::acTL];
+    for name in AT_MOST_ONCE.iter().filter(|name| !skipped.contains(name)) {
+        let duplicates = types.iter().filter(|(_, t)| *t == name.bytes()).skip(1);
+        for &(offset, _) in duplicates {
+            problems.push(match name.is_critical() {
+                true => Problem::error(offset, format!("Duplicate {} chunk", name)),
+                false => Problem::warning(
+                    offset,
+                    format!("Duplicate {} chunk, only one is allowed", name),
+                ),
+            });
+        }
+    }
+}
+
+/// Checks that the sequence numbers of an animated png count up from zero
+/// and that the acTL chunk gives the number of frames there are
+fn check_animation(chunks: &[(usize, Chunk)], problems: &mut Vec<Problem>) {
+    let mut control = None;
+    let mut frames = 0;
+    let mut expected = 0;
+    for (offset, chunk) in chunks {
+        if chunk.chunk_type() == &ChunkType::acTL {
+            match AnimationControl::try_from(chunk) {
+                Ok(found) if control.is_none() => control = Some((*offset, found)),
+                Ok(_) => problems.push(Problem::warning(*offset, "Duplicate acTL chunk")),
+                Err(e) => problems.push(Problem::warning(*offset, e.to_string())),
+            }
+            continue;
+        }
+        if chunk.chunk_type() == &ChunkType::fcTL {
+            frames += 1;
+        }
+        match apng::sequence_number(chunk) {
+            Some(found) if found == expected => {}
+            Some(found) => problems.push(Problem::warning(
+                *offset,
+                format!(
+                    "{} sequence number {} where {} was expected",
+                    chunk.chunk_type(),
+                    found,
+                    expected
+                ),
+            )),
+            None => problems.push(Problem::warning(
+                *offset,
+                format!(
+                    "{} chunk is too short for a sequence number",
+                    chunk.chunk_type()
+                ),
+            )),
+        }
+        expected += 1;
+    }
+
+    match control {
+        Some((offset, control)) if control.frames != frames => problems.push(Problem::warning(
+            offset,
+            format!(
+                "acTL declares {} frame(s) but there are {} fcTL chunk(s)",
+                control.frames, frames
+            ),
+        )),
+        None if frames > 0 => problems.push(Problem::warning(
+            chunks[0].0,
+            "Frame control chunks without an acTL chunk",
+        )),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apng::tests::{actl_chunk, fctl_chunk, fdat_chunk};
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn png_bytes(chunks: &[(&str, &[u8])]) -> Vec<u8> {
+        let chunks = chunks.iter().map(|(t, d)| chunk(t, d)).collect();
+        Png::from_chunks(chunks).as_bytes()
+    }
+
+    fn messages(report: &ValidationReport) -> Vec<&str> {
+        report.problems.iter().map(|p| p.message.as_str()).collect()
+    }
+
+    fn valid_png() -> Vec<u8> {
+        png_bytes(&[("IHDR", &[0; 13]), ("IDAT", &[1, 2, 3]), ("IEND", &[])])
+    }
+
+    #[test]
+    fn test_verify_valid() {
+        assert_eq!(verify(&valid_png()), ValidationReport::default());
+    }
+
+    #[test]
+    fn test_verify_bad_signature() {
+        let mut bytes = valid_png();
+        bytes[0] = 13;
+        assert_eq!(
+            verify(&bytes).problems,
+            [Problem::error(0, "Invalid PNG signature")]
+        );
+        assert_eq!(verify(&bytes[..4]).problems.len(), 1);
+    }
+
+    #[test]
+    fn test_verify_reports_every_bad_crc() {
+        let mut bytes = valid_png();
+        // last byte of the IHDR crc and of the IDAT crc
+        bytes[8 + 25 - 1] ^= 0xff;
+        bytes[8 + 25 + 15 - 1] ^= 0xff;
+
+        let problems = verify(&bytes).problems;
+        assert_eq!(problems.len(), 2);
+        assert_eq!(problems[0].offset, 8);
+        assert!(problems[0].message.starts_with("IHDR chunk CRC mismatch"));
+        assert_eq!(problems[1].offset, 8 + 25);
+        assert!(problems[1].message.starts_with("IDAT chunk CRC mismatch"));
+        assert!(problems.iter().all(|p| p.checksum));
+        assert!(verify(&bytes).has_bad_checksum());
+        assert!(!verify(&valid_png()).has_bad_checksum());
+    }
+
+    #[test]
+    fn test_verify_truncated() {
+        let bytes = valid_png();
+        let report = verify(&bytes[..8 + 25 + 10]);
+        assert_eq!(
+            messages(&report),
+            [
+                "IDAT chunk is truncated: declares 3 data bytes but only 2 remain",
+                "Missing IDAT chunk",
+                "Missing IEND chunk",
+            ]
+        );
+        assert_eq!(report.problems[0].offset, 8 + 25);
+    }
+
+    #[test]
+    fn test_verify_trailing_data() {
+        let mut bytes = valid_png();
+        let end = bytes.len();
+        bytes.extend_from_slice(b"garbage");
+        let report = verify(&bytes);
+        assert_eq!(
+            report.problems,
+            [Problem::warning(end, "7 bytes of trailing data after IEND")]
+        );
+        assert!(report.is_valid());
+        assert_eq!(report.warnings().count(), 1);
+    }
+
+    #[test]
+    fn test_verify_ordering() {
+        let bytes = png_bytes(&[
+            ("IDAT", &[1]),
+            ("IHDR", &[0; 13]),
+            ("IHDR", &[0; 13]),
+            ("tEXt", b"a"),
+            ("IDAT", &[2]),
+            ("PLTE", &[0; 3]),
+        ]);
+        assert_eq!(
+            messages(&verify(&bytes)),
+            [
+                "First chunk is not IHDR",
+                "IDAT chunks are not consecutive",
+                "Duplicate IHDR chunk",
+                "PLTE chunk appears after IDAT",
+                "Missing IEND chunk",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_verify_duplicates() {
+        let bytes = png_bytes(&[
+            ("IHDR", &[0; 13]),
+            ("gAMA", &[0, 0, 0, 1]),
+            ("gAMA", &[0, 0, 0, 1]),
+            ("PLTE", &[0; 3]),
+            ("PLTE", &[1; 3]),
+            ("IDAT", &[1]),
+            ("tEXt", b"a\0same"),
+            ("tEXt", b"a\0other"),
+            ("tEXt", b"a\0same"),
+            ("IEND", &[]),
+        ]);
+        let report = verify(&bytes);
+        assert_eq!(
+            messages(&report),
+            [
+                "Duplicate gAMA chunk, only one is allowed",
+                "Duplicate PLTE chunk",
+                "tEXt chunk repeats the one at offset 0x6c",
+            ]
+        );
+        assert!(!report.is_valid());
+        assert_eq!(report.warnings().count(), 2);
+    }
+
+    #[test]
+    fn test_verify_chunks_after_iend() {
+        let bytes = png_bytes(&[
+            ("IHDR", &[0; 13]),
+            ("IDAT", &[1]),
+            ("IEND", &[]),
+            ("IDAT", &[1]),
+        ]);
+        let report = verify(&bytes);
+        assert_eq!(messages(&report), ["13 bytes of trailing data after IEND"]);
+    }
+
+    #[test]
+    fn test_verify_animation() {
+        let actl = actl_chunk(2, 0);
+        let fctl = fctl_chunk(0, 1, 10);
+        let idat = chunk("IDAT", &[1]);
+        let frame = [fctl_chunk(1, 1, 10), fdat_chunk(2)];
+        let apng = |chunks: &[&Chunk]| {
+            let mut all = vec![chunk("IHDR", &[0; 13])];
+            all.extend(chunks.iter().map(|&c| c.clone()));
+            all.push(chunk("IEND", &[]));
+            Png::from_chunks(all).as_bytes()
+        };
+
+        let valid = apng(&[&actl, &fctl, &idat, &frame[0], &frame[1]]);
+        assert!(verify(&valid).problems.is_empty());
+
+        let report = verify(&apng(&[&actl, &fctl, &idat, &frame[1]]));
+        assert_eq!(
+            messages(&report),
+            [
+                "acTL declares 2 frame(s) but there are 1 fcTL chunk(s)",
+                "fdAT sequence number 2 where 1 was expected",
+            ]
+        );
+        assert!(report.is_valid());
+
+        let report = verify(&apng(&[&fctl, &idat]));
+        assert_eq!(
+            messages(&report),
+            ["Frame control chunks without an acTL chunk"]
+        );
+    }
+
+    #[test]
+    fn test_verify_ancillary_ordering() {
+        let bytes = png_bytes(&[
+            ("IHDR", &[0; 13]),
+            ("PLTE", &[0; 3]),
+            ("gAMA", &[0; 4]),
+            ("IDAT", &[1]),
+            ("pHYs", &[0; 9]),
+            ("tEXt", b"a"),
+            ("IEND", &[]),
+        ]);
+        let report = verify(&bytes);
+        assert_eq!(
+            messages(&report),
+            [
+                "gAMA chunk should appear before PLTE and IDAT",
+                "pHYs chunk should appear before IDAT",
+            ]
+        );
+        assert!(report.is_valid());
+        assert_eq!(report.errors().count(), 0);
+    }
+}
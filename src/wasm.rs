@@ -0,0 +1,78 @@
+use std::str::FromStr;
+
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    png::{Png, PngError},
+};
+
+/// A chunk as `listChunks` describes it to JavaScript
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkInfo {
+    #[wasm_bindgen(getter_with_clone, js_name = chunkType)]
+    pub chunk_type: String,
+    pub length: usize,
+    pub crc: u32,
+    pub critical: bool,
+}
+
+impl From<&Chunk> for ChunkInfo {
+    fn from(chunk: &Chunk) -> Self {
+        Self {
+            chunk_type: chunk.chunk_type().to_string(),
+            length: chunk.length(),
+            crc: chunk.crc(),
+            critical: chunk.chunk_type().is_critical(),
+        }
+    }
+}
+
+/// Hides `message` in a new chunk of `chunk_type` before IEND and returns
+/// the new png, taking and returning a Uint8Array
+#[wasm_bindgen]
+pub fn encode(png: &[u8], chunk_type: &str, message: &str) -> Result<Vec<u8>, JsError> {
+    let mut png = Png::try_from(png)?;
+    let chunk_type = ChunkType::from_str(chunk_type)?;
+    png.insert_chunk(Chunk::try_new(chunk_type, message.as_bytes().to_vec())?);
+    Ok(png.as_bytes())
+}
+
+/// The message in the first chunk of `chunk_type`
+#[wasm_bindgen]
+pub fn decode(png: &[u8], chunk_type: &str) -> Result<String, JsError> {
+    let png = Png::try_from(png)?;
+    let chunk = png
+        .chunk_by_type(chunk_type)
+        .ok_or(PngError::UnknownChunkType)?;
+    Ok(chunk.data_as_string()?)
+}
+
+/// Every chunk of the png in file order
+#[wasm_bindgen(js_name = listChunks)]
+pub fn list_chunks(png: &[u8]) -> Result<Vec<ChunkInfo>, JsError> {
+    let png = Png::try_from(png)?;
+    Ok(png.chunks().map(ChunkInfo::from).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::tests::testing_png_bytes;
+
+    // JsError can only be built on wasm32, so only the successful calls run
+    // natively
+    #[test]
+    fn test_encode_decode_and_list() {
+        let png = encode(&testing_png_bytes(), "ruSt", "from the browser").unwrap();
+        assert_eq!(decode(&png, "ruSt").unwrap(), "from the browser");
+
+        let chunks = list_chunks(&png).unwrap();
+        let types: Vec<&str> = chunks.iter().map(|c| c.chunk_type.as_str()).collect();
+        assert_eq!(types, ["IHDR", "IDAT", "ruSt", "IEND"]);
+        assert_eq!(chunks[2].length, 16);
+        assert!(chunks[0].critical && !chunks[2].critical);
+    }
+}
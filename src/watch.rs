@@ -0,0 +1,215 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    time::{Duration, Instant},
+};
+
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+
+use crate::{inputs::has_extension, Result};
+
+#[derive(Debug)]
+pub enum WatchError {
+    InvalidCommand(String),
+}
+
+impl std::error::Error for WatchError {}
+
+impl Display for WatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidCommand(command) => {
+                write!(f, "Can't split '{}' into arguments", command)
+            }
+        }
+    }
+}
+
+/// Stands for the added file in the command run on it
+pub const PLACEHOLDER: &str = "{}";
+
+/// The arguments of `command` with `file` in place of each `{}`, or after
+/// the last one when there is no `{}`. Arguments are split like a shell
+/// does, quotes included
+pub fn command_line(command: &str, file: &Path) -> Result<Vec<String>> {
+    let mut args =
+        shlex::split(command).ok_or_else(|| WatchError::InvalidCommand(command.to_string()))?;
+    if args.is_empty() {
+        return Err(WatchError::InvalidCommand(command.to_string()).into());
+    }
+    let file = file.to_string_lossy();
+    match args.iter().any(|arg| arg == PLACEHOLDER) {
+        true => {
+            for arg in args.iter_mut().filter(|arg| *arg == PLACEHOLDER) {
+                *arg = file.to_string();
+            }
+        }
+        false => args.push(file.into_owned()),
+    }
+    Ok(args)
+}
+
+/// Watches `dir` and hands every file with one of `extensions` added to it
+/// to `on_add`, once it has gone `settle` without changing so files still
+/// being copied in are left alone. `existing` files are handed over first.
+/// Each file is handed over once until it is removed again, so commands
+/// rewriting the file don't set themselves off. Errors the watcher reports
+/// along the way go to `on_error`. Runs until interrupted
+pub fn watch(
+    dir: &Path,
+    recursive: bool,
+    settle: Duration,
+    extensions: &[&str],
+    existing: Vec<PathBuf>,
+    on_add: impl FnMut(&Path),
+    on_error: impl FnMut(notify::Error),
+) -> Result<()> {
+    let (sender, events) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender)?;
+    let mode = match recursive {
+        true => RecursiveMode::Recursive,
+        false => RecursiveMode::NonRecursive,
+    };
+    watcher.watch(dir, mode)?;
+    handle_events(events, settle, extensions, existing, on_add, on_error)
+}
+
+/// The event loop of `watch`, returning once `events` has no sender left
+fn handle_events(
+    events: Receiver<notify::Result<Event>>,
+    settle: Duration,
+    extensions: &[&str],
+    existing: Vec<PathBuf>,
+    mut on_add: impl FnMut(&Path),
+    mut on_error: impl FnMut(notify::Error),
+) -> Result<()> {
+    let mut seen = HashSet::new();
+    for file in existing {
+        on_add(&file);
+        seen.insert(file);
+    }
+    // the last time each file not handed over yet changed
+    let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+    let mut connected = true;
+    while connected || !pending.is_empty() {
+        let received = match pending.values().min() {
+            Some(last) if connected => {
+                let wait = (*last + settle).saturating_duration_since(Instant::now());
+                events.recv_timeout(wait)
+            }
+            Some(_) => Err(RecvTimeoutError::Disconnected),
+            None => events.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match received {
+            Ok(Ok(event)) => {
+                for path in event.paths {
+                    if !has_extension(&path, extensions) {
+                        continue;
+                    }
+                    match event.kind {
+                        EventKind::Create(_) | EventKind::Modify(_) if !seen.contains(&path) => {
+                            pending.insert(path, Instant::now());
+                        }
+                        EventKind::Remove(_) => {
+                            seen.remove(&path);
+                            pending.remove(&path);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Ok(Err(error)) => on_error(error),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => connected = false,
+        }
+
+        let now = Instant::now();
+        let mut settled: Vec<PathBuf> = pending
+            .iter()
+            .filter(|(_, &last)| !connected || now >= last + settle)
+            .map(|(path, _)| path.clone())
+            .collect();
+        settled.sort();
+        for path in settled {
+            pending.remove(&path);
+            // renamed away or removed again before it settled
+            if path.is_file() {
+                on_add(&path);
+                seen.insert(path);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{CreateKind, ModifyKind, RemoveKind};
+    use std::{fs, thread};
+
+    #[test]
+    fn test_command_line() {
+        let file = Path::new("drop/new image.png");
+        assert_eq!(
+            command_line("strip --keep tEXt", file).unwrap(),
+            ["strip", "--keep", "tEXt", "drop/new image.png"]
+        );
+        assert_eq!(
+            command_line("encode {} ruSt 'ingested by picmes'", file).unwrap(),
+            ["encode", "drop/new image.png", "ruSt", "ingested by picmes"]
+        );
+        assert!(command_line("encode {} ruSt 'unterminated", file).is_err());
+        assert!(command_line("  ", file).is_err());
+    }
+
+    #[test]
+    fn test_handle_events() {
+        let dir = std::env::temp_dir().join(format!("picmes-{}-watch", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let [old, new, notes] = ["old.png", "new.PNG", "notes.txt"].map(|name| dir.join(name));
+        for file in [&old, &new, &notes] {
+            fs::write(file, []).unwrap();
+        }
+
+        let event = |kind, path: &PathBuf| Ok(Event::new(kind).add_path(path.clone()));
+        let (sender, events) = mpsc::channel();
+        let create = EventKind::Create(CreateKind::File);
+        let modify = EventKind::Modify(ModifyKind::Any);
+        let remove = EventKind::Remove(RemoveKind::File);
+        let sent = [
+            event(create, &new),
+            event(modify, &new),
+            event(create, &notes),
+            // rewriting a file already handed over doesn't count
+            event(modify, &old),
+            event(remove, &new),
+            Err(notify::Error::generic("lost track")),
+            event(create, &new),
+        ];
+        let producer = thread::spawn(move || {
+            for event in sent {
+                sender.send(event).unwrap();
+                thread::sleep(Duration::from_millis(20));
+            }
+        });
+
+        let mut added = Vec::new();
+        let mut errors = Vec::new();
+        handle_events(
+            events,
+            Duration::from_millis(5),
+            &["png"],
+            vec![old.clone()],
+            |file| added.push(file.to_path_buf()),
+            |error| errors.push(error.to_string()),
+        )
+        .unwrap();
+        producer.join().unwrap();
+        assert_eq!(added, [old, new.clone(), new]);
+        assert_eq!(errors, ["lost track"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
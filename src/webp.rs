@@ -0,0 +1,292 @@
+use std::{convert::TryFrom, fmt::Display, path::Path};
+
+use crate::{carrier::Section, chunk::Chunk, chunk_type::ChunkType, plan, Result};
+
+#[derive(Debug)]
+pub enum WebpError {
+    InvalidHeader,
+    Truncated,
+    ReservedChunkType(String),
+}
+
+impl std::error::Error for WebpError {}
+
+impl Display for WebpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidHeader => write!(f, "A webp should start with RIFF, a size and WEBP"),
+            Self::Truncated => write!(f, "The file ends in the middle of a chunk"),
+            Self::ReservedChunkType(chunk_type) => write!(
+                f,
+                "'{}' is a webp chunk, payloads need a chunk type of their own",
+                chunk_type
+            ),
+        }
+    }
+}
+
+/// The chunks the WebP container specification defines. Any other chunk
+/// with a valid chunk type as its FourCC is a payload
+const RESERVED: [&[u8; 4]; 9] = [
+    b"VP8 ", b"VP8L", b"VP8X", b"ALPH", b"ANIM", b"ANMF", b"ICCP", b"EXIF", b"XMP ",
+];
+/// `RIFF | file size | WEBP`
+const HEADER_LENGTH: usize = 12;
+/// `FourCC | data size`
+const CHUNK_HEADER_LENGTH: usize = 8;
+
+#[derive(Debug, Clone, PartialEq)]
+enum RiffChunk {
+    Other { fourcc: [u8; 4], data: Vec<u8> },
+    Payload(Chunk),
+}
+
+impl RiffChunk {
+    fn new(fourcc: [u8; 4], data: Vec<u8>) -> Self {
+        match ChunkType::try_from(fourcc) {
+            Ok(chunk_type) if !RESERVED.contains(&&fourcc) => {
+                Self::Payload(Chunk::new(chunk_type, data))
+            }
+            _ => Self::Other { fourcc, data },
+        }
+    }
+
+    fn fourcc(&self) -> [u8; 4] {
+        match self {
+            Self::Other { fourcc, .. } => *fourcc,
+            Self::Payload(chunk) => chunk.chunk_type().bytes(),
+        }
+    }
+
+    fn data(&self) -> &[u8] {
+        match self {
+            Self::Other { data, .. } => data,
+            Self::Payload(chunk) => chunk.data(),
+        }
+    }
+
+    /// The length in the file, padding to an even length included
+    fn stored_length(&self) -> usize {
+        CHUNK_HEADER_LENGTH + self.data().len().next_multiple_of(2)
+    }
+
+    fn write_to(&self, bytes: &mut Vec<u8>) {
+        let data = self.data();
+        bytes.extend(self.fourcc());
+        bytes.extend((data.len() as u32).to_le_bytes());
+        bytes.extend(data);
+        if data.len() % 2 == 1 {
+            bytes.push(0);
+        }
+    }
+}
+
+/// A webp as the list of chunks in its RIFF container. Payloads are stored
+/// in chunks of their own type, which decoders skip
+#[derive(Debug, Clone, PartialEq)]
+pub struct Webp {
+    chunks: Vec<RiffChunk>,
+    /// Anything past the size the RIFF header gives, kept as is
+    trailer: Vec<u8>,
+}
+
+impl Webp {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        Self::try_from(std::fs::read(path)?.as_slice())
+    }
+
+    /// Whether `bytes` start like a webp
+    pub fn has_signature(bytes: &[u8]) -> bool {
+        bytes.starts_with(b"RIFF") && bytes.get(8..HEADER_LENGTH) == Some(b"WEBP")
+    }
+
+    /// The payload chunks stored in the webp, in file order
+    pub fn chunks(&self) -> impl Iterator<Item = &Chunk> {
+        self.chunks.iter().filter_map(|chunk| match chunk {
+            RiffChunk::Payload(chunk) => Some(chunk),
+            _ => None,
+        })
+    }
+
+    /// Lists the RIFF header and every chunk with their lengths
+    pub fn sections(&self) -> Vec<Section> {
+        let header = Section {
+            name: "RIFF".to_string(),
+            length: HEADER_LENGTH,
+            chunk_type: None,
+        };
+        let chunks = self.chunks.iter().map(|chunk| Section {
+            name: String::from_utf8_lossy(&chunk.fourcc()).into_owned(),
+            length: chunk.stored_length(),
+            chunk_type: match chunk {
+                RiffChunk::Payload(chunk) => Some(chunk.chunk_type().clone()),
+                RiffChunk::Other { .. } => None,
+            },
+        });
+        std::iter::once(header).chain(chunks).collect()
+    }
+
+    /// Appends `chunk` after the image data, where the container
+    /// specification allows unknown chunks
+    pub fn insert_chunk(&mut self, chunk: Chunk) -> Result<()> {
+        if RESERVED.contains(&&chunk.chunk_type().bytes()) {
+            return Err(WebpError::ReservedChunkType(chunk.chunk_type().to_string()).into());
+        }
+        self.chunks.push(RiffChunk::Payload(chunk));
+        Ok(())
+    }
+
+    /// Removes the payloads matching `predicate`, returning them
+    pub fn remove_chunks_where(&mut self, mut predicate: impl FnMut(&Chunk) -> bool) -> Vec<Chunk> {
+        let mut removed = Vec::new();
+        let mut kept = Vec::new();
+        for chunk in self.chunks.drain(..) {
+            match chunk {
+                RiffChunk::Payload(chunk) if predicate(&chunk) => removed.push(chunk),
+                chunk => kept.push(chunk),
+            }
+        }
+        self.chunks = kept;
+        removed
+    }
+
+    pub fn remove_first_chunk(&mut self, chunk_type: &str) -> Option<Chunk> {
+        let mut first = true;
+        self.remove_chunks_where(|chunk| {
+            chunk.chunk_type().to_string() == chunk_type && std::mem::take(&mut first)
+        })
+        .pop()
+    }
+
+    pub fn remove_all_chunks(&mut self, chunk_type: &str) -> Vec<Chunk> {
+        self.remove_chunks_where(|chunk| chunk.chunk_type().to_string() == chunk_type)
+    }
+
+    /// Writes the container with its size updated to the chunks it now holds
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let size: usize = 4 + self
+            .chunks
+            .iter()
+            .map(RiffChunk::stored_length)
+            .sum::<usize>();
+        let mut bytes = b"RIFF".to_vec();
+        bytes.extend((size as u32).to_le_bytes());
+        bytes.extend(b"WEBP");
+        for chunk in &self.chunks {
+            chunk.write_to(&mut bytes);
+        }
+        bytes.extend(&self.trailer);
+        bytes
+    }
+
+    /// The size of the webp in bytes once written
+    pub fn encoded_length(&self) -> usize {
+        let chunks: usize = self.chunks.iter().map(RiffChunk::stored_length).sum();
+        HEADER_LENGTH + chunks + self.trailer.len()
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        plan::write_atomic(path.as_ref(), &self.as_bytes())
+    }
+}
+
+impl TryFrom<&[u8]> for Webp {
+    type Error = crate::Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        if !Self::has_signature(bytes) {
+            return Err(WebpError::InvalidHeader.into());
+        }
+        let size = u32::from_le_bytes(bytes[4..8].try_into()?) as usize;
+        let end = 8 + size;
+        let body = bytes.get(HEADER_LENGTH..end).ok_or(WebpError::Truncated)?;
+
+        let mut chunks = Vec::new();
+        let mut offset = 0;
+        while offset < body.len() {
+            let header = body
+                .get(offset..offset + CHUNK_HEADER_LENGTH)
+                .ok_or(WebpError::Truncated)?;
+            let fourcc: [u8; 4] = header[..4].try_into()?;
+            let length = u32::from_le_bytes(header[4..].try_into()?) as usize;
+            let start = offset + CHUNK_HEADER_LENGTH;
+            let data = body
+                .get(start..start + length)
+                .ok_or(WebpError::Truncated)?;
+            chunks.push(RiffChunk::new(fourcc, data.to_vec()));
+            offset = start + length.next_multiple_of(2);
+        }
+        Ok(Self {
+            chunks,
+            trailer: bytes[end..].to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{
+        carrier::tests::{assert_chunks_round_trip, assert_round_trip},
+        png::tests::testing_chunk,
+    };
+    use std::str::FromStr;
+
+    /// An extended format webp whose image chunk holds placeholder bytes
+    pub(crate) fn testing_webp_bytes() -> Vec<u8> {
+        let chunks = [
+            RiffChunk::Other {
+                fourcc: *b"VP8X",
+                data: vec![0; 10],
+            },
+            RiffChunk::Other {
+                fourcc: *b"VP8L",
+                data: vec![0x2f, 0, 0, 0, 0x10, 0x07, 0x10, 0x11, 0x11],
+            },
+        ];
+        Webp {
+            chunks: chunks.to_vec(),
+            trailer: vec![],
+        }
+        .as_bytes()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let bytes = testing_webp_bytes();
+        assert_eq!(&bytes[4..8], &(bytes.len() as u32 - 8).to_le_bytes());
+        let webp = Webp::try_from(bytes.as_slice()).unwrap();
+        let names: Vec<String> = webp.sections().into_iter().map(|s| s.name).collect();
+        assert_eq!(names, ["RIFF", "VP8X", "VP8L"]);
+        assert_eq!(webp.sections()[2].length, 18);
+        assert_round_trip(&bytes);
+        assert_round_trip(&[&bytes[..], b"appended"].concat());
+    }
+
+    #[test]
+    fn test_insert_and_remove_chunks() {
+        let bytes = testing_webp_bytes();
+        let mut webp = Webp::try_from(bytes.as_slice()).unwrap();
+        webp.insert_chunk(testing_chunk(b"odd")).unwrap();
+        webp.insert_chunk(testing_chunk(b"even")).unwrap();
+        let exif = Chunk::new(ChunkType::from_str("EXIF").unwrap(), vec![]);
+        assert!(webp.insert_chunk(exif).is_err());
+
+        let written = webp.as_bytes();
+        assert_eq!(written.len(), bytes.len() + 12 + 12);
+        assert_eq!(&written[4..8], &(written.len() as u32 - 8).to_le_bytes());
+        let removed = assert_chunks_round_trip(webp, &[b"odd", b"even"]);
+        assert_eq!(removed.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_invalid_webps() {
+        assert!(Webp::try_from(&b"RIFF\x04\0\0\0WAVE"[..]).is_err());
+        let bytes = testing_webp_bytes();
+        assert!(Webp::try_from(&bytes[..bytes.len() - 1]).is_err());
+
+        let mut overlong = bytes.clone();
+        overlong[16] = 0xff;
+        assert!(Webp::try_from(overlong.as_slice()).is_err());
+    }
+}